@@ -0,0 +1,145 @@
+//! Positions of the fragment identifiers inside the content documents.
+
+use regex::Regex;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+
+use crate::doc::{Anchor, EpubDoc};
+use crate::error::EpubError;
+use crate::href;
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Finds the element pointed by `href`, a full path in the archive with
+    /// a fragment like "OEBPS/ch3.xhtml#section-2", and returns its
+    /// position in the content document. The element is the one with that
+    /// `id`, or the legacy `<a name="...">` anchor. An href without
+    /// fragment points to the start of the document.
+    ///
+    /// The offsets are in the utf-8 content returned by
+    /// `get_resource_str_by_path`. Returns None if no element has that id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let chapter = "<html><body><p>Intro</p><h2 id=\"storm\">The Storm</h2></body></html>";
+    /// doc.modify_entry("OEBPS/Text/001.xhtml", chapter).unwrap();
+    ///
+    /// let anchor = doc.find_anchor("OEBPS/Text/001.xhtml#storm").unwrap().unwrap();
+    /// assert_eq!(24, anchor.byte_offset);
+    /// let rest = doc.get_resource_from_anchor("OEBPS/Text/001.xhtml#storm").unwrap();
+    /// assert!(rest.starts_with("<h2 id=\"storm\">The Storm"));
+    ///
+    /// assert!(doc.find_anchor("OEBPS/Text/001.xhtml#calm").unwrap().is_none());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document doesn't exist or can't be decoded.
+    pub fn find_anchor(&mut self, href: &str) -> Result<Option<Anchor>, EpubError> {
+        let (path, fragment) = href::split_fragment(href);
+        let path = PathBuf::from(href::normalize(path));
+        let content = self.get_resource_str_by_path(&path)?;
+        let id = match fragment {
+            Some(fragment) if !fragment.is_empty() => href::normalize(fragment),
+            _ => {
+                return Ok(Some(Anchor {
+                    path,
+                    id: String::new(),
+                    byte_offset: 0,
+                    char_offset: 0,
+                }))
+            }
+        };
+        Ok(anchor_offset(&content, &id).map(|byte_offset| Anchor {
+            char_offset: content[..byte_offset].chars().count(),
+            path,
+            id,
+            byte_offset,
+        }))
+    }
+
+    /// Returns the position in the current document of the fragment of the
+    /// last `go_to_path` or `go_to_href`, or None if there isn't a fragment
+    /// or no element has that id. See `find_anchor`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the current document can't be read.
+    pub fn get_current_anchor(&mut self) -> Result<Option<Anchor>, EpubError> {
+        let fragment = match self.get_current_fragment() {
+            Some(fragment) => fragment.to_string(),
+            None => return Ok(None),
+        };
+        let path = self.get_current_path()?;
+        self.find_anchor(&format!("{}#{}", path.display(), fragment))
+    }
+
+    /// Returns the content of the document pointed by `href` from the
+    /// element of the fragment to the end. See `find_anchor`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document doesn't exist, or
+    /// `EpubError::ResourceNotFound` if no element has the id of the
+    /// fragment.
+    pub fn get_resource_from_anchor(&mut self, href: &str) -> Result<String, EpubError> {
+        let anchor = self
+            .find_anchor(href)?
+            .ok_or_else(|| EpubError::ResourceNotFound(href.to_string()))?;
+        let mut content = self.get_resource_str_by_path(&anchor.path)?;
+        Ok(content.split_off(anchor.byte_offset))
+    }
+}
+
+/// Byte offset of the start tag of the element with the `id`, or of the
+/// `<a name>` anchor
+fn anchor_offset(content: &str, id: &str) -> Option<usize> {
+    let re = Regex::new(&format!(
+        r#"<[A-Za-z][^<>]*?\s(?:xml:id|id)\s*=\s*["']{}["']"#,
+        regex::escape(id)
+    ))
+    .expect("valid anchor regex");
+    if let Some(m) = re.find(content) {
+        return Some(m.start());
+    }
+    let re = Regex::new(&format!(
+        r#"<a\s[^<>]*?\bname\s*=\s*["']{}["']"#,
+        regex::escape(id)
+    ))
+    .expect("valid anchor regex");
+    re.find(content).map(|m| m.start())
+}
+
+/// Returns the source of the element with the `id`, from its start tag to
+/// the end tag, or None if no element has that id or it isn't closed
+pub(crate) fn element_source<'a>(content: &'a str, id: &str) -> Option<&'a str> {
+    let start = anchor_offset(content, id)?;
+    let rest = &content[start..];
+    let start_tag = &rest[..rest.find('>')? + 1];
+    if start_tag.ends_with("/>") {
+        return Some(start_tag);
+    }
+    let name: String = start_tag[1..]
+        .chars()
+        .take_while(|c| !c.is_whitespace() && *c != '>' && *c != '/')
+        .collect();
+    let tags = Regex::new(&format!(r"<(/?){}(?:\s[^<>]*?)?(/?)>", regex::escape(&name)))
+        .expect("valid tag regex");
+    let mut depth = 0;
+    for tag in tags.captures_iter(rest) {
+        if &tag[2] == "/" {
+            continue;
+        }
+        if &tag[1] == "/" {
+            depth -= 1;
+        } else {
+            depth += 1;
+        }
+        if depth == 0 {
+            return Some(&rest[..tag.get(0)?.end()]);
+        }
+    }
+    None
+}
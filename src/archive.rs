@@ -3,19 +3,339 @@
 //! Provides easy methods to navigate through the epub parts and to get
 //! the content as string.
 
-use anyhow::Error;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::{Path, PathBuf};
+use crate::encoding;
+use crate::error::EpubError;
+use crate::href;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{BufReader, Cursor};
+use std::path::{Component, Path, PathBuf};
 
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
+
+#[cfg(feature = "mmap")]
+use crate::mmap::Mmap;
+use crate::store::{collect_files, ResourceStore};
+use crate::stream::EpubStream;
+
+/// Pending modifications of an archive, used to restore a previous state
+#[derive(Clone)]
+pub(crate) struct PendingChanges {
+    files: Vec<String>,
+    modified: HashMap<String, Vec<u8>>,
+    removed: HashSet<String>,
+}
+
+/// Compression method of a zip entry
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Stored without compression
+    Stored,
+    /// Deflate compression, with the default level
+    Deflated,
+    /// Deflate compression, with a level between 0 and 9
+    DeflatedLevel(u32),
+}
+
+impl Compression {
+    fn apply(self, options: zip::write::FileOptions) -> zip::write::FileOptions {
+        match self {
+            Compression::Stored => options.compression_method(zip::CompressionMethod::Stored),
+            Compression::Deflated => options.compression_method(zip::CompressionMethod::Deflated),
+            Compression::DeflatedLevel(level) => options
+                .compression_method(zip::CompressionMethod::Deflated)
+                .compression_level(Some(level as i32)),
+        }
+    }
+
+    /// Checks if an entry compressed with `method` can be copied as is
+    fn matches(self, method: zip::CompressionMethod) -> bool {
+        match self {
+            Compression::Stored => method == zip::CompressionMethod::Stored,
+            Compression::Deflated => method == zip::CompressionMethod::Deflated,
+            Compression::DeflatedLevel(_) => false,
+        }
+    }
+}
+
+/// Information of a zip entry, read from the zip directory without
+/// decompressing the entry
+#[derive(Clone, Debug)]
+pub struct EntryInfo {
+    /// entry name in the zip
+    pub name: String,
+    /// size of the compressed content
+    pub compressed_size: u64,
+    /// size of the content
+    pub size: u64,
+    /// CRC-32 checksum of the content
+    pub crc32: u32,
+    pub compression: zip::CompressionMethod,
+    pub last_modified: zip::DateTime,
+}
+
+/// Limits checked when opening a zip archive, to reject zip bombs,
+/// small archives that decompress to a huge size. There are no limits by
+/// default, use `ArchiveLimits::untrusted` when opening untrusted files.
+///
+/// The sizes are the ones declared in the zip directory. When a size or
+/// ratio limit is set, entries are also checked while they are read, and
+/// reading fails if an entry is bigger than declared.
+///
+/// The maximum entry size is checked when each entry is read, so an epub
+/// with some big resources, like videos, can be opened, but those
+/// resources can't be read in memory.
+///
+/// # Examples
+///
+/// ```
+/// use epub::archive::{ArchiveLimits, EpubArchive};
+///
+/// let mut limits = ArchiveLimits::new();
+/// limits.max_entries(10);
+/// assert!(EpubArchive::new_with_limits("test.epub", &limits).is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ArchiveLimits {
+    max_total_size: Option<u64>,
+    max_ratio: Option<u64>,
+    max_entries: Option<usize>,
+    max_entry_size: Option<u64>,
+}
+
+impl ArchiveLimits {
+    pub fn new() -> ArchiveLimits {
+        ArchiveLimits::default()
+    }
+
+    /// Limits for untrusted files, like uploads: 1 GiB of total
+    /// decompressed size, an expansion ratio of 100 and 10000 entries
+    pub fn untrusted() -> ArchiveLimits {
+        ArchiveLimits {
+            max_total_size: Some(1 << 30),
+            max_ratio: Some(100),
+            max_entries: Some(10000),
+            max_entry_size: None,
+        }
+    }
+
+    /// Maximum total decompressed size of the entries, in bytes
+    pub fn max_total_size(&mut self, size: u64) -> &mut Self {
+        self.max_total_size = Some(size);
+        self
+    }
+
+    /// Maximum ratio between the decompressed and the compressed size of
+    /// each entry
+    pub fn max_ratio(&mut self, ratio: u64) -> &mut Self {
+        self.max_ratio = Some(ratio);
+        self
+    }
+
+    /// Maximum number of entries in the archive
+    pub fn max_entries(&mut self, entries: usize) -> &mut Self {
+        self.max_entries = Some(entries);
+        self
+    }
+
+    /// Maximum decompressed size of an entry read in memory, in bytes
+    pub fn max_entry_size(&mut self, size: u64) -> &mut Self {
+        self.max_entry_size = Some(size);
+        self
+    }
+
+    /// Checks if the entries must be checked while they are read
+    fn checks_size(&self) -> bool {
+        self.max_total_size.is_some() || self.max_ratio.is_some() || self.max_entry_size.is_some()
+    }
+
+    fn check_entry_size(&self, name: &str, size: u64) -> Result<(), EpubError> {
+        match self.max_entry_size {
+            Some(max) if size > max => Err(EpubError::LimitExceeded(format!(
+                "{} has {} bytes, the maximum is {}",
+                name, size, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the zip entry content. With size limits, fails if the content
+    /// is bigger than the size declared in the zip.
+    fn read_entry(
+        &self,
+        file: &mut zip::read::ZipFile,
+        entry: &mut Vec<u8>,
+    ) -> Result<(), EpubError> {
+        if !self.checks_size() {
+            file.read_to_end(entry)?;
+            return Ok(());
+        }
+        let size = file.size();
+        self.check_entry_size(file.name(), size)?;
+        file.take(size + 1).read_to_end(entry)?;
+        if entry.len() as u64 > size {
+            return Err(EpubError::LimitExceeded(format!(
+                "{} is bigger than its declared size",
+                file.name()
+            )));
+        }
+        Ok(())
+    }
+
+    fn check<R: Read + Seek>(&self, zip: &mut zip::ZipArchive<R>) -> Result<(), EpubError> {
+        if let Some(max) = self.max_entries {
+            if zip.len() > max {
+                return Err(EpubError::LimitExceeded(format!(
+                    "{} entries, the maximum is {}",
+                    zip.len(),
+                    max
+                )));
+            }
+        }
+        let mut total: u64 = 0;
+        for i in 0..zip.len() {
+            let file = zip.by_index_raw(i)?;
+            total = total.saturating_add(file.size());
+            if let Some(max) = self.max_ratio {
+                let ratio = file.size() / file.compressed_size().max(1);
+                if ratio > max {
+                    return Err(EpubError::LimitExceeded(format!(
+                        "{} expands {} times, the maximum is {}",
+                        file.name(),
+                        ratio,
+                        max
+                    )));
+                }
+            }
+        }
+        if let Some(max) = self.max_total_size {
+            if total > max {
+                return Err(EpubError::LimitExceeded(format!(
+                    "{} bytes decompressed, the maximum is {}",
+                    total, max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What to do when extracting a file that already exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Replace the existing file
+    Replace,
+    /// Keep the existing file, without extracting the entry
+    Skip,
+    /// Stop the extraction with an error
+    Fail,
+}
+
+/// Options used to write the epub archive
+///
+/// The mimetype is always written as the first entry, without
+/// compression, as required by the epub specification.
+///
+/// # Examples
+///
+/// ```
+/// use epub::archive::{Compression, WriteOptions};
+///
+/// let mut options = WriteOptions::new();
+/// options
+///     .deterministic(true)
+///     .compression("image/*", Compression::Stored)
+///     .compression("application/xhtml+xml", Compression::DeflatedLevel(9));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    deterministic: bool,
+    /// compression by media type, or by "type/*"
+    compression: Vec<(String, Compression)>,
+    /// compression for the entries without a media type rule
+    default_compression: Option<Compression>,
+    /// entry name -> media type, used instead of the file extension
+    pub(crate) media_types: HashMap<String, String>,
+}
+
+impl WriteOptions {
+    /// Creates the default options, entries are written in the original
+    /// order and unmodified entries are copied as they are.
+    pub fn new() -> WriteOptions {
+        WriteOptions::default()
+    }
+
+    /// Makes the output reproducible, the same content always produces a
+    /// byte-identical epub.
+    ///
+    /// Every entry is written again, with a fixed timestamp and
+    /// permissions and the same compression, deflated unless there's a
+    /// compression rule for it. Entries are sorted by name, after the
+    /// mimetype and the container file.
+    pub fn deterministic(&mut self, deterministic: bool) -> &mut Self {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Uses `compression` for the entries with the `media_type`, that can
+    /// be a full media type like "image/jpeg" or a "type/*" pattern, like
+    /// "image/*". The full media type rule wins over the pattern.
+    ///
+    /// Entries are recompressed if the method doesn't match. The media
+    /// type comes from the package manifest when the epub is written with
+    /// `EpubDoc::save_as_with`, and from the file extension otherwise.
+    pub fn compression(&mut self, media_type: &str, compression: Compression) -> &mut Self {
+        self.compression.push((media_type.to_string(), compression));
+        self
+    }
+
+    /// Uses `compression` for every entry without a media type rule,
+    /// instead of keeping the original compression.
+    pub fn default_compression(&mut self, compression: Compression) -> &mut Self {
+        self.default_compression = Some(compression);
+        self
+    }
+
+    /// Returns the compression rule for the entry `name`
+    fn compression_for(&self, name: &str) -> Option<Compression> {
+        let mime = match self.media_types.get(name) {
+            Some(mime) => mime.as_str(),
+            None => guess_media_type(name),
+        };
+        let pattern = mime.split('/').next().map(|t| format!("{}/*", t));
+        let rule = |m: &str| self.compression.iter().rev().find(|(k, _)| k == m);
+        rule(mime)
+            .or_else(|| pattern.as_deref().and_then(rule))
+            .map(|(_, c)| *c)
+            .or(self.default_compression)
+    }
+}
+
+/// Where the archive files are read from
+enum Source<R: Read + Seek> {
+    Zip(zip::ZipArchive<R>),
+    Store(Box<dyn ResourceStore>),
+}
 
 /// Epub archive struct. Here it's stored the file path and the list of
 /// files in the zip archive.
 pub struct EpubArchive<R: Read + Seek> {
-    zip: zip::ZipArchive<R>,
+    source: Source<R>,
     pub path: PathBuf,
     pub files: Vec<String>,
+    /// pending modifications, entry name -> new content
+    modified: HashMap<String, Vec<u8>>,
+    /// entries pending to be removed
+    removed: HashSet<String>,
+    /// the zip content, if it's a memory map
+    #[cfg(feature = "mmap")]
+    map: Option<Mmap>,
+    limits: ArchiveLimits,
+    /// retry the lookups ignoring the case and the separators
+    fuzzy: bool,
+    /// the password of the encrypted zip entries
+    password: Option<Vec<u8>>,
 }
 
 impl EpubArchive<BufReader<File>> {
@@ -25,13 +345,233 @@ impl EpubArchive<BufReader<File>> {
     ///
     /// Returns an error if the zip is broken or if the file doesn't
     /// exists.
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<EpubArchive<BufReader<File>>, Error> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<EpubArchive<BufReader<File>>, EpubError> {
         let path = path.as_ref();
         let file = File::open(path)?;
         let mut archive = EpubArchive::from_reader(BufReader::new(file))?;
         archive.path = path.to_path_buf();
         Ok(archive)
     }
+
+    /// Opens the epub file in `path`, a zip with its entries encrypted
+    /// with the `password`. See `EpubArchive::password`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken, if the file doesn't exists
+    /// or `EpubError::InvalidPassword` if the password is wrong.
+    pub fn new_with_password<P: AsRef<Path>>(
+        path: P,
+        password: &str,
+    ) -> Result<EpubArchive<BufReader<File>>, EpubError> {
+        let mut archive = EpubArchive::new(path)?;
+        archive.password(password)?;
+        Ok(archive)
+    }
+
+    /// Opens the epub file in `path`, checking the `limits`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken, if the file doesn't exists
+    /// or if the archive exceeds the limits.
+    pub fn new_with_limits<P: AsRef<Path>>(
+        path: P,
+        limits: &ArchiveLimits,
+    ) -> Result<EpubArchive<BufReader<File>>, EpubError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let mut archive = EpubArchive::from_reader_with_limits(BufReader::new(file), limits)?;
+        archive.path = path.to_path_buf();
+        Ok(archive)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl EpubArchive<Cursor<Mmap>> {
+    /// Opens the epub file in `path` as a memory map, instead of reading
+    /// it with a buffer. Recommended for big epubs, like audio books.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the archive is
+    /// open, see `Mmap::open`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken or if the file can't be
+    /// mapped.
+    pub unsafe fn new_mmap<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<EpubArchive<Cursor<Mmap>>, EpubError> {
+        EpubArchive::new_mmap_with_limits(path, &ArchiveLimits::default())
+    }
+
+    /// Opens the epub file in `path` as a memory map, checking the
+    /// `limits`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the archive is
+    /// open, see `Mmap::open`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken, if the file can't be mapped
+    /// or if the archive exceeds the limits.
+    pub unsafe fn new_mmap_with_limits<P: AsRef<Path>>(
+        path: P,
+        limits: &ArchiveLimits,
+    ) -> Result<EpubArchive<Cursor<Mmap>>, EpubError> {
+        let path = path.as_ref();
+        let map = Mmap::open(path)?;
+        let mut archive = EpubArchive::from_reader_with_limits(Cursor::new(map.clone()), limits)?;
+        archive.path = path.to_path_buf();
+        archive.map = Some(map);
+        Ok(archive)
+    }
+
+    /// Returns the content of the file by the `name` without copying it,
+    /// directly from the memory map.
+    ///
+    /// Only entries stored without compression, like images and audio
+    /// usually are, can be returned this way. Returns `None` for
+    /// compressed, encrypted or modified entries, and for the entries
+    /// bigger than the `max_entry_size` of the limits, use `get_entry` for
+    /// them.
+    pub fn get_mapped_entry<P: AsRef<Path>>(&mut self, name: P) -> Option<&[u8]> {
+        let name = self.entry_name(name);
+        if self.modified.contains_key(&name) || self.removed.contains(&name) {
+            return None;
+        }
+        let zip = match self.source {
+            Source::Zip(ref mut zip) => zip,
+            Source::Store(_) => return None,
+        };
+        let index = entry_index(zip, &name)?;
+        if is_encrypted(zip, index) {
+            return None;
+        }
+        let file = zip.by_index_raw(index).ok()?;
+        // the stored entries have the same size in the zip and
+        // decompressed, another size is a broken zip
+        if file.compression() != zip::CompressionMethod::Stored
+            || file.size() != file.compressed_size()
+        {
+            return None;
+        }
+        self.limits.check_entry_size(&name, file.size()).ok()?;
+        let start = usize::try_from(file.data_start()).ok()?;
+        let end = start.checked_add(usize::try_from(file.size()).ok()?)?;
+        self.map.as_ref()?.get(start..end)
+    }
+}
+
+impl EpubArchive<std::io::Empty> {
+    /// Opens the epub stored in `store`, like a directory or a custom
+    /// backend, instead of a zip file. The reader type isn't used.
+    pub fn from_store<S: ResourceStore + 'static>(store: S) -> EpubArchive<std::io::Empty> {
+        EpubArchive {
+            files: store.names(),
+            source: Source::Store(Box::new(store)),
+            path: PathBuf::new(),
+            modified: HashMap::new(),
+            removed: HashSet::new(),
+            #[cfg(feature = "mmap")]
+            map: None,
+            limits: ArchiveLimits::default(),
+            fuzzy: false,
+            password: None,
+        }
+    }
+
+    /// Reads the epub from a non seekable `reader`, like a socket, entry
+    /// by entry, see the `stream` module.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::archive::EpubArchive;
+    ///
+    /// let file = std::fs::File::open("test.epub").unwrap();
+    /// let mut stream = EpubArchive::from_stream(file);
+    /// let doc = stream.read_package().unwrap();
+    /// assert_eq!("Todo es mío", doc.mdata("title").unwrap());
+    /// while let Some(entry) = stream.next_entry().unwrap() {
+    ///     println!("{}: {} bytes", entry.name(), entry.size());
+    /// }
+    /// ```
+    pub fn from_stream<S: Read>(reader: S) -> EpubStream<S> {
+        EpubStream::new(reader)
+    }
+}
+
+impl EpubArchive<Cursor<Vec<u8>>> {
+    /// Opens the epub from its content in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken.
+    pub fn from_bytes(content: Vec<u8>) -> Result<EpubArchive<Cursor<Vec<u8>>>, EpubError> {
+        EpubArchive::from_reader(Cursor::new(content))
+    }
+
+    /// Builds the epub from an unpacked epub directory, to pack it again
+    /// after editing the files.
+    ///
+    /// Every file under `path` is added, with the path relative to the
+    /// directory as entry name. The mimetype goes first and uncompressed,
+    /// it's created if the directory doesn't have one. The archive is
+    /// built in memory, use `save_as` to write it to disk. To read the
+    /// directory without packing it, use `from_store` with a
+    /// `DirectoryStore`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some file can't be read, or if the directory
+    /// doesn't have the "META-INF/container.xml" file or the package
+    /// document it points to.
+    pub fn from_directory<P: AsRef<Path>>(
+        path: P,
+    ) -> Result<EpubArchive<Cursor<Vec<u8>>>, EpubError> {
+        let path = path.as_ref();
+        let container = path.join("META-INF").join("container.xml");
+        if !container.is_file() {
+            return Err(EpubError::MissingContainer);
+        }
+        let root_file = crate::doc::get_root_file(fs::read(&container)?)?;
+        if !path.join(&root_file).is_file() {
+            return Err(EpubError::InvalidContainer(format!(
+                "package document not found: {}",
+                root_file.display()
+            )));
+        }
+
+        let mut names = vec![];
+        collect_files(path, "", &mut names)?;
+        names.sort_by(|a, b| entry_rank(a).cmp(&entry_rank(b)).then_with(|| a.cmp(b)));
+
+        let mut out = zip::ZipWriter::new(Cursor::new(vec![]));
+        let mimetype = if names.iter().any(|n| n == "mimetype") {
+            fs::read_to_string(path.join("mimetype"))?
+                .trim()
+                .to_string()
+        } else {
+            String::from("application/epub+zip")
+        };
+        let stored =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        out.start_file("mimetype", stored)?;
+        out.write_all(mimetype.as_bytes())?;
+        for name in names.iter().filter(|n| *n != "mimetype") {
+            out.start_file(name.as_str(), zip::write::FileOptions::default())?;
+            out.write_all(&fs::read(path.join(name))?)?;
+        }
+
+        let mut archive = EpubArchive::from_bytes(out.finish()?.into_inner())?;
+        archive.path = path.to_path_buf();
+        Ok(archive)
+    }
 }
 
 impl<R: Read + Seek> EpubArchive<R> {
@@ -40,52 +580,362 @@ impl<R: Read + Seek> EpubArchive<R> {
     /// # Errors
     ///
     /// Returns an error if the zip is broken.
-    pub fn from_reader(reader: R) -> Result<EpubArchive<R>, Error> {
+    pub fn from_reader(reader: R) -> Result<EpubArchive<R>, EpubError> {
         let zip = zip::ZipArchive::new(reader)?;
 
         let files:Vec<String> = zip.file_names().map(|f| f.to_string()).collect();
 
         Ok(EpubArchive {
-            zip,
+            source: Source::Zip(zip),
             path: PathBuf::new(),
             files,
+            modified: HashMap::new(),
+            removed: HashSet::new(),
+            #[cfg(feature = "mmap")]
+            map: None,
+            limits: ArchiveLimits::default(),
+            fuzzy: false,
+            password: None,
         })
     }
 
+    /// Opens the epub contained in `reader`, checking the `limits`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken or if the archive exceeds the
+    /// limits.
+    pub fn from_reader_with_limits(
+        reader: R,
+        limits: &ArchiveLimits,
+    ) -> Result<EpubArchive<R>, EpubError> {
+        let mut archive = EpubArchive::from_reader(reader)?;
+        if let Source::Zip(ref mut zip) = archive.source {
+            limits.check(zip)?;
+        }
+        archive.limits = limits.clone();
+        Ok(archive)
+    }
+
+    /// Returns the name of the file matching `name`: the same name if
+    /// there's a file with it, or the first file with the same normalized
+    /// path, see `href::normalize`. Unknown names are returned as they are.
+    fn entry_name<P: AsRef<Path>>(&self, name: P) -> String {
+        let name = name.as_ref().display().to_string();
+        let name = if cfg!(windows) {
+            name.replace('\\', "/")
+        } else {
+            name
+        };
+        if self.files.contains(&name) || self.removed.contains(&name) {
+            return name;
+        }
+        let normalized = href::normalize(&name);
+        let found = self
+            .files
+            .iter()
+            .find(|f| **f == normalized || href::normalize(f) == normalized);
+        let found = match found {
+            None if self.fuzzy => {
+                let fuzzy = fuzzy_name(&name);
+                self.files.iter().find(|f| fuzzy_name(f) == fuzzy)
+            }
+            found => found,
+        };
+        found.cloned().unwrap_or(name)
+    }
+
+    /// Enables the fuzzy lookup of the files, disabled by default. When a
+    /// name isn't found, it's retried replacing the `\` separators with `/`
+    /// and ignoring the case, for the zips where the hrefs don't match the
+    /// file names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::archive::EpubArchive;
+    /// let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// assert!(archive.get_entry("oebps\\text\\TITLEPAGE.xhtml").is_err());
+    /// archive.fuzzy_lookup(true);
+    /// assert!(archive.get_entry("oebps\\text\\TITLEPAGE.xhtml").is_ok());
+    /// ```
+    pub fn fuzzy_lookup(&mut self, fuzzy: bool) -> &mut Self {
+        self.fuzzy = fuzzy;
+        self
+    }
+
+    /// Decrypts the encrypted zip entries with the `password`, with the
+    /// ZipCrypto algorithm. The password is checked with the first
+    /// encrypted entry, but the algorithm can't always tell a wrong
+    /// password, then reading the entries fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::InvalidPassword` if the password is wrong, or
+    /// an error if the zip is broken.
+    pub fn password(&mut self, password: &str) -> Result<&mut Self, EpubError> {
+        self.password = None;
+        if let Source::Zip(ref mut zip) = self.source {
+            for i in 0..zip.len() {
+                if !is_encrypted(zip, i) {
+                    continue;
+                }
+                let name = zip.by_index_raw(i)?.name().to_string();
+                if zip.by_index_decrypt(i, password.as_bytes())?.is_err() {
+                    return Err(EpubError::InvalidPassword(name));
+                }
+                break;
+            }
+        }
+        self.password = Some(password.as_bytes().to_vec());
+        Ok(self)
+    }
+
     /// Returns the content of the file by the `name` as `Vec<u8>`.
     ///
     /// # Errors
     ///
     /// Returns an error if the name doesn't exists in the zip archive.
-    pub fn get_entry<P: AsRef<Path>>(&mut self, name: P) -> Result<Vec<u8>, Error> {
+    pub fn get_entry<P: AsRef<Path>>(&mut self, name: P) -> Result<Vec<u8>, EpubError> {
         let mut entry: Vec<u8> = vec![];
-        let name = name.as_ref().display().to_string();
-        match self.zip.by_name(&name) {
-            Ok(mut zipfile) => {
-                zipfile.read_to_end(&mut entry)?;
-                return Ok(entry);
+        let name = self.entry_name(name);
+        if let Some(content) = self.modified.get(&name) {
+            return Ok(content.clone());
+        }
+        if self.removed.contains(&name) {
+            return Err(EpubError::ResourceNotFound(name));
+        }
+        let limits = &self.limits;
+        let zip = match self.source {
+            Source::Zip(ref mut zip) => zip,
+            Source::Store(ref mut store) => {
+                let content = read_store(store.as_mut(), &name)?;
+                limits.check_entry_size(&name, content.len() as u64)?;
+                return Ok(content);
+            }
+        };
+        let mut zipfile = zip_entry(zip, &name, self.password.as_deref())?;
+        limits.read_entry(&mut zipfile, &mut entry)?;
+        Ok(entry)
+    }
+
+    /// Returns a reader of the file by the `name`, to stream the content
+    /// without reading it all in memory, like big audio or video files.
+    ///
+    /// Zip entries are decompressed while they are read. Modified entries,
+    /// and entries of stores, are already in memory. With archive limits,
+    /// the content is truncated to the size declared in the zip.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::archive::EpubArchive;
+    /// # let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// let mut reader = archive.get_entry_reader("OEBPS/Images/portada.png").unwrap();
+    /// let copied = std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+    /// assert_eq!(1186183, copied);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name doesn't exists in the zip archive, or
+    /// if the entry is bigger than the `max_entry_size` of the limits. With
+    /// size limits, the reads fail with an `EpubError::LimitExceeded` in an
+    /// `io::Error` if the content is bigger than the size declared in the
+    /// zip.
+    pub fn get_entry_reader<P: AsRef<Path>>(
+        &mut self,
+        name: P,
+    ) -> Result<Box<dyn Read + '_>, EpubError> {
+        let name = self.entry_name(name);
+        if self.modified.contains_key(&name) {
+            return Ok(Box::new(&self.modified[&name][..]));
+        }
+        if self.removed.contains(&name) {
+            return Err(EpubError::ResourceNotFound(name));
+        }
+        let limits = &self.limits;
+        let zip = match self.source {
+            Source::Zip(ref mut zip) => zip,
+            Source::Store(ref mut store) => {
+                let content = read_store(store.as_mut(), &name)?;
+                return Ok(Box::new(Cursor::new(content)));
             }
-            Err(zip::result::ZipError::FileNotFound) => {}
-            Err(e) => {
-                return Err(e.into());
+        };
+        let file = zip_entry(zip, &name, self.password.as_deref())?;
+        if limits.checks_size() {
+            let size = file.size();
+            limits.check_entry_size(&name, size)?;
+            return Ok(Box::new(SizedReader {
+                inner: file,
+                name,
+                remaining: size,
+            }));
+        }
+        Ok(Box::new(file))
+    }
+
+    /// Returns the information of the zip entry by the `name`, like the
+    /// sizes or the checksum, without reading the content.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::archive::EpubArchive;
+    /// # let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// let info = archive.entry_info("OEBPS/Styles/stylesheet.css").unwrap();
+    /// assert_eq!(188, info.size);
+    /// assert_eq!(zip::CompressionMethod::Deflated, info.compression);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name doesn't exists in the zip archive, if
+    /// the archive isn't a zip or if the entry has pending modifications,
+    /// because the content isn't compressed yet.
+    pub fn entry_info<P: AsRef<Path>>(&mut self, name: P) -> Result<EntryInfo, EpubError> {
+        let name = self.entry_name(name);
+        if self.modified.contains_key(&name) {
+            return Err(EpubError::InvalidOperation(format!(
+                "entry modified, info not available: {}",
+                name
+            )));
+        }
+        if self.removed.contains(&name) {
+            return Err(EpubError::ResourceNotFound(name));
+        }
+        let zip = match self.source {
+            Source::Zip(ref mut zip) => zip,
+            Source::Store(_) => {
+                return Err(EpubError::InvalidOperation(String::from(
+                    "entry info only available for zip files",
+                )))
             }
         };
+        let index = match entry_index(zip, &name) {
+            Some(index) => index,
+            None => return Err(EpubError::ResourceNotFound(name)),
+        };
+        let file = zip.by_index_raw(index)?;
+        Ok(EntryInfo {
+            name: file.name().to_string(),
+            compressed_size: file.compressed_size(),
+            size: file.size(),
+            crc32: file.crc32(),
+            compression: file.compression(),
+            last_modified: file.last_modified(),
+        })
+    }
 
-        // try percent encoding
-        let name = percent_encoding::percent_decode(name.as_str().as_bytes()).decode_utf8()?;
-        let mut zipfile = self.zip.by_name(&name)?;
-        zipfile.read_to_end(&mut entry)?;
-        Ok(entry)
+    /// Returns the size of the content of the file by the `name`. The size
+    /// is the one declared in the zip directory, or the size of the pending
+    /// modification, so the content isn't read, except for stores.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::archive::EpubArchive;
+    /// # let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// assert_eq!(188, archive.entry_size("OEBPS/Styles/stylesheet.css").unwrap());
+    /// archive.modify_entry("OEBPS/Styles/stylesheet.css", "p {}");
+    /// assert_eq!(4, archive.entry_size("OEBPS/Styles/stylesheet.css").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name doesn't exists in the archive.
+    pub fn entry_size<P: AsRef<Path>>(&mut self, name: P) -> Result<u64, EpubError> {
+        let name = self.entry_name(name);
+        if let Some(content) = self.modified.get(&name) {
+            return Ok(content.len() as u64);
+        }
+        if self.removed.contains(&name) {
+            return Err(EpubError::ResourceNotFound(name));
+        }
+        let zip = match self.source {
+            Source::Zip(ref mut zip) => zip,
+            Source::Store(ref mut store) => {
+                return Ok(read_store(store.as_mut(), &name)?.len() as u64);
+            }
+        };
+        let index = match entry_index(zip, &name) {
+            Some(index) => index,
+            None => return Err(EpubError::ResourceNotFound(name)),
+        };
+        let file = zip.by_index_raw(index)?;
+        Ok(file.size())
+    }
+
+    /// Checks if there's a file by the `name`, with the pending
+    /// modifications.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::archive::EpubArchive;
+    /// # let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// assert!(archive.contains("OEBPS/Styles/stylesheet.css"));
+    /// archive.remove_entry("OEBPS/Styles/stylesheet.css").unwrap();
+    /// assert!(!archive.contains("OEBPS/Styles/stylesheet.css"));
+    /// ```
+    pub fn contains<P: AsRef<Path>>(&self, name: P) -> bool {
+        let name = self.entry_name(name);
+        self.files.contains(&name)
+    }
+
+    /// Returns the names of the files matching the glob `pattern`, sorted.
+    ///
+    /// In the pattern `*` matches any text inside a directory, `**` matches
+    /// any text including directories, and `?` matches one character.
+    /// Without wildcards, the pattern must be the exact name. Directory
+    /// entries aren't included.
+    pub fn names_matching(&self, pattern: &str) -> Vec<String> {
+        let re = glob_regex(pattern);
+        let mut names: Vec<String> = self
+            .files
+            .iter()
+            .filter(|name| !name.ends_with('/') && re.is_match(name))
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Returns an iterator over the files matching the glob `pattern`,
+    /// with the name and the content. Each content is read when the
+    /// iterator reaches it. See `names_matching` for the pattern syntax.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::archive::EpubArchive;
+    /// # let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// for entry in archive.entries_matching("OEBPS/Images/*") {
+    ///     let (name, content) = entry.unwrap();
+    ///     println!("{}: {} bytes", name, content.len());
+    /// }
+    /// assert_eq!(2, archive.entries_matching("**.png").count());
+    /// ```
+    pub fn entries_matching(&mut self, pattern: &str) -> Entries<'_, R> {
+        Entries {
+            names: self.names_matching(pattern).into_iter(),
+            archive: self,
+        }
     }
 
     /// Returns the content of the file by the `name` as `String`.
     ///
+    /// The content is decoded from the encoding of its byte order mark, xml
+    /// declaration or html meta charset. Content without an encoding that
+    /// isn't valid utf-8 is read as windows-1252.
+    ///
     /// # Errors
     ///
-    /// Returns an error if the name doesn't exists in the zip archive.
-    pub fn get_entry_as_str<P: AsRef<Path>>(&mut self, name: P) -> Result<String, Error> {
+    /// Returns an error if the name doesn't exists in the zip archive, or if
+    /// the declared encoding isn't supported.
+    pub fn get_entry_as_str<P: AsRef<Path>>(&mut self, name: P) -> Result<String, EpubError> {
         let content = self.get_entry(name)?;
-        String::from_utf8(content).map_err(Error::from)
+        Ok(encoding::decode(&content)?.into_owned())
     }
 
     /// Returns the content of container file "META-INF/container.xml".
@@ -93,8 +943,532 @@ impl<R: Read + Seek> EpubArchive<R> {
     /// # Errors
     ///
     /// Returns an error if the epub doesn't have the container file.
-    pub fn get_container_file(&mut self) -> Result<Vec<u8>, Error> {
-        let content = self.get_entry("META-INF/container.xml")?;
-        Ok(content)
+    pub fn get_container_file(&mut self) -> Result<Vec<u8>, EpubError> {
+        match self.get_entry("META-INF/container.xml") {
+            Err(EpubError::ResourceNotFound(_)) => Err(EpubError::MissingContainer),
+            content => content,
+        }
+    }
+
+    /// Replaces the content of the file by the `name`, or adds a new file
+    /// if it doesn't exists in the zip archive.
+    ///
+    /// The content can be text or binary data, like images or fonts. The
+    /// modification is stored in memory, so next calls to `get_entry`
+    /// will return the new content, and it's written to disk all at once
+    /// calling to `save_as`.
+    pub fn modify_entry<P: AsRef<Path>, C: AsRef<[u8]>>(&mut self, name: P, content: C) {
+        let name = self.entry_name(name);
+        if !self.files.contains(&name) {
+            self.files.push(name.clone());
+        }
+        self.removed.remove(&name);
+        self.modified.insert(name, content.as_ref().to_vec());
+    }
+
+    /// Removes the file by the `name` from the zip archive.
+    ///
+    /// Like modifications, the removal is stored in memory and applied
+    /// calling to `save_as`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name doesn't exists in the zip archive.
+    pub fn remove_entry<P: AsRef<Path>>(&mut self, name: P) -> Result<(), EpubError> {
+        let name = self.entry_name(name);
+        let pos = match self.files.iter().position(|f| f == &name) {
+            Some(pos) => pos,
+            None => return Err(EpubError::ResourceNotFound(name)),
+        };
+        self.files.remove(pos);
+        self.modified.remove(&name);
+        self.removed.insert(name);
+        Ok(())
+    }
+
+    /// Unpacks every file of the archive, with the pending modifications,
+    /// to the `dir` directory. Existing files are replaced. Returns the
+    /// written files.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some entry can't be read or written, or if some
+    /// entry name is an absolute path or points outside the directory,
+    /// like "../file". Nothing is written outside the directory.
+    pub fn extract_to<P: AsRef<Path>>(&mut self, dir: P) -> Result<Vec<PathBuf>, EpubError> {
+        self.extract_to_with(dir, Overwrite::Replace)
+    }
+
+    /// Unpacks every file of the archive to the `dir` directory, using
+    /// the `overwrite` policy for existing files. Returns the written
+    /// files.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::archive::{EpubArchive, Overwrite};
+    ///
+    /// let dir = std::env::temp_dir().join("epub-rs-doc-extract");
+    /// let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// archive.extract_to(&dir).unwrap();
+    /// let written = archive.extract_to_with(&dir, Overwrite::Skip).unwrap();
+    /// assert!(written.is_empty());
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some entry can't be read or written, if some
+    /// entry points outside the directory, or if a file exists with the
+    /// `Overwrite::Fail` policy.
+    pub fn extract_to_with<P: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        overwrite: Overwrite,
+    ) -> Result<Vec<PathBuf>, EpubError> {
+        let dir = dir.as_ref();
+        let mut names = self.files.clone();
+        names.sort();
+        // checked before writing anything
+        for name in names.iter() {
+            let safe = Path::new(name)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if !safe {
+                return Err(EpubError::UnsafePath(name.clone()));
+            }
+        }
+
+        let mut written = vec![];
+        for name in names.iter() {
+            let dest = dir.join(name);
+            if name.ends_with('/') {
+                fs::create_dir_all(&dest)?;
+                continue;
+            }
+            if dest.exists() {
+                match overwrite {
+                    Overwrite::Replace => {}
+                    Overwrite::Skip => continue,
+                    Overwrite::Fail => {
+                        return Err(EpubError::AlreadyExists(dest.display().to_string()))
+                    }
+                }
+            }
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut file = File::create(&dest)?;
+            std::io::copy(&mut self.get_entry_reader(name)?, &mut file)?;
+            written.push(dest);
+        }
+        Ok(written)
+    }
+
+    /// Writes the archive, with all the pending modifications, to a new
+    /// file in `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written or if `path` is the
+    /// file used to open this archive.
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<(), EpubError> {
+        self.save_as_with(path, &WriteOptions::default())
+    }
+
+    /// Writes the archive, with all the pending modifications, to a new
+    /// file in `path`, using the write `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written or if `path` is the
+    /// file used to open this archive.
+    pub fn save_as_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<(), EpubError> {
+        let path = path.as_ref();
+        if !self.path.as_os_str().is_empty() && same_file(path, &self.path) {
+            return Err(EpubError::InvalidOperation(String::from(
+                "can't overwrite the opened epub file",
+            )));
+        }
+
+        // writing to a temporary file first, so a failure never leaves a
+        // partially written epub in path
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".part");
+        let file = File::create(&tmp)?;
+        if let Err(e) = self.write_to_with(file, options) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Writes the archive, with all the pending modifications, to
+    /// `writer`, returning it back.
+    ///
+    /// Unmodified entries are copied without decompressing them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if something fails writing the zip.
+    pub fn write_to<W: Write + Seek>(&mut self, writer: W) -> Result<W, EpubError> {
+        self.write_to_with(writer, &WriteOptions::default())
+    }
+
+    /// Writes the archive, with all the pending modifications, to
+    /// `writer` using the write `options`, returning it back.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::archive::{EpubArchive, WriteOptions};
+    /// use std::io::Cursor;
+    ///
+    /// let mut archive = EpubArchive::new("test.epub").unwrap();
+    /// let mut options = WriteOptions::new();
+    /// options.deterministic(true);
+    /// let first = archive.write_to_with(Cursor::new(vec![]), &options).unwrap();
+    /// let second = archive.write_to_with(Cursor::new(vec![]), &options).unwrap();
+    /// assert_eq!(first.into_inner(), second.into_inner());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if something fails writing the zip.
+    pub fn write_to_with<W: Write + Seek>(
+        &mut self,
+        writer: W,
+        options: &WriteOptions,
+    ) -> Result<W, EpubError> {
+        let mut out = zip::ZipWriter::new(writer);
+
+        // entry name and index in the source zip, for existing entries
+        let mut entries: Vec<(String, Option<usize>)> = vec![];
+        match self.source {
+            Source::Zip(ref mut zip) => {
+                for i in 0..zip.len() {
+                    let name = zip.by_index_raw(i)?.name().to_string();
+                    if !self.removed.contains(&name) {
+                        entries.push((name, Some(i)));
+                    }
+                }
+            }
+            Source::Store(ref store) => {
+                for name in store.names() {
+                    if !self.removed.contains(&name) {
+                        entries.push((name, None));
+                    }
+                }
+            }
+        }
+        for name in self.files.iter() {
+            if self.modified.contains_key(name) && !entries.iter().any(|(n, _)| n == name) {
+                entries.push((name.clone(), None));
+            }
+        }
+        if options.deterministic {
+            entries
+                .sort_by(|(a, _), (b, _)| entry_rank(a).cmp(&entry_rank(b)).then_with(|| a.cmp(b)));
+        }
+
+        // the mimetype goes always first and uncompressed
+        let mimetype = match self.get_entry("mimetype") {
+            Ok(content) => content,
+            Err(_) => b"application/epub+zip".to_vec(),
+        };
+        let stored = stable_options("mimetype").compression_method(zip::CompressionMethod::Stored);
+        out.start_file("mimetype", stored)?;
+        out.write_all(&mimetype)?;
+
+        for (name, index) in entries {
+            if name == "mimetype" {
+                continue;
+            }
+            let rule = options.compression_for(&name);
+            let original = match (&mut self.source, index) {
+                (Source::Zip(zip), Some(i)) => Some(zip.by_index_raw(i)?.compression()),
+                _ => None,
+            };
+            let modified = self.modified.get(&name);
+
+            let keep = match (rule, original) {
+                (Some(rule), Some(method)) => rule.matches(method),
+                _ => true,
+            };
+            if !options.deterministic && modified.is_none() && keep {
+                if let (Source::Zip(zip), Some(i)) = (&mut self.source, index) {
+                    out.raw_copy_file(zip.by_index_raw(i)?)?;
+                    continue;
+                }
+            }
+
+            let mut file_options = if options.deterministic {
+                stable_options(&name)
+            } else {
+                zip::write::FileOptions::default()
+            };
+            match (rule, original) {
+                (Some(rule), _) => file_options = rule.apply(file_options),
+                (None, Some(method)) if !options.deterministic => {
+                    file_options = file_options.compression_method(method)
+                }
+                _ => {}
+            }
+
+            match (modified, &mut self.source, index) {
+                (Some(content), _, _) => {
+                    out.start_file(name.as_str(), file_options)?;
+                    out.write_all(content)?;
+                }
+                (None, Source::Zip(zip), Some(_)) => {
+                    let mut file = zip_entry(zip, &name, self.password.as_deref())?;
+                    if file.is_dir() {
+                        out.add_directory(name.as_str(), file_options)?;
+                    } else {
+                        out.start_file(name.as_str(), file_options)?;
+                        std::io::copy(&mut file, &mut out)?;
+                    }
+                }
+                (None, Source::Store(store), _) => {
+                    let content = read_store(store.as_mut(), &name)?;
+                    out.start_file(name.as_str(), file_options)?;
+                    out.write_all(&content)?;
+                }
+                (None, Source::Zip(_), None) => {}
+            }
+        }
+
+        Ok(out.finish()?)
+    }
+
+    pub(crate) fn pending_changes(&self) -> PendingChanges {
+        PendingChanges {
+            files: self.files.clone(),
+            modified: self.modified.clone(),
+            removed: self.removed.clone(),
+        }
+    }
+
+    pub(crate) fn restore_changes(&mut self, changes: PendingChanges) {
+        self.files = changes.files;
+        self.modified = changes.modified;
+        self.removed = changes.removed;
+    }
+}
+
+/// Iterator over archive files, created with `entries_matching`. Yields
+/// the name and the content of each file.
+pub struct Entries<'a, R: Read + Seek> {
+    archive: &'a mut EpubArchive<R>,
+    names: std::vec::IntoIter<String>,
+}
+
+impl<'a, R: Read + Seek> Iterator for Entries<'a, R> {
+    type Item = Result<(String, Vec<u8>), EpubError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        Some(self.archive.get_entry(&name).map(|content| (name, content)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.names.size_hint()
+    }
+}
+
+/// Builds the regex for a glob pattern, with the `*`, `**` and `?`
+/// wildcards
+fn glob_regex(pattern: &str) -> regex::Regex {
+    let mut re = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                re.push_str(".*");
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    regex::Regex::new(&re).expect("valid glob regex")
+}
+
+/// Converts the zip error reading the entry by the `name`, with a
+/// `ResourceNotFound` error if it doesn't exist
+fn entry_error(e: zip::result::ZipError, name: &str) -> EpubError {
+    match e {
+        zip::result::ZipError::FileNotFound => EpubError::ResourceNotFound(name.to_string()),
+        zip::result::ZipError::UnsupportedArchive(reason)
+            if reason == zip::result::ZipError::PASSWORD_REQUIRED =>
+        {
+            EpubError::InvalidPassword(name.to_string())
+        }
+        e => e.into(),
+    }
+}
+
+/// Reader of a zip entry that fails if the content is bigger than the
+/// size declared in the zip
+struct SizedReader<R> {
+    inner: R,
+    name: String,
+    remaining: u64,
+}
+
+impl<R: Read> Read for SizedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            // one more byte is more than the declared size
+            return match self.inner.read(&mut [0])? {
+                0 => Ok(0),
+                _ => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    EpubError::LimitExceeded(format!(
+                        "{} is bigger than its declared size",
+                        self.name
+                    )),
+                )),
+            };
+        }
+        let max = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let read = self.inner.read(&mut buf[..max])?;
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Checks if the paths `a` and `b` are the same file, with the relative
+/// paths, the `..` and the symbolic links resolved. A path that doesn't
+/// exist yet is resolved from its directory.
+fn same_file(a: &Path, b: &Path) -> bool {
+    fn canonical(path: &Path) -> Option<PathBuf> {
+        if let Ok(path) = fs::canonicalize(path) {
+            return Some(path);
+        }
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
+    }
+    match (canonical(a), canonical(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Checks if the zip entry at `index` is encrypted, reading it needs the
+/// password
+fn is_encrypted<R: Read + Seek>(zip: &mut zip::ZipArchive<R>, index: usize) -> bool {
+    matches!(
+        zip.by_index(index),
+        Err(zip::result::ZipError::UnsupportedArchive(reason))
+            if reason == zip::result::ZipError::PASSWORD_REQUIRED
+    )
+}
+
+/// Returns the zip entry by the `name`, decrypted with the `password`
+fn zip_entry<'a, R: Read + Seek>(
+    zip: &'a mut zip::ZipArchive<R>,
+    name: &str,
+    password: Option<&[u8]>,
+) -> Result<zip::read::ZipFile<'a>, EpubError> {
+    let file = match password {
+        Some(password) => zip.by_name_decrypt(name, password),
+        None => zip.by_name(name).map(Ok),
+    };
+    match file {
+        Ok(Ok(file)) => Ok(file),
+        Ok(Err(_)) => Err(EpubError::InvalidPassword(name.to_string())),
+        Err(e) => Err(entry_error(e, name)),
+    }
+}
+
+/// Normalized and lowercase name, with `/` as separator, for the fuzzy
+/// lookup
+fn fuzzy_name(name: &str) -> String {
+    href::normalize(&name.replace('\\', "/")).to_lowercase()
+}
+
+/// Returns the index of the zip entry by the `name`
+fn entry_index<R: Read + Seek>(zip: &mut zip::ZipArchive<R>, name: &str) -> Option<usize> {
+    (0..zip.len()).find(|i| matches!(zip.by_index_raw(*i), Ok(f) if f.name() == name))
+}
+
+/// Reads the file from the store, trying also the normalized name
+fn read_store(store: &mut dyn ResourceStore, name: &str) -> Result<Vec<u8>, EpubError> {
+    let normalized = href::normalize(name);
+    for name in [name, normalized.as_str()].iter() {
+        match store.read(name) {
+            Ok(content) => return Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(EpubError::ResourceNotFound(name.to_string()))
+}
+
+/// Position of the entry in a deterministic archive, the mimetype goes
+/// first, and then the META-INF files
+fn entry_rank(name: &str) -> usize {
+    match name {
+        "mimetype" => 0,
+        "META-INF/container.xml" => 1,
+        n if n.starts_with("META-INF/") => 2,
+        _ => 3,
+    }
+}
+
+/// File options that don't depend on the source entry or on the time,
+/// deflated by default
+fn stable_options(name: &str) -> zip::write::FileOptions {
+    zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .last_modified_time(zip::DateTime::default())
+        .unix_permissions(if name.ends_with('/') { 0o755 } else { 0o644 })
+}
+
+/// Returns the media type of the file, using the file name extension
+pub(crate) fn guess_media_type(name: &str) -> &'static str {
+    let ext = match name.rfind('.') {
+        Some(i) => name[i + 1..].to_lowercase(),
+        None => return "application/octet-stream",
+    };
+    match ext.as_str() {
+        "xhtml" | "xht" => "application/xhtml+xml",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "opf" => "application/oebps-package+xml",
+        "ncx" => "application/x-dtbncx+xml",
+        "smil" => "application/smil+xml",
+        "xml" => "application/xml",
+        "txt" => "text/plain",
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "m4a" | "mp4" => "audio/mp4",
+        "ogg" | "opus" => "audio/ogg",
+        _ => "application/octet-stream",
     }
 }
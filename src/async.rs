@@ -0,0 +1,369 @@
+//! Async version of the epub doc, to read epubs from async code without
+//! blocking the executor.
+//!
+//! The blocking work, reading the zip and parsing the xml, runs in a pool
+//! of threads and the returned futures are woken when it's done. The
+//! threads are started when needed, up to 16, and stop after some idle
+//! time. It doesn't depend on any runtime, so it works with tokio,
+//! async-std or any other executor. To run the work in the blocking
+//! threads of the runtime instead, like with tokio `spawn_blocking`, set
+//! the spawner with `set_spawner`.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use epub::r#async::EpubDoc;
+//!
+//! let doc = EpubDoc::open("test.epub").await?;
+//! let title = doc.mdata("title").await;
+//! let cover = doc.get_cover().await?;
+//! ```
+
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::{BufReader, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+use crate::archive::Overwrite;
+use crate::doc;
+use crate::error::EpubError;
+
+/// The maximum number of threads of the pool, the next tasks wait for a
+/// free thread
+const MAX_THREADS: usize = 16;
+
+/// The time an idle thread of the pool waits for a task before it stops
+const KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+/// Async handle to an epub doc.
+///
+/// Every method locks the doc and runs in a thread of the pool. Cloning
+/// the handle is cheap, the clones share the same doc.
+pub struct EpubDoc<R: Read + Seek + Send + 'static> {
+    doc: Arc<Mutex<doc::EpubDoc<R>>>,
+}
+
+impl<R: Read + Seek + Send + 'static> Clone for EpubDoc<R> {
+    fn clone(&self) -> Self {
+        EpubDoc {
+            doc: self.doc.clone(),
+        }
+    }
+}
+
+impl EpubDoc<BufReader<File>> {
+    /// Opens the epub file in `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken or if the file doesn't
+    /// exists.
+    pub async fn open<P: AsRef<Path>>(path: P) -> Result<EpubDoc<BufReader<File>>, EpubError> {
+        let path = path.as_ref().to_path_buf();
+        let doc = blocking(move || doc::EpubDoc::new(path)).await??;
+        Ok(EpubDoc::from_doc(doc))
+    }
+}
+
+impl EpubDoc<Cursor<Vec<u8>>> {
+    /// Opens the epub from its content in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken.
+    pub async fn from_bytes(content: Vec<u8>) -> Result<EpubDoc<Cursor<Vec<u8>>>, EpubError> {
+        let doc = blocking(move || doc::EpubDoc::from_bytes(content)).await??;
+        Ok(EpubDoc::from_doc(doc))
+    }
+}
+
+impl<R: Read + Seek + Send + 'static> EpubDoc<R> {
+    /// Wraps an already opened doc
+    pub fn from_doc(doc: doc::EpubDoc<R>) -> EpubDoc<R> {
+        EpubDoc {
+            doc: Arc::new(Mutex::new(doc)),
+        }
+    }
+
+    /// Runs `f` with the doc in a thread of the pool and returns its
+    /// result. Use it for the methods that don't have an async version.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::InvalidOperation` if `f` panics, the doc may be
+    /// left half modified, then the next calls fail the same way.
+    pub async fn with<T, F>(&self, f: F) -> Result<T, EpubError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut doc::EpubDoc<R>) -> T + Send + 'static,
+    {
+        let doc = self.doc.clone();
+        blocking(move || match doc.lock() {
+            Ok(mut doc) => Ok(f(&mut doc)),
+            Err(_) => Err(EpubError::InvalidOperation(String::from(
+                "the doc is poisoned, a task panicked with it",
+            ))),
+        })
+        .await?
+    }
+
+    /// Returns the first metadata found with this name.
+    pub async fn mdata(&self, name: &str) -> Option<String> {
+        let name = name.to_string();
+        self.with(move |doc| doc.mdata(&name)).await.ok().flatten()
+    }
+
+    /// Returns the resource content by the id defined in the manifest
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the id doesn't exists in the epub
+    pub async fn get_resource(&self, id: &str) -> Result<Vec<u8>, EpubError> {
+        let id = id.to_string();
+        self.with(move |doc| doc.get_resource(&id)).await?
+    }
+
+    /// Returns the resource content by full path in the epub archive
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exists in the epub
+    pub async fn get_resource_by_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> Result<Vec<u8>, EpubError> {
+        let path = path.as_ref().to_path_buf();
+        self.with(move |doc| doc.get_resource_by_path(path)).await?
+    }
+
+    /// Returns the resource content by the id as String
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the id doesn't exists in the epub or if the
+    /// content isn't valid utf-8
+    pub async fn get_resource_str(&self, id: &str) -> Result<String, EpubError> {
+        let id = id.to_string();
+        self.with(move |doc| doc.get_resource_str(&id)).await?
+    }
+
+    /// Returns the cover image content
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub doesn't have a cover
+    pub async fn get_cover(&self) -> Result<Vec<u8>, EpubError> {
+        self.with(|doc| doc.get_cover()).await?
+    }
+
+    /// Writes the resource by the id to the file in `dest`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the id doesn't exists in the epub or if the
+    /// file can't be written.
+    pub async fn extract_resource<P: AsRef<Path>>(
+        &self,
+        id: &str,
+        dest: P,
+    ) -> Result<(), EpubError> {
+        let id = id.to_string();
+        let dest = dest.as_ref().to_path_buf();
+        self.with(move |doc| {
+            let content = doc.get_resource(&id)?;
+            fs::write(dest, content)?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Unpacks every file of the epub archive to the `dir` directory.
+    /// Returns the written files. See `EpubArchive::extract_to_with`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some entry can't be read or written, or if it
+    /// points outside the directory.
+    pub async fn extract_to<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        overwrite: Overwrite,
+    ) -> Result<Vec<PathBuf>, EpubError> {
+        let dir = dir.as_ref().to_path_buf();
+        self.with(move |doc| doc.archive.extract_to_with(dir, overwrite))
+            .await?
+    }
+
+    /// Saves the epub with the pending modifications to `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if something fails writing the file.
+    pub async fn save_as<P: AsRef<Path>>(&self, path: P) -> Result<(), EpubError> {
+        let path = path.as_ref().to_path_buf();
+        self.with(move |doc| doc.save_as(path)).await?
+    }
+}
+
+/// A blocking task, run by the pool or by the spawner of `set_spawner`
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// The function running the blocking tasks, see `set_spawner`
+type Spawner = Box<dyn Fn(Job) + Send + Sync>;
+
+static SPAWNER: OnceLock<Spawner> = OnceLock::new();
+
+/// Sets the function that runs the blocking tasks of every doc, instead of
+/// the pool of threads. The task must be run in a thread where it can
+/// block, like with tokio `spawn_blocking`. It can only be set once, and
+/// the tasks started before keep running in the pool.
+///
+/// # Examples
+///
+/// ```ignore
+/// let runtime = tokio::runtime::Handle::current();
+/// epub::r#async::set_spawner(move |job| {
+///     runtime.spawn_blocking(job);
+/// })
+/// .unwrap();
+/// ```
+///
+/// # Errors
+///
+/// Returns `EpubError::InvalidOperation` if the spawner is already set.
+pub fn set_spawner<F>(spawner: F) -> Result<(), EpubError>
+where
+    F: Fn(Job) + Send + Sync + 'static,
+{
+    SPAWNER
+        .set(Box::new(spawner))
+        .map_err(|_| EpubError::InvalidOperation(String::from("the spawner is already set")))
+}
+
+/// The threads running the blocking work
+struct Pool {
+    jobs: Mutex<Sender<Job>>,
+    receiver: Arc<Mutex<Receiver<Job>>>,
+    /// the number of threads, and of idle threads not yet given a task
+    threads: Mutex<(usize, usize)>,
+}
+
+impl Pool {
+    /// The pool of the process, created on the first task
+    fn get() -> &'static Pool {
+        static POOL: OnceLock<Pool> = OnceLock::new();
+        POOL.get_or_init(|| {
+            let (jobs, receiver) = mpsc::channel();
+            Pool {
+                jobs: Mutex::new(jobs),
+                receiver: Arc::new(Mutex::new(receiver)),
+                threads: Mutex::new((0, 0)),
+            }
+        })
+    }
+
+    /// Runs the `job` in an idle thread, or in a new thread if there's
+    /// none and the pool isn't full
+    fn run(&'static self, job: Job) {
+        {
+            let mut threads = self.threads.lock().unwrap_or_else(|e| e.into_inner());
+            let (ref mut count, ref mut idle) = *threads;
+            if *idle > 0 {
+                *idle -= 1;
+            } else if *count < MAX_THREADS {
+                *count += 1;
+                thread::spawn(move || self.work());
+            }
+        }
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        // the receiver lives as long as the pool
+        let _ = jobs.send(job);
+    }
+
+    /// Runs the tasks of the pool, until it's idle for `KEEP_ALIVE`
+    fn work(&self) {
+        loop {
+            let job = {
+                let receiver = self.receiver.lock().unwrap_or_else(|e| e.into_inner());
+                receiver.recv_timeout(KEEP_ALIVE)
+            };
+            match job {
+                Ok(job) => {
+                    job();
+                    let mut threads = self.threads.lock().unwrap_or_else(|e| e.into_inner());
+                    threads.1 += 1;
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut threads = self.threads.lock().unwrap_or_else(|e| e.into_inner());
+                    // without idle threads, a task was just given to this one
+                    if threads.1 > 0 {
+                        threads.1 -= 1;
+                        threads.0 -= 1;
+                        return;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+/// Shared state between the thread and the future
+struct State<T> {
+    result: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Future of a blocking function running in the pool
+struct Blocking<T> {
+    state: Arc<Mutex<State<T>>>,
+}
+
+fn blocking<T, F>(f: F) -> Blocking<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let state = Arc::new(Mutex::new(State {
+        result: None,
+        waker: None,
+    }));
+    let shared = state.clone();
+    let job: Job = Box::new(move || {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+        let mut state = shared.lock().unwrap_or_else(|e| e.into_inner());
+        state.result = Some(result);
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    });
+    match SPAWNER.get() {
+        Some(spawn) => spawn(job),
+        None => Pool::get().run(job),
+    }
+    Blocking { state }
+}
+
+impl<T> Future for Blocking<T> {
+    type Output = Result<T, EpubError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.result.take() {
+            Some(Ok(value)) => Poll::Ready(Ok(value)),
+            Some(Err(_)) => Poll::Ready(Err(EpubError::InvalidOperation(String::from(
+                "epub task panicked",
+            )))),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
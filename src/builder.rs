@@ -0,0 +1,717 @@
+//! Creates new epub files.
+//!
+//! Provides a simple builder to generate a valid EPUB3 from scratch,
+//! adding metadata, resources and content documents.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Cursor, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use xml::escape::escape_str_pcdata;
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+use zip::write::{FileOptions, ZipWriter};
+use zip::CompressionMethod;
+
+use crate::doc::{EpubDoc, NavPoint};
+use crate::error::EpubError;
+use crate::navigation;
+
+const OPF_NS: &str = "http://www.idpf.org/2007/opf";
+pub(crate) const DC_NS: &str = "http://purl.org/dc/elements/1.1/";
+pub(crate) const XHTML_NS: &str = "http://www.w3.org/1999/xhtml";
+pub(crate) const OPS_NS: &str = "http://www.idpf.org/2007/ops";
+const CONTAINER_NS: &str = "urn:oasis:names:tc:opendocument:xmlns:container";
+
+/// Characters that are percent encoded in the generated hrefs
+const HREF_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Base path of the package document and every resource in the generated epub
+pub(crate) const ROOT_BASE: &str = "OEBPS";
+
+/// Files generated next to the package document, relative to it, that
+/// can't be used by the resources
+const PACKAGE_FILES: &[&str] = &["content.opf", "nav.xhtml", "toc.ncx"];
+
+/// Metadata names that are written as Dublin Core elements, the rest
+/// are written as `<meta property="...">`
+pub(crate) const DC_ELEMENTS: &[&str] = &[
+    "contributor",
+    "coverage",
+    "creator",
+    "date",
+    "description",
+    "format",
+    "identifier",
+    "language",
+    "publisher",
+    "relation",
+    "rights",
+    "source",
+    "subject",
+    "title",
+    "type",
+];
+
+/// A resource that will be written in the epub
+struct BuilderResource {
+    /// manifest id
+    id: String,
+    /// path relative to the package document
+    path: PathBuf,
+    /// resource mimetype
+    mime: String,
+    /// raw content
+    content: Vec<u8>,
+}
+
+/// Struct to build a new epub document
+///
+/// # Examples
+///
+/// ```
+/// use epub::builder::EpubBuilder;
+/// use epub::doc::EpubDoc;
+/// use std::io::Cursor;
+///
+/// let mut builder = EpubBuilder::new();
+/// builder
+///     .metadata("title", "My book")
+///     .metadata("creator", "Me")
+///     .metadata("language", "en")
+///     .metadata("identifier", "urn:isbn:9780000000000")
+///     .add_content("chapter1.xhtml", "<html>...</html>".as_bytes().to_vec())
+///     .add_toc_entry("Chapter 1", "chapter1.xhtml");
+///
+/// let epub = builder.write_to(Cursor::new(vec![])).unwrap();
+/// let doc = EpubDoc::from_reader(Cursor::new(epub.into_inner())).unwrap();
+/// assert_eq!(doc.mdata("title").unwrap(), "My book");
+/// ```
+#[derive(Default)]
+pub struct EpubBuilder {
+    /// metadata stored as name -> value, in insertion order
+    metadata: Vec<(String, String)>,
+
+    /// every resource, in manifest order
+    resources: Vec<BuilderResource>,
+
+    /// ids of the resources in reading order
+    spine: Vec<String>,
+
+    /// table of content, with paths relative to the epub root
+    pub toc: Vec<NavPoint>,
+}
+
+/// An epub file generated in memory
+pub struct EpubOutput {
+    /// the epub file content
+    pub content: Vec<u8>,
+}
+
+impl EpubOutput {
+    /// Writes the epub to the `path` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), EpubError> {
+        fs::write(path, &self.content)?;
+        Ok(())
+    }
+
+    /// Opens the generated epub.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub can't be parsed.
+    pub fn into_doc(self) -> Result<EpubDoc<Cursor<Vec<u8>>>, EpubError> {
+        EpubDoc::from_bytes(self.content)
+    }
+}
+
+impl EpubBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> EpubBuilder {
+        EpubBuilder::default()
+    }
+
+    /// Creates a builder for the skeleton of a valid epub, with the
+    /// required metadata and one empty chapter.
+    ///
+    /// A random `urn:uuid:` identifier is generated, more content can be
+    /// added to the returned builder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::builder::EpubBuilder;
+    /// use epub::doc::EpubDoc;
+    /// use std::io::Cursor;
+    ///
+    /// let epub = EpubBuilder::minimal("My book", "Me", "en")
+    ///     .write_to(Cursor::new(vec![]))
+    ///     .unwrap();
+    /// let doc = EpubDoc::from_reader(Cursor::new(epub.into_inner())).unwrap();
+    /// assert_eq!(doc.mdata("creator").unwrap(), "Me");
+    /// assert_eq!(1, doc.spine.len());
+    /// ```
+    pub fn minimal(title: &str, author: &str, lang: &str) -> EpubBuilder {
+        let mut builder = EpubBuilder::new();
+        builder
+            .metadata("identifier", &format!("urn:uuid:{}", new_uuid()))
+            .metadata("title", title)
+            .metadata("creator", author)
+            .metadata("language", lang)
+            .add_chapter(title, "");
+        builder
+    }
+
+    /// Adds a metadata entry.
+    ///
+    /// Dublin Core names like "title", "creator", "language" or
+    /// "identifier" are written as `dc:` elements, any other name is
+    /// written as a `<meta property="name">` element. The first
+    /// "identifier" is used as the package unique identifier.
+    pub fn metadata(&mut self, name: &str, value: &str) -> &mut Self {
+        self.metadata.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds a resource to the manifest, not included in the spine.
+    ///
+    /// The `path` is relative to the package document.
+    pub fn add_resource<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        content: Vec<u8>,
+        mime: &str,
+    ) -> &mut Self {
+        self.push_resource(path.as_ref(), content, mime);
+        self
+    }
+
+    /// Adds a xhtml content document to the manifest and to the end of the
+    /// spine.
+    ///
+    /// The `path` is relative to the package document.
+    pub fn add_content<P: AsRef<Path>>(&mut self, path: P, content: Vec<u8>) -> &mut Self {
+        let id = self.push_resource(path.as_ref(), content, "application/xhtml+xml");
+        self.spine.push(id);
+        self
+    }
+
+    /// Adds a resource to the manifest and to the end of the spine, with any
+    /// media type
+    pub(crate) fn add_spine_resource(&mut self, path: &Path, content: Vec<u8>, mime: &str) {
+        let id = self.push_resource(path, content, mime);
+        self.spine.push(id);
+    }
+
+    /// Checks if there's a resource, or a generated file, in `path`,
+    /// relative to the package document, or inside the `path` directory
+    pub(crate) fn has_path(&self, path: &Path) -> bool {
+        self.resources.iter().any(|r| r.path.starts_with(path))
+            || PACKAGE_FILES.iter().any(|f| Path::new(f).starts_with(path))
+    }
+
+    /// Adds a new chapter with the `title`, to the manifest, the spine and
+    /// the table of content.
+    ///
+    /// The `xhtml` is the chapter body content, that is wrapped in a valid
+    /// xhtml content document. If it's already a complete document, with
+    /// the `<html>` element, it's used as is.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::builder::EpubBuilder;
+    ///
+    /// let mut builder = EpubBuilder::new();
+    /// builder
+    ///     .add_chapter("Chapter 1", "<h1>Chapter 1</h1><p>Once upon a time</p>")
+    ///     .add_chapter("Chapter 2", "<h1>Chapter 2</h1><p>The end</p>");
+    /// assert_eq!(2, builder.toc.len());
+    /// ```
+    pub fn add_chapter(&mut self, title: &str, xhtml: &str) -> &mut Self {
+        let mut n = self.spine.len() + 1;
+        let mut path = PathBuf::from(format!("chapter_{}.xhtml", n));
+        while self.resources.iter().any(|r| r.path == path) {
+            n += 1;
+            path = PathBuf::from(format!("chapter_{}.xhtml", n));
+        }
+
+        let content = if is_xhtml_document(xhtml) {
+            xhtml.to_string()
+        } else {
+            xhtml_document(title, xhtml)
+        };
+        self.add_content(&path, content.into_bytes());
+        self.add_toc_entry(title, &path)
+    }
+
+    /// Adds a new chapter with the `title`, converting the `markdown` to
+    /// xhtml.
+    ///
+    /// The markdown headings are added as children of the chapter table of
+    /// content entry. If the markdown starts with a single top level
+    /// heading equal to the title, its subheadings are used instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::builder::EpubBuilder;
+    ///
+    /// let mut builder = EpubBuilder::new();
+    /// builder.add_chapter_md("Chapter 1", "# Chapter 1\n\n## Start\n\nOnce *upon* a time");
+    /// assert_eq!(1, builder.toc.len());
+    /// assert_eq!("Start", builder.toc[0].children[0].label);
+    /// ```
+    #[cfg(feature = "markdown")]
+    pub fn add_chapter_md(&mut self, title: &str, markdown: &str) -> &mut Self {
+        let (body, headings) = crate::markdown::to_xhtml(markdown);
+        self.add_chapter(title, &body);
+
+        let chapter = self.toc.last_mut().expect("chapter toc entry");
+        let headings: Vec<_> = headings
+            .into_iter()
+            .map(|h| (chapter.content.clone(), h))
+            .collect();
+        let mut children = navigation::heading_navpoints(&headings);
+        if children.len() == 1 && children[0].label == title {
+            children = children.remove(0).children;
+        }
+        let mut play_order = chapter.play_order;
+        navigation::renumber(&mut children, &mut play_order);
+        chapter.children = children;
+        self
+    }
+
+    /// Adds an entry at the end of the table of content pointing to `path`,
+    /// relative to the package document.
+    pub fn add_toc_entry<P: AsRef<Path>>(&mut self, label: &str, path: P) -> &mut Self {
+        let navpoint = NavPoint {
+            label: label.to_string(),
+            content: Path::new(ROOT_BASE).join(path),
+            children: vec![],
+            play_order: navpoint_count(&self.toc) + 1,
+        };
+        self.toc.push(navpoint);
+        self
+    }
+
+    /// Replaces the table of content with the `h1`, `h2` and `h3` headings
+    /// of the content documents, in reading order.
+    ///
+    /// Lower level headings are nested under the previous higher level
+    /// heading. Entries point to the heading id, or to the document if the
+    /// heading doesn't have an id.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::builder::EpubBuilder;
+    ///
+    /// let mut builder = EpubBuilder::new();
+    /// builder
+    ///     .add_content("one.xhtml", br#"<html><body><h1 id="one">One</h1>
+    ///         <h2 id="two">Two</h2></body></html>"#.to_vec())
+    ///     .toc_from_headings();
+    /// assert_eq!("One", builder.toc[0].label);
+    /// assert_eq!("Two", builder.toc[0].children[0].label);
+    /// ```
+    pub fn toc_from_headings(&mut self) -> &mut Self {
+        let mut headings = vec![];
+        for id in self.spine.iter() {
+            if let Some(res) = self.resources.iter().find(|r| &r.id == id) {
+                let path = Path::new(ROOT_BASE).join(&res.path);
+                for h in navigation::find_headings(&res.content, 3) {
+                    headings.push((path.clone(), h));
+                }
+            }
+        }
+        self.toc = navigation::heading_navpoints(&headings);
+        navigation::renumber(&mut self.toc, &mut 0);
+        self
+    }
+
+    /// Generates the epub in memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some required metadata is missing or if two
+    /// resources have the same path.
+    pub fn build(&self) -> Result<EpubOutput, EpubError> {
+        let content = self.write_to(Cursor::new(vec![]))?.into_inner();
+        Ok(EpubOutput { content })
+    }
+
+    /// Writes the epub to the `path` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created, if some required
+    /// metadata is missing or if two resources have the same path.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), EpubError> {
+        let file = File::create(path)?;
+        self.write_to(file)?;
+        Ok(())
+    }
+
+    /// Writes the epub to `writer`, returning it back.
+    ///
+    /// The mimetype is written first and uncompressed, followed by the
+    /// container file, the package document, the navigation documents and
+    /// every resource. The table of content is written both as an EPUB3 nav
+    /// document and as an EPUB2 toc.ncx, for older readers.
+    ///
+    /// The entries are always written in the same order, with a fixed
+    /// timestamp, so the output is reproducible if the "dcterms:modified"
+    /// metadata is set, otherwise the current time is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the title, language or identifier metadata is
+    /// missing, [`EpubError::AlreadyExists`] if two resources have the same
+    /// path, or one of the generated files path, like "nav.xhtml", or if
+    /// something fails writing the zip.
+    pub fn write_to<W: Write + Seek>(&self, writer: W) -> Result<W, EpubError> {
+        for required in ["identifier", "title", "language"].iter() {
+            if self.get_metadata(required).is_none() {
+                return Err(EpubError::MissingMetadata(required.to_string()));
+            }
+        }
+        self.check_paths()?;
+
+        let mut zip = ZipWriter::new(writer);
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        zip.start_file("META-INF/container.xml", deflated)?;
+        zip.write_all(&self.container_xml()?)?;
+
+        zip.start_file(format!("{}/content.opf", ROOT_BASE), deflated)?;
+        zip.write_all(&self.package_document()?)?;
+
+        let title = self.get_metadata("title").unwrap_or_default();
+        let uid = self.get_metadata("identifier").unwrap_or_default();
+        let base = Path::new(ROOT_BASE);
+        zip.start_file(format!("{}/nav.xhtml", ROOT_BASE), deflated)?;
+        zip.write_all(&navigation::nav_document(title, &self.toc, base)?)?;
+        zip.start_file(format!("{}/toc.ncx", ROOT_BASE), deflated)?;
+        zip.write_all(&navigation::ncx_document(uid, title, &self.toc, base)?)?;
+
+        for res in self.resources.iter() {
+            let path = Path::new(ROOT_BASE).join(&res.path);
+            zip.start_file(zip_name(&path), deflated)?;
+            zip.write_all(&res.content)?;
+        }
+
+        Ok(zip.finish()?)
+    }
+
+    pub(crate) fn get_metadata(&self, name: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub(crate) fn has_metadata(&self, name: &str, value: &str) -> bool {
+        self.metadata.iter().any(|(k, v)| k == name && v == value)
+    }
+
+    /// Checks that every resource is written to a different zip entry, not
+    /// used by the generated files
+    fn check_paths(&self) -> Result<(), EpubError> {
+        let mut names: HashSet<String> = ["mimetype", "META-INF/container.xml"]
+            .iter()
+            .map(|name| name.to_string())
+            .chain(PACKAGE_FILES.iter().map(|f| format!("{}/{}", ROOT_BASE, f)))
+            .collect();
+        for res in self.resources.iter() {
+            if !names.insert(zip_name(&Path::new(ROOT_BASE).join(&res.path))) {
+                return Err(EpubError::AlreadyExists(res.path.display().to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn push_resource(&mut self, path: &Path, content: Vec<u8>, mime: &str) -> String {
+        let id = self.unique_id(path);
+        self.resources.push(BuilderResource {
+            id: id.clone(),
+            path: path.to_path_buf(),
+            mime: mime.to_string(),
+            content,
+        });
+        id
+    }
+
+    /// Builds a valid xml id from the resource path, adding a suffix if
+    /// the id is already in use
+    fn unique_id(&self, path: &Path) -> String {
+        let mut base: String = zip_name(path)
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '.' | '_' => c,
+                _ => '_',
+            })
+            .collect();
+        if !base.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            base = format!("id_{}", base);
+        }
+
+        let mut id = base.clone();
+        let mut n = 1;
+        while id == "nav" || id == "ncx" || self.resources.iter().any(|r| r.id == id) {
+            id = format!("{}_{}", base, n);
+            n += 1;
+        }
+        id
+    }
+
+    fn container_xml(&self) -> Result<Vec<u8>, EpubError> {
+        let mut b = Vec::new();
+        {
+            let mut w = xml_writer(&mut b);
+            let opf = format!("{}/content.opf", ROOT_BASE);
+            w.write(
+                XmlEvent::start_element("container")
+                    .default_ns(CONTAINER_NS)
+                    .attr("version", "1.0"),
+            )?;
+            w.write(XmlEvent::start_element("rootfiles"))?;
+            w.write(
+                XmlEvent::start_element("rootfile")
+                    .attr("full-path", &opf)
+                    .attr("media-type", "application/oebps-package+xml"),
+            )?;
+            w.write(XmlEvent::end_element())?;
+            w.write(XmlEvent::end_element())?;
+            w.write(XmlEvent::end_element())?;
+        }
+        Ok(b)
+    }
+
+    fn package_document(&self) -> Result<Vec<u8>, EpubError> {
+        let mut b = Vec::new();
+        {
+            let mut w = xml_writer(&mut b);
+            w.write(
+                XmlEvent::start_element("package")
+                    .default_ns(OPF_NS)
+                    .attr("version", "3.0")
+                    .attr("unique-identifier", "uid"),
+            )?;
+
+            w.write(XmlEvent::start_element("metadata").ns("dc", DC_NS))?;
+            let mut has_uid = false;
+            for (k, v) in self.metadata.iter() {
+                if DC_ELEMENTS.contains(&k.as_str()) {
+                    let name = format!("dc:{}", k);
+                    if k == "identifier" && !has_uid {
+                        has_uid = true;
+                        w.write(XmlEvent::start_element(name.as_str()).attr("id", "uid"))?;
+                    } else {
+                        w.write(XmlEvent::start_element(name.as_str()))?;
+                    }
+                } else {
+                    w.write(XmlEvent::start_element("meta").attr("property", k))?;
+                }
+                w.write(XmlEvent::characters(v))?;
+                w.write(XmlEvent::end_element())?;
+            }
+            if self.get_metadata("dcterms:modified").is_none() {
+                let modified = utc_timestamp(SystemTime::now());
+                w.write(XmlEvent::start_element("meta").attr("property", "dcterms:modified"))?;
+                w.write(XmlEvent::characters(&modified))?;
+                w.write(XmlEvent::end_element())?;
+            }
+            w.write(XmlEvent::end_element())?;
+
+            w.write(XmlEvent::start_element("manifest"))?;
+            w.write(
+                XmlEvent::start_element("item")
+                    .attr("id", "nav")
+                    .attr("href", "nav.xhtml")
+                    .attr("media-type", "application/xhtml+xml")
+                    .attr("properties", "nav"),
+            )?;
+            w.write(XmlEvent::end_element())?;
+            w.write(
+                XmlEvent::start_element("item")
+                    .attr("id", "ncx")
+                    .attr("href", "toc.ncx")
+                    .attr("media-type", "application/x-dtbncx+xml"),
+            )?;
+            w.write(XmlEvent::end_element())?;
+            for res in self.resources.iter() {
+                let href = encode_href(&zip_name(&res.path));
+                w.write(
+                    XmlEvent::start_element("item")
+                        .attr("id", &res.id)
+                        .attr("href", &href)
+                        .attr("media-type", &res.mime),
+                )?;
+                w.write(XmlEvent::end_element())?;
+            }
+            w.write(XmlEvent::end_element())?;
+
+            w.write(XmlEvent::start_element("spine").attr("toc", "ncx"))?;
+            for id in self.spine.iter() {
+                w.write(XmlEvent::start_element("itemref").attr("idref", id))?;
+                w.write(XmlEvent::end_element())?;
+            }
+            w.write(XmlEvent::end_element())?;
+
+            w.write(XmlEvent::end_element())?;
+        }
+        Ok(b)
+    }
+}
+
+/// Generates a random version 4 uuid
+pub(crate) fn new_uuid() -> String {
+    // RandomState is randomly seeded, so each hasher gives a new value
+    let random = || RandomState::new().build_hasher().finish();
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random().to_be_bytes());
+    bytes[8..].copy_from_slice(&random().to_be_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Number of navpoints in the tree
+fn navpoint_count(navpoints: &[NavPoint]) -> usize {
+    navpoints
+        .iter()
+        .map(|nav| 1 + navpoint_count(&nav.children))
+        .sum()
+}
+
+/// Checks if the content is a complete xhtml document instead of a
+/// fragment of the body
+fn is_xhtml_document(content: &str) -> bool {
+    let content = content.trim_start();
+    content.starts_with("<?xml") || content.starts_with("<!DOCTYPE") || content.starts_with("<html")
+}
+
+/// Wraps a body fragment in a xhtml content document
+fn xhtml_document(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="{}" xmlns:epub="{}">
+<head>
+  <title>{}</title>
+</head>
+<body>
+{}
+</body>
+</html>
+"#,
+        XHTML_NS,
+        OPS_NS,
+        escape_str_pcdata(title),
+        body
+    )
+}
+
+fn xml_writer<W: Write>(sink: W) -> EventWriter<W> {
+    EmitterConfig::new()
+        .perform_indent(true)
+        .create_writer(sink)
+}
+
+/// Converts a path to a zip entry name, using always unix separators
+pub(crate) fn zip_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the path to reach `target` from a document inside the `base`
+/// directory, both paths relative to the epub root
+pub(crate) fn relative_path(base: &Path, target: &Path) -> String {
+    let base: Vec<_> = base.components().collect();
+    let target: Vec<_> = target.components().collect();
+    let common = base
+        .iter()
+        .zip(target.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts: Vec<String> = vec![String::from(".."); base.len() - common];
+    for c in target[common..].iter() {
+        if let Component::Normal(s) = c {
+            parts.push(s.to_string_lossy().to_string());
+        }
+    }
+    parts.join("/")
+}
+
+pub(crate) fn encode_href(href: &str) -> String {
+    utf8_percent_encode(href, HREF_ENCODE_SET).to_string()
+}
+
+/// Formats the time as UTC "CCYY-MM-DDThh:mm:ssZ", the format required by
+/// the dcterms:modified property
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = ((secs / 86400) as i64, secs % 86400);
+
+    // days since epoch to civil date, from Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,183 @@
+//! The spine step of the EPUB canonical fragment identifiers.
+//!
+//! A CFI like `epubcfi(/6/4[chap01]!/4/2/1:3)` points to a position in a
+//! content document. The steps before the `!` select the spine item: `/6`
+//! is the spine element of the package document and `/4` is its second
+//! itemref, with the id of the itemref in brackets. The steps inside the
+//! document aren't resolved, the position is mapped to the document only.
+
+use std::io::{Read, Seek};
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+
+/// Step of the spine element, the third child of the package element
+const SPINE_STEP: usize = 6;
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the CFI of the start of the spine item `index`, with the
+    /// step of the itemref and its id if it has one, like
+    /// `epubcfi(/6/4[chap01]!)`. Returns None if the index isn't valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!(Some(String::from("epubcfi(/6/2!)")), doc.spine_cfi(0));
+    /// assert_eq!(Some(String::from("epubcfi(/6/8!)")), doc.spine_cfi(3));
+    /// assert_eq!(None, doc.spine_cfi(50));
+    /// ```
+    pub fn spine_cfi(&self, index: usize) -> Option<String> {
+        let item = self.spine.get(index)?;
+        let assertion = match item.id {
+            Some(ref id) => format!("[{}]", escape(id)),
+            None => String::new(),
+        };
+        Some(format!(
+            "epubcfi(/{}/{}{}!)",
+            SPINE_STEP,
+            (index + 1) * 2,
+            assertion
+        ))
+    }
+
+    /// Returns the spine index of the document pointed by the `cfi`. The
+    /// id assertion of the itemref step, if any, takes precedence over
+    /// the position, so CFIs created before the spine changed still find
+    /// the document. The `epubcfi(...)` wrapper is optional.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!(3, doc.cfi_spine_index("epubcfi(/6/8!/4/2/1:3)").unwrap());
+    /// assert!(doc.cfi_spine_index("epubcfi(/6/80!)").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::InvalidOperation` if the CFI isn't valid or
+    /// doesn't point to a spine item.
+    pub fn cfi_spine_index(&self, cfi: &str) -> Result<usize, EpubError> {
+        let (step, assertion) = itemref_step(cfi)
+            .ok_or_else(|| EpubError::InvalidOperation(format!("invalid cfi {}", cfi)))?;
+
+        if let Some(assertion) = assertion {
+            let by_id = self
+                .spine
+                .iter()
+                .position(|item| item.id.as_deref() == Some(assertion.as_str()));
+            if let Some(index) = by_id {
+                return Ok(index);
+            }
+        }
+        if step < 2 || step % 2 != 0 || step / 2 > self.spine.len() {
+            return Err(EpubError::InvalidOperation(format!(
+                "the cfi {} isn't in the spine",
+                cfi
+            )));
+        }
+        Ok(step / 2 - 1)
+    }
+
+    /// Changes the current page to the document pointed by the `cfi`. See
+    /// `cfi_spine_index`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.go_to_cfi("epubcfi(/6/6!/4/2)").unwrap();
+    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::InvalidOperation` if the CFI isn't valid or
+    /// doesn't point to a spine item. The current page doesn't change.
+    pub fn go_to_cfi(&mut self, cfi: &str) -> Result<(), EpubError> {
+        let index = self.cfi_spine_index(cfi)?;
+        self.set_current_page(index)
+    }
+}
+
+/// Returns the spine index of the itemref step of the `cfi`, without
+/// checking that the spine has the item, or None if the CFI isn't valid
+pub(crate) fn spine_index(cfi: &str) -> Option<usize> {
+    let (step, _) = itemref_step(cfi)?;
+    if step < 2 || step % 2 != 0 {
+        return None;
+    }
+    Some(step / 2 - 1)
+}
+
+/// Parses the index and the id assertion of the itemref step of the `cfi`
+fn itemref_step(cfi: &str) -> Option<(usize, Option<String>)> {
+    let path = cfi.trim();
+    let path = match path.strip_prefix("epubcfi(") {
+        Some(path) => path.strip_suffix(')')?,
+        None => path,
+    };
+    let spine_path = path.split('!').next().unwrap_or_default();
+    match steps(spine_path)?.as_slice() {
+        [(spine, _), itemref] if *spine == SPINE_STEP => Some(itemref.clone()),
+        _ => None,
+    }
+}
+
+/// Parses the steps of a CFI path, like `/6/4[chap01]`, as the index and
+/// the id assertion of each step
+fn steps(path: &str) -> Option<Vec<(usize, Option<String>)>> {
+    let mut steps = vec![];
+    let mut chars = path.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '/' {
+            return None;
+        }
+        let mut index = String::new();
+        while let Some(c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+            index.push(*c);
+            chars.next();
+        }
+        let index = index.parse().ok()?;
+        let mut assertion = None;
+        if chars.peek() == Some(&'[') {
+            chars.next();
+            let mut id = String::new();
+            // the assertion may have parameters after the id, like [id;s=b]
+            let mut parameters = false;
+            loop {
+                match chars.next()? {
+                    '^' => {
+                        let c = chars.next()?;
+                        if !parameters {
+                            id.push(c);
+                        }
+                    }
+                    ']' => break,
+                    ';' => parameters = true,
+                    c if !parameters => id.push(c),
+                    _ => {}
+                }
+            }
+            assertion = Some(id).filter(|id| !id.is_empty());
+        }
+        steps.push((index, assertion));
+    }
+    Some(steps)
+}
+
+/// Escapes the special characters of a CFI with `^`
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '^' | '[' | ']' | '(' | ')' | ',' | ';' | '=') {
+            escaped.push('^');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
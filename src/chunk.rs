@@ -0,0 +1,285 @@
+//! The splitting of the text of the book in chunks of a bounded size, for
+//! the text to speech engines or the embeddings of the language models.
+//!
+//! The chunks are made of whole paragraphs when they fit, of whole
+//! sentences when a paragraph is too long, and of words, or characters,
+//! only when a sentence is too long. Each chunk is a slice of the text of
+//! its spine item, see `EpubDoc::get_text`, so its offset maps it back to
+//! the book.
+
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+
+/// The bounds of the chunks of `EpubDoc::chunks`
+///
+/// # Examples
+///
+/// ```
+/// use epub::chunk::ChunkOptions;
+///
+/// let mut options = ChunkOptions::new();
+/// options.max_chars(2000).max_tokens(512);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChunkOptions {
+    max_chars: usize,
+    max_tokens: Option<usize>,
+    tokenizer: fn(&str) -> usize,
+}
+
+impl Default for ChunkOptions {
+    fn default() -> ChunkOptions {
+        ChunkOptions {
+            max_chars: 1000,
+            max_tokens: None,
+            tokenizer: estimate_tokens,
+        }
+    }
+}
+
+impl ChunkOptions {
+    /// Creates the default options: chunks of at most 1000 characters, and
+    /// no bound of tokens
+    pub fn new() -> ChunkOptions {
+        ChunkOptions::default()
+    }
+
+    /// Sets the maximum number of characters of a chunk, at least 1
+    pub fn max_chars(&mut self, max: usize) -> &mut Self {
+        self.max_chars = max.max(1);
+        self
+    }
+
+    /// Sets the maximum number of tokens of a chunk, counted with the
+    /// `tokenizer`
+    pub fn max_tokens(&mut self, max: usize) -> &mut Self {
+        self.max_tokens = Some(max.max(1));
+        self
+    }
+
+    /// Sets the function that counts the tokens of a text, `estimate_tokens`
+    /// by default
+    pub fn tokenizer(&mut self, tokenizer: fn(&str) -> usize) -> &mut Self {
+        self.tokenizer = tokenizer;
+        self
+    }
+
+    fn fits(&self, text: &str) -> bool {
+        text.chars().count() <= self.max_chars
+            && self
+                .max_tokens
+                .map_or(true, |max| (self.tokenizer)(text) <= max)
+    }
+}
+
+/// Estimates the number of tokens of `text` for the language models, about
+/// four characters a token
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(3, epub::chunk::estimate_tokens("Hello, world"));
+/// ```
+pub fn estimate_tokens(text: &str) -> usize {
+    (text.chars().count() + 3) / 4
+}
+
+/// A chunk of the text of a spine item
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chunk {
+    /// the index of the item in the spine
+    pub spine_index: usize,
+    /// the byte offset of the chunk in the text of the item
+    pub offset: usize,
+    /// the text of the chunk
+    pub text: String,
+}
+
+/// Splits `text` in chunks bounded by the `options`, and returns their byte
+/// ranges in `text`. The chunks don't start or end with whitespace.
+///
+/// # Examples
+///
+/// ```
+/// use epub::chunk::{chunk_text, ChunkOptions};
+///
+/// let text = "One sentence. Another sentence.\n\nA paragraph.";
+/// let mut options = ChunkOptions::new();
+/// options.max_chars(20);
+/// let chunks: Vec<&str> = chunk_text(text, &options)
+///     .into_iter()
+///     .map(|range| &text[range])
+///     .collect();
+/// assert_eq!(vec!["One sentence.", "Another sentence.", "A paragraph."], chunks);
+/// ```
+pub fn chunk_text(text: &str, options: &ChunkOptions) -> Vec<Range<usize>> {
+    let mut chunks = vec![];
+    pack(text, 0..text.len(), Level::Paragraph, options, &mut chunks);
+    chunks
+}
+
+/// The units of the text, from the largest
+#[derive(Clone, Copy)]
+enum Level {
+    Paragraph,
+    Sentence,
+    Word,
+    Char,
+}
+
+impl Level {
+    fn next(self) -> Option<Level> {
+        match self {
+            Level::Paragraph => Some(Level::Sentence),
+            Level::Sentence => Some(Level::Word),
+            Level::Word => Some(Level::Char),
+            Level::Char => None,
+        }
+    }
+}
+
+/// Adds the chunks of the `range` of `text`, packing its units of the
+/// `level`, and splitting in smaller units the ones that don't fit
+fn pack(
+    text: &str,
+    range: Range<usize>,
+    level: Level,
+    options: &ChunkOptions,
+    chunks: &mut Vec<Range<usize>>,
+) {
+    let mut current: Option<Range<usize>> = None;
+    for unit in units(text, range, level) {
+        if let Some(ref chunk) = current {
+            if options.fits(&text[chunk.start..unit.end]) {
+                current = Some(chunk.start..unit.end);
+                continue;
+            }
+            chunks.push(chunk.clone());
+            current = None;
+        }
+        if options.fits(&text[unit.clone()]) {
+            current = Some(unit);
+        } else {
+            match level.next() {
+                Some(next) => pack(text, unit, next, options, chunks),
+                // a single character is always a chunk
+                None => chunks.push(unit),
+            }
+        }
+    }
+    chunks.extend(current);
+}
+
+/// The ranges of the units of the `level` in the `range` of `text`, without
+/// the whitespace around them
+fn units(text: &str, range: Range<usize>, level: Level) -> Vec<Range<usize>> {
+    let start = range.start;
+    let part = &text[range];
+    let mut units = vec![];
+    match level {
+        Level::Paragraph => {
+            let mut offset = 0;
+            for paragraph in part.split("\n\n") {
+                units.push(trimmed(part, offset..offset + paragraph.len()));
+                offset += paragraph.len() + 2;
+            }
+        }
+        Level::Sentence => {
+            // a sentence ends at a punctuation, with its closing quotes,
+            // followed by whitespace, or at a line break
+            let mut begin = 0;
+            let mut chars = part.char_indices().peekable();
+            let mut after_end = false;
+            while let Some((i, c)) = chars.next() {
+                let next = chars.peek().map(|(_, next)| *next);
+                if matches!(c, '.' | '!' | '?' | '…' | '。' | '！' | '？') {
+                    after_end = true;
+                } else if !matches!(c, '"' | '\'' | '”' | '’' | '»' | ')' | ']') {
+                    after_end = after_end && c.is_whitespace();
+                }
+                let boundary = c == '\n'
+                    || (after_end && !c.is_whitespace() && next.map_or(true, char::is_whitespace));
+                if boundary || next.is_none() {
+                    let end = i + c.len_utf8();
+                    units.push(trimmed(part, begin..end));
+                    begin = end;
+                    after_end = false;
+                }
+            }
+        }
+        Level::Word => {
+            let mut begin = None;
+            for (i, c) in part.char_indices() {
+                match (c.is_whitespace(), begin) {
+                    (false, None) => begin = Some(i),
+                    (true, Some(b)) => {
+                        units.push(b..i);
+                        begin = None;
+                    }
+                    _ => {}
+                }
+            }
+            units.extend(begin.map(|b| b..part.len()));
+        }
+        Level::Char => {
+            units.extend(part.char_indices().map(|(i, c)| i..i + c.len_utf8()));
+        }
+    }
+    units
+        .into_iter()
+        .filter(|unit| !unit.is_empty())
+        .map(|unit| start + unit.start..start + unit.end)
+        .collect()
+}
+
+/// The `range` of `text` without the whitespace around it
+fn trimmed(text: &str, range: Range<usize>) -> Range<usize> {
+    let part = &text[range.clone()];
+    let start = range.start + (part.len() - part.trim_start().len());
+    let end = range.end - (part.len() - part.trim_end().len());
+    start..end.max(start)
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Splits the text of the linear spine items in chunks bounded by the
+    /// `options`, in reading order, see `chunk_text`. A chunk never spans
+    /// two spine items.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// use epub::chunk::ChunkOptions;
+    ///
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let mut options = ChunkOptions::new();
+    /// options.max_chars(500);
+    /// for chunk in doc.chunks(&options).unwrap() {
+    ///     assert!(chunk.text.chars().count() <= 500);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn chunks(&mut self, options: &ChunkOptions) -> Result<Vec<Chunk>, EpubError> {
+        let mut chunks = vec![];
+        for index in 0..self.spine.len() {
+            if !self.spine[index].linear {
+                continue;
+            }
+            let text = self.get_text(index)?;
+            for range in chunk_text(&text, options) {
+                chunks.push(Chunk {
+                    spine_index: index,
+                    offset: range.start,
+                    text: text[range].to_string(),
+                });
+            }
+        }
+        Ok(chunks)
+    }
+}
@@ -0,0 +1,252 @@
+//! The EPUB Dictionaries and Glossaries: the search key maps that index
+//! the headwords of a dictionary, or of the glossaries of a book, and
+//! point to their definitions.
+//!
+//! A dictionary is a publication with `<dc:type>dictionary</dc:type>`.
+//! Its search key map is the manifest item with the "dictionary" and
+//! "search-key-map" properties, like
+//!
+//! ```xml
+//! <search-key-map xmlns="http://www.idpf.org/2007/ops" xml:lang="en">
+//!   <search-key-group href="dict.xhtml#run">
+//!     <match value="run"><value value="ran"/><value value="running"/></match>
+//!   </search-key-group>
+//! </search-key-map>
+//! ```
+//!
+//! The glossaries of the other publications have the same search key maps,
+//! with the "glossary" property.
+
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::anchor::element_source;
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::href;
+use crate::semantics::html_text;
+use crate::xmlutils::{self, XMLNode};
+
+/// The media type of the search key maps
+pub const SEARCH_KEY_MAP_MIME: &str = "application/vnd.epub.search-key-map+xml";
+
+/// A headword of a search key map
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchKey {
+    /// the headword, the `value` of the `match` element
+    pub value: String,
+    /// the inflected forms that find the headword too, like "ran" for
+    /// "run"
+    pub inflections: Vec<String>,
+    /// the full path of the definition, with the fragment
+    pub href: PathBuf,
+}
+
+impl SearchKey {
+    /// Returns true if `word` is the headword or one of its inflections,
+    /// ignoring the case
+    pub fn matches(&self, word: &str) -> bool {
+        let word = word.trim().to_lowercase();
+        self.value.to_lowercase() == word
+            || self.inflections.iter().any(|i| i.to_lowercase() == word)
+    }
+}
+
+/// A search key map document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchKeyMap {
+    /// the manifest id of the document
+    pub id: String,
+    /// the full path of the document in the archive
+    pub path: PathBuf,
+    /// the language of the headwords, the `xml:lang` of the root
+    pub lang: Option<String>,
+    /// the headwords, in document order
+    pub keys: Vec<SearchKey>,
+}
+
+impl SearchKeyMap {
+    /// Parses the search key map `content` at `path`
+    pub(crate) fn parse(id: &str, content: &[u8], path: &Path) -> Result<SearchKeyMap, EpubError> {
+        let root = xmlutils::XMLReader::parse(content)?;
+        let root = root.borrow();
+        let lang = root
+            .attrs
+            .iter()
+            .find(|attr| attr.name.local_name == "lang")
+            .map(|attr| attr.value.clone());
+        let mut keys = vec![];
+        collect_keys(&root, path, None, &mut keys);
+        Ok(SearchKeyMap {
+            id: id.to_string(),
+            path: path.to_path_buf(),
+            lang,
+            keys,
+        })
+    }
+
+    /// Returns the keys that match the `word`, see `SearchKey::matches`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::dictionary::{SearchKey, SearchKeyMap};
+    /// # use std::path::PathBuf;
+    ///
+    /// let map = SearchKeyMap {
+    ///     id: String::from("skm"),
+    ///     path: PathBuf::from("skm.xml"),
+    ///     lang: None,
+    ///     keys: vec![SearchKey {
+    ///         value: String::from("run"),
+    ///         inflections: vec![String::from("ran")],
+    ///         href: PathBuf::from("dict.xhtml#run"),
+    ///     }],
+    /// };
+    /// assert_eq!(1, map.lookup("Ran").len());
+    /// assert!(map.lookup("walk").is_empty());
+    /// ```
+    pub fn lookup(&self, word: &str) -> Vec<&SearchKey> {
+        self.keys.iter().filter(|key| key.matches(word)).collect()
+    }
+}
+
+/// The definition of a headword, found with `EpubDoc::lookup`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Definition {
+    /// the headword of the search key map
+    pub headword: String,
+    /// the full path of the definition, with the fragment
+    pub href: PathBuf,
+    /// the source of the element of the definition, or the whole document
+    /// if the href doesn't have a fragment
+    pub html: String,
+    /// the text of the definition, with the whitespace collapsed
+    pub text: String,
+}
+
+/// Adds the `match` elements under `node`, with the href of their
+/// `search-key-group` if they don't have one
+fn collect_keys(node: &XMLNode, path: &Path, group: Option<&str>, keys: &mut Vec<SearchKey>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        match child.name.local_name.as_str() {
+            "search-key-group" => {
+                let group = child.get_attr("href").ok();
+                collect_keys(&child, path, group.as_deref(), keys);
+            }
+            "match" => {
+                let link = match child
+                    .get_attr("href")
+                    .ok()
+                    .or_else(|| group.map(String::from))
+                {
+                    Some(link) => link,
+                    None => continue,
+                };
+                let inflections = child
+                    .childs
+                    .iter()
+                    .filter(|c| c.borrow().name.local_name == "value")
+                    .filter_map(|c| c.borrow().get_attr("value").ok())
+                    .collect();
+                keys.push(SearchKey {
+                    value: child.get_attr("value").unwrap_or_default(),
+                    inflections,
+                    href: href::resolve_with_fragment(path, &link),
+                });
+            }
+            _ => collect_keys(&child, path, group, keys),
+        }
+    }
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns true if the publication is a dictionary, with the
+    /// `dictionary` dc:type
+    pub fn is_dictionary(&self) -> bool {
+        self.metadata
+            .get("type")
+            .map(|types| types.iter().any(|t| t.trim() == "dictionary"))
+            .unwrap_or(false)
+    }
+
+    /// Returns the ids of the glossaries, the content documents with the
+    /// "glossary" property, sorted
+    pub fn glossaries(&self) -> Vec<String> {
+        self.resources_with_property("glossary")
+            .into_iter()
+            .filter(|id| !self.is_search_key_map(id))
+            .collect()
+    }
+
+    fn is_search_key_map(&self, id: &str) -> bool {
+        self.resource_has_property(id, "search-key-map")
+            || self.resources.get(id).map(|(_, mime)| mime.as_str()) == Some(SEARCH_KEY_MAP_MIME)
+    }
+
+    /// Returns the search key maps of the dictionary or the glossaries,
+    /// the manifest items with the "search-key-map" property or media
+    /// type, sorted by id
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a search key map can't be read or parsed.
+    pub fn search_key_maps(&mut self) -> Result<Vec<SearchKeyMap>, EpubError> {
+        let mut ids: Vec<String> = self
+            .resources
+            .keys()
+            .filter(|id| self.is_search_key_map(id))
+            .cloned()
+            .collect();
+        ids.sort();
+        let mut maps = vec![];
+        for id in ids {
+            let path = self.resources[&id].0.clone();
+            let content = self.get_resource_by_path(&path)?;
+            maps.push(SearchKeyMap::parse(&id, &content, &path)?);
+        }
+        Ok(maps)
+    }
+
+    /// Looks up the `word` in the search key maps, and returns the
+    /// definitions of the headwords it matches, in the order of the maps.
+    /// The definitions are the elements pointed by the hrefs of the keys,
+    /// the ones that don't exist are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a search key map can't be read or parsed.
+    pub fn lookup(&mut self, word: &str) -> Result<Vec<Definition>, EpubError> {
+        let mut definitions: Vec<Definition> = vec![];
+        for map in self.search_key_maps()? {
+            for key in map.lookup(word) {
+                if definitions.iter().any(|d| d.href == key.href) {
+                    continue;
+                }
+                let target = key.href.display().to_string();
+                let (path, fragment) = href::split_fragment(&target);
+                let source = match self.get_resource_str_by_path(path) {
+                    Ok(source) => source,
+                    Err(_) => continue,
+                };
+                let html = match fragment {
+                    Some(fragment) if !fragment.is_empty() => {
+                        match element_source(&source, &href::normalize(fragment)) {
+                            Some(html) => html.to_string(),
+                            None => continue,
+                        }
+                    }
+                    _ => source,
+                };
+                definitions.push(Definition {
+                    headword: key.value.clone(),
+                    href: key.href.clone(),
+                    text: html_text(&html),
+                    html,
+                });
+            }
+        }
+        Ok(definitions)
+    }
+}
@@ -3,19 +3,43 @@
 //! Provides easy methods to navigate through the epub content, cover,
 //! chapters, etc.
 
-use anyhow::{anyhow, Error};
+use crate::error::EpubError;
+use regex::{Captures, NoExpand, Regex};
 use xmlutils::XMLError;
+use std::cell::{Cell, RefCell};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::BufReader;
-use std::io::{Read, Seek};
-use std::path::{Component, Path, PathBuf};
-
-use crate::archive::EpubArchive;
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::SystemTime;
+
+use crate::archive::{guess_media_type, ArchiveLimits, EpubArchive, WriteOptions};
+#[cfg(feature = "mmap")]
+use crate::mmap::Mmap;
+use crate::builder::{encode_href, relative_path, utc_timestamp, DC_ELEMENTS, DC_NS};
+use crate::edit::EditSession;
+use crate::encryption::{
+    self, Drm, EncryptedData, LcpLicense, Rights, ENCRYPTION_FILE, LCP_LICENSE_FILE, RIGHTS_FILE,
+};
+use crate::href;
+use crate::metadata::{
+    self, AccessibilityInfo, ContainerMetadata, Contributor, EventDate, Identifier, LangString,
+    MetadataItem, MetadataLink, Prefixes, Series, Title, TitleType, CONTAINER_METADATA_FILE,
+};
+use crate::navigation;
+use crate::rendition::{self, Rendition};
+#[cfg(feature = "integrity")]
+use crate::signatures::IntegrityCheck;
+use crate::signatures::{self, Signature, SIGNATURES_FILE};
+use crate::store::ResourceStore;
 
 use crate::xmlutils;
 
+pub(crate) const NCX_MIME: &str = "application/x-dtbncx+xml";
+
 /// Struct that represent a navigation point in a table of content
 #[derive(Eq)]
 pub struct NavPoint {
@@ -47,20 +71,358 @@ impl PartialEq for NavPoint {
     }
 }
 
+/// An entry of the landmarks of the navigation document, a structural part
+/// of the book like the cover or the start of the content
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Landmark {
+    /// the title of the landmark
+    pub label: String,
+    /// the resource path, with the fragment
+    pub content: PathBuf,
+    /// the epub:type of the link, like "bodymatter" or "toc"
+    pub epub_type: String,
+}
+
+/// A print page of the page list, the place where a page of the print
+/// edition starts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PageTarget {
+    /// the page number or name, like "12" or "xiv"
+    pub label: String,
+    /// the resource path, with the fragment
+    pub content: PathBuf,
+}
+
+/// A toc entry resolved to the spine, returned by `EpubDoc::toc_links`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TocLink {
+    /// the title of the entry
+    pub label: String,
+    /// the order in the toc, the NCX playOrder
+    pub play_order: usize,
+    /// the nesting level, 0 for the top level entries
+    pub level: usize,
+    /// the normalized path of the document, without the fragment
+    pub path: PathBuf,
+    /// the fragment, the id of the target inside the document
+    pub fragment: Option<String>,
+    /// the position of the document in the spine, if it's in the spine
+    pub spine_index: Option<usize>,
+}
+
+/// The position of an element in a content document, found with
+/// `EpubDoc::find_anchor`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Anchor {
+    /// the full path of the document in the archive
+    pub path: PathBuf,
+    /// the id of the element, empty for the start of the document
+    pub id: String,
+    /// the offset of the start tag of the element, in bytes
+    pub byte_offset: usize,
+    /// the offset of the start tag of the element, in characters
+    pub char_offset: usize,
+}
+
+/// A reference of the EPUB2 guide, the predecessor of the landmarks
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuideReference {
+    /// the type of the reference, like "cover", "toc" or "text"
+    pub reference_type: String,
+    /// the title of the reference
+    pub title: String,
+    /// the resource path, with the fragment
+    pub content: PathBuf,
+}
+
+impl GuideReference {
+    /// Returns the reference as a landmark, with the epub:type equivalent
+    /// to the guide type, like "bodymatter" for "text"
+    pub fn to_landmark(&self) -> Landmark {
+        let epub_type = match self.reference_type.as_str() {
+            "text" => "bodymatter",
+            "title-page" => "titlepage",
+            "acknowledgements" => "acknowledgments",
+            "notes" => "endnotes",
+            "other.backmatter" => "backmatter",
+            "other.frontmatter" => "frontmatter",
+            t => t,
+        };
+        Landmark {
+            label: self.title.clone(),
+            content: self.content.clone(),
+            epub_type: epub_type.to_string(),
+        }
+    }
+}
+
+/// An item of the spine, the reading order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpineItem {
+    /// the id of the manifest item
+    pub idref: String,
+    /// the id of the itemref element
+    pub id: Option<String>,
+    /// false for the items with `linear="no"`, like notes or answers,
+    /// outside of the main reading order
+    pub linear: bool,
+    /// the properties of the itemref, like "page-spread-left"
+    pub properties: Vec<String>,
+}
+
+impl SpineItem {
+    /// Returns true if the itemref has the `property`
+    pub fn has_property(&self, property: &str) -> bool {
+        self.properties.iter().any(|p| p == property)
+    }
+
+    /// Returns the page spread of a fixed layout item, "left", "right" or
+    /// "center", from the `page-spread-*` or `rendition:page-spread-*`
+    /// properties
+    pub fn page_spread(&self) -> Option<&str> {
+        self.properties.iter().find_map(|p| {
+            let p = p.strip_prefix("rendition:").unwrap_or(p);
+            p.strip_prefix("page-spread-")
+        })
+    }
+}
+
+impl AsRef<str> for SpineItem {
+    fn as_ref(&self) -> &str {
+        &self.idref
+    }
+}
+
+/// The result of `EpubDoc::resource_usage`, the files of the archive that
+/// are never used and the manifest items without file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// the files that aren't referenced from the package document, the
+    /// navigation or the content documents, sorted
+    pub unreferenced: Vec<PathBuf>,
+    /// the files of the archive that aren't in the manifest, sorted
+    pub not_in_manifest: Vec<PathBuf>,
+    /// the manifest items whose file isn't in the archive, as the id and
+    /// the path, sorted by path
+    pub missing: Vec<(String, PathBuf)>,
+}
+
+/// A document with scripts, found with `EpubDoc::scripted_report`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptedDocument {
+    /// the manifest id of the document
+    pub id: String,
+    /// the full path of the document in the archive
+    pub path: PathBuf,
+    /// true if the manifest item has the "scripted" property
+    pub declared: bool,
+    /// the number of `script` elements
+    pub scripts: usize,
+    /// the src of the external scripts, as written in the document
+    pub external_scripts: Vec<String>,
+    /// the number of event handler attributes, like `onclick`
+    pub event_handlers: usize,
+    /// the number of `javascript:` urls
+    pub javascript_urls: usize,
+}
+
+impl ScriptedDocument {
+    /// Returns true if the content has scripts
+    pub fn has_scripts(&self) -> bool {
+        self.scripts > 0 || self.event_handlers > 0 || self.javascript_urls > 0
+    }
+
+    /// Returns true if the content has scripts but the manifest doesn't
+    /// declare them, or the other way around
+    pub fn is_mismatch(&self) -> bool {
+        self.declared != self.has_scripts()
+    }
+}
+
+/// The direction of the page progression
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadingDirection {
+    /// left to right, the next page is on the right
+    Ltr,
+    /// right to left, the next page is on the left, like Arabic or Hebrew
+    /// books and Japanese books with vertical text
+    Rtl,
+}
+
+/// A resource of the manifest, returned by `EpubDoc::resources`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Resource {
+    /// the manifest id
+    pub id: String,
+    /// the href as written in the manifest, relative to the package
+    /// document
+    pub href: String,
+    /// the full path in the archive
+    pub path: PathBuf,
+    /// the media type declared in the manifest
+    pub media_type: String,
+    /// the manifest properties, like "nav" or "scripted"
+    pub properties: Vec<String>,
+    /// the id of the fallback resource
+    pub fallback: Option<String>,
+    /// the size of the content, None if the file isn't in the archive
+    pub size: Option<u64>,
+}
+
+/// The attributes of a manifest item that aren't in `EpubDoc::resources`
+#[derive(Clone, Debug)]
+struct ManifestItem {
+    href: String,
+    properties: Vec<String>,
+    fallback: Option<String>,
+    /// the id of the SMIL media overlay of the document
+    media_overlay: Option<String>,
+}
+
+/// How to handle epubs that don't follow the spec
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Recovers from the errors when possible, ignoring the broken parts.
+    /// The default.
+    #[default]
+    Lenient,
+    /// Fails on the first error, with a reference to the spec
+    Strict,
+}
+
+/// Options to open an epub doc: the parse mode, and limits for the memory
+/// used by hostile or broken files. There are no limits by default.
+///
+/// # Examples
+///
+/// ```
+/// use epub::doc::{EpubDoc, OpenOptions};
+///
+/// let mut options = OpenOptions::new();
+/// options.max_resource_size(1024 * 1024).max_toc_depth(1);
+/// let mut doc = EpubDoc::new_with_options("test.epub", &options).unwrap();
+/// assert!(doc.toc.iter().all(|nav| nav.children.is_empty()));
+/// // the cover image is bigger than 1 MiB
+/// assert!(doc.get_cover().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct OpenOptions {
+    limits: ArchiveLimits,
+    max_toc_depth: Option<usize>,
+    max_metadata_items: Option<usize>,
+    mode: ParseMode,
+    fuzzy_lookup: bool,
+    include_non_linear: bool,
+    password: Option<String>,
+}
+
+impl OpenOptions {
+    pub fn new() -> OpenOptions {
+        OpenOptions::default()
+    }
+
+    /// Options for untrusted files: the `ArchiveLimits::untrusted` limits,
+    /// resources up to 64 MiB, 32 toc levels and 10000 metadata items
+    pub fn untrusted() -> OpenOptions {
+        let mut options = OpenOptions {
+            limits: ArchiveLimits::untrusted(),
+            ..OpenOptions::default()
+        };
+        options
+            .max_resource_size(64 << 20)
+            .max_toc_depth(32)
+            .max_metadata_items(10000);
+        options
+    }
+
+    /// Sets the parse mode, lenient by default
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::{EpubDoc, OpenOptions, ParseMode};
+    ///
+    /// // test.epub has a duplicate manifest id
+    /// let mut options = OpenOptions::new();
+    /// assert!(EpubDoc::new_with_options("test.epub", &options).is_ok());
+    /// options.mode(ParseMode::Strict);
+    /// assert!(EpubDoc::new_with_options("test.epub", &options).is_err());
+    /// ```
+    pub fn mode(&mut self, mode: ParseMode) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Limits checked when opening the zip archive
+    pub fn archive_limits(&mut self, limits: ArchiveLimits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Maximum size of a resource read in memory, in bytes. Reading a
+    /// bigger resource fails, but it can still be read with a reader.
+    pub fn max_resource_size(&mut self, size: u64) -> &mut Self {
+        self.limits.max_entry_size(size);
+        self
+    }
+
+    /// Maximum depth of the table of contents, deeper entries are ignored
+    pub fn max_toc_depth(&mut self, depth: usize) -> &mut Self {
+        self.max_toc_depth = Some(depth);
+        self
+    }
+
+    /// Maximum number of metadata items, the items after it are ignored
+    pub fn max_metadata_items(&mut self, items: usize) -> &mut Self {
+        self.max_metadata_items = Some(items);
+        self
+    }
+
+    /// Finds the files ignoring the case and the separators when the hrefs
+    /// don't match the file names, see `EpubArchive::fuzzy_lookup`
+    pub fn fuzzy_lookup(&mut self, fuzzy: bool) -> &mut Self {
+        self.fuzzy_lookup = fuzzy;
+        self
+    }
+
+    /// Decrypts the entries of a password protected zip, see
+    /// `EpubArchive::password`
+    pub fn password(&mut self, password: &str) -> &mut Self {
+        self.password = Some(password.to_string());
+        self
+    }
+
+    /// Makes `go_next` and `go_prev` stop on the non-linear spine items,
+    /// like notes, that are skipped by default
+    pub fn include_non_linear(&mut self, include: bool) -> &mut Self {
+        self.include_non_linear = include;
+        self
+    }
+}
+
 /// Struct to control the epub document
 pub struct EpubDoc<R: Read + Seek> {
     /// the zip archive
-    archive: EpubArchive<R>,
+    pub(crate) archive: EpubArchive<R>,
 
     /// The current chapter, is an spine index
     current: usize,
 
-    /// epub spine ids
-    pub spine: Vec<String>,
+    /// the fragment of the last `go_to_href`, until the page changes
+    fragment: Option<(usize, String)>,
+
+    /// epub spine, the items in reading order
+    pub spine: Vec<SpineItem>,
 
     /// resource id -> (path, mime)
     pub resources: HashMap<String, (PathBuf, String)>,
 
+    /// resource id -> the manifest attributes that aren't in `resources`
+    manifest: HashMap<String, ManifestItem>,
+
+    /// the page-progression-direction of the spine
+    page_progression: Option<ReadingDirection>,
+
     /// table of content, list of `NavPoint` in the toc.ncx
     pub toc: Vec<NavPoint>,
 
@@ -88,6 +450,42 @@ pub struct EpubDoc<R: Read + Seek> {
 
     /// unique identifier
     pub unique_identifier: Option<String>,
+
+    /// the options used to open the doc
+    options: OpenOptions,
+
+    /// recoverable problems found opening the doc
+    warnings: Vec<String>,
+
+    /// the metadata elements with their refinements
+    metadata_items: Vec<MetadataItem>,
+
+    /// the link elements of the metadata
+    metadata_links: Vec<MetadataLink>,
+
+    /// the prefixes of the metadata properties
+    prefixes: Prefixes,
+
+    /// the lists of the navigation document by epub:type
+    navs: HashMap<String, Vec<NavPoint>>,
+
+    /// the landmarks of the navigation document
+    landmarks: Vec<Landmark>,
+
+    /// the print pages of the navigation document or the toc.ncx
+    page_list: Vec<PageTarget>,
+
+    /// the rootfiles of the container
+    renditions: Vec<Rendition>,
+
+    /// the index of the open rendition
+    rendition: usize,
+
+    /// the encrypted entries of the encryption.xml
+    encryption: Vec<EncryptedData>,
+
+    /// the references of the EPUB2 guide
+    guide: Vec<GuideReference>,
 }
 
 impl EpubDoc<BufReader<File>> {
@@ -109,13 +507,155 @@ impl EpubDoc<BufReader<File>> {
     ///
     /// Returns an error if the epub is broken or if the file doesn't
     /// exists.
-    pub fn new<P: AsRef<Path>>(path: P) -> Result<EpubDoc<BufReader<File>>, Error> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<EpubDoc<BufReader<File>>, EpubError> {
         let path = path.as_ref();
         let file = File::open(path)?;
         let mut doc = EpubDoc::from_reader(BufReader::new(file))?;
         doc.archive.path = path.to_path_buf();
         Ok(doc)
     }
+
+    /// Opens the epub file in `path` with the `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken, if the file doesn't exists
+    /// or if the archive exceeds the limits of the options.
+    pub fn new_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<EpubDoc<BufReader<File>>, EpubError> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let mut doc = EpubDoc::from_reader_with_options(BufReader::new(file), options)?;
+        doc.archive.path = path.to_path_buf();
+        Ok(doc)
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl EpubDoc<Cursor<Mmap>> {
+    /// Opens the epub file in `path` as a memory map. Recommended for big
+    /// epubs, like fixed layout or audio books.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::EpubDoc;
+    ///
+    /// // test.epub isn't modified while it's open
+    /// let mut doc = unsafe { EpubDoc::new_mmap("test.epub") }.unwrap();
+    /// assert_eq!(17, doc.spine.len());
+    /// assert!(doc.get_current_str().is_ok());
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the doc is open,
+    /// see `Mmap::open`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken or if the file can't be
+    /// mapped.
+    pub unsafe fn new_mmap<P: AsRef<Path>>(path: P) -> Result<EpubDoc<Cursor<Mmap>>, EpubError> {
+        EpubDoc::new_mmap_with_options(path, &OpenOptions::default())
+    }
+
+    /// Opens the epub file in `path` as a memory map with the `options`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the doc is open,
+    /// see `Mmap::open`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken, if the file can't be mapped
+    /// or if the archive exceeds the limits of the options.
+    pub unsafe fn new_mmap_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &OpenOptions,
+    ) -> Result<EpubDoc<Cursor<Mmap>>, EpubError> {
+        let mut archive = EpubArchive::new_mmap_with_limits(path, &options.limits)?;
+        if let Some(ref password) = options.password {
+            archive.password(password)?;
+        }
+        EpubDoc::from_archive(archive, options.clone())
+    }
+
+    /// Returns the resource content by the `id` without copying it,
+    /// directly from the memory map. Only works for resources stored
+    /// without compression, see `EpubArchive::get_mapped_entry`, and not
+    /// for the resources of the encryption.xml, like the obfuscated fonts.
+    pub fn get_mapped_resource(&mut self, id: &str) -> Option<&[u8]> {
+        let path = self.resources.get(id)?.0.clone();
+        if self.encryption_of(&path).is_some() {
+            return None;
+        }
+        self.archive.get_mapped_entry(path)
+    }
+}
+
+impl EpubDoc<std::io::Empty> {
+    /// Opens the epub stored in `store`, instead of a zip file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::EpubDoc;
+    /// use epub::store::MemoryStore;
+    ///
+    /// let container = r#"<container version="1.0">
+    ///   <rootfiles>
+    ///     <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
+    ///   </rootfiles>
+    /// </container>"#;
+    /// let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+    ///   <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    ///     <dc:title>In memory</dc:title>
+    ///   </metadata>
+    ///   <manifest/>
+    ///   <spine/>
+    /// </package>"#;
+    /// let mut store = MemoryStore::new();
+    /// store
+    ///     .insert("META-INF/container.xml", container)
+    ///     .insert("content.opf", opf);
+    ///
+    /// let doc = EpubDoc::from_store(store).unwrap();
+    /// assert_eq!("In memory", doc.mdata("title").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken.
+    pub fn from_store<S: ResourceStore + 'static>(
+        store: S,
+    ) -> Result<EpubDoc<std::io::Empty>, EpubError> {
+        EpubDoc::from_archive(EpubArchive::from_store(store), OpenOptions::default())
+    }
+}
+
+impl EpubDoc<Cursor<Vec<u8>>> {
+    /// Opens the epub from its content in memory, like an uploaded file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let content = std::fs::read("test.epub").unwrap();
+    /// let doc = EpubDoc::from_bytes(content);
+    /// assert!(doc.is_ok());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken.
+    pub fn from_bytes(content: Vec<u8>) -> Result<EpubDoc<Cursor<Vec<u8>>>, EpubError> {
+        EpubDoc::from_reader(Cursor::new(content))
+    }
 }
 
 impl<R: Read + Seek> EpubDoc<R> {
@@ -144,30 +684,148 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// # Errors
     ///
     /// Returns an error if the epub is broken.
-    pub fn from_reader(reader: R) -> Result<EpubDoc<R>, Error> {
-        let mut archive = EpubArchive::<R>::from_reader(reader)?;
-        let spine: Vec<String> = vec![];
+    pub fn from_reader(reader: R) -> Result<EpubDoc<R>, EpubError> {
+        EpubDoc::from_archive(
+            EpubArchive::<R>::from_reader(reader)?,
+            OpenOptions::default(),
+        )
+    }
+
+    /// Opens the epub contained in `reader` with the `options`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub is broken or if the archive exceeds the
+    /// limits of the options.
+    pub fn from_reader_with_options(
+        reader: R,
+        options: &OpenOptions,
+    ) -> Result<EpubDoc<R>, EpubError> {
+        let mut archive = EpubArchive::<R>::from_reader_with_limits(reader, &options.limits)?;
+        if let Some(ref password) = options.password {
+            archive.password(password)?;
+        }
+        EpubDoc::from_archive(archive, options.clone())
+    }
+
+    fn from_archive(
+        mut archive: EpubArchive<R>,
+        options: OpenOptions,
+    ) -> Result<EpubDoc<R>, EpubError> {
+        let spine: Vec<SpineItem> = vec![];
         let resources = HashMap::new();
 
+        archive.fuzzy_lookup(options.fuzzy_lookup);
         let container = archive.get_container_file()?;
-        let root_file = get_root_file(container)?;
+        let renditions = rendition::get_rootfiles(&container)?;
+        let root_file = renditions[0].path.clone();
         let base_path = root_file.parent().expect("All files have a parent");
         let mut doc = EpubDoc {
             archive,
             spine,
             toc: vec![],
             resources,
+            manifest: HashMap::new(),
+            page_progression: None,
             metadata: HashMap::new(),
             root_file: root_file.clone(),
             root_base: base_path.to_path_buf(),
             current: 0,
+            fragment: None,
             extra_css: vec![],
             unique_identifier: None,
+            options,
+            warnings: vec![],
+            metadata_items: vec![],
+            metadata_links: vec![],
+            prefixes: Prefixes::default(),
+            navs: HashMap::new(),
+            landmarks: vec![],
+            page_list: vec![],
+            renditions,
+            rendition: 0,
+            encryption: vec![],
+            guide: vec![],
         };
         doc.fill_resources()?;
         Ok(doc)
     }
 
+    /// Returns the recoverable problems found opening the doc, like spec
+    /// violations ignored in lenient mode, unknown elements, a missing
+    /// cover or toc entries that aren't in the manifest.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert!(doc.warnings().iter().any(|w| w.contains("duplicate manifest id")));
+    /// ```
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Returns the renditions of the publication, the rootfiles of the
+    /// `container.xml`, in document order. The first one is open by
+    /// default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!(1, doc.renditions().len());
+    /// assert!(doc.renditions()[0].is_package());
+    /// ```
+    pub fn renditions(&self) -> &[Rendition] {
+        &self.renditions
+    }
+
+    /// Returns the index of the open rendition
+    pub fn current_rendition(&self) -> usize {
+        self.rendition
+    }
+
+    /// Opens the rendition `index` of `renditions`, parsing its package
+    /// document. The current page goes back to the start.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::InvalidOperation` if the rendition doesn't exist
+    /// or isn't a package document, or an error if the package document is
+    /// broken. The open rendition doesn't change on error.
+    pub fn open_rendition(&mut self, index: usize) -> Result<(), EpubError> {
+        let path = match self.renditions.get(index) {
+            Some(rendition) if rendition.is_package() => rendition.path.clone(),
+            Some(rendition) => {
+                return Err(EpubError::InvalidOperation(format!(
+                    "the rendition {} is a {}, not a package document",
+                    index, rendition.media_type
+                )))
+            }
+            None => {
+                return Err(EpubError::InvalidOperation(format!(
+                    "the container doesn't have the rendition {}",
+                    index
+                )))
+            }
+        };
+        let previous = (self.root_file.clone(), self.root_base.clone());
+        self.root_base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.root_file = path;
+        self.current = 0;
+        self.fragment = None;
+        if let Err(e) = self.reload() {
+            self.root_file = previous.0;
+            self.root_base = previous.1;
+            self.reload()?;
+            return Err(e);
+        }
+        self.rendition = index;
+        Ok(())
+    }
+
     /// Returns the first metadata found with this name.
     ///
     /// #Examples
@@ -180,68 +838,398 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// assert_eq!(title.unwrap(), "Todo es mío");
     pub fn mdata(&self, name: &str) -> Option<String> {
         match self.metadata.get(name) {
-            Some(v) => v.get(0).cloned(),
+            Some(v) => v.first().cloned(),
             None => None,
         }
     }
 
-    /// Returns the id of the epub cover.
-    ///
-    /// The cover is searched in the doc metadata, by the tag <meta name="cover" value"..">
+    /// Returns the metadata elements in document order, each one with the
+    /// elements refining it, like the role and the file-as of a creator.
     ///
     /// # Examples
     ///
-    /// ```rust
-    /// use epub::doc::EpubDoc;
-    ///
-    /// let doc = EpubDoc::new("test.epub");
-    /// assert!(doc.is_ok());
-    /// let mut doc = doc.unwrap();
-    ///
-    /// let cover_id = doc.get_cover_id().unwrap();
     /// ```
-    ///
-    /// # Errors
-    ///
-    /// Returns an error if the cover path can't be found.
-    pub fn get_cover_id(&self) -> Result<String, Error> {
-        match self.mdata("cover") {
-            Some(id) => Ok(id),
-            None => Err(anyhow!("Cover not found")),
-        }
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let properties: Vec<&str> = doc
+    ///     .metadata_items()
+    ///     .iter()
+    ///     .map(|item| item.property.as_str())
+    ///     .collect();
+    /// assert_eq!(&["title", "creator", "language", "identifier"], &properties[..4]);
+    /// ```
+    pub fn metadata_items(&self) -> &[MetadataItem] {
+        &self.metadata_items
     }
 
-    /// Returns the cover as Vec<u8>
+    /// Returns the `link` elements of the metadata, like ONIX records,
+    /// previews or accessibility reports. The links to files of the epub
+    /// have the path and the manifest id to read them.
     ///
     /// # Examples
     ///
-    /// ```rust,ignore
-    /// use std::fs;
-    /// use std::io::Write;
-    /// use epub::doc::EpubDoc;
-    ///
-    /// let doc = EpubDoc::new("test.epub");
-    /// assert!(doc.is_ok());
-    /// let mut doc = doc.unwrap();
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let onix = doc
+    ///     .metadata_links()
+    ///     .iter()
+    ///     .find(|link| link.has_rel("onix-record"))
+    ///     .and_then(|link| link.resource_id.clone());
+    /// if let Some(id) = onix {
+    ///     let record = doc.get_resource_str(&id).unwrap();
+    ///     println!("{}", record);
+    /// }
+    /// ```
+    pub fn metadata_links(&self) -> &[MetadataLink] {
+        &self.metadata_links
+    }
+
+    /// Returns the first metadata element with this name, with its
+    /// refinements. See `MetadataItem`.
+    pub fn mdata_item(&self, name: &str) -> Option<&MetadataItem> {
+        self.metadata_items
+            .iter()
+            .find(|item| item.property == name)
+    }
+
+    /// Returns the metadata elements with this property, comparing the full
+    /// iris, so the prefix chosen by the producer doesn't matter. The
+    /// `property` is an iri or uses the reserved prefixes, like
+    /// "rendition:layout".
     ///
-    /// let cover_data = doc.get_cover().unwrap();
+    /// # Examples
     ///
-    /// let f = fs::File::create("/tmp/cover.png");
-    /// assert!(f.is_ok());
-    /// let mut f = f.unwrap();
-    /// let resp = f.write_all(&cover_data);
     /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let modified = doc.mdata_items_by_property("http://purl.org/dc/terms/modified");
+    /// assert_eq!("2015-08-10T18:12:03Z", modified[0].value);
+    /// assert_eq!(modified, doc.mdata_items_by_property("dcterms:modified"));
+    /// ```
+    pub fn mdata_items_by_property(&self, property: &str) -> Vec<&MetadataItem> {
+        let iri = match Prefixes::default().expand(property) {
+            Some(iri) if property.contains(':') => iri,
+            _ => property.to_string(),
+        };
+        self.metadata_items
+            .iter()
+            .filter(|item| {
+                item.property == property
+                    || self.prefixes.expand(&item.property).as_deref() == Some(iri.as_str())
+            })
+            .collect()
+    }
+
+    /// Returns the prefixes of the package, to expand the metadata
+    /// properties. See `Prefixes`.
+    pub fn prefixes(&self) -> &Prefixes {
+        &self.prefixes
+    }
+
+    /// Returns every value of the metadata `name`, with the alternate
+    /// scripts, and their language tags. The values without `xml:lang` get
+    /// the language of the package.
     ///
-    /// # Errors
+    /// # Examples
     ///
-    /// Returns an error if the cover can't be found.
-    pub fn get_cover(&mut self) -> Result<Vec<u8>, Error> {
-        let cover_id = self.get_cover_id()?;
-        let cover_data = self.get_resource(&cover_id)?;
-        Ok(cover_data)
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let titles = doc.mdata_variants("title");
+    /// assert_eq!(1, titles.len());
+    /// assert_eq!("Todo es mío", titles[0].value);
+    /// ```
+    pub fn mdata_variants(&self, name: &str) -> Vec<LangString> {
+        self.metadata_items
+            .iter()
+            .filter(|item| item.property == name)
+            .flat_map(|item| item.variants())
+            .collect()
     }
 
-    /// Returns Release Identifier defined at
+    /// Returns the titles of the publication, with their types, in the
+    /// `display-seq` order, and in document order without it
+    pub fn titles(&self) -> Vec<Title> {
+        let mut titles: Vec<Title> = self
+            .metadata_items
+            .iter()
+            .filter(|item| item.property == "title")
+            .map(Title::from_item)
+            .collect();
+        titles.sort_by_key(|t| t.display_seq.unwrap_or(usize::MAX));
+        titles
+    }
+
+    /// Returns the main title: the title with the "main" type, or the
+    /// first one without a type, or the first one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!("Todo es mío", doc.main_title().unwrap());
+    /// assert_eq!(None, doc.subtitle());
+    /// assert_eq!("Todo es mío", doc.sort_title().unwrap());
+    /// ```
+    pub fn main_title(&self) -> Option<String> {
+        self.main_title_item().map(|t| t.value)
+    }
+
+    /// Returns the first title with the "subtitle" type
+    pub fn subtitle(&self) -> Option<String> {
+        self.titles()
+            .into_iter()
+            .find(|t| t.title_type == Some(TitleType::Subtitle))
+            .map(|t| t.value)
+    }
+
+    /// Returns the main title for sorting: its `file-as` refinement, or the
+    /// main title itself
+    pub fn sort_title(&self) -> Option<String> {
+        self.main_title_item().map(|t| t.file_as.unwrap_or(t.value))
+    }
+
+    fn main_title_item(&self) -> Option<Title> {
+        let titles = self.titles();
+        let main = titles
+            .iter()
+            .position(|t| t.title_type == Some(TitleType::Main))
+            .or_else(|| titles.iter().position(|t| t.title_type.is_none()))
+            .unwrap_or(0);
+        titles.into_iter().nth(main)
+    }
+
+    /// Returns the series or collections of the publication, from the
+    /// `belongs-to-collection` metadata, or the `calibre:series` meta
+    /// used by many EPUB2 books
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// for series in doc.series() {
+    ///     println!("{} #{}", series.name, series.position.unwrap_or(1.0));
+    /// }
+    /// ```
+    pub fn series(&self) -> Vec<Series> {
+        metadata::series(&self.metadata_items)
+    }
+
+    /// Returns the dates of the metadata, parsed, with their events: the
+    /// publication and the EPUB2 events of `dc:date`, and the modification
+    /// date of `dcterms:modified`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// use epub::metadata::DateEvent;
+    ///
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let dates = doc.dates();
+    /// assert_eq!(DateEvent::Modification, dates[0].event);
+    /// assert_eq!(2015, dates[0].date.unwrap().year);
+    /// ```
+    pub fn dates(&self) -> Vec<EventDate> {
+        metadata::dates(&self.metadata_items)
+    }
+
+    /// Returns every list of the EPUB3 navigation document by its epub:type,
+    /// like "toc", "landmarks", "lot" (list of tables), "loi" (list of
+    /// illustrations) or the navs of the publisher. The entries without a
+    /// link, headings of a group of entries, point to the first link of the
+    /// group.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// if let Some(tables) = doc.navs().get("lot") {
+    ///     for table in tables {
+    ///         println!("{}: {}", table.label, table.content.display());
+    ///     }
+    /// }
+    /// // test.epub is an EPUB2 book, without nav document
+    /// assert!(doc.navs().is_empty());
+    /// ```
+    pub fn navs(&self) -> &HashMap<String, Vec<NavPoint>> {
+        &self.navs
+    }
+
+    /// Returns the landmarks of the EPUB3 navigation document, like the
+    /// cover or the start of the content, in document order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let start = doc
+    ///     .landmarks()
+    ///     .iter()
+    ///     .find(|landmark| landmark.epub_type == "bodymatter");
+    /// // test.epub is an EPUB2 book, without landmarks
+    /// assert!(start.is_none());
+    /// ```
+    pub fn landmarks(&self) -> &[Landmark] {
+        &self.landmarks
+    }
+
+    /// Returns the print pages of the EPUB3 navigation document, in
+    /// document order, or those of the toc.ncx `pageList` in play order
+    pub fn page_list(&self) -> &[PageTarget] {
+        &self.page_list
+    }
+
+    /// Returns the references of the EPUB2 guide, in document order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("tests/docs/Metamorphosis-jackson.epub").unwrap();
+    /// let cover = &doc.guide()[1];
+    /// assert_eq!("cover", cover.reference_type);
+    /// assert_eq!("OEBPS/front-cover.html", cover.content.to_str().unwrap());
+    ///
+    /// // the start of the content, from the "text" reference
+    /// let start = doc.find_landmark("bodymatter").unwrap();
+    /// assert_eq!("OEBPS/pressbooks-promo.html", start.content.to_str().unwrap());
+    /// ```
+    pub fn guide(&self) -> &[GuideReference] {
+        &self.guide
+    }
+
+    /// Returns the landmarks of the navigation document or, if there
+    /// aren't any, the references of the EPUB2 guide as landmarks. See
+    /// `GuideReference::to_landmark`.
+    pub fn landmarks_or_guide(&self) -> Vec<Landmark> {
+        if !self.landmarks.is_empty() {
+            return self.landmarks.clone();
+        }
+        self.guide.iter().map(|r| r.to_landmark()).collect()
+    }
+
+    /// Returns the first landmark of the navigation document or the guide
+    /// with this epub:type, like "bodymatter" for the start of the content
+    pub fn find_landmark(&self, epub_type: &str) -> Option<Landmark> {
+        self.landmarks_or_guide()
+            .into_iter()
+            .find(|landmark| landmark.epub_type == epub_type)
+    }
+
+    /// Returns the accessibility metadata of the publication. See
+    /// `AccessibilityInfo::from_items`.
+    pub fn accessibility(&self) -> AccessibilityInfo {
+        AccessibilityInfo::from_items(&self.metadata_items)
+    }
+
+    /// Returns the identifiers of the publication, with their schemes, in
+    /// document order. See `Identifier::from_item`.
+    pub fn identifiers(&self) -> Vec<Identifier> {
+        self.metadata_items
+            .iter()
+            .filter(|item| item.property == "identifier")
+            .map(Identifier::from_item)
+            .collect()
+    }
+
+    /// Returns the creators of the publication, with their roles, in
+    /// document order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// use epub::metadata::Role;
+    ///
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let creators = doc.creators();
+    /// assert_eq!("Daniel Garcia", creators[0].name);
+    /// assert_eq!(vec![Role::Author], creators[0].roles);
+    /// let authors = creators.iter().filter(|c| c.has_role(&Role::Author));
+    /// assert_eq!(1, authors.count());
+    /// ```
+    pub fn creators(&self) -> Vec<Contributor> {
+        self.contributors_by("creator")
+    }
+
+    /// Returns the contributors of the publication, the secondary
+    /// creators like translators or narrators, with their roles, in
+    /// document order
+    pub fn contributors(&self) -> Vec<Contributor> {
+        self.contributors_by("contributor")
+    }
+
+    fn contributors_by(&self, property: &str) -> Vec<Contributor> {
+        self.metadata_items
+            .iter()
+            .filter(|item| item.property == property)
+            .map(Contributor::from_item)
+            .collect()
+    }
+
+    /// Returns the id of the epub cover.
+    ///
+    /// The cover is searched in the doc metadata, by the tag <meta name="cover" value"..">
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let doc = EpubDoc::new("test.epub");
+    /// assert!(doc.is_ok());
+    /// let mut doc = doc.unwrap();
+    ///
+    /// let cover_id = doc.get_cover_id().unwrap();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cover path can't be found.
+    pub fn get_cover_id(&self) -> Result<String, EpubError> {
+        match self.mdata("cover") {
+            Some(id) => Ok(id),
+            None => Err(EpubError::ResourceNotFound(String::from("cover"))),
+        }
+    }
+
+    /// Returns the cover as Vec<u8>
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// use std::fs;
+    /// use std::io::Write;
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let doc = EpubDoc::new("test.epub");
+    /// assert!(doc.is_ok());
+    /// let mut doc = doc.unwrap();
+    ///
+    /// let cover_data = doc.get_cover().unwrap();
+    ///
+    /// let f = fs::File::create("/tmp/cover.png");
+    /// assert!(f.is_ok());
+    /// let mut f = f.unwrap();
+    /// let resp = f.write_all(&cover_data);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the cover can't be found.
+    pub fn get_cover(&mut self) -> Result<Vec<u8>, EpubError> {
+        let cover_id = self.get_cover_id()?;
+        let cover_data = self.get_resource(&cover_id)?;
+        Ok(cover_data)
+    }
+
+    /// Returns Release Identifier defined at
     /// https://www.w3.org/publishing/epub3/epub-packages.html#sec-metadata-elem-identifiers-pid
     pub fn get_release_identifier(&self) -> Option<String> {
         match (
@@ -260,31 +1248,284 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// # Errors
     ///
     /// Returns an error if the path doesn't exists in the epub
-    pub fn get_resource_by_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, Error> {
-        let content = self.archive.get_entry(path)?;
+    pub fn get_resource_by_path<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<u8>, EpubError> {
+        self.check_encryption(path.as_ref())?;
+        let mut content = self.archive.get_entry(path.as_ref())?;
+        self.deobfuscate(path.as_ref(), &mut content);
         Ok(content)
     }
 
+    /// Returns the entries of the archive encrypted with the algorithms of
+    /// the `META-INF/encryption.xml`, like the obfuscated fonts or the
+    /// content protected by a DRM, in document order.
+    ///
+    /// The resources encrypted with an algorithm that can't be decrypted
+    /// fail to be read with `EpubError::Encrypted`.
+    pub fn encrypted_resources(&self) -> &[EncryptedData] {
+        &self.encryption
+    }
+
+    /// Returns the encryption of the entry `path`, None if it isn't
+    /// encrypted
+    pub fn encryption_of<P: AsRef<Path>>(&self, path: P) -> Option<&EncryptedData> {
+        let path = PathBuf::from(href::normalize(&path.as_ref().to_string_lossy()));
+        self.encryption.iter().find(|data| data.path == path)
+    }
+
+    /// Returns the DRM protecting the publication, from the license files
+    /// of the `META-INF` directory and the algorithms of the
+    /// encryption.xml, to tell why its resources can't be read.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::EpubDoc;
+    /// use epub::encryption::Drm;
+    ///
+    /// let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!(Drm::None, doc.drm());
+    /// ```
+    pub fn drm(&self) -> Drm {
+        encryption::detect_drm(&self.archive.files, &self.encryption)
+    }
+
+    /// Returns the Readium LCP license of the publication, the
+    /// `META-INF/license.lcpl`, None if it doesn't have one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the license can't be read or isn't a json
+    /// object.
+    pub fn lcp_license(&mut self) -> Result<Option<LcpLicense>, EpubError> {
+        match self.optional_entry(LCP_LICENSE_FILE)? {
+            Some(content) => Ok(Some(LcpLicense::parse(&content)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the `META-INF/rights.xml` of the vendor, None if the
+    /// publication doesn't have one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read.
+    pub fn rights(&mut self) -> Result<Option<Rights>, EpubError> {
+        Ok(self.optional_entry(RIGHTS_FILE)?.map(Rights::parse))
+    }
+
+    /// Returns the `META-INF/metadata.xml` of the container, None if the
+    /// publication doesn't have one
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read.
+    pub fn container_metadata(&mut self) -> Result<Option<ContainerMetadata>, EpubError> {
+        Ok(self
+            .optional_entry(CONTAINER_METADATA_FILE)?
+            .map(ContainerMetadata::parse))
+    }
+
+    /// The content of the archive entry `name`, None if it doesn't exist
+    fn optional_entry(&mut self, name: &str) -> Result<Option<String>, EpubError> {
+        if !self.archive.files.iter().any(|f| f == name) {
+            return Ok(None);
+        }
+        Ok(Some(self.archive.get_entry_as_str(name)?))
+    }
+
+    /// Returns the signatures of the `META-INF/signatures.xml`, empty if
+    /// the publication isn't signed. See `Signature::referenced_paths` for
+    /// the entries of a signature.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signatures.xml can't be read or parsed.
+    pub fn signatures(&mut self) -> Result<Vec<Signature>, EpubError> {
+        if !self.archive.files.iter().any(|f| f == SIGNATURES_FILE) {
+            return Ok(vec![]);
+        }
+        let content = self.archive.get_entry(SIGNATURES_FILE)?;
+        signatures::parse_signatures(&content)
+    }
+
+    /// Runs the integrity check of the entries referenced by the
+    /// signatures: compares their digests with the entries of the archive
+    /// as they are stored, in the order of the signatures.xml. It isn't a
+    /// signature verification, the signature values aren't verified, see
+    /// the `signatures` module.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signatures.xml can't be read or parsed.
+    #[cfg(feature = "integrity")]
+    pub fn check_integrity(&mut self) -> Result<Vec<IntegrityCheck>, EpubError> {
+        let mut checks = vec![];
+        for signature in self.signatures()? {
+            for reference in signature.references {
+                let content = match &reference.path {
+                    Some(path) => self.archive.get_entry(path).ok(),
+                    None => None,
+                };
+                checks.push(IntegrityCheck {
+                    signature: signature.id.clone(),
+                    status: signatures::check_digest(&reference, content.as_deref()),
+                    reference,
+                });
+            }
+        }
+        Ok(checks)
+    }
+
+    /// Fails with `EpubError::Encrypted` if the entry `path` is encrypted
+    /// with an algorithm that can't be decrypted
+    fn check_encryption(&self, path: &Path) -> Result<(), EpubError> {
+        match self.encryption_of(path) {
+            Some(data) if !data.is_obfuscation() => Err(EpubError::Encrypted {
+                path: data.path.clone(),
+                algorithm: data.algorithm.clone(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reverts the obfuscation of the font `content` of the entry `path`,
+    /// if the encryption.xml declares one
+    fn deobfuscate(&self, path: &Path, content: &mut [u8]) -> bool {
+        let data = match self.encryption_of(path) {
+            Some(data) => data,
+            None => return false,
+        };
+        let uid = self.unique_identifier.as_deref().unwrap_or_default();
+        // the adobe key is the uuid identifier, that may not be the unique one
+        let identifier = match data.algorithm.as_str() {
+            encryption::ADOBE_OBFUSCATION if !encryption::is_uuid(uid) => self
+                .metadata
+                .get("identifier")
+                .and_then(|ids| ids.iter().find(|id| encryption::is_uuid(id)))
+                .map(String::as_str)
+                .unwrap_or(uid),
+            _ => uid,
+        };
+        encryption::deobfuscate(&data.algorithm, identifier, content)
+    }
+
+    /// A reader of the entry `path`, with the obfuscated fonts read in
+    /// memory to revert the obfuscation
+    fn entry_reader(&mut self, path: &Path) -> Result<Box<dyn Read + '_>, EpubError> {
+        self.check_encryption(path)?;
+        if self.encryption_of(path).is_some() {
+            let mut content = self.archive.get_entry(path)?;
+            self.deobfuscate(path, &mut content);
+            return Ok(Box::new(Cursor::new(content)));
+        }
+        self.archive.get_entry_reader(path)
+    }
+
+    /// Parses the `META-INF/encryption.xml`, if the archive has one
+    fn read_encryption(&mut self) -> Result<(), EpubError> {
+        self.encryption.clear();
+        let content = match self.archive.get_entry(ENCRYPTION_FILE) {
+            Ok(content) => content,
+            Err(_) => return Ok(()),
+        };
+        match encryption::parse_encryption(&content) {
+            Ok(encryption) => self.encryption = encryption,
+            Err(e) => self.violation(
+                &format!("the encryption.xml can't be parsed: {}", e),
+                "EPUB 3.3, OCF: encryption file",
+            )?,
+        }
+        Ok(())
+    }
+
     /// Returns the resource content by the id defined in the spine
     ///
     /// # Errors
     ///
     /// Returns an error if the id doesn't exists in the epub
-    pub fn get_resource(&mut self, id: &str) -> Result<Vec<u8>, Error> {
+    pub fn get_resource(&mut self, id: &str) -> Result<Vec<u8>, EpubError> {
         let path = match self.resources.get(id) {
             Some(s) => s.0.clone(),
-            None => return Err(anyhow!("id not found")),
+            None => return Err(EpubError::ResourceNotFound(id.to_string())),
         };
         let content = self.get_resource_by_path(&path)?;
         Ok(content)
     }
 
+    /// Returns a reader of the resource content by full path in the epub
+    /// archive, and its mime type, to stream big resources like audio or
+    /// video without reading them in memory. See
+    /// `EpubArchive::get_entry_reader`. The mime type of the files that
+    /// aren't in the manifest is guessed from the file name.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let (mut reader, mime) = doc.get_resource_by_path_reader("OEBPS/Images/portada.png").unwrap();
+    /// assert_eq!("image/png", mime);
+    /// let copied = std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+    /// assert_eq!(1186183, copied);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the path doesn't exists in the epub
+    pub fn get_resource_by_path_reader<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(Box<dyn Read + '_>, String), EpubError> {
+        let path = path.as_ref();
+        let mime = match self.get_resource_mime_by_path(path) {
+            Ok(mime) => mime,
+            Err(_) => guess_media_type(&path.display().to_string()).to_string(),
+        };
+        let reader = self.entry_reader(path)?;
+        Ok((reader, mime))
+    }
+
+    /// Returns a reader of the resource content by the id defined in the
+    /// spine, and its mime type. See `get_resource_by_path_reader`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # use std::io::Read;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let (mut reader, mime) = doc.get_resource_reader("stylesheet.css").unwrap();
+    /// let mut css = String::new();
+    /// reader.read_to_string(&mut css).unwrap();
+    /// assert_eq!("text/css", mime);
+    /// assert_eq!(188, css.len());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the id doesn't exists in the epub
+    pub fn get_resource_reader(
+        &mut self,
+        id: &str,
+    ) -> Result<(Box<dyn Read + '_>, String), EpubError> {
+        let (path, mime) = match self.resources.get(id) {
+            Some(res) => res.clone(),
+            None => return Err(EpubError::ResourceNotFound(id.to_string())),
+        };
+        let reader = self.entry_reader(&path)?;
+        Ok((reader, mime))
+    }
+
     /// Returns the resource content by full path in the epub archive, as String
     ///
     /// # Errors
     ///
     /// Returns an error if the path doesn't exists in the epub
-    pub fn get_resource_str_by_path<P: AsRef<Path>>(&mut self, path: P) -> Result<String, Error> {
+    pub fn get_resource_str_by_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<String, EpubError> {
+        self.check_encryption(path.as_ref())?;
         let content = self.archive.get_entry_as_str(path)?;
         Ok(content)
     }
@@ -294,10 +1535,10 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// # Errors
     ///
     /// Returns an error if the id doesn't exists in the epub
-    pub fn get_resource_str(&mut self, id: &str) -> Result<String, Error> {
+    pub fn get_resource_str(&mut self, id: &str) -> Result<String, EpubError> {
         let path = match self.resources.get(id) {
             Some(s) => s.0.clone(),
-            None => return Err(anyhow!("id not found")),
+            None => return Err(EpubError::ResourceNotFound(id.to_string())),
         };
         let content = self.get_resource_str_by_path(path)?;
         Ok(content)
@@ -317,11 +1558,11 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// # Errors
     ///
     /// Fails if the resource can't be found.
-    pub fn get_resource_mime(&self, id: &str) -> Result<String, Error> {
-        if let Some(&(_, ref res)) = self.resources.get(id) {
+    pub fn get_resource_mime(&self, id: &str) -> Result<String, EpubError> {
+        if let Some((_, res)) = self.resources.get(id) {
             return Ok(res.to_string());
         }
-        Err(anyhow!("id not found"))
+        Err(EpubError::ResourceNotFound(id.to_string()))
     }
 
     /// Returns the resource mime searching by source full path
@@ -339,53 +1580,216 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// # Errors
     ///
     /// Fails if the resource can't be found.
-    pub fn get_resource_mime_by_path<P: AsRef<Path>>(&self, path: P) -> Result<String, Error> {
+    pub fn get_resource_mime_by_path<P: AsRef<Path>>(&self, path: P) -> Result<String, EpubError> {
         let path = path.as_ref();
+        let normalized = PathBuf::from(href::normalize(&path.display().to_string()));
 
         for (_, v) in self.resources.iter() {
-            if v.0 == path {
+            if v.0 == path || v.0 == normalized {
                 return Ok(v.1.to_string());
             }
         }
-        Err(anyhow!("path not found"))
+        Err(EpubError::ResourceNotFound(path.display().to_string()))
     }
 
-    /// Returns the current chapter content
+    /// Returns an iterator over the resources with a media type starting
+    /// with `mime`, like "image/" or "text/css", sorted by path. Yields the
+    /// resource id and content, and each content is read when the iterator
+    /// reaches it.
     ///
-    /// The current follows the epub spine order. You can modify the current
-    /// calling to `go_next`, `go_prev` or `set_current` methods.
-    ///
-    /// # Errors
+    /// # Examples
     ///
-    /// This call shouldn't fail, but can return an error if the epub doc is
-    /// broken.
-    pub fn get_current(&mut self) -> Result<Vec<u8>, Error> {
-        let current_id = self.get_current_id()?;
-        self.get_resource(&current_id)
-    }
-
-    pub fn get_current_str(&mut self) -> Result<String, Error> {
-        let current_id = self.get_current_id()?;
-        self.get_resource_str(&current_id)
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let images: Vec<String> = doc
+    ///     .resources_with_mime("image/")
+    ///     .map(|r| r.unwrap().0)
+    ///     .collect();
+    /// assert_eq!(vec!["cc.png", "portada.png"], images);
+    /// ```
+    pub fn resources_with_mime(&mut self, mime: &str) -> ResourceContents<'_, R> {
+        let mut items: Vec<(PathBuf, String)> = self
+            .resources
+            .iter()
+            .filter(|(_, (_, m))| m.starts_with(mime))
+            .map(|(id, (path, _))| (path.clone(), id.clone()))
+            .collect();
+        items.sort();
+        ResourceContents {
+            items: items.into_iter(),
+            doc: self,
+        }
     }
 
-    /// Returns the current chapter data, with resource uris renamed so they
-    /// have the epub:// prefix and all are relative to the root file
-    ///
-    /// This method is useful to render the content with a html engine, because inside the epub
-    /// local paths are relatives, so you can provide that content, because the engine will look
-    /// for the relative path in the filesystem and that file isn't there. You should provide files
-    /// with epub:// using the get_resource_by_path
+    /// Returns an iterator over the resources of the manifest, sorted by
+    /// path. The size of each content is looked up when the iterator
+    /// reaches it, without reading the content.
     ///
     /// # Examples
     ///
     /// ```
     /// # use epub::doc::EpubDoc;
+    /// # use std::path::Path;
     /// # let mut doc = EpubDoc::new("test.epub").unwrap();
-    /// let current = doc.get_current_with_epub_uris().unwrap();
+    /// let css = doc.resources().find(|r| r.id == "stylesheet.css").unwrap();
+    /// assert_eq!("Styles/stylesheet.css", css.href);
+    /// assert_eq!(Path::new("OEBPS/Styles/stylesheet.css"), css.path);
+    /// assert_eq!("text/css", css.media_type);
+    /// assert_eq!(Some(188), css.size);
+    /// ```
+    pub fn resources(&mut self) -> Resources<'_, R> {
+        let mut ids: Vec<(PathBuf, String)> = self
+            .resources
+            .iter()
+            .map(|(id, (path, _))| (path.clone(), id.clone()))
+            .collect();
+        ids.sort();
+        Resources {
+            ids: ids.into_iter(),
+            doc: self,
+        }
+    }
+
+    /// Returns the manifest properties of the resource `id`, like "nav",
+    /// "cover-image", "mathml", "svg", "remote-resources" or "scripted".
+    /// Empty if the resource doesn't have properties or doesn't exist.
+    pub fn resource_properties(&self, id: &str) -> &[String] {
+        self.manifest
+            .get(id)
+            .map(|item| item.properties.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns true if the resource `id` has the manifest `property`
+    pub fn resource_has_property(&self, id: &str, property: &str) -> bool {
+        self.resource_properties(id).iter().any(|p| p == property)
+    }
+
+    /// Returns the ids of the resources with the manifest `property`,
+    /// sorted
+    pub fn resources_with_property(&self, property: &str) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .manifest
+            .iter()
+            .filter(|(_, item)| item.properties.iter().any(|p| p == property))
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    /// Returns the ids of the documents with scripts, marked with the
+    /// "scripted" property
+    pub fn scripted_resources(&self) -> Vec<String> {
+        self.resources_with_property("scripted")
+    }
+
+    /// Returns the ids of the documents with MathML, marked with the
+    /// "mathml" property
+    pub fn mathml_resources(&self) -> Vec<String> {
+        self.resources_with_property("mathml")
+    }
+
+    /// Returns the ids of the documents with embedded SVG, marked with the
+    /// "svg" property
+    pub fn svg_resources(&self) -> Vec<String> {
+        self.resources_with_property("svg")
+    }
+
+    /// Returns the ids of the documents that use resources outside of the
+    /// epub, marked with the "remote-resources" property
+    pub fn remote_resource_users(&self) -> Vec<String> {
+        self.resources_with_property("remote-resources")
+    }
+
+    /// Returns the manifest fallback chain of the resource `id`: the id
+    /// itself and the ids of its fallbacks, in order. The chain stops at the
+    /// first fallback that isn't in the manifest or that is already in the
+    /// chain. Empty if the resource doesn't exist.
+    pub fn fallback_chain(&self, id: &str) -> Vec<String> {
+        let mut chain: Vec<String> = vec![];
+        let mut next = Some(id);
+        while let Some(id) = next {
+            if !self.resources.contains_key(id) || chain.iter().any(|c| c == id) {
+                break;
+            }
+            chain.push(id.to_string());
+            next = self.fallback(id);
+        }
+        chain
+    }
+
+    /// Returns the id of the manifest fallback of the resource `id`
+    fn fallback(&self, id: &str) -> Option<&str> {
+        self.manifest.get(id)?.fallback.as_deref()
+    }
+
+    /// Returns the id of the SMIL media overlay of the resource `id`, from
+    /// the `media-overlay` attribute of the manifest. See
+    /// `get_media_overlay`.
+    pub fn media_overlay_id(&self, id: &str) -> Option<&str> {
+        self.manifest.get(id)?.media_overlay.as_deref()
+    }
+
+    /// Walks the fallback chain of the resource `id` and returns the first
+    /// resource with a media type that the reader can render, according to
+    /// `supported`. Returns the resource itself if it's supported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let html = |mime: &str| mime == "application/xhtml+xml";
+    /// assert_eq!(Some(String::from("000.xhtml")), doc.resolve_fallback("000.xhtml", html));
+    /// assert_eq!(None, doc.resolve_fallback("cc.png", html));
+    /// ```
+    pub fn resolve_fallback<F>(&self, id: &str, supported: F) -> Option<String>
+    where
+        F: Fn(&str) -> bool,
+    {
+        self.fallback_chain(id)
+            .into_iter()
+            .find(|id| matches!(self.resources.get(id), Some((_, mime)) if supported(mime)))
+    }
+
+    /// Returns the current chapter content
+    ///
+    /// The current follows the epub spine order. You can modify the current
+    /// calling to `go_next`, `go_prev` or `set_current` methods.
+    ///
+    /// # Errors
+    ///
+    /// This call shouldn't fail, but can return an error if the epub doc is
+    /// broken.
+    pub fn get_current(&mut self) -> Result<Vec<u8>, EpubError> {
+        let current_id = self.get_current_id()?;
+        self.get_resource(&current_id)
+    }
+
+    pub fn get_current_str(&mut self) -> Result<String, EpubError> {
+        let current_id = self.get_current_id()?;
+        self.get_resource_str(&current_id)
+    }
+
+    /// Returns the current chapter data, with resource uris renamed so they
+    /// have the epub:// prefix and all are relative to the root file
+    ///
+    /// This method is useful to render the content with a html engine, because inside the epub
+    /// local paths are relatives, so you can provide that content, because the engine will look
+    /// for the relative path in the filesystem and that file isn't there. You should provide files
+    /// with epub:// using the get_resource_by_path
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let current = doc.get_current_with_epub_uris().unwrap();
     /// let text = String::from_utf8(current).unwrap();
     /// assert!(text.contains("epub://OEBPS/Images/portada.png"));
-
+    ///
     /// doc.go_next();
     /// let current = doc.get_current_with_epub_uris().unwrap();
     /// let text = String::from_utf8(current).unwrap();
@@ -393,7 +1797,7 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// assert!(text.contains("http://creativecommons.org/licenses/by-sa/3.0/"));
     /// ```
     ///
-    pub fn get_current_with_epub_uris(&mut self) -> Result<Vec<u8>, Error> {
+    pub fn get_current_with_epub_uris(&mut self) -> Result<Vec<u8>, EpubError> {
         let path = self.get_current_path()?;
         let current = self.get_current()?;
 
@@ -409,10 +1813,7 @@ impl<R: Read + Seek> EpubDoc<R> {
             &self.extra_css,
         );
 
-        match resp {
-            Ok(a) => Ok(a),
-            Err(error) => Err(anyhow!("{}", error.error)),
-        }
+        resp.map_err(EpubError::from)
     }
 
     /// Returns the current chapter mimetype
@@ -426,7 +1827,7 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// let m = doc.get_current_mime();
     /// assert_eq!("application/xhtml+xml", m.unwrap());
     /// ```
-    pub fn get_current_mime(&self) -> Result<String, Error> {
+    pub fn get_current_mime(&self) -> Result<String, EpubError> {
         let current_id = self.get_current_id()?;
         self.get_resource_mime(&current_id)
     }
@@ -443,11 +1844,11 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// let p = doc.get_current_path();
     /// assert_eq!(Path::new("OEBPS/Text/titlepage.xhtml"), p.unwrap());
     /// ```
-    pub fn get_current_path(&self) -> Result<PathBuf, Error> {
+    pub fn get_current_path(&self) -> Result<PathBuf, EpubError> {
         let current_id = self.get_current_id()?;
         match self.resources.get(&current_id) {
-            Some(&(ref p, _)) => Ok(p.clone()),
-            None => Err(anyhow!("Current not found")),
+            Some((p, _)) => Ok(p.clone()),
+            None => Err(EpubError::ResourceNotFound(current_id)),
         }
     }
 
@@ -462,15 +1863,18 @@ impl<R: Read + Seek> EpubDoc<R> {
     /// let id = doc.get_current_id();
     /// assert_eq!("titlepage.xhtml", id.unwrap());
     /// ```
-    pub fn get_current_id(&self) -> Result<String, Error> {
+    pub fn get_current_id(&self) -> Result<String, EpubError> {
         let current_id = self.spine.get(self.current);
         match current_id {
-            Some(id) => Ok(id.to_string()),
-            None => Err(anyhow!("current is broken")),
+            Some(item) => Ok(item.idref.clone()),
+            None => Err(EpubError::InvalidOperation(String::from(
+                "current is broken",
+            ))),
         }
     }
 
-    /// Changes current to the next chapter
+    /// Changes current to the next chapter, skipping the non-linear spine
+    /// items unless `OpenOptions::include_non_linear` is set
     ///
     /// # Examples
     ///
@@ -490,165 +1894,1474 @@ impl<R: Read + Seek> EpubDoc<R> {
     ///
     /// # Errors
     ///
-    /// If the page is the last, will not change and an error will be returned
-    pub fn go_next(&mut self) -> Result<(), Error> {
-        if self.current + 1 >= self.spine.len() {
-            return Err(anyhow!("last page"));
-        }
-        self.current += 1;
-        Ok(())
+    /// If the page is the last, will not change and an error will be returned
+    pub fn go_next(&mut self) -> Result<(), EpubError> {
+        let next = (self.current + 1..self.spine.len()).find(|&i| self.in_page_flow(i));
+        match next {
+            Some(n) => {
+                self.current = n;
+                self.fragment = None;
+                Ok(())
+            }
+            None => Err(EpubError::InvalidOperation(String::from("last page"))),
+        }
+    }
+
+    /// Changes current to the prev chapter, skipping the non-linear spine
+    /// items like `go_next`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let mut doc = doc.unwrap();
+    /// assert!(doc.go_prev().is_err());
+    ///
+    /// doc.go_next(); // 000.xhtml
+    /// doc.go_next(); // 001.xhtml
+    /// doc.go_next(); // 002.xhtml
+    /// doc.go_prev(); // 001.xhtml
+    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the page is the first, will not change and an error will be returned
+    pub fn go_prev(&mut self) -> Result<(), EpubError> {
+        let prev = (0..self.current).rev().find(|&i| self.in_page_flow(i));
+        match prev {
+            Some(n) => {
+                self.current = n;
+                self.fragment = None;
+                Ok(())
+            }
+            None => Err(EpubError::InvalidOperation(String::from("first page"))),
+        }
+    }
+
+    /// True if `go_next` and `go_prev` stop on the spine item `i`
+    fn in_page_flow(&self, i: usize) -> bool {
+        self.options.include_non_linear || self.spine[i].linear
+    }
+
+    /// Returns the spine items in the main reading order, without the
+    /// non-linear ones
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let doc = EpubDoc::new("tests/docs/Metamorphosis-jackson.epub").unwrap();
+    /// // the cover isn't linear
+    /// assert_eq!(8, doc.spine_all().count());
+    /// let first = doc.spine_linear().next().unwrap();
+    /// assert_eq!("title-page", first.idref);
+    /// ```
+    pub fn spine_linear(&self) -> impl Iterator<Item = &SpineItem> {
+        self.spine.iter().filter(|item| item.linear)
+    }
+
+    /// Returns all the spine items, linear or not
+    pub fn spine_all(&self) -> impl Iterator<Item = &SpineItem> {
+        self.spine.iter()
+    }
+
+    /// Returns the page progression direction declared in the spine, None
+    /// if it isn't declared or it's "default"
+    pub fn page_progression_direction(&self) -> Option<ReadingDirection> {
+        self.page_progression
+    }
+
+    /// Returns the direction of the page progression: the one declared in
+    /// the spine, or right to left if the first language of the book is
+    /// written in a right to left script, like Arabic or Hebrew. Left to
+    /// right otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::{EpubDoc, ReadingDirection};
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// // the book is in Spanish
+    /// assert_eq!(ReadingDirection::Ltr, doc.reading_direction());
+    /// doc.set_language("ar").unwrap();
+    /// assert_eq!(ReadingDirection::Rtl, doc.reading_direction());
+    /// ```
+    pub fn reading_direction(&self) -> ReadingDirection {
+        if let Some(direction) = self.page_progression {
+            return direction;
+        }
+        match self.mdata("language") {
+            Some(language) if is_rtl_language(&language) => ReadingDirection::Rtl,
+            _ => ReadingDirection::Ltr,
+        }
+    }
+
+    /// Returns the side of the spread where the spine item `index` is
+    /// shown, "left" or "right", or "center" for the items on both pages.
+    /// The side is the declared page spread of the item, or the side after
+    /// the previous item in the reading direction. The first page is on the
+    /// right in left to right books, like a printed book, and on the left
+    /// in right to left books.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!(Some("right"), doc.page_spread(0));
+    /// assert_eq!(Some("left"), doc.page_spread(1));
+    /// assert_eq!(None, doc.page_spread(50));
+    /// ```
+    pub fn page_spread(&self, index: usize) -> Option<&'static str> {
+        if index >= self.spine.len() {
+            return None;
+        }
+        let (first, second) = match self.reading_direction() {
+            ReadingDirection::Ltr => ("right", "left"),
+            ReadingDirection::Rtl => ("left", "right"),
+        };
+        let mut side = second;
+        for item in self.spine[..=index].iter() {
+            side = match item.page_spread() {
+                Some("left") => "left",
+                Some("right") => "right",
+                Some("center") => "center",
+                // the page after a center spread starts a new spread
+                _ if side == second || side == "center" => first,
+                _ => second,
+            };
+        }
+        Some(side)
+    }
+
+    /// Returns the number of chapters
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let mut doc = doc.unwrap();
+    /// assert_eq!(17, doc.get_num_pages());
+    /// ```
+    pub fn get_num_pages(&self) -> usize {
+        self.spine.len()
+    }
+
+    /// Returns the current chapter number, starting from 0
+    pub fn get_current_page(&self) -> usize {
+        self.current
+    }
+
+    /// Changes the current page
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let mut doc = doc.unwrap();
+    /// assert_eq!(0, doc.get_current_page());
+    /// doc.set_current_page(2);
+    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
+    /// assert_eq!(2, doc.get_current_page());
+    /// assert!(doc.set_current_page(50).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// If the page isn't valid, will not change and an error will be returned
+    pub fn set_current_page(&mut self, n: usize) -> Result<(), EpubError> {
+        if n >= self.spine.len() {
+            return Err(EpubError::InvalidOperation(String::from("page not valid")));
+        }
+        self.current = n;
+        self.fragment = None;
+        Ok(())
+    }
+
+    /// Changes the current page to the document of `path`, a full path in
+    /// the archive like the content of the toc entries, and keeps its
+    /// fragment as the target inside the document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let content = doc.toc[1].content.display().to_string();
+    /// doc.go_to_path(&content).unwrap();
+    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
+    ///
+    /// doc.go_to_href("Text/002.xhtml#top").unwrap();
+    /// assert_eq!("002.xhtml", doc.get_current_id().unwrap());
+    /// assert_eq!(Some("top"), doc.get_current_fragment());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::ResourceNotFound` if the document isn't in the
+    /// manifest, or `EpubError::InvalidOperation` if it isn't in the spine.
+    /// The current page doesn't change.
+    pub fn go_to_path(&mut self, path: &str) -> Result<(), EpubError> {
+        let (doc_path, fragment) = href::split_fragment(path);
+        let doc_path = PathBuf::from(href::normalize(doc_path));
+        let n = match self.resource_uri_to_chapter(&doc_path) {
+            Some(n) => n,
+            None if self.resources.values().any(|(p, _)| *p == doc_path) => {
+                return Err(EpubError::InvalidOperation(format!(
+                    "{} isn't in the spine",
+                    doc_path.display()
+                )))
+            }
+            None => return Err(EpubError::ResourceNotFound(path.to_string())),
+        };
+        self.current = n;
+        self.fragment = fragment
+            .filter(|f| !f.is_empty())
+            .map(|f| (n, href::normalize(f)));
+        Ok(())
+    }
+
+    /// Like `go_to_path`, but `href` is relative to the navigation document,
+    /// the EPUB3 nav document or the toc.ncx, like the hrefs written in
+    /// them. Without navigation, it's relative to the package document.
+    ///
+    /// # Errors
+    ///
+    /// See `go_to_path`.
+    pub fn go_to_href(&mut self, href: &str) -> Result<(), EpubError> {
+        let base = self
+            .get_nav_path()
+            .or_else(|| {
+                self.resources
+                    .values()
+                    .find(|(_, mime)| mime == NCX_MIME)
+                    .map(|(path, _)| path.clone())
+            })
+            .unwrap_or_else(|| self.root_file.clone());
+        let path = href::resolve_with_fragment(base, href);
+        self.go_to_path(&path.display().to_string())
+    }
+
+    /// Returns the fragment of the last `go_to_path` or `go_to_href`, the
+    /// id of the target inside the current document, until the page
+    /// changes. See `get_current_anchor` to find its position.
+    pub fn get_current_fragment(&self) -> Option<&str> {
+        match self.fragment {
+            Some((n, ref fragment)) if n == self.current => Some(fragment),
+            _ => None,
+        }
+    }
+
+    /// This will inject this css in every html page getted with the
+    /// get_current_with_epub_uris call
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub");
+    /// # let mut doc = doc.unwrap();
+    /// # let _ = doc.set_current_page(2);
+    /// let extracss = "body { background-color: black; color: white }";
+    /// doc.add_extra_css(extracss);
+    /// let current = doc.get_current_with_epub_uris().unwrap();
+    /// let text = String::from_utf8(current).unwrap();
+    /// assert!(text.contains(extracss));
+    /// ```
+    pub fn add_extra_css(&mut self, css: &str) {
+        self.extra_css.push(String::from(css));
+    }
+
+    /// Function to convert a resource path to a chapter number in the spine
+    /// If the resourse isn't in the spine list, None will be returned
+    ///
+    /// This method is useful to convert a toc NavPoint content to a chapter number
+    /// to be able to navigate easily. The fragment of the uri is ignored.
+    pub fn resource_uri_to_chapter(&self, uri: &Path) -> Option<usize> {
+        let uri = uri.display().to_string();
+        let (uri, _) = href::split_fragment(&uri);
+        let normalized = PathBuf::from(href::normalize(uri));
+        for (k, (path, _mime)) in self.resources.iter() {
+            if path == Path::new(uri) || *path == normalized {
+                return self.resource_id_to_chapter(k);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the toc entries in document order, each one with its nesting
+    /// level, 0 for the top level entries
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// for (level, nav) in doc.toc_flat() {
+    ///     println!("{}{}", "  ".repeat(level), nav.label);
+    /// }
+    /// assert_eq!(1, doc.toc_depth());
+    ///
+    /// let nav = doc.find_toc_entry("OEBPS/Text/001.xhtml#start").unwrap();
+    /// assert_eq!("Despertar", nav.label);
+    /// ```
+    pub fn toc_flat(&self) -> Vec<(usize, &NavPoint)> {
+        let mut flat = vec![];
+        navigation::flatten(&self.toc, 0, &mut flat);
+        flat
+    }
+
+    /// Returns the toc entries resolved to their spine positions, in play
+    /// order: the NCX playOrder, or the document order of the nav document.
+    /// Jumping to a chapter is then `set_current_page(link.spine_index)`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let links = doc.toc_links();
+    /// assert_eq!("Despertar", links[1].label);
+    /// assert_eq!("OEBPS/Text/001.xhtml", links[1].path.to_str().unwrap());
+    /// doc.set_current_page(links[1].spine_index.unwrap()).unwrap();
+    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
+    /// ```
+    pub fn toc_links(&self) -> Vec<TocLink> {
+        let positions: HashMap<&Path, usize> = self
+            .spine
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| {
+                self.resources
+                    .get(&item.idref)
+                    .map(|(path, _)| (path.as_path(), i))
+            })
+            .rev()
+            .collect();
+        let mut links: Vec<TocLink> = self
+            .toc_flat()
+            .into_iter()
+            .map(|(level, nav)| {
+                let content = nav.content.display().to_string();
+                let (path, fragment) = href::split_fragment(&content);
+                let path = PathBuf::from(href::normalize(path));
+                TocLink {
+                    label: nav.label.clone(),
+                    play_order: nav.play_order,
+                    level,
+                    spine_index: positions.get(path.as_path()).copied(),
+                    path,
+                    fragment: fragment.filter(|f| !f.is_empty()).map(String::from),
+                }
+            })
+            .collect();
+        links.sort_by_key(|link| link.play_order);
+        links
+    }
+
+    /// Returns the number of nesting levels of the toc, 0 if it's empty
+    pub fn toc_depth(&self) -> usize {
+        navigation::depth(&self.toc)
+    }
+
+    /// Returns the toc entry pointing to `href`, the full path in the
+    /// archive with an optional fragment. The entry with the same fragment
+    /// is preferred, otherwise the first entry pointing to the file is
+    /// returned.
+    pub fn find_toc_entry(&self, href: &str) -> Option<&NavPoint> {
+        let (path, fragment) = href::split_fragment(href);
+        let path = href::normalize(path);
+        let flat = self.toc_flat();
+        let entries: Vec<&NavPoint> = flat
+            .into_iter()
+            .map(|(_, nav)| nav)
+            .filter(|nav| {
+                let content = nav.content.display().to_string();
+                let (p, _) = href::split_fragment(&content);
+                href::normalize(p) == path
+            })
+            .collect();
+        let same_fragment = entries.iter().find(|nav| {
+            let content = nav.content.display().to_string();
+            href::split_fragment(&content).1 == fragment
+        });
+        same_fragment.or_else(|| entries.first()).copied()
+    }
+
+    /// Returns the toc entry of the chapter containing the spine item
+    /// `index`: the first entry pointing to that item or, for the items
+    /// without an entry, the last entry before it in reading order. Returns
+    /// None if no entry points to that item or a previous one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// // the title page is the first spine item
+    /// assert!(doc.chapter_for_spine_index(0).is_none());
+    /// let chapter = doc.chapter_for_spine_index(3).unwrap();
+    /// assert_eq!("Vestidor", chapter.label);
+    /// ```
+    pub fn chapter_for_spine_index(&self, index: usize) -> Option<&NavPoint> {
+        let mut chapter: Option<(usize, &NavPoint)> = None;
+        for (_, nav) in self.toc_flat() {
+            let position = match self.resource_uri_to_chapter(&nav.content) {
+                Some(position) if position <= index => position,
+                _ => continue,
+            };
+            match chapter {
+                Some((current, _)) if current == index => {}
+                Some((current, _)) if position < current => {}
+                _ => chapter = Some((position, nav)),
+            }
+        }
+        chapter.map(|(_, nav)| nav)
+    }
+
+    /// Returns the toc entry of the chapter containing the current spine
+    /// item. See `chapter_for_spine_index`.
+    pub fn current_chapter(&self) -> Option<&NavPoint> {
+        self.chapter_for_spine_index(self.current)
+    }
+
+    /// Function to convert a resource id to a chapter number in the spine
+    /// If the resourse isn't in the spine list, None will be returned
+    pub fn resource_id_to_chapter(&self, uri: &str) -> Option<usize> {
+        self.spine.iter().position(|item| item.idref == uri)
+    }
+
+    /// Replaces the content of the file by full path in the epub archive
+    ///
+    /// The content can be text or binary data, like images or fonts.
+    /// The modification is kept in memory until `save_as` is called. If the
+    /// modified file is the package document, the toc.ncx or the EPUB3
+    /// navigation document, the spine, resources, toc and metadata are
+    /// reloaded from the new content, with the navs and landmarks of the
+    /// navigation document. The path is normalized, like
+    /// "OEBPS/./Text/../content.opf" or percent encoded paths.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let path = doc.get_current_path().unwrap();
+    /// doc.modify_entry(&path, "<html>new content</html>").unwrap();
+    /// assert_eq!("<html>new content</html>", doc.get_current_str().unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the new package document can't be parsed.
+    pub fn modify_entry<P: AsRef<Path>, C: AsRef<[u8]>>(
+        &mut self,
+        path: P,
+        content: C,
+    ) -> Result<(), EpubError> {
+        let path = PathBuf::from(href::normalize(&path.as_ref().to_string_lossy()));
+        self.archive.modify_entry(&path, content);
+
+        let is_navigation = self.resources.iter().any(|(id, (p, mime))| {
+            *p == path && (mime == NCX_MIME || self.resource_has_property(id, "nav"))
+        });
+        if path == self.root_file || is_navigation {
+            self.reload()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a new resource to the epub archive and to the package document
+    /// manifest
+    ///
+    /// The `href` is the path relative to the package document, it's
+    /// percent encoded in the manifest. Use `add_spine_item` to include the
+    /// new resource in the reading order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.add_resource("notes", "Text/notes.xhtml", "application/xhtml+xml", "<html/>")
+    ///     .unwrap();
+    /// assert_eq!("<html/>", doc.get_resource_str("notes").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there's already a resource with this id or this
+    /// href.
+    pub fn add_resource<C: AsRef<[u8]>>(
+        &mut self,
+        id: &str,
+        href: &str,
+        mime: &str,
+        content: C,
+    ) -> Result<(), EpubError> {
+        if self.resources.contains_key(id) {
+            return Err(EpubError::AlreadyExists(id.to_string()));
+        }
+        let encoded = encode_href(href);
+        let path = self.convert_path_separators(&encoded);
+        if self.resources.values().any(|(p, _)| *p == path) {
+            return Err(EpubError::AlreadyExists(href.to_string()));
+        }
+        self.modify_package(|root| {
+            let manifest = root.find("manifest")?;
+            let item = manifest.borrow().new_child(
+                "item",
+                &[("id", id), ("href", &encoded), ("media-type", mime)],
+            );
+            manifest.borrow_mut().add_child(item);
+            Ok(())
+        })?;
+        self.archive.modify_entry(&path, content);
+        Ok(())
+    }
+
+    /// Appends the resource `id` at the end of the spine
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the resource id doesn't exists.
+    pub fn add_spine_item(&mut self, id: &str) -> Result<(), EpubError> {
+        if !self.resources.contains_key(id) {
+            return Err(EpubError::ResourceNotFound(id.to_string()));
+        }
+        self.modify_package(|root| {
+            let spine = root.find("spine")?;
+            let itemref = spine.borrow().new_child("itemref", &[("idref", id)]);
+            spine.borrow_mut().add_child(itemref);
+            Ok(())
+        })
+    }
+
+    /// Removes the resource `id` from the epub archive
+    ///
+    /// The resource is also removed from the manifest, the spine, the
+    /// guide and from the toc.ncx and the navigation document, so the epub
+    /// stays valid. Toc entries nested under a removed entry are moved up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.remove_resource("001.xhtml").unwrap();
+    /// assert_eq!(16, doc.spine.len());
+    /// assert!(doc.get_resource("001.xhtml").is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the resource id doesn't exists.
+    pub fn remove_resource(&mut self, id: &str) -> Result<(), EpubError> {
+        let path = match self.resources.get(id) {
+            Some(s) => s.0.clone(),
+            None => return Err(EpubError::ResourceNotFound(id.to_string())),
+        };
+        // the toc.ncx and the nav document, before removing their manifest items
+        let mut tocs: Vec<PathBuf> = self
+            .resources
+            .values()
+            .filter(|(_, mime)| mime == NCX_MIME)
+            .map(|(p, _)| p.clone())
+            .collect();
+        tocs.extend(self.get_nav_path());
+
+        self.archive.remove_entry(&path)?;
+
+        for toc in tocs.iter().filter(|p| **p != path) {
+            let content = self.archive.get_entry(toc)?;
+            let root = xmlutils::XMLReader::parse(content.as_slice())?;
+            remove_toc_entries(&mut root.borrow_mut(), toc, &path);
+            self.archive.modify_entry(toc, root.borrow().to_xml()?);
+        }
+
+        let root_file = self.root_file.clone();
+        self.modify_package(|root| {
+            for section in ["manifest", "spine", "guide", "metadata"].iter() {
+                if let Ok(node) = root.find(section) {
+                    node.borrow_mut().childs.retain(|c| {
+                        let c = c.borrow();
+                        match c.name.local_name.as_ref() {
+                            "item" => c.get_attr("id").ok().as_deref() != Some(id),
+                            "itemref" => c.get_attr("idref").ok().as_deref() != Some(id),
+                            "reference" => c
+                                .get_attr("href")
+                                .map(|h| href::resolve(&root_file, &h) != path)
+                                .unwrap_or(true),
+                            "meta" => {
+                                c.get_attr("name").ok().as_deref() != Some("cover")
+                                    || c.get_attr("content").ok().as_deref() != Some(id)
+                            }
+                            _ => true,
+                        }
+                    });
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Renames or moves a resource inside the epub archive
+    ///
+    /// Both `old_href` and `new_href` are relative to the package document,
+    /// as they're written in the manifest. Every reference to the resource
+    /// is rewritten: the manifest and guide, the toc.ncx, the navigation
+    /// document, the links in content documents and the `url()` in css
+    /// files. If the resource is moved to another directory, its own
+    /// relative links are also updated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # use std::path::Path;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.rename_resource("Images/portada.png", "images/cover.png").unwrap();
+    /// let cover = doc.resources.get("portada.png").unwrap();
+    /// assert_eq!(Path::new("OEBPS/images/cover.png"), cover.0);
+    ///
+    /// let title_page = doc.get_resource_str("titlepage.xhtml").unwrap();
+    /// assert!(title_page.contains("../images/cover.png"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the old resource doesn't exists, if there's
+    /// already a resource in `new_href` or if a css file that must be
+    /// rewritten isn't valid UTF-8.
+    pub fn rename_resource(&mut self, old_href: &str, new_href: &str) -> Result<(), EpubError> {
+        let old_path = self.convert_path_separators(old_href);
+        let new_path = self.convert_path_separators(new_href);
+        if self.archive.contains(&new_path) {
+            return Err(EpubError::AlreadyExists(new_href.to_string()));
+        }
+        let moved = self.archive.get_entry(&old_path)?;
+
+        let documents: Vec<(PathBuf, String)> = self
+            .resources
+            .values()
+            .filter(|(_, mime)| is_linking_mime(mime))
+            .cloned()
+            .collect();
+        let mut rewritten = vec![];
+        for (path, mime) in documents.iter() {
+            let destination = if *path == old_path { &new_path } else { path };
+            let content = match self.archive.get_entry(path) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let new_ref = |value: &str| -> Option<String> {
+                rewrite_href(path, destination, &old_path, &new_path, value)
+            };
+            if let Some(content) = rewrite_references(&content, mime, new_ref)? {
+                rewritten.push((destination.clone(), content));
+            }
+        }
+
+        self.archive.remove_entry(&old_path)?;
+        self.archive.modify_entry(&new_path, moved);
+        for (path, content) in rewritten {
+            self.archive.modify_entry(path, content);
+        }
+
+        let root_file = self.root_file.clone();
+        self.modify_package(|root| {
+            rewrite_node_hrefs(root, &|value| {
+                rewrite_href(&root_file, &root_file, &old_path, &new_path, value)
+            });
+            Ok(())
+        })
+    }
+
+    /// Sets the value of the metadata `name` in the package document
+    ///
+    /// Dublin Core names like "title" or "language" are written as `dc:`
+    /// elements, other names as `<meta>` elements. The first element with
+    /// this name is replaced, or a new one is added if there's none. The
+    /// "dcterms:modified" is updated to the current time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.set_metadata("publisher", "Me").unwrap();
+    /// assert_eq!("Me", doc.mdata("publisher").unwrap());
+    /// assert_ne!("2015-08-10T18:12:03Z", doc.mdata("dcterms:modified").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed.
+    pub fn set_metadata(&mut self, name: &str, value: &str) -> Result<(), EpubError> {
+        self.write_metadata(name, value, false)
+    }
+
+    /// Adds a new value for the metadata `name` in the package document,
+    /// keeping the existing ones
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed.
+    pub fn add_metadata(&mut self, name: &str, value: &str) -> Result<(), EpubError> {
+        self.write_metadata(name, value, true)
+    }
+
+    /// Sets the epub title
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed.
+    pub fn set_title(&mut self, title: &str) -> Result<(), EpubError> {
+        self.set_metadata("title", title)
+    }
+
+    /// Sets the epub creator
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed.
+    pub fn set_creator(&mut self, creator: &str) -> Result<(), EpubError> {
+        self.set_metadata("creator", creator)
+    }
+
+    /// Sets the epub language
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed.
+    pub fn set_language(&mut self, language: &str) -> Result<(), EpubError> {
+        self.set_metadata("language", language)
+    }
+
+    /// Adds a new identifier to the epub. The unique identifier doesn't
+    /// change.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed.
+    pub fn add_identifier(&mut self, identifier: &str) -> Result<(), EpubError> {
+        self.add_metadata("identifier", identifier)
+    }
+
+    fn write_metadata(&mut self, name: &str, value: &str, append: bool) -> Result<(), EpubError> {
+        let modified = utc_timestamp(SystemTime::now());
+        let has_modified = self.metadata.contains_key("dcterms:modified");
+        self.modify_package(|root| {
+            let epub3 = root
+                .get_attr("version")
+                .map(|v| v.starts_with('3'))
+                .unwrap_or(false);
+            let metadata = root.find("metadata")?;
+            let mut metadata = metadata.borrow_mut();
+            write_metadata_value(&mut metadata, name, value, append, epub3);
+            if name != "dcterms:modified" && (epub3 || has_modified) {
+                write_metadata_value(&mut metadata, "dcterms:modified", &modified, false, true);
+            }
+            Ok(())
+        })
+    }
+
+    /// Replaces the cover image, or adds a new one if the epub doesn't
+    /// have a cover image
+    ///
+    /// The cover resource is referenced by the EPUB2 `<meta name="cover">`
+    /// element and, in EPUB3, marked with the "cover-image" manifest
+    /// property. If the old cover has another media type, it's renamed
+    /// with the extension of the new one. A cover that isn't an image,
+    /// like a xhtml cover page, is kept and a new image is added. Like
+    /// other modifications, it's written calling `save_as`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # use std::path::Path;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let jpeg = vec![0xff, 0xd8, 0xff, 0xe0];
+    /// doc.set_cover(&jpeg, "image/jpeg").unwrap();
+    /// assert_eq!(jpeg, doc.get_cover().unwrap());
+    /// assert_eq!("image/jpeg", doc.get_resource_mime("portada.png").unwrap());
+    /// assert_eq!(Path::new("OEBPS/Images/portada.jpg"), doc.resources["portada.png"].0);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the `mime` isn't an image type or if the
+    /// package document can't be parsed.
+    pub fn set_cover<C: AsRef<[u8]>>(&mut self, content: C, mime: &str) -> Result<(), EpubError> {
+        if !mime.starts_with("image/") {
+            return Err(EpubError::InvalidOperation(format!(
+                "the cover must be an image, not {}",
+                mime
+            )));
+        }
+        let ext = match mime {
+            "image/jpeg" => "jpg",
+            "image/svg+xml" => "svg",
+            m => m.trim_start_matches("image/"),
+        };
+        let cover = self
+            .get_cover_id()
+            .ok()
+            .and_then(|id| Some((self.resources.get(&id)?.clone(), id)))
+            .filter(|((_, old_mime), _)| old_mime.starts_with("image/"));
+
+        let (id, replaced) = match cover {
+            Some(((path, _), id)) => {
+                if guess_media_type(&path.to_string_lossy()) != mime {
+                    let href = encode_href(&relative_path(&self.root_base, &path));
+                    let stem = href.rfind('.').map_or(href.as_str(), |i| &href[..i]);
+                    let new_href = self.unused_href(stem, ext);
+                    self.rename_resource(&href, &new_href)?;
+                }
+                (id, true)
+            }
+            None => {
+                let href = self.unused_href("cover", ext);
+                let id = self.unused_id("cover-image");
+                self.add_resource(&id, &href, mime, &content)?;
+                (id, false)
+            }
+        };
+
+        self.modify_package(|root| {
+            let epub3 = root
+                .get_attr("version")
+                .map(|v| v.starts_with('3'))
+                .unwrap_or(false);
+            let manifest = root.find("manifest")?;
+            for item in manifest.borrow().childs.iter() {
+                let mut item = item.borrow_mut();
+                let mut properties: Vec<String> = item
+                    .get_attr("properties")
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .filter(|p| *p != "cover-image")
+                    .map(String::from)
+                    .collect();
+                if item.get_attr("id").ok().as_deref() == Some(&id) {
+                    if epub3 {
+                        properties.push(String::from("cover-image"));
+                    }
+                    item.set_attr("media-type", mime);
+                }
+                if properties.is_empty() {
+                    item.attrs.retain(|a| a.name.local_name != "properties");
+                } else {
+                    item.set_attr("properties", &properties.join(" "));
+                }
+            }
+
+            let metadata = root.find("metadata")?;
+            write_metadata_value(&mut metadata.borrow_mut(), "cover", &id, false, false);
+            Ok(())
+        })?;
+        if replaced {
+            let path = self.resources[&id].0.clone();
+            self.archive.modify_entry(&path, content);
+        }
+        Ok(())
+    }
+
+    /// Changes the reading order, moving the spine item in the `from`
+    /// position to the `to` position
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.move_spine_item(0, 16).unwrap();
+    /// assert_eq!("000.xhtml", doc.spine[0].idref);
+    /// assert_eq!("titlepage.xhtml", doc.spine[16].idref);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the positions isn't valid.
+    pub fn move_spine_item(&mut self, from: usize, to: usize) -> Result<(), EpubError> {
+        if from >= self.spine.len() || to >= self.spine.len() {
+            return Err(EpubError::InvalidOperation(String::from("page not valid")));
+        }
+        let mut order: Vec<String> = self.spine.iter().map(|item| item.idref.clone()).collect();
+        let id = order.remove(from);
+        order.insert(to, id);
+        self.set_spine_order(&order)
+    }
+
+    /// Changes the reading order, `ids` should contain the same ids that
+    /// are in the spine, in the new order
+    ///
+    /// The current page doesn't change, even if it has a new position.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ids` doesn't have the same ids that the spine.
+    pub fn set_spine_order<S: AsRef<str>>(&mut self, ids: &[S]) -> Result<(), EpubError> {
+        let mut sorted: Vec<&str> = ids.iter().map(|id| id.as_ref()).collect();
+        let mut current: Vec<&str> = self.spine.iter().map(|item| item.idref.as_str()).collect();
+        sorted.sort_unstable();
+        current.sort_unstable();
+        if sorted != current {
+            return Err(EpubError::InvalidOperation(String::from(
+                "the new order doesn't match the spine items",
+            )));
+        }
+
+        let current_id = self.get_current_id().ok();
+        self.modify_package(|root| {
+            let spine = root.find("spine")?;
+            let mut spine = spine.borrow_mut();
+            let (mut itemrefs, others): (Vec<_>, Vec<_>) = spine
+                .childs
+                .drain(..)
+                .partition(|c| c.borrow().name.local_name == "itemref");
+            let mut childs = vec![];
+            for id in ids.iter() {
+                let pos = itemrefs.iter().position(|c| {
+                    c.borrow().get_attr("idref").ok().as_deref() == Some(id.as_ref())
+                });
+                if let Some(pos) = pos {
+                    childs.push(itemrefs.remove(pos));
+                }
+            }
+            childs.extend(others);
+            spine.childs = childs;
+            Ok(())
+        })?;
+
+        if let Some(n) = current_id.and_then(|id| self.resource_id_to_chapter(&id)) {
+            self.current = n;
+        }
+        Ok(())
+    }
+
+    /// Writes the `toc` to the navigation documents
+    ///
+    /// The toc in the EPUB3 navigation document and the toc.ncx navMap are
+    /// replaced with the current `toc` content. If the epub doesn't have a
+    /// toc.ncx, it's created, and the same for the navigation document in
+    /// EPUB3 packages, so the epub works in both new and old readers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// doc.toc.truncate(2);
+    /// doc.update_navigation().unwrap();
+    /// let ncx = doc.get_resource_str("ncx").unwrap();
+    /// assert!(ncx.contains("<text>Despertar</text>"));
+    /// assert!(!ncx.contains("<text>Vestidor</text>"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document or the navigation documents
+    /// can't be parsed.
+    pub fn update_navigation(&mut self) -> Result<(), EpubError> {
+        let toc = std::mem::take(&mut self.toc);
+        let result = self.write_navigation(&toc);
+        self.toc = toc;
+        result
     }
 
-    /// Changes current to the prev chapter
+    /// Builds a table of content from the `h1`, `h2` and `h3` headings of
+    /// the spine documents, in reading order
+    ///
+    /// Lower level headings are nested under the previous higher level
+    /// heading. Entries point to the heading id, or to the document if the
+    /// heading doesn't have an id. Useful for books without navigation, the
+    /// result can be stored in `toc` and written with `update_navigation`.
     ///
     /// # Examples
     ///
     /// ```
     /// # use epub::doc::EpubDoc;
-    /// # let doc = EpubDoc::new("test.epub");
-    /// # let mut doc = doc.unwrap();
-    /// assert!(doc.go_prev().is_err());
-    ///
-    /// doc.go_next(); // 000.xhtml
-    /// doc.go_next(); // 001.xhtml
-    /// doc.go_next(); // 002.xhtml
-    /// doc.go_prev(); // 001.xhtml
-    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
+    /// # let mut doc = EpubDoc::new("tests/docs/Metamorphosis-jackson.epub").unwrap();
+    /// if doc.toc.is_empty() {
+    ///     doc.toc = doc.toc_from_headings().unwrap();
+    ///     doc.update_navigation().unwrap();
+    /// }
     /// ```
     ///
     /// # Errors
     ///
-    /// If the page is the first, will not change and an error will be returned
-    pub fn go_prev(&mut self) -> Result<(), Error> {
-        if self.current < 1 {
-            return Err(anyhow!("first page"));
+    /// Returns an error if a spine document can't be read.
+    pub fn toc_from_headings(&mut self) -> Result<Vec<NavPoint>, EpubError> {
+        let mut headings = vec![];
+        for item in self.spine.clone().iter() {
+            let (path, mime) = match self.resources.get(&item.idref) {
+                Some(res) => res.clone(),
+                None => continue,
+            };
+            if !mime.contains("html") {
+                continue;
+            }
+            let content = self.archive.get_entry(&path)?;
+            for h in navigation::find_headings(&content, 3) {
+                headings.push((path.clone(), h));
+            }
         }
-        self.current -= 1;
-        Ok(())
+        let mut toc = navigation::heading_navpoints(&headings);
+        navigation::renumber(&mut toc, &mut 0);
+        Ok(toc)
     }
 
-    /// Returns the number of chapters
+    /// Starts an edit session, where every modification is applied in
+    /// memory and written all at once with `EditSession::commit`, or
+    /// discarded if the session is rolled back or dropped.
     ///
     /// # Examples
     ///
     /// ```
     /// # use epub::doc::EpubDoc;
-    /// # let doc = EpubDoc::new("test.epub");
-    /// # let mut doc = doc.unwrap();
-    /// assert_eq!(17, doc.get_num_pages());
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// {
+    ///     let mut session = doc.edit();
+    ///     session.remove_resource("001.xhtml").unwrap();
+    ///     assert_eq!(16, session.spine.len());
+    ///     session.rollback().unwrap();
+    /// }
+    /// assert_eq!(17, doc.spine.len());
     /// ```
-    pub fn get_num_pages(&self) -> usize {
-        self.spine.len()
+    pub fn edit(&mut self) -> EditSession<'_, R> {
+        EditSession::new(self)
     }
 
-    /// Returns the current chapter number, starting from 0
-    pub fn get_current_page(&self) -> usize {
-        self.current
+    /// Writes the epub, with all the pending modifications, to a new file
+    /// in `path`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written or if `path` is the
+    /// file used to open this doc.
+    pub fn save_as<P: AsRef<Path>>(&mut self, path: P) -> Result<(), EpubError> {
+        self.archive.save_as(path)
     }
 
-    /// Changes the current page
+    /// Writes the epub, with all the pending modifications, to a new file
+    /// in `path` using the write `options`
     ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use epub::doc::EpubDoc;
-    /// # let doc = EpubDoc::new("test.epub");
-    /// # let mut doc = doc.unwrap();
-    /// assert_eq!(0, doc.get_current_page());
-    /// doc.set_current_page(2);
-    /// assert_eq!("001.xhtml", doc.get_current_id().unwrap());
-    /// assert_eq!(2, doc.get_current_page());
-    /// assert!(doc.set_current_page(50).is_err());
-    /// ```
+    /// The compression rules in `options` use the media types defined in
+    /// the manifest.
     ///
     /// # Errors
     ///
-    /// If the page isn't valid, will not change and an error will be returned
-    pub fn set_current_page(&mut self, n: usize) -> Result<(), Error> {
-        if n >= self.spine.len() {
-            return Err(anyhow!("page not valid"));
+    /// Returns an error if the file can't be written or if `path` is the
+    /// file used to open this doc.
+    pub fn save_as_with<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &WriteOptions,
+    ) -> Result<(), EpubError> {
+        let mut options = options.clone();
+        for (path, mime) in self.resources.values() {
+            let name = path.display().to_string();
+            options.media_types.insert(name, mime.clone());
         }
-        self.current = n;
-        Ok(())
+        self.archive.save_as_with(path, &options)
     }
 
-    /// This will inject this css in every html page getted with the
-    /// get_current_with_epub_uris call
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use epub::doc::EpubDoc;
-    /// # let doc = EpubDoc::new("test.epub");
-    /// # let mut doc = doc.unwrap();
-    /// # let _ = doc.set_current_page(2);
-    /// let extracss = "body { background-color: black; color: white }";
-    /// doc.add_extra_css(extracss);
-    /// let current = doc.get_current_with_epub_uris().unwrap();
-    /// let text = String::from_utf8(current).unwrap();
-    /// assert!(text.contains(extracss));
-    /// ```
-    pub fn add_extra_css(&mut self, css: &str) {
-        self.extra_css.push(String::from(css));
+    // Resolves an href of the package document to the full path in the
+    // archive, with unix separators, so ZipArchive's by_name method will
+    // retrieve the proper file on Windows too.
+    fn convert_path_separators(&self, href: &str) -> PathBuf {
+        href::resolve(&self.root_file, href)
     }
 
-    /// Function to convert a resource path to a chapter number in the spine
-    /// If the resourse isn't in the spine list, None will be returned
-    ///
-    /// This method is useful to convert a toc NavPoint content to a chapter number
-    /// to be able to navigate easily
-    pub fn resource_uri_to_chapter(&self, uri: &PathBuf) -> Option<usize> {
-        for (k, (path, _mime)) in self.resources.iter() {
-            if path == uri {
-                return self.resource_id_to_chapter(&k);
+    /// Returns the path of the EPUB3 navigation document, the manifest
+    /// item with the "nav" property
+    pub(crate) fn get_nav_path(&mut self) -> Option<PathBuf> {
+        let container = self.archive.get_entry(&self.root_file).ok()?;
+        let root = xmlutils::XMLReader::parse(container.as_slice()).ok()?;
+        let manifest = root.borrow().find("manifest").ok()?;
+        let manifest = manifest.borrow();
+        manifest.childs.iter().find_map(|item| {
+            let item = item.borrow();
+            let properties = item.get_attr("properties").ok()?;
+            if properties.split_whitespace().any(|p| p == "nav") {
+                let href = item.get_attr("href").ok()?;
+                Some(self.convert_path_separators(&href))
+            } else {
+                None
             }
-        }
+        })
+    }
 
-        None
+    /// Returns an href, relative to the package document, not used by any
+    /// file in the archive, like `name.ext` or `name-1.ext`
+    fn unused_href(&mut self, name: &str, ext: &str) -> String {
+        let mut href = format!("{}.{}", name, ext);
+        let mut n = 1;
+        while self
+            .get_resource_by_path(self.convert_path_separators(&href))
+            .is_ok()
+        {
+            href = format!("{}-{}.{}", name, n, ext);
+            n += 1;
+        }
+        href
     }
 
-    /// Function to convert a resource id to a chapter number in the spine
-    /// If the resourse isn't in the spine list, None will be returned
-    pub fn resource_id_to_chapter(&self, uri: &str) -> Option<usize> {
-        self.spine.iter().position(|item| item == uri)
+    /// Returns a manifest id not used by any resource, based on `id`
+    fn unused_id(&self, id: &str) -> String {
+        let mut id = String::from(id);
+        while self.resources.contains_key(&id) {
+            id.push('_');
+        }
+        id
     }
 
-    // Forcibly converts separators in a filepath to unix separators to
-    // to ensure that ZipArchive's by_name method will retrieve the proper
-    // file. Failing to convert to unix-style on Windows causes the
-    // ZipArchive not to find the file.
-    fn convert_path_separators(&self, href: &str) -> PathBuf {
-        let path = self.root_base.join(href.split("/").collect::<PathBuf>());
-        if cfg!(windows) {
-            let path = path.as_path().display().to_string().replace("\\", "/");
-            return PathBuf::from(path);
+    fn write_navigation(&mut self, toc: &[NavPoint]) -> Result<(), EpubError> {
+        let title = self.mdata("title").unwrap_or_default();
+        let uid = self.unique_identifier.clone().unwrap_or_default();
+        let opf = self.archive.get_entry(&self.root_file)?;
+        let opf = xmlutils::XMLReader::parse(opf.as_slice())?;
+        let epub3 = opf
+            .borrow()
+            .get_attr("version")
+            .map(|v| v.starts_with('3'))
+            .unwrap_or(false);
+        let ncx_id = opf
+            .borrow()
+            .find("spine")
+            .and_then(|s| s.borrow().get_attr("toc"))
+            .ok();
+
+        // new manifest items as id, href, mime and properties
+        let mut new_items = vec![];
+
+        match self.get_nav_path() {
+            Some(path) => {
+                let base = path.parent().unwrap_or_else(|| Path::new(""));
+                let nav = String::from_utf8(navigation::nav_element(toc, base)?)?;
+                let content = self.archive.get_entry_as_str(&path)?;
+                let re = TOC_NAV.with(Regex::clone);
+                let content = if re.is_match(&content) {
+                    re.replace(&content, NoExpand(&nav)).to_string()
+                } else {
+                    content.replacen("</body>", &format!("{}</body>", nav), 1)
+                };
+                self.archive.modify_entry(&path, content);
+            }
+            None if epub3 => {
+                let href = self.unused_href("nav", "xhtml");
+                let path = self.convert_path_separators(&href);
+                let content = navigation::nav_document(&title, toc, &self.root_base)?;
+                self.archive.modify_entry(&path, content);
+                new_items.push((self.unused_id("nav"), href, "application/xhtml+xml", "nav"));
+            }
+            None => {}
+        }
+
+        let ncx_path = ncx_id
+            .and_then(|id| self.resources.get(&id))
+            .or_else(|| self.resources.values().find(|(_, mime)| mime == NCX_MIME))
+            .map(|(path, _)| path.clone());
+        match ncx_path {
+            Some(path) => {
+                let base = path.parent().unwrap_or_else(|| Path::new(""));
+                let content = self.archive.get_entry_as_str(&path)?;
+                let re = NAV_MAP.with(Regex::clone);
+                let content = if re.is_match(&content) {
+                    let nav_map = String::from_utf8(navigation::nav_map_element(toc, base)?)?;
+                    re.replace(&content, NoExpand(&nav_map)).into_owned().into_bytes()
+                } else {
+                    navigation::ncx_document(&uid, &title, toc, base)?
+                };
+                self.archive.modify_entry(&path, content);
+            }
+            None => {
+                let href = self.unused_href("toc", "ncx");
+                let path = self.convert_path_separators(&href);
+                let content = navigation::ncx_document(&uid, &title, toc, &self.root_base)?;
+                self.archive.modify_entry(&path, content);
+                new_items.push((self.unused_id("ncx"), href, NCX_MIME, ""));
+            }
         }
-        PathBuf::from(path)
+
+        if new_items.is_empty() {
+            return Ok(());
+        }
+        self.modify_package(|root| {
+            let manifest = root.find("manifest")?;
+            let spine = root.find("spine")?;
+            for (id, href, mime, properties) in new_items.iter() {
+                let mut attrs = vec![("id", id.as_str()), ("href", href), ("media-type", mime)];
+                if !properties.is_empty() {
+                    attrs.push(("properties", properties));
+                }
+                let item = manifest.borrow().new_child("item", &attrs);
+                manifest.borrow_mut().add_child(item);
+                if *mime == NCX_MIME {
+                    spine.borrow_mut().set_attr("toc", id);
+                }
+            }
+            Ok(())
+        })
     }
 
-    fn fill_resources(&mut self) -> Result<(), Error> {
+    /// Parses the package document, lets `f` modify it and stores the
+    /// result, reloading the doc
+    fn modify_package<F>(&mut self, f: F) -> Result<(), EpubError>
+    where
+        F: FnOnce(&xmlutils::XMLNode) -> Result<(), EpubError>,
+    {
         let container = self.archive.get_entry(&self.root_file)?;
         let root = xmlutils::XMLReader::parse(container.as_slice())?;
+        f(&root.borrow())?;
+        let content = root.borrow().to_xml()?;
+        let root_file = self.root_file.clone();
+        self.modify_entry(root_file, content)
+    }
+
+    /// Clears the parsed package document and parses it again
+    pub(crate) fn reload(&mut self) -> Result<(), EpubError> {
+        self.spine.clear();
+        self.resources.clear();
+        self.manifest.clear();
+        self.page_progression = None;
+        self.toc.clear();
+        self.metadata.clear();
+        self.metadata_items.clear();
+        self.metadata_links.clear();
+        self.navs.clear();
+        self.landmarks.clear();
+        self.page_list.clear();
+        self.guide.clear();
+        self.unique_identifier = None;
+        self.warnings.clear();
+        self.fill_resources()?;
+        if self.current >= self.spine.len() {
+            self.current = 0;
+        }
+        Ok(())
+    }
+
+    /// Handles a spec violation: fails in strict mode, with the `spec`
+    /// reference, and adds a warning in lenient mode
+    fn violation(&mut self, problem: &str, spec: &str) -> Result<(), EpubError> {
+        if self.options.mode == ParseMode::Strict {
+            return Err(EpubError::SpecViolation {
+                problem: problem.to_string(),
+                spec: spec.to_string(),
+            });
+        }
+        self.warnings.push(format!("{} ({})", problem, spec));
+        Ok(())
+    }
+
+    fn check_mimetype(&mut self) -> Result<(), EpubError> {
+        let mimetype = self.archive.get_entry("mimetype").unwrap_or_default();
+        if mimetype != b"application/epub+zip" {
+            self.violation(
+                "the mimetype file is missing or doesn't contain application/epub+zip",
+                "EPUB 3.3, OCF ZIP container: the mimetype file",
+            )?;
+        }
+        Ok(())
+    }
+
+    fn fill_resources(&mut self) -> Result<(), EpubError> {
+        self.check_mimetype()?;
+        self.read_encryption()?;
+        let container = self.archive.get_entry(&self.root_file)?;
+        let invalid = |e: XMLError| EpubError::InvalidOpf {
+            path: self.root_file.clone(),
+            reason: e.error,
+        };
+        let (root, error) =
+            xmlutils::XMLReader::parse_recover(container.as_slice()).map_err(invalid)?;
+        if let Some(e) = error {
+            if self.options.mode == ParseMode::Strict {
+                return Err(invalid(e));
+            }
+            self.warnings.push(format!(
+                "the package document is malformed, the elements after the error are ignored: {}",
+                e.error
+            ));
+        }
+        let manifest = self.package_part(&root.borrow(), "manifest")?;
+        let spine = self.package_part(&root.borrow(), "spine")?;
+        let metadata = self.package_part(&root.borrow(), "metadata")?;
+        for child in root.borrow().childs.iter() {
+            let name = child.borrow().name.local_name.clone();
+            if !PACKAGE_ELEMENTS.contains(&name.as_str()) {
+                self.warnings
+                    .push(format!("unknown package element {}", name));
+            }
+        }
         let unique_identifier_id = &root.borrow().get_attr("unique-identifier").ok();
+        let (prefixes, malformed) =
+            Prefixes::parse(&root.borrow().get_attr("prefix").unwrap_or_default());
+        self.prefixes = prefixes;
+        self.warnings.extend(malformed);
+        let epub3 = root
+            .borrow()
+            .get_attr("version")
+            .map(|v| v.starts_with('3'))
+            .unwrap_or(false);
         // resources from manifest
-        let manifest = root.borrow().find("manifest")?;
+        let mut nav_path = None;
+        let mut has_cover_image = false;
         for r in manifest.borrow().childs.iter() {
             let item = r.borrow();
-            let _ = self.insert_resource(&item);
+            if item.name.local_name != "item" {
+                self.warnings
+                    .push(format!("unknown manifest element {}", item.name.local_name));
+                continue;
+            }
+            if let Ok(id) = item.get_attr("id") {
+                if self.resources.contains_key(&id) {
+                    self.violation(
+                        &format!("duplicate manifest id {}", id),
+                        "EPUB 3.3, the item element: the id must be unique",
+                    )?;
+                }
+            }
+            if let Ok(properties) = item.get_attr("properties") {
+                if properties.split_whitespace().any(|p| p == "nav") {
+                    nav_path = item
+                        .get_attr("href")
+                        .ok()
+                        .map(|href| self.convert_path_separators(&href));
+                }
+                has_cover_image |= properties.split_whitespace().any(|p| p == "cover-image");
+            }
+            if let Err(e) = self.insert_resource(&item) {
+                self.violation(
+                    &format!("invalid manifest item: {}", e.error),
+                    "EPUB 3.3, the item element: id, href and media-type are required",
+                )?;
+            }
         }
+        let mut circular: Vec<String> = vec![];
+        for id in self.manifest.keys() {
+            let chain = self.fallback_chain(id);
+            let last = chain.last().and_then(|last| self.fallback(last));
+            if matches!(last, Some(fallback) if chain.iter().any(|c| c == fallback)) {
+                circular.push(format!("the fallback chain of {} is circular", id));
+            }
+        }
+        circular.sort();
+        self.warnings.extend(circular);
         // items from spine
-        let spine = root.borrow().find("spine")?;
+        self.page_progression = match spine.borrow().get_attr("page-progression-direction") {
+            Ok(d) if d == "rtl" => Some(ReadingDirection::Rtl),
+            Ok(d) if d == "ltr" => Some(ReadingDirection::Ltr),
+            _ => None,
+        };
         for r in spine.borrow().childs.iter() {
             let item = r.borrow();
-            let _ = self.insert_spine(&item);
+            if item.name.local_name != "itemref" {
+                self.warnings
+                    .push(format!("unknown spine element {}", item.name.local_name));
+                continue;
+            }
+            match item.get_attr("idref") {
+                Ok(id) if self.resources.contains_key(&id) => self.spine.push(SpineItem {
+                    idref: id,
+                    id: item.get_attr("id").ok(),
+                    linear: item.get_attr("linear").map_or(true, |l| l.trim() != "no"),
+                    properties: item
+                        .get_attr("properties")
+                        .map(|p| p.split_whitespace().map(String::from).collect())
+                        .unwrap_or_default(),
+                }),
+                Ok(id) => self.violation(
+                    &format!("the spine item {} isn't in the manifest", id),
+                    "EPUB 3.3, the itemref element: the idref must be a manifest item",
+                )?,
+                Err(e) => self.violation(
+                    &format!("invalid spine item: {}", e.error),
+                    "EPUB 3.3, the itemref element: the idref is required",
+                )?,
+            }
         }
         // toc.ncx
-        if let Ok(toc) = spine.borrow().get_attr("toc") {
-            let _ = self.fill_toc(&toc);
+        match spine.borrow().get_attr("toc") {
+            Ok(toc) => {
+                if let Err(e) = self.fill_toc(&toc) {
+                    self.violation(
+                        &format!("the toc.ncx can't be read: {}", e),
+                        "OPF 2.0.1, 2.4.1 spine: the toc attribute is the NCX id",
+                    )?;
+                }
+            }
+            Err(_) if !epub3 => self.violation(
+                "the spine doesn't have a toc attribute",
+                "OPF 2.0.1, 2.4.1 spine: the toc attribute is required",
+            )?,
+            Err(_) => {}
+        }
+        if epub3 && nav_path.is_none() {
+            self.violation(
+                "the manifest doesn't have a navigation document",
+                "EPUB 3.3, the nav property: one manifest item must be the navigation document",
+            )?;
+        }
+        if let Some(nav) = nav_path {
+            if let Err(e) = self.fill_nav_lists(&nav) {
+                self.warnings
+                    .push(format!("the navigation document can't be read: {}", e));
+            }
+        }
+        // EPUB2 guide
+        if let Ok(guide) = root.borrow().find("guide") {
+            for r in guide.borrow().childs.iter() {
+                let item = r.borrow();
+                if item.name.local_name != "reference" {
+                    continue;
+                }
+                if let (Ok(reference_type), Ok(href)) =
+                    (item.get_attr("type"), item.get_attr("href"))
+                {
+                    self.guide.push(GuideReference {
+                        reference_type,
+                        title: item.get_attr("title").unwrap_or_default(),
+                        content: href::resolve_with_fragment(&self.root_file, &href),
+                    });
+                }
+            }
         }
         // metadata
-        let metadata = root.borrow().find("metadata")?;
-        for r in metadata.borrow().childs.iter() {
+        let max_items = self.options.max_metadata_items.unwrap_or(usize::MAX);
+        let mut first_identifier = None;
+        if metadata.borrow().childs.len() > max_items {
+            self.warnings
+                .push(format!("metadata items after {} ignored", max_items));
+        }
+        let lang = metadata
+            .borrow()
+            .get_attr("lang")
+            .or_else(|_| root.borrow().get_attr("lang"))
+            .ok();
+        let (items, unresolved) =
+            metadata::parse_items(&metadata.borrow(), max_items, lang.as_deref());
+        self.metadata_items = items;
+        self.metadata_links = metadata::parse_links(&metadata.borrow(), max_items, &self.root_file);
+        for link in self.metadata_links.iter_mut() {
+            if let Some(ref path) = link.path {
+                link.resource_id = self
+                    .resources
+                    .iter()
+                    .find(|(_, (p, _))| p == path)
+                    .map(|(id, _)| id.clone());
+            }
+        }
+        self.warnings.extend(unresolved);
+        for r in metadata.borrow().childs.iter().take(max_items) {
             let item = r.borrow();
             if item.name.local_name == "meta" {
                 if let (Ok(k), Ok(v)) = (item.get_attr("name"), item.get_attr("content")) {
@@ -676,6 +3389,9 @@ impl<R: Read + Seek> EpubDoc<R> {
                         }
                     }
                 }
+                if k == "identifier" && first_identifier.is_none() {
+                    first_identifier = Some(v.to_string());
+                }
                 if self.metadata.contains_key(k) {
                     if let Some(arr) = self.metadata.get_mut(k) {
                         arr.push(v);
@@ -685,45 +3401,168 @@ impl<R: Read + Seek> EpubDoc<R> {
                 }
             }
         }
+        if self.unique_identifier.is_none() {
+            let problem = match unique_identifier_id {
+                Some(id) => format!("the unique-identifier {} isn't an identifier", id),
+                None => String::from("the package doesn't have a unique-identifier"),
+            };
+            self.violation(
+                &problem,
+                "EPUB 3.3, the package element: unique-identifier must be a dc:identifier id",
+            )?;
+            self.unique_identifier = first_identifier;
+        }
+        // cover
+        match self.mdata("cover") {
+            Some(id) if !self.resources.contains_key(&id) => self
+                .warnings
+                .push(format!("the cover meta points to the missing item {}", id)),
+            None if !has_cover_image => self.warnings.push(String::from("no cover meta")),
+            _ => {}
+        }
+        // toc hrefs
+        let paths: HashSet<&Path> = self.resources.values().map(|r| r.0.as_path()).collect();
+        let mut unresolved = vec![];
+        unresolved_navpoints(&self.toc, &paths, &mut unresolved);
+        for path in unresolved {
+            self.warnings
+                .push(format!("the toc entry {} isn't in the manifest", path));
+        }
         Ok(())
     }
 
+    /// Returns the package element by the `name`. A missing element is a
+    /// violation, read as empty in lenient mode.
+    fn package_part(
+        &mut self,
+        root: &xmlutils::XMLNode,
+        name: &str,
+    ) -> Result<Rc<RefCell<xmlutils::XMLNode>>, EpubError> {
+        if let Ok(part) = root.find(name) {
+            return Ok(part);
+        }
+        self.violation(
+            &format!("the package document doesn't have a {} element", name),
+            "EPUB 3.3, the package element: metadata, manifest and spine are required",
+        )?;
+        Ok(Rc::new(RefCell::new(root.new_child(name, &[]))))
+    }
+
     fn insert_resource(&mut self, item: &xmlutils::XMLNode) -> Result<(), XMLError> {
         let id = item.get_attr("id")?;
         let href = item.get_attr("href")?;
         let mtype = item.get_attr("media-type")?;
         let path = self.convert_path_separators(&href);
+        let properties: Vec<String> = match item.get_attr("properties") {
+            Ok(p) => p.split_whitespace().map(String::from).collect(),
+            Err(_) => vec![],
+        };
+        let manifest_item = ManifestItem {
+            href,
+            properties,
+            fallback: item.get_attr("fallback").ok(),
+            media_overlay: item.get_attr("media-overlay").ok(),
+        };
+        self.manifest.insert(id.clone(), manifest_item);
         self.resources
             .insert(id, (path, mtype));
         Ok(())
     }
 
-    fn insert_spine(&mut self, item:&xmlutils::XMLNode) -> Result<(), XMLError> {
-        let id = item.get_attr("idref")?;
-        self.spine.push(id);
-        Ok(())
-    }
-
-    fn fill_toc(&mut self, id: &str) -> Result<(), Error> {
+    fn fill_toc(&mut self, id: &str) -> Result<(), EpubError> {
         let toc_res = self
             .resources
             .get(id)
-            .ok_or_else(|| anyhow!("No toc found"))?;
+            .ok_or_else(|| EpubError::ResourceNotFound(id.to_string()))?;
 
         let container = self.archive.get_entry(&toc_res.0)?;
         let root = xmlutils::XMLReader::parse(container.as_slice())?;
 
         let mapnode = root.borrow().find("navMap")?;
 
-        self.toc.append(&mut self.get_navpoints(&mapnode.borrow()));
+        let depth = self.options.max_toc_depth.unwrap_or(usize::MAX);
+        let toc = toc_res.0.clone();
+        let mut navpoints = self.get_navpoints(&mapnode.borrow(), &toc, depth);
+        self.toc.append(&mut navpoints);
         self.toc.sort();
 
+        if let Ok(page_list) = root.borrow().find("pageList") {
+            let mut targets = vec![];
+            for target in page_list.borrow().childs.iter() {
+                let target = target.borrow();
+                if target.name.local_name != "pageTarget" {
+                    continue;
+                }
+                let play_order = target
+                    .get_attr("playOrder")
+                    .ok()
+                    .and_then(|n| n.parse::<usize>().ok());
+                let content = match target.find("content") {
+                    Ok(c) => c.borrow().get_attr("src").ok(),
+                    _ => None,
+                };
+                let label = match target.find("navLabel") {
+                    Ok(l) => l
+                        .borrow()
+                        .find("text")
+                        .ok()
+                        .and_then(|t| t.borrow().text.clone()),
+                    _ => None,
+                };
+                // the value is the page number, for the pages without a label
+                let label = label.or_else(|| target.get_attr("value").ok());
+                if let (Some(content), Some(label)) = (content, label) {
+                    let page = PageTarget {
+                        label: label.trim().to_string(),
+                        content: href::resolve_with_fragment(&toc, &content),
+                    };
+                    targets.push((play_order, page));
+                }
+            }
+            // stable, so the targets without playOrder keep the document order
+            targets.sort_by_key(|(play_order, _)| *play_order);
+            self.page_list = targets.into_iter().map(|(_, page)| page).collect();
+        }
+
+        Ok(())
+    }
+
+    /// Reads the navs, the landmarks and the page list of the navigation
+    /// document
+    fn fill_nav_lists(&mut self, nav: &Path) -> Result<(), EpubError> {
+        let content = self.archive.get_entry(nav)?;
+        let root = xmlutils::XMLReader::parse(content.as_slice())?;
+        let root = root.borrow();
+        let depth = self.options.max_toc_depth.unwrap_or(usize::MAX);
+        for (nav_type, navpoints) in navigation::nav_trees(&root, nav, depth) {
+            self.navs.entry(nav_type).or_insert(navpoints);
+        }
+        self.landmarks = navigation::nav_links(&root, "landmarks", nav)
+            .into_iter()
+            .map(|(label, content, epub_type)| Landmark {
+                label,
+                content,
+                epub_type: epub_type.unwrap_or_default(),
+            })
+            .collect();
+        let pages: Vec<PageTarget> = navigation::nav_links(&root, "page-list", nav)
+            .into_iter()
+            .map(|(label, content, _)| PageTarget { label, content })
+            .collect();
+        // the page list of the toc.ncx otherwise
+        if !pages.is_empty() {
+            self.page_list = pages;
+        }
         Ok(())
     }
 
-    /// Recursively extract all navpoints from a node.
-    fn get_navpoints(&self, parent: &xmlutils::XMLNode) -> Vec<NavPoint> {
+    /// Recursively extract all navpoints from a node, up to `depth` levels.
+    /// The content is resolved relative to the `toc` file.
+    fn get_navpoints(&self, parent: &xmlutils::XMLNode, toc: &Path, depth: usize) -> Vec<NavPoint> {
         let mut navpoints = Vec::new();
+        if depth == 0 {
+            return navpoints;
+        }
 
         // TODO: get docTitle
         // TODO: parse metadata (dtb:totalPageCount, dtb:depth, dtb:maxPageNumber)
@@ -736,20 +3575,20 @@ impl<R: Read + Seek> EpubDoc<R> {
             let play_order = item
                 .get_attr("playOrder")
                 .ok()
-                .and_then(|n| usize::from_str_radix(&n, 10).ok());
+                .and_then(|n| n.parse::<usize>().ok());
             let content = match item.find("content") {
                 Ok(c) => c
                     .borrow()
                     .get_attr("src")
                     .ok()
-                    .map(|p| self.root_base.join(p)),
+                    .map(|p| href::resolve_with_fragment(toc, &p)),
                 _ => None,
             };
             let label = match item.find("navLabel") {
                 Ok(l) => l
                     .borrow()
                     .childs
-                    .get(0)
+                    .first()
                     .and_then(|t| t.borrow().text.clone()),
                 _ => None,
             };
@@ -758,7 +3597,7 @@ impl<R: Read + Seek> EpubDoc<R> {
                 let navpoint = NavPoint {
                     label: l.clone(),
                     content: c.clone(),
-                    children: self.get_navpoints(&item),
+                    children: self.get_navpoints(&item, toc, depth - 1),
                     play_order: o,
                 };
                 navpoints.push(navpoint);
@@ -770,46 +3609,342 @@ impl<R: Read + Seek> EpubDoc<R> {
     }
 }
 
-fn get_root_file(container: Vec<u8>) -> Result<PathBuf, Error> {
-    let root = xmlutils::XMLReader::parse(container.as_slice())?;
-    let el = root.borrow();
-    let element = el.find("rootfile")?;
-    let el2 = element.borrow();
+/// Elements of the package document
+const PACKAGE_ELEMENTS: &[&str] = &[
+    "metadata",
+    "manifest",
+    "spine",
+    "guide",
+    "bindings",
+    "collection",
+    "tours",
+];
+
+thread_local! {
+    /// The `toc` nav of a navigation document, with `epub:type` or `type`
+    static TOC_NAV: Regex = Regex::new(
+        r#"(?s)<nav\b[^>]*\s(?:epub:)?type\s*=\s*["']([^"']*\s)?toc(\s[^"']*)?["'][^>]*>.*?</nav>"#,
+    )
+    .expect("valid nav regex");
+    /// The `navMap` of a ncx
+    static NAV_MAP: Regex = Regex::new(r"(?s)<navMap\b.*</navMap>").expect("valid navMap regex");
+    /// The `url()` and `@import` references of a css file
+    static CSS_REFERENCES: Regex =
+        Regex::new(r#"(url\(\s*["']?|@import\s+["'])([^"')]+)"#).expect("valid css regex");
+}
 
-    let attr = el2.get_attr("full-path")?;
+/// Adds to `unresolved` the content of the navpoints, without the fragment,
+/// that isn't in `paths`
+fn unresolved_navpoints(toc: &[NavPoint], paths: &HashSet<&Path>, unresolved: &mut Vec<String>) {
+    for nav in toc {
+        let content = nav.content.display().to_string();
+        let (path, _) = href::split_fragment(&content);
+        let normalized = href::normalize(path);
+        if !paths.contains(Path::new(path)) && !paths.contains(Path::new(&normalized)) {
+            unresolved.push(content.clone());
+        }
+        unresolved_navpoints(&nav.children, paths, unresolved);
+    }
+}
 
-    Ok(PathBuf::from(attr))
+/// Iterator over the content of some resources, created with
+/// `resources_with_mime`. Yields the resource id and the content.
+pub struct ResourceContents<'a, R: Read + Seek> {
+    doc: &'a mut EpubDoc<R>,
+    /// path and id of the resources
+    items: std::vec::IntoIter<(PathBuf, String)>,
 }
 
-fn build_epub_uri<P: AsRef<Path>>(path: P, append: &str) -> String {
-    // allowing external links
-    if append.starts_with("http") {
-        return String::from(append);
+impl<'a, R: Read + Seek> Iterator for ResourceContents<'a, R> {
+    type Item = Result<(String, Vec<u8>), EpubError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, id) = self.items.next()?;
+        let content = self.doc.archive.get_entry(&path);
+        Some(content.map(|content| (id, content)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.items.size_hint()
+    }
+}
+
+/// Iterator over the resources of the manifest, created with
+/// `EpubDoc::resources`
+pub struct Resources<'a, R: Read + Seek> {
+    doc: &'a mut EpubDoc<R>,
+    /// path and id of the resources
+    ids: std::vec::IntoIter<(PathBuf, String)>,
+}
+
+impl<'a, R: Read + Seek> Iterator for Resources<'a, R> {
+    type Item = Resource;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, id) = self.ids.next()?;
+        let media_type = self.doc.resources.get(&id)?.1.clone();
+        let item = self.doc.manifest.get(&id).cloned();
+        let size = self.doc.archive.entry_size(&path).ok();
+        let (href, properties, fallback) = match item {
+            Some(item) => (item.href, item.properties, item.fallback),
+            None => (path.display().to_string(), vec![], None),
+        };
+        Some(Resource {
+            id,
+            href,
+            path,
+            media_type,
+            properties,
+            fallback,
+            size,
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.ids.size_hint()
     }
+}
 
-    let path = path.as_ref();
-    let mut cpath = path.to_path_buf();
+/// True if the language tag is of a language written right to left, or
+/// with a right to left script subtag, like "az-Arab"
+fn is_rtl_language(language: &str) -> bool {
+    const RTL_LANGUAGES: &[&str] = &[
+        "ar", "arc", "ckb", "dv", "fa", "he", "iw", "ks", "ps", "sd", "syr", "ug", "ur", "yi",
+    ];
+    const RTL_SCRIPTS: &[&str] = &["arab", "hebr", "syrc", "thaa", "nkoo", "adlm"];
+    let mut subtags = language.split(['-', '_']);
+    let primary = subtags.next().unwrap_or_default().to_lowercase();
+    match subtags.find(|s| s.len() == 4) {
+        Some(script) => RTL_SCRIPTS.contains(&script.to_lowercase().as_str()),
+        None => RTL_LANGUAGES.contains(&primary.as_str()),
+    }
+}
 
-    // current file base dir
-    cpath.pop();
-    for p in Path::new(append).components() {
-        match p {
-            Component::ParentDir => {
-                cpath.pop();
+/// Writes a metadata value in the package document `metadata` element,
+/// replacing the first element with that name unless `append` is true
+fn write_metadata_value(
+    metadata: &mut xmlutils::XMLNode,
+    name: &str,
+    value: &str,
+    append: bool,
+    epub3: bool,
+) {
+    let is_dc = DC_ELEMENTS.contains(&name);
+    if !append {
+        for c in metadata.childs.iter() {
+            let mut item = c.borrow_mut();
+            if is_dc && item.name.local_name == name {
+                item.text = Some(value.to_string());
+                return;
             }
-            Component::Normal(s) => {
-                cpath.push(s);
+            if item.name.local_name == "meta" {
+                if item.get_attr("property").ok().as_deref() == Some(name) {
+                    item.text = Some(value.to_string());
+                    return;
+                }
+                if item.get_attr("name").ok().as_deref() == Some(name) {
+                    item.set_attr("content", value);
+                    return;
+                }
             }
-            _ => {}
-        };
+        }
     }
 
-    // If on Windows, replace all Windows path separators with Unix path separators
-    let path = if cfg!(windows) {
-        cpath.display().to_string().replace("\\", "/")
+    let item = if is_dc {
+        let mut item = metadata.new_child_ns("dc", DC_NS, name, &[]);
+        item.text = Some(value.to_string());
+        item
+    } else if epub3 {
+        let mut item = metadata.new_child("meta", &[("property", name)]);
+        item.text = Some(value.to_string());
+        item
     } else {
-        cpath.display().to_string()
+        metadata.new_child("meta", &[("name", name), ("content", value)])
     };
+    metadata.add_child(item);
+}
+
+/// Removes from a toc.ncx navMap or a navigation document every entry
+/// pointing to `target`, moving up the nested entries
+fn remove_toc_entries(node: &mut xmlutils::XMLNode, toc: &Path, target: &Path) {
+    let mut childs = vec![];
+    for c in node.childs.drain(..) {
+        remove_toc_entries(&mut c.borrow_mut(), toc, target);
+
+        let entry = c.borrow();
+        let href = match entry.name.local_name.as_ref() {
+            "navPoint" => entry
+                .childs
+                .iter()
+                .find(|n| n.borrow().name.local_name == "content")
+                .and_then(|n| n.borrow().get_attr("src").ok()),
+            "li" => entry
+                .childs
+                .iter()
+                .find(|n| n.borrow().name.local_name == "a")
+                .and_then(|n| n.borrow().get_attr("href").ok()),
+            _ => None,
+        };
+
+        match href {
+            Some(href) if href::resolve(toc, &href) == target => {
+                for nested in entry.childs.iter() {
+                    let n = nested.borrow();
+                    match n.name.local_name.as_ref() {
+                        "navPoint" => childs.push(nested.clone()),
+                        "ol" => childs.extend(n.childs.iter().cloned()),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {
+                drop(entry);
+                childs.push(c);
+            }
+        }
+    }
+    node.childs = childs;
+}
+
+/// Mimetypes of the resources that can have references to other resources
+fn is_linking_mime(mime: &str) -> bool {
+    matches!(
+        mime,
+        "application/xhtml+xml"
+            | "text/html"
+            | "image/svg+xml"
+            | "application/x-dtbncx+xml"
+            | "application/smil+xml"
+            | "text/css"
+    )
+}
+
+/// Returns the new value for a reference found in the document `doc`, that
+/// will be moved to `doc_dest`, when the resource `old` is moved to `new`.
+/// Returns None if the reference doesn't need to change.
+fn rewrite_href(
+    doc: &Path,
+    doc_dest: &Path,
+    old: &Path,
+    new: &Path,
+    value: &str,
+) -> Option<String> {
+    if is_external_href(value) {
+        return None;
+    }
+
+    let fragment = value.find('#').map(|i| &value[i..]).unwrap_or_default();
+    let mut target = href::resolve(doc, value);
+    if target == old {
+        target = new.to_path_buf();
+    } else if doc == doc_dest {
+        return None;
+    }
 
-    format!("epub://{}", path)
+    let base = doc_dest.parent().unwrap_or_else(|| Path::new(""));
+    Some(encode_href(&relative_path(base, &target)) + fragment)
+}
+
+/// Rewrites the references in a document using `new_ref`. Returns the new
+/// content or None if there's nothing to change.
+fn rewrite_references<F>(
+    content: &[u8],
+    mime: &str,
+    new_ref: F,
+) -> Result<Option<Vec<u8>>, EpubError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let changed = Cell::new(false);
+
+    if mime == "text/css" {
+        let css = std::str::from_utf8(content)?;
+        let re = CSS_REFERENCES.with(Regex::clone);
+        let css = re.replace_all(css, |caps: &Captures| match new_ref(&caps[2]) {
+            Some(href) => {
+                changed.set(true);
+                format!("{}{}", &caps[1], href)
+            }
+            None => caps[0].to_string(),
+        });
+        return Ok(if changed.get() {
+            Some(css.as_bytes().to_vec())
+        } else {
+            None
+        });
+    }
+
+    let resp = xmlutils::replace_attrs(
+        content,
+        |_element, attr, value| match attr {
+            "href" | "src" | "poster" => match new_ref(value) {
+                Some(href) => {
+                    changed.set(true);
+                    href
+                }
+                None => String::from(value),
+            },
+            _ => String::from(value),
+        },
+        &[],
+    );
+    match resp {
+        Ok(content) if changed.get() => Ok(Some(content)),
+        Ok(_) => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Returns the references to other files of the epub in a document, the
+/// href, src and poster attributes or the css urls, ignoring external urls
+pub(crate) fn find_references(content: &[u8], mime: &str) -> Vec<String> {
+    let refs = RefCell::new(vec![]);
+    let _ = rewrite_references(content, mime, |value| {
+        if !is_external_href(value) {
+            refs.borrow_mut().push(value.to_string());
+        }
+        None
+    });
+    refs.into_inner()
+}
+
+fn is_external_href(href: &str) -> bool {
+    href.is_empty() || href.contains(':') || href.starts_with('#') || href.starts_with('/')
+}
+
+/// Rewrites the href attributes of `node` and its childs using `new_ref`
+fn rewrite_node_hrefs(node: &xmlutils::XMLNode, new_ref: &dyn Fn(&str) -> Option<String>) {
+    for c in node.childs.iter() {
+        let mut child = c.borrow_mut();
+        for attr in child.attrs.iter_mut() {
+            if attr.name.local_name == "href" {
+                if let Some(href) = new_ref(&attr.value) {
+                    attr.value = href;
+                }
+            }
+        }
+        rewrite_node_hrefs(&child, new_ref);
+    }
+}
+
+/// Returns the path of the first rootfile of the `container`
+pub(crate) fn get_root_file(container: Vec<u8>) -> Result<PathBuf, EpubError> {
+    let mut renditions = rendition::get_rootfiles(&container)?;
+    Ok(renditions.swap_remove(0).path)
+}
+
+fn build_epub_uri<P: AsRef<Path>>(path: P, append: &str) -> String {
+    // allowing external links
+    if append.starts_with("http") {
+        return String::from(append);
+    }
+
+    // the href is kept as it's written, relative to the current file base dir
+    let base = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+    let base = if cfg!(windows) {
+        base.display().to_string().replace('\\', "/")
+    } else {
+        base.display().to_string()
+    };
+    format!("epub://{}", href::collapse(&format!("{}/{}", base, append)))
 }
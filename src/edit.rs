@@ -0,0 +1,94 @@
+//! Transactional modification of epub docs.
+//!
+//! Groups several modifications of an `EpubDoc` so they're written with a
+//! single zip rewrite, or discarded all together.
+
+use crate::error::EpubError;
+use std::io::{Read, Seek};
+use std::ops::{Deref, DerefMut};
+use std::path::Path;
+
+use crate::archive::PendingChanges;
+use crate::doc::EpubDoc;
+
+/// Edit session over an `EpubDoc`, created with `EpubDoc::edit`.
+///
+/// The session gives access to every `EpubDoc` method, so resources can be
+/// added, removed, renamed or modified. All the changes are kept in memory
+/// until `commit` writes the new epub in one pass. If the session is rolled
+/// back or dropped without committing, the doc returns to the state it had
+/// when the session started.
+pub struct EditSession<'a, R: Read + Seek> {
+    doc: &'a mut EpubDoc<R>,
+    /// pending changes when the session started
+    snapshot: Option<PendingChanges>,
+}
+
+impl<'a, R: Read + Seek> EditSession<'a, R> {
+    pub(crate) fn new(doc: &'a mut EpubDoc<R>) -> EditSession<'a, R> {
+        let snapshot = Some(doc.archive.pending_changes());
+        EditSession { doc, snapshot }
+    }
+
+    /// Writes the epub with all the changes to `path`.
+    ///
+    /// The epub is written to a temporary file and then moved to `path`,
+    /// so a failure never leaves a partially written file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the epub can't be written. In that case the
+    /// changes are rolled back.
+    pub fn commit<P: AsRef<Path>>(mut self, path: P) -> Result<(), EpubError> {
+        match self.doc.save_as(path) {
+            Ok(()) => {
+                self.snapshot = None;
+                Ok(())
+            }
+            Err(e) => {
+                self.restore()?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Discards all the changes made in this session.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the original package document can't be parsed
+    /// again.
+    pub fn rollback(mut self) -> Result<(), EpubError> {
+        self.restore()
+    }
+
+    fn restore(&mut self) -> Result<(), EpubError> {
+        match self.snapshot.take() {
+            Some(snapshot) => {
+                self.doc.archive.restore_changes(snapshot);
+                self.doc.reload()
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> Deref for EditSession<'a, R> {
+    type Target = EpubDoc<R>;
+
+    fn deref(&self) -> &EpubDoc<R> {
+        self.doc
+    }
+}
+
+impl<'a, R: Read + Seek> DerefMut for EditSession<'a, R> {
+    fn deref_mut(&mut self) -> &mut EpubDoc<R> {
+        self.doc
+    }
+}
+
+impl<'a, R: Read + Seek> Drop for EditSession<'a, R> {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
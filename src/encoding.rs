@@ -0,0 +1,142 @@
+//! Character encoding detection for the xml and html documents.
+//!
+//! The encoding is taken from the byte order mark, from the encoding
+//! declared in the xml prolog or in the html meta charset, or guessed from
+//! the first bytes for utf-16 without a byte order mark. Documents without
+//! an encoding that aren't valid utf-8 are read as windows-1252, the usual
+//! encoding of legacy books.
+
+use regex::bytes::Regex;
+use std::borrow::Cow;
+
+use crate::error::EpubError;
+
+/// windows-1252 characters for the bytes from 0x80 to 0x9f, the other
+/// bytes are the same as in latin-1
+const WINDOWS_1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+/// Decodes the document `content` to utf-8. If the content is transcoded,
+/// the encoding in the xml declaration is replaced with utf-8.
+///
+/// # Errors
+///
+/// Fails if the declared encoding isn't supported, or if the content isn't
+/// valid in the declared encoding.
+pub(crate) fn decode(content: &[u8]) -> Result<Cow<'_, str>, EpubError> {
+    if let Some(rest) = content.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        return Ok(Cow::Borrowed(std::str::from_utf8(rest)?));
+    }
+    if let Some(rest) = content.strip_prefix(&[0xff, 0xfe]) {
+        return Ok(Cow::Owned(utf8_declaration(decode_utf16(
+            rest,
+            Encoding::Utf16Le,
+        ))));
+    }
+    if let Some(rest) = content.strip_prefix(&[0xfe, 0xff]) {
+        return Ok(Cow::Owned(utf8_declaration(decode_utf16(
+            rest,
+            Encoding::Utf16Be,
+        ))));
+    }
+    match content {
+        [b'<', 0, ..] => {
+            return Ok(Cow::Owned(utf8_declaration(decode_utf16(
+                content,
+                Encoding::Utf16Le,
+            ))))
+        }
+        [0, b'<', ..] => {
+            return Ok(Cow::Owned(utf8_declaration(decode_utf16(
+                content,
+                Encoding::Utf16Be,
+            ))))
+        }
+        _ => {}
+    }
+
+    match declared_encoding(content)? {
+        Some(Encoding::Utf8) => Ok(Cow::Borrowed(std::str::from_utf8(content)?)),
+        Some(_) => Ok(Cow::Owned(utf8_declaration(decode_windows_1252(content)))),
+        None => match std::str::from_utf8(content) {
+            Ok(text) => Ok(Cow::Borrowed(text)),
+            Err(_) => Ok(Cow::Owned(decode_windows_1252(content))),
+        },
+    }
+}
+
+/// Returns the encoding declared in the xml prolog or in the html meta
+/// charset, in the first kilobyte of the document
+fn declared_encoding(content: &[u8]) -> Result<Option<Encoding>, EpubError> {
+    let head = &content[..content.len().min(1024)];
+    let xml =
+        Regex::new(r#"^\s*<\?xml[^>]*\sencoding\s*=\s*["']([^"']+)["']"#).expect("valid regex");
+    let meta =
+        Regex::new(r#"(?i)<meta[^>]*\scharset\s*=\s*["']?([a-z0-9_:.-]+)"#).expect("valid regex");
+    let label = xml
+        .captures(head)
+        .or_else(|| meta.captures(head))
+        .map(|c| String::from_utf8_lossy(&c[1]).trim().to_lowercase());
+    let label = match label {
+        Some(label) => label,
+        None => return Ok(None),
+    };
+    let encoding = match label.as_str() {
+        "utf-8" | "utf8" | "us-ascii" | "ascii" | "unicode-1-1-utf-8" => Encoding::Utf8,
+        // the declaration was read as ascii, so the content isn't utf-16
+        "utf-16" | "utf-16le" | "utf-16be" => Encoding::Utf8,
+        "windows-1252" | "cp1252" | "x-cp1252" | "iso-8859-1" | "iso8859-1" | "iso_8859-1"
+        | "latin1" | "latin-1" | "l1" => Encoding::Windows1252,
+        _ => {
+            return Err(EpubError::Encoding(format!(
+                "unsupported encoding {}",
+                label
+            )))
+        }
+    };
+    Ok(Some(encoding))
+}
+
+fn decode_utf16(content: &[u8], encoding: Encoding) -> String {
+    let units: Vec<u16> = content
+        .chunks_exact(2)
+        .map(|b| match encoding {
+            Encoding::Utf16Be => u16::from_be_bytes([b[0], b[1]]),
+            _ => u16::from_le_bytes([b[0], b[1]]),
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn decode_windows_1252(content: &[u8]) -> String {
+    content
+        .iter()
+        .map(|&b| match b {
+            0x80..=0x9f => WINDOWS_1252[(b - 0x80) as usize],
+            b => b as char,
+        })
+        .collect()
+}
+
+/// Replaces the encoding of the xml declaration with utf-8, for the
+/// transcoded documents
+fn utf8_declaration(text: String) -> String {
+    let re =
+        regex::Regex::new(r#"^(\s*<\?xml[^>]*\sencoding\s*=\s*["'])[^"']+"#).expect("valid regex");
+    match re.replace(&text, "${1}utf-8") {
+        Cow::Owned(replaced) => replaced,
+        Cow::Borrowed(_) => text,
+    }
+}
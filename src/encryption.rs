@@ -0,0 +1,457 @@
+//! The encrypted resources declared in `META-INF/encryption.xml`.
+//!
+//! Each `EncryptedData` element of the file names an encrypted entry of the
+//! archive and the algorithm used, like the font obfuscation algorithms or
+//! the ciphers of a DRM:
+//!
+//! ```xml
+//! <encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+//!     xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+//!   <enc:EncryptedData>
+//!     <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+//!     <enc:CipherData><enc:CipherReference URI="OEBPS/Fonts/serif.otf"/></enc:CipherData>
+//!   </enc:EncryptedData>
+//! </encryption>
+//! ```
+
+use std::path::PathBuf;
+
+use crate::digest;
+use crate::error::EpubError;
+use crate::href;
+use crate::json::{self, Value};
+use crate::metadata::Date;
+use crate::xmlutils::{self, XMLNode};
+
+/// The path of the encryption file in the archive
+pub const ENCRYPTION_FILE: &str = "META-INF/encryption.xml";
+
+/// The algorithm of the IDPF font obfuscation
+pub const IDPF_OBFUSCATION: &str = "http://www.idpf.org/2008/embedding";
+
+/// The algorithm of the Adobe font obfuscation
+pub const ADOBE_OBFUSCATION: &str = "http://ns.adobe.com/pdf/enc#RC";
+
+/// An encrypted entry of the archive, an `EncryptedData` element of the
+/// `encryption.xml`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncryptedData {
+    /// the full path of the encrypted entry, from the `CipherReference`
+    pub path: PathBuf,
+    /// the `Algorithm` of the `EncryptionMethod`, like
+    /// "http://www.w3.org/2001/04/xmlenc#aes256-cbc"
+    pub algorithm: String,
+    /// the `URI` of the `ds:RetrievalMethod` of the key, like the content
+    /// key of a license
+    pub retrieval_method: Option<String>,
+    /// the `ds:KeyName` of the key
+    pub key_name: Option<String>,
+    /// the compression method applied before the encryption, 8 for
+    /// deflate, from the `Compression` encryption property
+    pub compression_method: Option<u32>,
+    /// the length of the content before the compression
+    pub original_length: Option<u64>,
+}
+
+impl EncryptedData {
+    /// Returns true if the entry is an obfuscated font, with the IDPF or
+    /// the Adobe algorithm, and not encrypted with a DRM
+    pub fn is_obfuscation(&self) -> bool {
+        self.algorithm == IDPF_OBFUSCATION || self.algorithm == ADOBE_OBFUSCATION
+    }
+}
+
+/// Parses the `encryption.xml` file `content`
+pub(crate) fn parse_encryption(content: &[u8]) -> Result<Vec<EncryptedData>, EpubError> {
+    let root = xmlutils::XMLReader::parse(content)?;
+    let mut encrypted = vec![];
+    collect_encrypted(&root.borrow(), &mut encrypted);
+    Ok(encrypted)
+}
+
+fn collect_encrypted(node: &XMLNode, encrypted: &mut Vec<EncryptedData>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name != "EncryptedData" {
+            collect_encrypted(&child, encrypted);
+            continue;
+        }
+        let attr = |element: &str, name: &str| {
+            let element = child.find(element).ok()?;
+            let value = element.borrow().get_attr(name).ok();
+            value
+        };
+        let uri = match attr("CipherReference", "URI") {
+            Some(uri) => uri,
+            None => continue,
+        };
+        // the uris are relative to the root of the container
+        let (path, _) = href::split_fragment(&uri);
+        encrypted.push(EncryptedData {
+            path: PathBuf::from(href::normalize(path)),
+            algorithm: attr("EncryptionMethod", "Algorithm").unwrap_or_default(),
+            retrieval_method: attr("RetrievalMethod", "URI"),
+            key_name: child
+                .find("KeyName")
+                .ok()
+                .and_then(|e| e.borrow().text.clone())
+                .map(|name| name.trim().to_string()),
+            compression_method: attr("Compression", "Method").and_then(|m| m.trim().parse().ok()),
+            original_length: attr("Compression", "OriginalLength")
+                .and_then(|l| l.trim().parse().ok()),
+        });
+    }
+}
+
+/// The length of the obfuscated prefix of the fonts with the IDPF
+/// algorithm
+const IDPF_OBFUSCATED_LENGTH: usize = 1040;
+
+/// The length of the obfuscated prefix of the fonts with the Adobe
+/// algorithm
+const ADOBE_OBFUSCATED_LENGTH: usize = 1024;
+
+/// Returns the key of the obfuscation `algorithm` for the publication with
+/// the `identifier`, None if the algorithm isn't an obfuscation or if the
+/// identifier doesn't fit it.
+///
+/// The key of the IDPF algorithm is the SHA-1 of the unique identifier,
+/// without its whitespace. The key of the Adobe algorithm is the 16 bytes
+/// of the uuid of a `urn:uuid:` identifier.
+///
+/// # Examples
+///
+/// ```
+/// use epub::encryption::{obfuscation_key, ADOBE_OBFUSCATION, IDPF_OBFUSCATION};
+///
+/// let key = obfuscation_key(IDPF_OBFUSCATION, " urn:uuid:1234 ").unwrap();
+/// assert_eq!(key, obfuscation_key(IDPF_OBFUSCATION, "urn:uuid:1234").unwrap());
+/// assert_eq!(20, key.len());
+/// assert_eq!(&[0xcb, 0xae, 0x3e, 0x12], &key[..4]);
+///
+/// let uuid = "urn:uuid:0123abcd-0000-1111-2222-3333444455ff";
+/// let key = obfuscation_key(ADOBE_OBFUSCATION, uuid).unwrap();
+/// assert_eq!(16, key.len());
+/// assert_eq!(&[0x01, 0x23, 0xab, 0xcd], &key[..4]);
+/// assert_eq!(None, obfuscation_key(ADOBE_OBFUSCATION, "isbn:9780000000000"));
+///
+/// assert_eq!(None, obfuscation_key("http://www.w3.org/2001/04/xmlenc#aes256-cbc", uuid));
+/// ```
+pub fn obfuscation_key(algorithm: &str, identifier: &str) -> Option<Vec<u8>> {
+    match algorithm {
+        IDPF_OBFUSCATION => {
+            let identifier: String = identifier
+                .chars()
+                .filter(|c| !matches!(c, ' ' | '\t' | '\r' | '\n'))
+                .collect();
+            Some(digest::sha1(identifier.as_bytes()).to_vec())
+        }
+        ADOBE_OBFUSCATION => {
+            let identifier = identifier.trim();
+            let uuid = identifier
+                .strip_prefix("urn:uuid:")
+                .unwrap_or(identifier)
+                .replace('-', "");
+            if uuid.len() != 32 || !uuid.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            (0..32)
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&uuid[i..i + 2], 16).ok())
+                .collect()
+        }
+        _ => None,
+    }
+}
+
+/// Returns true if the `identifier` is a uuid, the identifier of the Adobe
+/// obfuscation key
+pub(crate) fn is_uuid(identifier: &str) -> bool {
+    obfuscation_key(ADOBE_OBFUSCATION, identifier).is_some()
+}
+
+/// Reverts the obfuscation `algorithm` of the font `content` in place, for
+/// the publication with the `identifier`, see `obfuscation_key`. Returns
+/// false, leaving the content untouched, if there isn't a key.
+///
+/// The obfuscation is a XOR of the start of the font with the key, so this
+/// obfuscates a font too.
+pub fn deobfuscate(algorithm: &str, identifier: &str, content: &mut [u8]) -> bool {
+    let key = match obfuscation_key(algorithm, identifier) {
+        Some(key) => key,
+        None => return false,
+    };
+    let length = match algorithm {
+        ADOBE_OBFUSCATION => ADOBE_OBFUSCATED_LENGTH,
+        _ => IDPF_OBFUSCATED_LENGTH,
+    };
+    let length = content.len().min(length);
+    for (byte, k) in content[..length].iter_mut().zip(key.iter().cycle()) {
+        *byte ^= k;
+    }
+    true
+}
+
+/// The DRM protecting a publication, see `EpubDoc::drm`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Drm {
+    /// not protected, the resources are at most obfuscated
+    None,
+    /// Adobe Adept, with a `META-INF/rights.xml`
+    AdobeAdept,
+    /// Apple FairPlay, with a `META-INF/sinf.xml`
+    AppleFairPlay,
+    /// Kobo KDRM, the encrypted resources without a license file nor a key
+    KoboKdrm,
+    /// Readium LCP, with a `META-INF/license.lcpl`
+    ReadiumLcp,
+    /// encrypted resources of an unknown DRM
+    Unknown,
+}
+
+/// Detects the DRM of the archive with the entries `files` and the
+/// `encryption` of its encryption.xml
+pub(crate) fn detect_drm(files: &[String], encryption: &[EncryptedData]) -> Drm {
+    let has_file = |name: &str| files.iter().any(|f| f == name);
+    let encrypted: Vec<&EncryptedData> = encryption
+        .iter()
+        .filter(|data| !data.is_obfuscation())
+        .collect();
+    if has_file(LCP_LICENSE_FILE)
+        || encrypted.iter().any(|data| {
+            data.retrieval_method
+                .as_deref()
+                .is_some_and(|uri| uri.starts_with("license.lcpl#"))
+        })
+    {
+        Drm::ReadiumLcp
+    } else if has_file("META-INF/sinf.xml") {
+        Drm::AppleFairPlay
+    } else if has_file("META-INF/rights.xml") {
+        Drm::AdobeAdept
+    } else if encrypted.is_empty() {
+        Drm::None
+    } else if encrypted.iter().all(|data| {
+        data.algorithm.ends_with("#aes128-cbc")
+            && data.retrieval_method.is_none()
+            && data.key_name.is_none()
+    }) {
+        // the kobo keys are in the database of the reader, not the book
+        Drm::KoboKdrm
+    } else {
+        Drm::Unknown
+    }
+}
+
+/// The path of the Readium LCP license in the archive
+pub const LCP_LICENSE_FILE: &str = "META-INF/license.lcpl";
+
+/// A Readium LCP license document, the `META-INF/license.lcpl`. The
+/// content key isn't decrypted, the license only tells the rights of the
+/// user and where to get the rest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LcpLicense {
+    /// the identifier of the license
+    pub id: String,
+    /// the uri of the provider of the license
+    pub provider: String,
+    /// when the license was issued
+    pub issued: Option<Date>,
+    /// when the license was last updated
+    pub updated: Option<Date>,
+    /// the encryption profile, like "http://readium.org/lcp/basic-profile"
+    pub profile: Option<String>,
+    /// the hint of the passphrase of the user
+    pub text_hint: Option<String>,
+    /// the rights granted to the user
+    pub rights: LcpRights,
+    /// the links, like the "publication", "hint" and "status" ones
+    pub links: Vec<LcpLink>,
+}
+
+impl LcpLicense {
+    /// Parses the license.lcpl `content`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::encryption::LcpLicense;
+    ///
+    /// let license = LcpLicense::parse(r#"{
+    ///     "id": "ef15e740", "provider": "https://example.com",
+    ///     "rights": {"print": 10, "end": "2030-01-01T00:00:00Z"},
+    ///     "links": [{"rel": "status", "href": "https://example.com/status"}]
+    /// }"#).unwrap();
+    /// assert_eq!(Some(10), license.rights.print);
+    /// assert_eq!(None, license.rights.copy);
+    /// assert_eq!(Some(2030), license.rights.end.map(|end| end.year));
+    /// assert_eq!("https://example.com/status", license.link("status").unwrap().href);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::Json` if the content isn't a json object.
+    pub fn parse(content: &str) -> Result<LcpLicense, EpubError> {
+        let root = json::parse(content)?;
+        if !matches!(root, Value::Object(_)) {
+            return Err(EpubError::Json(String::from(
+                "the license isn't a json object",
+            )));
+        }
+        let string = |value: Option<&Value>| value.and_then(Value::as_str).map(String::from);
+        let date = |value: Option<&Value>| value.and_then(Value::as_str).and_then(Date::parse);
+        let count = |value: Option<&Value>| value.and_then(Value::as_usize).map(|n| n as u64);
+        let encryption = root.get("encryption");
+        let rights = root.get("rights");
+        let links = root
+            .get("links")
+            .and_then(Value::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|link| {
+                Some(LcpLink {
+                    rel: string(link.get("rel"))?,
+                    href: string(link.get("href"))?,
+                    media_type: string(link.get("type")),
+                    title: string(link.get("title")),
+                    templated: link.get("templated") == Some(&Value::Bool(true)),
+                })
+            })
+            .collect();
+        Ok(LcpLicense {
+            id: string(root.get("id")).unwrap_or_default(),
+            provider: string(root.get("provider")).unwrap_or_default(),
+            issued: date(root.get("issued")),
+            updated: date(root.get("updated")),
+            profile: string(encryption.and_then(|e| e.get("profile"))),
+            text_hint: string(
+                encryption
+                    .and_then(|e| e.get("user_key"))
+                    .and_then(|key| key.get("text_hint")),
+            ),
+            rights: LcpRights {
+                print: count(rights.and_then(|r| r.get("print"))),
+                copy: count(rights.and_then(|r| r.get("copy"))),
+                start: date(rights.and_then(|r| r.get("start"))),
+                end: date(rights.and_then(|r| r.get("end"))),
+            },
+            links,
+        })
+    }
+
+    /// Returns the first link with the relation `rel`
+    pub fn link(&self, rel: &str) -> Option<&LcpLink> {
+        self.links.iter().find(|link| link.rel == rel)
+    }
+}
+
+/// The rights of a Readium LCP license, None when they aren't limited
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LcpRights {
+    /// the number of pages that can be printed
+    pub print: Option<u64>,
+    /// the number of characters that can be copied
+    pub copy: Option<u64>,
+    /// the start of the loan
+    pub start: Option<Date>,
+    /// the expiry of the loan
+    pub end: Option<Date>,
+}
+
+/// A link of a Readium LCP license
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LcpLink {
+    /// the relation, like "publication" or "hint"
+    pub rel: String,
+    /// the uri, or the uri template if `templated`
+    pub href: String,
+    /// the media type of the linked resource
+    pub media_type: Option<String>,
+    /// the title of the link
+    pub title: Option<String>,
+    /// true if the href is an uri template
+    pub templated: bool,
+}
+
+/// The path of the rights file in the archive
+pub const RIGHTS_FILE: &str = "META-INF/rights.xml";
+
+/// The `META-INF/rights.xml`, the license of the vendor. The fields are
+/// read from the Adobe Adept license token, best-effort, the other vendors
+/// only have the raw content.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rights {
+    /// the content of the file
+    pub raw: String,
+    /// the namespace of the root element, like "http://ns.adobe.com/adept"
+    pub namespace: Option<String>,
+    /// the account of the user the book was bought for
+    pub user: Option<String>,
+    /// the identifier of the licensed resource, the book
+    pub resource: Option<String>,
+    /// the url of the license server
+    pub license_url: Option<String>,
+    /// the url of the operator that fulfilled the book
+    pub operator_url: Option<String>,
+    /// the permissions granted, the elements of `permissions`, like
+    /// "display" or "print"
+    pub permissions: Vec<String>,
+}
+
+impl Rights {
+    /// Parses the rights.xml `raw` content. The content that isn't xml is
+    /// kept without the parsed fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::encryption::Rights;
+    ///
+    /// let rights = Rights::parse(String::from(
+    ///     r#"<adept:rights xmlns:adept="http://ns.adobe.com/adept">
+    ///       <adept:licenseToken>
+    ///         <adept:resource>urn:uuid:1234</adept:resource>
+    ///         <adept:permissions><adept:display/><adept:print/></adept:permissions>
+    ///       </adept:licenseToken>
+    ///     </adept:rights>"#,
+    /// ));
+    /// assert_eq!(Some("urn:uuid:1234"), rights.resource.as_deref());
+    /// assert_eq!(vec!["display", "print"], rights.permissions);
+    /// assert_eq!(None, Rights::parse(String::from("not xml")).namespace);
+    /// ```
+    pub fn parse(raw: String) -> Rights {
+        let root = match xmlutils::XMLReader::parse(raw.as_bytes()) {
+            Ok(root) => root,
+            Err(_) => {
+                return Rights {
+                    raw,
+                    ..Rights::default()
+                }
+            }
+        };
+        let root = root.borrow();
+        let text = |element: &str| {
+            let element = root.find(element).ok()?;
+            let text = element.borrow().text.clone()?;
+            Some(text.trim().to_string()).filter(|t| !t.is_empty())
+        };
+        let permissions = match root.find("permissions") {
+            Ok(permissions) => permissions
+                .borrow()
+                .childs
+                .iter()
+                .map(|p| p.borrow().name.local_name.clone())
+                .collect(),
+            Err(_) => vec![],
+        };
+        Rights {
+            namespace: root.name.namespace.clone(),
+            user: text("user"),
+            resource: text("resource"),
+            license_url: text("licenseURL"),
+            operator_url: text("operatorURL"),
+            permissions,
+            raw,
+        }
+    }
+}
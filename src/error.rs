@@ -0,0 +1,131 @@
+//! Errors of the epub archive and doc.
+//!
+//! `EpubError` has a variant for each kind of failure, so the callers can
+//! match on them, for example to tell a missing resource from a broken
+//! file.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::xmlutils::XMLError;
+
+/// Error opening, reading or modifying an epub
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum EpubError {
+    /// The zip archive is broken
+    ZipError(zip::result::ZipError),
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// The META-INF/container.xml file doesn't exist
+    MissingContainer,
+    /// The container.xml file doesn't point to a package document
+    InvalidContainer(String),
+    /// The package document can't be parsed
+    InvalidOpf { path: PathBuf, reason: String },
+    /// An xml document, like the toc, can't be parsed or written
+    Xml(String),
+    /// There's no resource, or zip entry, by this id or path
+    ResourceNotFound(String),
+    /// There's already a resource, or a file, by this id or path
+    AlreadyExists(String),
+    /// The content isn't valid in its encoding, or the encoding isn't supported
+    Encoding(String),
+    /// The epub doesn't follow the spec, in strict mode
+    SpecViolation { problem: String, spec: String },
+    /// The archive exceeds the limits of `ArchiveLimits`
+    LimitExceeded(String),
+    /// The entry points outside the directory, extracting the archive
+    UnsafePath(String),
+    /// The operation isn't valid, like going past the last page
+    InvalidOperation(String),
+    /// The json, like a toc written with `NavPoint::list_to_json`, can't be
+    /// parsed
+    Json(String),
+    /// The resource is encrypted, with an algorithm of the
+    /// `encryption.xml` that can't be decrypted, like the ones of a DRM
+    Encrypted { path: PathBuf, algorithm: String },
+    /// The zip entry is encrypted and the password is wrong, or missing
+    InvalidPassword(String),
+    /// A metadata required to write the epub, like the title, is missing
+    MissingMetadata(String),
+}
+
+impl fmt::Display for EpubError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EpubError::ZipError(e) => write!(f, "zip error: {}", e),
+            EpubError::Io(e) => write!(f, "{}", e),
+            EpubError::MissingContainer => write!(f, "container.xml not found"),
+            EpubError::InvalidContainer(reason) => write!(f, "invalid container.xml: {}", reason),
+            EpubError::InvalidOpf { path, reason } => {
+                write!(f, "invalid package document {}: {}", path.display(), reason)
+            }
+            EpubError::Xml(reason) => write!(f, "xml error: {}", reason),
+            EpubError::ResourceNotFound(name) => write!(f, "resource not found: {}", name),
+            EpubError::AlreadyExists(name) => write!(f, "already exists: {}", name),
+            EpubError::Encoding(reason) => write!(f, "encoding error: {}", reason),
+            EpubError::SpecViolation { problem, spec } => write!(f, "{} ({})", problem, spec),
+            EpubError::LimitExceeded(reason) => write!(f, "archive limits exceeded: {}", reason),
+            EpubError::UnsafePath(name) => write!(f, "entry outside the directory: {}", name),
+            EpubError::InvalidOperation(reason) => write!(f, "{}", reason),
+            EpubError::Json(reason) => write!(f, "json error: {}", reason),
+            EpubError::Encrypted { path, algorithm } => {
+                write!(f, "{} is encrypted with {}", path.display(), algorithm)
+            }
+            EpubError::InvalidPassword(name) => write!(f, "wrong password for {}", name),
+            EpubError::MissingMetadata(name) => write!(f, "missing {} metadata", name),
+        }
+    }
+}
+
+impl Error for EpubError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EpubError::ZipError(e) => Some(e),
+            EpubError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for EpubError {
+    fn from(e: zip::result::ZipError) -> EpubError {
+        match e {
+            zip::result::ZipError::Io(e) => EpubError::Io(e),
+            e => EpubError::ZipError(e),
+        }
+    }
+}
+
+impl From<io::Error> for EpubError {
+    fn from(e: io::Error) -> EpubError {
+        EpubError::Io(e)
+    }
+}
+
+impl From<XMLError> for EpubError {
+    fn from(e: XMLError) -> EpubError {
+        EpubError::Xml(e.error)
+    }
+}
+
+impl From<xml::writer::Error> for EpubError {
+    fn from(e: xml::writer::Error) -> EpubError {
+        EpubError::Xml(e.to_string())
+    }
+}
+
+impl From<std::str::Utf8Error> for EpubError {
+    fn from(e: std::str::Utf8Error) -> EpubError {
+        EpubError::Encoding(e.to_string())
+    }
+}
+
+impl From<std::string::FromUtf8Error> for EpubError {
+    fn from(e: std::string::FromUtf8Error) -> EpubError {
+        EpubError::Encoding(e.to_string())
+    }
+}
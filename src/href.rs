@@ -0,0 +1,118 @@
+//! Resolution of the hrefs of the epub to the paths in the archive.
+//!
+//! The hrefs in the package document, the toc and the content documents
+//! are urls relative to the document where they are. They are resolved to
+//! the full path of the file in the archive, percent-decoded and without
+//! `.` and `..` segments. The archive uses the same normalization to find
+//! the files, so every way of writing a path finds the same file.
+//!
+//! # Examples
+//!
+//! ```
+//! use epub::href;
+//! use std::path::Path;
+//!
+//! let opf = Path::new("OEBPS/content.opf");
+//! let path = href::resolve(opf, "Text/../Images/my%20cover.png#top");
+//! assert_eq!(Path::new("OEBPS/Images/my cover.png"), path);
+//!
+//! assert_eq!("OEBPS/Text/ch1.xhtml", href::normalize("OEBPS/./Text//ch1.xhtml"));
+//! assert_eq!(("ch1.xhtml", Some("top")), href::split_fragment("ch1.xhtml#top"));
+//! ```
+
+use percent_encoding::percent_decode_str;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+/// Splits the `href` in the path and the fragment, without the `#`
+pub fn split_fragment(href: &str) -> (&str, Option<&str>) {
+    match href.find('#') {
+        Some(i) => (&href[..i], Some(&href[i + 1..])),
+        None => (href, None),
+    }
+}
+
+/// Normalizes a path of the archive: decodes the percent-encoded
+/// characters and removes the empty and `.` segments, and the `..` segments
+/// with the previous one.
+pub fn normalize(path: &str) -> String {
+    collapse(&decode(path))
+}
+
+/// Resolves the `href` found in the document `base` to the full path in the
+/// archive, normalized. The fragment and the query are ignored, and the
+/// hrefs starting with `/` are relative to the root of the archive.
+///
+/// # Examples
+///
+/// ```
+/// # use epub::href;
+/// # use std::path::Path;
+/// let chapter = Path::new("OEBPS/Text/ch1.xhtml");
+/// assert_eq!(Path::new("OEBPS/Styles/main.css"), href::resolve(chapter, "../Styles/main.css"));
+/// assert_eq!(Path::new("OEBPS/Text/ch1.xhtml"), href::resolve(chapter, "#note1"));
+/// assert_eq!(Path::new("cover.png"), href::resolve(chapter, "/cover.png"));
+/// ```
+pub fn resolve<P: AsRef<Path>>(base: P, href: &str) -> PathBuf {
+    let (href, _) = split_fragment(href);
+    let href = href.split('?').next().unwrap_or_default();
+    let href = decode(href);
+    if href.starts_with('/') {
+        return PathBuf::from(collapse(&href));
+    }
+
+    let base = base.as_ref().to_string_lossy();
+    let base = if cfg!(windows) {
+        Cow::Owned(base.replace('\\', "/"))
+    } else {
+        base
+    };
+    // an empty href, like "#note", is the base document itself
+    if href.is_empty() {
+        return PathBuf::from(collapse(&base));
+    }
+    let path = match base.rfind('/') {
+        Some(i) => format!("{}/{}", &base[..i], href),
+        None => href.into_owned(),
+    };
+    PathBuf::from(collapse(&path))
+}
+
+/// Like `resolve`, but keeps the fragment of the `href`, to point to an
+/// element inside the document.
+pub fn resolve_with_fragment<P: AsRef<Path>>(base: P, href: &str) -> PathBuf {
+    let path = resolve(base, href);
+    match split_fragment(href) {
+        (_, Some(fragment)) => {
+            let mut path = path.into_os_string();
+            path.push("#");
+            path.push(fragment);
+            PathBuf::from(path)
+        }
+        _ => path,
+    }
+}
+
+/// Decodes the percent-encoded characters, keeping the path as it is if
+/// they aren't valid utf-8
+fn decode(path: &str) -> Cow<'_, str> {
+    percent_decode_str(path)
+        .decode_utf8()
+        .unwrap_or(Cow::Borrowed(path))
+}
+
+/// Removes the empty and `.` segments, and the `..` segments with the
+/// previous one, without decoding the path
+pub(crate) fn collapse(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            s => segments.push(s),
+        }
+    }
+    segments.join("/")
+}
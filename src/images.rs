@@ -0,0 +1,179 @@
+//! The images of the content documents, with their alt text and captions,
+//! for the accessibility audits or the datasets of image captions.
+//!
+//! The images are the `img` elements and the `image` elements of the
+//! inline svg. The alt text of an `image` is the `aria-label` or the
+//! `title` of its `svg`, and the caption of an image is the `figcaption`
+//! of the `figure` around it.
+
+use std::io::{Read, Seek};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::href;
+use crate::text::{attr, tokens, Token};
+
+/// An image referenced by a content document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImageReference {
+    /// the manifest id of the document
+    pub document: String,
+    /// the reference, as written in the document
+    pub href: String,
+    /// the full path of the image in the archive
+    pub path: PathBuf,
+    /// the manifest id of the image, None if it isn't in the manifest
+    pub resource: Option<String>,
+    /// the alt text, Some("") for the decorative images and None if the
+    /// image doesn't have one
+    pub alt: Option<String>,
+    /// the text of the caption of the figure of the image
+    pub caption: Option<String>,
+    /// the byte range of the `img` or `image` tag in the document
+    pub range: Range<usize>,
+}
+
+/// An open `figure` or `svg`, with the index of its first image
+struct Container {
+    name: &'static str,
+    first: usize,
+    /// the `aria-label` of the svg
+    label: Option<String>,
+    /// the text of the `figcaption` or the `title`
+    text: Option<String>,
+}
+
+/// Returns the images of the content document `html`, the document `id`
+/// at `path`, without their manifest ids
+fn scan(html: &str, id: &str, path: &Path) -> Vec<ImageReference> {
+    let mut images = vec![];
+    let mut open: Vec<Container> = vec![];
+    // the text of the open figcaption or title
+    let mut text: Option<String> = None;
+    for (range, token) in tokens(html) {
+        match token {
+            Token::Start { name, attrs, empty } => match name.as_str() {
+                "img" | "image" => {
+                    let link = match name.as_str() {
+                        "img" => attr(&attrs, "src"),
+                        _ => attr(&attrs, "href").or_else(|| attr(&attrs, "xlink:href")),
+                    };
+                    if let Some(link) = link {
+                        let alt = attr(&attrs, "alt").or_else(|| attr(&attrs, "aria-label"));
+                        images.push(ImageReference {
+                            document: id.to_string(),
+                            path: href::resolve(path, &link),
+                            href: link,
+                            resource: None,
+                            alt,
+                            caption: None,
+                            range,
+                        });
+                    }
+                }
+                "figure" | "svg" if !empty => open.push(Container {
+                    name: if name == "svg" { "svg" } else { "figure" },
+                    first: images.len(),
+                    label: attr(&attrs, "aria-label"),
+                    text: None,
+                }),
+                "figcaption" | "title" if !empty => text = Some(String::new()),
+                _ => {}
+            },
+            Token::Text(t) => {
+                if let Some(ref mut text) = text {
+                    text.push_str(&t);
+                }
+            }
+            Token::End(name) => match name.as_str() {
+                "figcaption" | "title" => {
+                    let caption = text.take().unwrap_or_default();
+                    let caption = caption.split_whitespace().collect::<Vec<_>>().join(" ");
+                    let kind = if name == "title" { "svg" } else { "figure" };
+                    if let Some(container) = open.iter_mut().rev().find(|c| c.name == kind) {
+                        container.text = Some(caption).filter(|c| !c.is_empty());
+                    }
+                }
+                "figure" | "svg" => {
+                    let container = match open.iter().rposition(|c| c.name == name) {
+                        Some(i) => open.remove(i),
+                        None => continue,
+                    };
+                    for image in images[container.first..].iter_mut() {
+                        if container.name == "figure" {
+                            if image.caption.is_none() {
+                                image.caption = container.text.clone();
+                            }
+                        } else if image.alt.is_none() {
+                            image.alt = container.label.clone().or_else(|| container.text.clone());
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Token::Ruby(_) => {}
+        }
+    }
+    images
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the images of the content document `id`, in document order
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let images = doc.images_of("titlepage.xhtml").unwrap();
+    /// assert_eq!(Some("portada.png"), images[0].resource.as_deref());
+    /// assert_eq!(Some("cover"), images[0].alt.as_deref());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document isn't in the manifest or can't be
+    /// read.
+    pub fn images_of(&mut self, id: &str) -> Result<Vec<ImageReference>, EpubError> {
+        let path = match self.resources.get(id) {
+            Some((path, _)) => path.clone(),
+            None => return Err(EpubError::ResourceNotFound(id.to_string())),
+        };
+        let html = self.get_resource_str_by_path(&path)?;
+        let mut images = scan(&html, id, &path);
+        for image in images.iter_mut() {
+            image.resource = self.resource_at(&image.path);
+        }
+        Ok(images)
+    }
+
+    /// Returns the images of the spine documents, in reading order, see
+    /// `images_of`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn image_references(&mut self) -> Result<Vec<ImageReference>, EpubError> {
+        let mut ids: Vec<String> = vec![];
+        for item in self.spine.iter() {
+            if !ids.contains(&item.idref) && self.resources.contains_key(&item.idref) {
+                ids.push(item.idref.clone());
+            }
+        }
+        let mut images = vec![];
+        for id in ids {
+            images.extend(self.images_of(&id)?);
+        }
+        Ok(images)
+    }
+
+    /// The manifest id of the resource at `path`
+    fn resource_at(&self, path: &Path) -> Option<String> {
+        self.resources
+            .iter()
+            .find(|(_, (p, _))| p == path)
+            .map(|(id, _)| id.clone())
+    }
+}
@@ -0,0 +1,228 @@
+//! The back-of-book indexes of the EPUB Indexes spec, as terms with the
+//! locations where they appear.
+//!
+//! An index is an element with the `index` epub:type, usually in a
+//! document with the "index" manifest property, with lists of entries:
+//!
+//! ```html
+//! <section epub:type="index">
+//!   <ul epub:type="index-entry-list">
+//!     <li epub:type="index-entry">
+//!       <span epub:type="index-term">storms</span>,
+//!       <a epub:type="index-locator" href="ch1.xhtml#p3">3</a>
+//!       <ul epub:type="index-entry-list">...</ul>
+//!     </li>
+//!   </ul>
+//! </section>
+//! ```
+
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::href;
+use crate::semantics::{epub_types, node_text};
+use crate::xmlutils::{self, XMLNode};
+
+/// A location of an index term, an `index-locator` link or an
+/// `index-locator-range`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexLocator {
+    /// the text of the link, like a page number
+    pub label: String,
+    /// the full path of the location, with the fragment
+    pub href: PathBuf,
+    /// the end of the location, for the ranges
+    pub end: Option<PathBuf>,
+}
+
+/// An entry of an index, with its subentries
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// the term, the text of the `index-term`
+    pub term: String,
+    /// the locations of the term, in document order
+    pub locators: Vec<IndexLocator>,
+    /// the preferred terms, the "see" cross references
+    pub see: Vec<String>,
+    /// the related terms, the "see also" cross references
+    pub see_also: Vec<String>,
+    /// the subentries
+    pub children: Vec<IndexEntry>,
+}
+
+/// An index of the publication
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Index {
+    /// the manifest id of the document
+    pub document: String,
+    /// the full path of the document in the archive
+    pub path: PathBuf,
+    /// the top level entries, in document order
+    pub entries: Vec<IndexEntry>,
+}
+
+impl Index {
+    /// Returns the first entry, or subentry, with the `term`, ignoring the
+    /// case
+    pub fn find(&self, term: &str) -> Option<&IndexEntry> {
+        let term = term.trim().to_lowercase();
+        find_entry(&self.entries, &term)
+    }
+
+    /// Returns the locations of the entry with the `term`, empty if the
+    /// index doesn't have it. See `find`.
+    pub fn locations(&self, term: &str) -> Vec<&IndexLocator> {
+        match self.find(term) {
+            Some(entry) => entry.locators.iter().collect(),
+            None => vec![],
+        }
+    }
+}
+
+fn find_entry<'a>(entries: &'a [IndexEntry], term: &str) -> Option<&'a IndexEntry> {
+    entries.iter().find_map(|entry| {
+        if entry.term.to_lowercase() == term {
+            Some(entry)
+        } else {
+            find_entry(&entry.children, term)
+        }
+    })
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the indexes of the publication, the elements with the
+    /// `index` epub:type of the documents with the "index" manifest
+    /// property, or of the spine documents if no document has that
+    /// property.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read or parsed.
+    pub fn indexes(&mut self) -> Result<Vec<Index>, EpubError> {
+        let mut ids = self.resources_with_property("index");
+        if ids.is_empty() {
+            for item in self.spine.iter() {
+                if !ids.contains(&item.idref) && self.resources.contains_key(&item.idref) {
+                    ids.push(item.idref.clone());
+                }
+            }
+        }
+        let mut indexes = vec![];
+        for id in ids {
+            let path = self.resources[&id].0.clone();
+            let content = self.get_resource_by_path(&path)?;
+            let (root, _) = xmlutils::XMLReader::parse_recover(&content)?;
+            let mut entries = vec![];
+            find_indexes(&root.borrow(), &path, &mut entries);
+            for entries in entries {
+                indexes.push(Index {
+                    document: id.clone(),
+                    path: path.clone(),
+                    entries,
+                });
+            }
+        }
+        Ok(indexes)
+    }
+}
+
+fn has_type(node: &XMLNode, epub_type: &str) -> bool {
+    epub_types(node).iter().any(|t| t == epub_type)
+}
+
+/// Adds the entries of each `index` element under `node`
+fn find_indexes(node: &XMLNode, path: &Path, indexes: &mut Vec<Vec<IndexEntry>>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if has_type(&child, "index") {
+            let mut entries = vec![];
+            collect_entries(&child, path, &mut entries);
+            indexes.push(entries);
+        } else {
+            find_indexes(&child, path, indexes);
+        }
+    }
+}
+
+/// Adds the `index-entry` elements under `node`
+fn collect_entries(node: &XMLNode, path: &Path, entries: &mut Vec<IndexEntry>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if has_type(&child, "index-entry") {
+            let mut entry = IndexEntry::default();
+            read_entry(&child, path, &mut entry);
+            entries.push(entry);
+        } else {
+            collect_entries(&child, path, entries);
+        }
+    }
+}
+
+/// Reads the term, the locators and the subentries of an `index-entry`
+fn read_entry(node: &XMLNode, path: &Path, entry: &mut IndexEntry) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        let types = epub_types(&child);
+        let text = || {
+            let mut text = String::new();
+            node_text(&child, &mut text);
+            text.split_whitespace().collect::<Vec<_>>().join(" ")
+        };
+        match types
+            .iter()
+            .map(String::as_str)
+            .find(|t| t.starts_with("index-"))
+        {
+            Some("index-term") if entry.term.is_empty() => entry.term = text(),
+            Some("index-locator") => entry.locators.extend(locator(&child, path, text())),
+            Some("index-locator-range") => {
+                let mut locators = vec![];
+                collect_locators(&child, path, &mut locators);
+                if let Some(first) = locators.first().cloned() {
+                    let labels: Vec<&str> = locators.iter().map(|l| l.label.as_str()).collect();
+                    entry.locators.push(IndexLocator {
+                        label: labels.join("-"),
+                        end: locators
+                            .last()
+                            .map(|l| l.href.clone())
+                            .filter(|_| locators.len() > 1),
+                        href: first.href,
+                    });
+                }
+            }
+            Some("index-xref-preferred") => entry.see.push(text()),
+            Some("index-xref-related") => entry.see_also.push(text()),
+            Some("index-entry-list") => collect_entries(&child, path, &mut entry.children),
+            Some("index-entry") => {
+                let mut subentry = IndexEntry::default();
+                read_entry(&child, path, &mut subentry);
+                entry.children.push(subentry);
+            }
+            _ => read_entry(&child, path, entry),
+        }
+    }
+}
+
+fn collect_locators(node: &XMLNode, path: &Path, locators: &mut Vec<IndexLocator>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        let mut label = String::new();
+        node_text(&child, &mut label);
+        match locator(&child, path, label.trim().to_string()) {
+            Some(locator) => locators.push(locator),
+            None => collect_locators(&child, path, locators),
+        }
+    }
+}
+
+/// The locator of a link, None if it isn't a link
+fn locator(node: &XMLNode, path: &Path, label: String) -> Option<IndexLocator> {
+    let link = node.get_attr("href").ok()?;
+    Some(IndexLocator {
+        label,
+        href: href::resolve_with_fragment(path, &link),
+        end: None,
+    })
+}
@@ -0,0 +1,372 @@
+//! Json representation of the navigation structures, to send the toc to a
+//! javascript reader or to cache it.
+//!
+//! The crate doesn't depend on serde, so this is a small json writer and
+//! parser only for these structures. A `NavPoint` is written as
+//! `{"label": "...", "content": "...", "play_order": 1, "children": [...]}`.
+//!
+//! The members are named like the fields of the structs, and the paths
+//! are written as strings, the same json that the serde derives write, so
+//! the other side can read it with its own `#[derive(Deserialize)]`
+//! structs. The structs don't implement `Serialize` and `Deserialize`
+//! themselves.
+
+use std::path::PathBuf;
+
+use crate::doc::{Landmark, NavPoint, PageTarget};
+use crate::error::EpubError;
+
+/// Maximum nesting of arrays and objects, to stop on hostile input
+const MAX_DEPTH: usize = 256;
+
+/// A parsed json value
+#[derive(Debug, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Returns the member `key` of an object
+    pub(crate) fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `s` as a json string, with the quotes
+pub(crate) fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Parses the json `text`
+///
+/// # Errors
+///
+/// Returns `EpubError::Json` if the text isn't valid json.
+pub(crate) fn parse(text: &str) -> Result<Value, EpubError> {
+    let mut parser = Parser {
+        chars: text.char_indices().peekable(),
+    };
+    let value = parser.value(0)?;
+    parser.skip_whitespace();
+    match parser.chars.next() {
+        None => Ok(value),
+        Some((i, _)) => Err(EpubError::Json(format!("trailing characters at {}", i))),
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        while let Some((_, c)) = self.chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.chars.next();
+        }
+    }
+
+    fn error(&mut self, expected: &str) -> EpubError {
+        match self.chars.peek() {
+            Some((i, c)) => EpubError::Json(format!("expected {} at {}, found {}", expected, i, c)),
+            None => EpubError::Json(format!("expected {}, found the end", expected)),
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), EpubError> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some((_, c)) if *c == expected => {
+                self.chars.next();
+                Ok(())
+            }
+            _ => Err(self.error(&expected.to_string())),
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, EpubError> {
+        if depth > MAX_DEPTH {
+            return Err(EpubError::Json(String::from("too deeply nested")));
+        }
+        self.skip_whitespace();
+        let c = match self.chars.peek() {
+            Some((_, c)) => *c,
+            None => return Err(self.error("a value")),
+        };
+        match c {
+            '{' => {
+                self.chars.next();
+                let mut members = vec![];
+                self.skip_whitespace();
+                if let Some((_, '}')) = self.chars.peek() {
+                    self.chars.next();
+                    return Ok(Value::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => continue,
+                        Some((_, '}')) => return Ok(Value::Object(members)),
+                        _ => return Err(EpubError::Json(String::from("unclosed object"))),
+                    }
+                }
+            }
+            '[' => {
+                self.chars.next();
+                let mut values = vec![];
+                self.skip_whitespace();
+                if let Some((_, ']')) = self.chars.peek() {
+                    self.chars.next();
+                    return Ok(Value::Array(values));
+                }
+                loop {
+                    values.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.chars.next() {
+                        Some((_, ',')) => continue,
+                        Some((_, ']')) => return Ok(Value::Array(values)),
+                        _ => return Err(EpubError::Json(String::from("unclosed array"))),
+                    }
+                }
+            }
+            '"' => Ok(Value::String(self.string()?)),
+            't' => self.literal("true", Value::Bool(true)),
+            'f' => self.literal("false", Value::Bool(false)),
+            'n' => self.literal("null", Value::Null),
+            _ => self.number(),
+        }
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, EpubError> {
+        for expected in word.chars() {
+            match self.chars.next() {
+                Some((_, c)) if c == expected => {}
+                _ => {
+                    return Err(EpubError::Json(format!(
+                        "invalid literal, expected {}",
+                        word
+                    )))
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    fn number(&mut self) -> Result<Value, EpubError> {
+        let mut text = String::new();
+        while let Some((_, c)) = self.chars.peek() {
+            if !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                break;
+            }
+            text.push(*c);
+            self.chars.next();
+        }
+        if text.is_empty() {
+            return Err(self.error("a value"));
+        }
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| EpubError::Json(format!("invalid number {}", text)))
+    }
+
+    fn string(&mut self) -> Result<String, EpubError> {
+        match self.chars.next() {
+            Some((_, '"')) => {}
+            _ => return Err(EpubError::Json(String::from("expected a string"))),
+        }
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, '"')) => s.push('"'),
+                    Some((_, '\\')) => s.push('\\'),
+                    Some((_, '/')) => s.push('/'),
+                    Some((_, 'b')) => s.push('\u{8}'),
+                    Some((_, 'f')) => s.push('\u{c}'),
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 'r')) => s.push('\r'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, 'u')) => {
+                        let unit = self.hex4()?;
+                        let c = if (0xd800..0xdc00).contains(&unit) {
+                            // surrogate pair
+                            self.literal("\\u", Value::Null)?;
+                            let low = self.hex4()?;
+                            let code =
+                                0x10000 + ((unit - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                            char::from_u32(code)
+                        } else {
+                            char::from_u32(unit)
+                        };
+                        s.push(c.unwrap_or('\u{fffd}'));
+                    }
+                    _ => return Err(EpubError::Json(String::from("invalid escape"))),
+                },
+                Some((_, c)) => s.push(c),
+                None => return Err(EpubError::Json(String::from("unclosed string"))),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, EpubError> {
+        let mut unit = 0;
+        for _ in 0..4 {
+            let digit = self
+                .chars
+                .next()
+                .and_then(|(_, c)| c.to_digit(16))
+                .ok_or_else(|| EpubError::Json(String::from("invalid unicode escape")))?;
+            unit = unit * 16 + digit;
+        }
+        Ok(unit)
+    }
+}
+
+impl NavPoint {
+    /// Returns the navpoint and its children as a json object, like
+    /// `{"label": "One", "content": "OEBPS/ch1.xhtml", "play_order": 1,
+    /// "children": []}`
+    pub fn to_json(&self) -> String {
+        let children: Vec<String> = self.children.iter().map(|nav| nav.to_json()).collect();
+        format!(
+            "{{\"label\":{},\"content\":{},\"play_order\":{},\"children\":[{}]}}",
+            string(&self.label),
+            string(&self.content.to_string_lossy()),
+            self.play_order,
+            children.join(",")
+        )
+    }
+
+    /// Returns the toc as a json array of navpoints. See `to_json`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::{EpubDoc, NavPoint};
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let json = NavPoint::list_to_json(&doc.toc);
+    /// assert!(json.starts_with(r#"[{"label":"Todo es mío","content":"OEBPS/Text/000.xhtml""#));
+    ///
+    /// let toc = NavPoint::list_from_json(&json).unwrap();
+    /// assert_eq!(doc.toc.len(), toc.len());
+    /// assert_eq!(doc.toc[1].label, toc[1].label);
+    /// ```
+    pub fn list_to_json(navpoints: &[NavPoint]) -> String {
+        let navpoints: Vec<String> = navpoints.iter().map(|nav| nav.to_json()).collect();
+        format!("[{}]", navpoints.join(","))
+    }
+
+    /// Reads a toc written with `list_to_json`. The play order is optional,
+    /// the children are optional too.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::Json` if the text isn't valid json or if a
+    /// navpoint doesn't have a label or a content.
+    pub fn list_from_json(json: &str) -> Result<Vec<NavPoint>, EpubError> {
+        navpoints_from_value(&parse(json)?)
+    }
+}
+
+fn navpoints_from_value(value: &Value) -> Result<Vec<NavPoint>, EpubError> {
+    let values = value
+        .as_array()
+        .ok_or_else(|| EpubError::Json(String::from("expected an array of navpoints")))?;
+    let mut navpoints = vec![];
+    for value in values {
+        let field = |name: &str| {
+            value
+                .get(name)
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| EpubError::Json(format!("the navpoint doesn't have a {}", name)))
+        };
+        navpoints.push(NavPoint {
+            label: field("label")?.to_string(),
+            content: PathBuf::from(field("content")?),
+            play_order: value
+                .get("play_order")
+                .and_then(|v| v.as_usize())
+                .unwrap_or(0),
+            children: match value.get("children") {
+                Some(children) => navpoints_from_value(children)?,
+                None => vec![],
+            },
+        });
+    }
+    Ok(navpoints)
+}
+
+impl Landmark {
+    /// Returns the landmark as a json object, like `{"label": "Start",
+    /// "content": "OEBPS/ch1.xhtml", "epub_type": "bodymatter"}`
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"label\":{},\"content\":{},\"epub_type\":{}}}",
+            string(&self.label),
+            string(&self.content.to_string_lossy()),
+            string(&self.epub_type)
+        )
+    }
+}
+
+impl PageTarget {
+    /// Returns the page as a json object, like `{"label": "12", "content":
+    /// "OEBPS/ch1.xhtml#page12"}`
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"label\":{},\"content\":{}}}",
+            string(&self.label),
+            string(&self.content.to_string_lossy())
+        )
+    }
+}
@@ -46,14 +46,14 @@
 //!
 //! ## Navigating using the spine
 //!
-//! Spine is a Vec<String> storing the epub spine as resources ids
+//! Spine is a Vec<SpineItem> storing the epub spine, with the resources ids
 //!
 //! ```
 //! # use epub::doc::EpubDoc;
 //! # let doc = EpubDoc::new("test.epub");
 //! # let doc = doc.unwrap();
 //! assert_eq!(17, doc.spine.len());
-//! assert_eq!("titlepage.xhtml", doc.spine[0]);
+//! assert_eq!("titlepage.xhtml", doc.spine[0].idref);
 //! ```
 //!
 //! ## Navigation using the doc internal state
@@ -99,8 +99,63 @@
 //! let mut f = f.unwrap();
 //! let resp = f.write_all(&cover_data);
 //! ```
+//!
+//! ## Creating a new epub
+//!
+//! ```
+//! use epub::builder::EpubBuilder;
+//!
+//! let mut builder = EpubBuilder::new();
+//! builder
+//!     .metadata("title", "My book")
+//!     .metadata("language", "en")
+//!     .metadata("identifier", "urn:isbn:9780000000000")
+//!     .add_content("chapter1.xhtml", b"<html>...</html>".to_vec())
+//!     .add_toc_entry("Chapter 1", "chapter1.xhtml");
+//!
+//! // builder.save("/tmp/book.epub") will write the epub to a file
+//! ```
 
 mod xmlutils;
+mod anchor;
+mod cfi;
+mod digest;
+mod encoding;
+mod json;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod merge;
+mod navigation;
+mod repair;
+mod scripts;
+mod split;
+mod usage;
 
 pub mod archive;
+#[cfg(feature = "async")]
+pub mod r#async;
+pub mod builder;
+pub mod chunk;
+pub mod dictionary;
 pub mod doc;
+pub mod edit;
+pub mod encryption;
+pub mod error;
+pub mod href;
+pub mod images;
+pub mod index;
+pub mod media;
+pub mod metadata;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod overlay;
+pub mod remote;
+pub mod search;
+pub mod rendition;
+pub mod semantics;
+pub mod signatures;
+pub mod stats;
+pub mod store;
+pub mod stream;
+pub mod tables;
+pub mod text;
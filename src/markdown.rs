@@ -0,0 +1,715 @@
+//! Minimal markdown to xhtml conversion, used by the builder to create
+//! chapters from markdown, and the conversion of the content documents
+//! back to markdown, see `EpubDoc::get_markdown`.
+//!
+//! The converter is written here, instead of using pulldown-cmark, to keep
+//! the feature without dependencies, so it isn't a CommonMark parser. It
+//! supports the common subset of markdown: ATX and setext headings,
+//! paragraphs, block quotes, ordered and unordered lists, nested by their
+//! indentation, fenced and indented code blocks, horizontal rules,
+//! emphasis, inline code, inline links and images, with the target in
+//! angle brackets too, and the backslash escapes of the punctuation, so
+//! the markdown of `EpubDoc::get_markdown` is converted back. The
+//! reference links, the autolinks and the tables aren't supported, they're
+//! written as text. Raw html in the markdown is escaped.
+
+use std::io::{Read, Seek};
+
+use regex::Regex;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::navigation::Heading;
+use crate::text::{attr, ruby_tokens, tokens, RubyMode, Token};
+
+thread_local! {
+    /// The inline markup of `inline`, with their replacement, in the order
+    /// they are applied
+    static INLINE_RULES: Vec<(Regex, &'static str)> = [
+        (
+            r"!\[([^\]]*)\]\(&lt;([^\n]*?)&gt;\)",
+            r#"<img src="$2" alt="$1" />"#,
+        ),
+        (r"\[([^\]]+)\]\(&lt;([^\n]*?)&gt;\)", r#"<a href="$2">$1</a>"#),
+        (
+            r"!\[([^\]]*)\]\(([^)\s]*)\)",
+            r#"<img src="$2" alt="$1" />"#,
+        ),
+        (r"\[([^\]]+)\]\(([^)\s]*)\)", r#"<a href="$2">$1</a>"#),
+        (r"\*\*([^*]+)\*\*", "<strong>$1</strong>"),
+        (r"__([^_]+)__", "<strong>$1</strong>"),
+        (r"\*([^*]+)\*", "<em>$1</em>"),
+        (r"\b_([^_]+)_\b", "<em>$1</em>"),
+        (r" {2,}\n", "<br />\n"),
+    ]
+    .iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).expect("valid regex"), *replacement))
+    .collect();
+    /// The links and the images, with their text
+    static LINKS: Regex = Regex::new(r"!?\[([^\]]*)\]\([^)]*\)").expect("valid regex");
+}
+
+/// Start of the private use area characters that replace the escaped
+/// characters, see `escaped`
+const ESCAPED_BASE: u32 = 0xf0000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Block {
+    None,
+    Paragraph,
+    UnorderedList,
+    OrderedList,
+    Quote,
+}
+
+/// Converts `markdown` to a xhtml fragment, returning also the list of
+/// headings
+pub(crate) fn to_xhtml(markdown: &str) -> (String, Vec<Heading>) {
+    let mut converter = Converter::default();
+    converter.convert(markdown);
+    (converter.out, converter.headings)
+}
+
+#[derive(Default)]
+struct Converter {
+    out: String,
+    headings: Vec<Heading>,
+    /// the open lists, with the indentation of their items
+    lists: Vec<(Block, usize)>,
+}
+
+impl Converter {
+    fn convert(&mut self, markdown: &str) {
+        let mut block = Block::None;
+        // text of the current paragraph, list item or quote
+        let mut text: Vec<&str> = vec![];
+        let mut lines = markdown.lines().peekable();
+
+        while let Some(line) = lines.next() {
+            let trimmed = line.trim();
+
+            if let Some(fence) = code_fence(trimmed) {
+                self.close(&mut block, &mut text);
+                let mut code = vec![];
+                for l in lines.by_ref() {
+                    if l.trim().starts_with(fence) {
+                        break;
+                    }
+                    code.push(l);
+                }
+                self.code_block(&code);
+                continue;
+            }
+
+            if block == Block::None && line.starts_with("    ") && !trimmed.is_empty() {
+                let mut code = vec![&line[4..]];
+                while let Some(l) = lines.peek() {
+                    if !l.starts_with("    ") && !l.trim().is_empty() {
+                        break;
+                    }
+                    code.push(l.get(4..).unwrap_or_default());
+                    lines.next();
+                }
+                while code.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+                    code.pop();
+                }
+                self.code_block(&code);
+                continue;
+            }
+
+            if trimmed.is_empty() {
+                self.close(&mut block, &mut text);
+                continue;
+            }
+
+            if block == Block::Paragraph && is_setext_underline(trimmed) {
+                let heading = text.iter().map(|l| l.trim()).collect::<Vec<_>>().join(" ");
+                text.clear();
+                block = Block::None;
+                let level = if trimmed.starts_with('=') { 1 } else { 2 };
+                self.heading(level, &heading);
+                continue;
+            }
+
+            if let Some((level, heading)) = atx_heading(trimmed) {
+                self.close(&mut block, &mut text);
+                self.heading(level, heading);
+                continue;
+            }
+
+            if is_rule(trimmed) {
+                self.close(&mut block, &mut text);
+                self.out.push_str("<hr />\n");
+                continue;
+            }
+
+            if let Some(quoted) = trimmed.strip_prefix('>') {
+                if block != Block::Quote {
+                    self.close(&mut block, &mut text);
+                    block = Block::Quote;
+                }
+                text.push(quoted.strip_prefix(' ').unwrap_or(quoted));
+                continue;
+            }
+
+            if let Some(item) = list_item(line) {
+                if !matches!(block, Block::UnorderedList | Block::OrderedList) {
+                    self.close(&mut block, &mut text);
+                    block = item.kind;
+                }
+                self.list_item(&item, &mut text);
+                text.push(item.text);
+                continue;
+            }
+
+            if block == Block::None {
+                block = Block::Paragraph;
+            }
+            text.push(line);
+        }
+        self.close(&mut block, &mut text);
+    }
+
+    /// Writes the current block
+    fn close(&mut self, block: &mut Block, text: &mut Vec<&str>) {
+        match block {
+            Block::None => {}
+            Block::Paragraph => {
+                self.out.push_str("<p>");
+                self.out.push_str(&inline(&text.join("\n")));
+                self.out.push_str("</p>\n");
+            }
+            Block::UnorderedList | Block::OrderedList => {
+                self.item_text(text);
+                while !self.lists.is_empty() {
+                    self.close_list();
+                }
+            }
+            Block::Quote => {
+                let mut inner = Converter::default();
+                inner.convert(&text.join("\n"));
+                self.out.push_str("<blockquote>\n");
+                self.out.push_str(&inner.out);
+                self.out.push_str("</blockquote>\n");
+            }
+        }
+        text.clear();
+        *block = Block::None;
+    }
+
+    /// Starts a new item of a list of `kind`. The item is nested in the
+    /// previous one if it's more indented, and it ends the more indented
+    /// lists otherwise.
+    fn list_item(&mut self, item: &ListItem, text: &mut Vec<&str>) {
+        self.item_text(text);
+        if let Some(&(_, last)) = self.lists.last() {
+            if item.indent <= last {
+                while self.lists.len() > 1 && self.lists.last().is_some_and(|l| l.1 > item.indent) {
+                    self.close_list();
+                }
+                if self.lists.last().map(|l| l.0) == Some(item.kind) {
+                    self.out.push_str("</li>\n<li>");
+                    return;
+                }
+                self.close_list();
+            }
+        }
+        match item.kind {
+            Block::OrderedList if item.number != 1 => self
+                .out
+                .push_str(&format!("<ol start=\"{}\">\n<li>", item.number)),
+            Block::OrderedList => self.out.push_str("<ol>\n<li>"),
+            _ => self.out.push_str("<ul>\n<li>"),
+        }
+        self.lists.push((item.kind, item.indent));
+    }
+
+    /// Writes the text of the open list item
+    fn item_text(&mut self, text: &mut Vec<&str>) {
+        let content: Vec<&str> = text.iter().map(|l| l.trim()).collect();
+        self.out.push_str(&inline(&content.join("\n")));
+        text.clear();
+    }
+
+    /// Ends the open item of the last list and the list
+    fn close_list(&mut self) {
+        if let Some((kind, _)) = self.lists.pop() {
+            self.out.push_str(match kind {
+                Block::OrderedList => "</li>\n</ol>\n",
+                _ => "</li>\n</ul>\n",
+            });
+        }
+    }
+
+    fn code_block(&mut self, code: &[&str]) {
+        self.out.push_str("<pre><code>");
+        self.out.push_str(&escape(&code.join("\n")));
+        self.out.push_str("</code></pre>\n");
+    }
+
+    fn heading(&mut self, level: usize, text: &str) {
+        let plain = strip_markup(text);
+        let mut id = slug(&plain);
+        let base = id.clone();
+        let mut n = 1;
+        while self.headings.iter().any(|h| h.id.as_ref() == Some(&id)) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        self.out.push_str(&format!(
+            "<h{} id=\"{}\">{}</h{}>\n",
+            level,
+            id,
+            inline(text),
+            level
+        ));
+        self.headings.push(Heading {
+            level,
+            text: plain,
+            id: Some(id),
+        });
+    }
+}
+
+/// Returns the fence string if the line opens a fenced code block
+fn code_fence(line: &str) -> Option<&'static str> {
+    if line.starts_with("```") {
+        Some("```")
+    } else if line.starts_with("~~~") {
+        Some("~~~")
+    } else {
+        None
+    }
+}
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some((level, text))
+}
+
+/// Checks if the line is the underline of a setext heading, `===` for the
+/// first level and `---` for the second
+fn is_setext_underline(line: &str) -> bool {
+    (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-')) && !line.is_empty()
+}
+
+fn is_rule(line: &str) -> bool {
+    let chars: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    chars.len() >= 3
+        && (chars.iter().all(|c| *c == '-')
+            || chars.iter().all(|c| *c == '*')
+            || chars.iter().all(|c| *c == '_'))
+}
+
+/// A line starting a list item
+struct ListItem<'a> {
+    kind: Block,
+    /// the spaces before the marker
+    indent: usize,
+    /// the number of an ordered list item
+    number: usize,
+    text: &'a str,
+}
+
+fn list_item(line: &str) -> Option<ListItem<'_>> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+    let line = trimmed.trim_end();
+    for marker in ["- ", "* ", "+ "].iter() {
+        if let Some(text) = line.strip_prefix(marker) {
+            return Some(ListItem {
+                kind: Block::UnorderedList,
+                indent,
+                number: 1,
+                text,
+            });
+        }
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if let Some(text) = rest.strip_prefix(". ").or_else(|| rest.strip_prefix(") ")) {
+            return Some(ListItem {
+                kind: Block::OrderedList,
+                indent,
+                number: line[..digits].parse().unwrap_or(1),
+                text,
+            });
+        }
+    }
+    None
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Converts the inline markup, code spans, images, links, emphasis and
+/// backslash escapes
+fn inline(text: &str) -> String {
+    let mut out = String::new();
+    // the text before the next code span, with the escaped characters
+    // replaced by `escaped`
+    let mut part = String::new();
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.clone().next() {
+                Some((_, next)) if next.is_ascii_punctuation() => {
+                    part.push(escaped(next));
+                    chars.next();
+                }
+                _ => part.push(c),
+            },
+            '`' => match text[i + 1..].find('`') {
+                Some(len) => {
+                    out.push_str(&inline_markup(&part));
+                    out.push_str("<code>");
+                    out.push_str(&escape(&text[i + 1..i + 1 + len]));
+                    out.push_str("</code>");
+                    out.push_str(&inline(&text[i + len + 2..]));
+                    return out;
+                }
+                None => part.push(c),
+            },
+            _ => part.push(c),
+        }
+    }
+    out.push_str(&inline_markup(&part));
+    out
+}
+
+/// Converts the images, links and emphasis of a text without code spans
+fn inline_markup(text: &str) -> String {
+    let mut text = escape(text);
+    INLINE_RULES.with(|rules| {
+        for (re, replacement) in rules.iter() {
+            text = re.replace_all(&text, *replacement).to_string();
+        }
+    });
+    text.chars()
+        .map(|c| match unescaped(c) {
+            Some(c) => escape(&c.to_string()),
+            None => c.to_string(),
+        })
+        .collect()
+}
+
+/// The character of the private use area that replaces the escaped ascii
+/// punctuation `c` while the markup is converted
+fn escaped(c: char) -> char {
+    std::char::from_u32(ESCAPED_BASE + c as u32).unwrap_or(c)
+}
+
+/// The ascii punctuation replaced by `c`, if it's an escaped character
+fn unescaped(c: char) -> Option<char> {
+    (c as u32)
+        .checked_sub(ESCAPED_BASE)
+        .and_then(std::char::from_u32)
+        .filter(|c| c.is_ascii_punctuation())
+}
+
+/// Returns the heading text without inline markup
+fn strip_markup(text: &str) -> String {
+    let re = LINKS.with(Regex::clone);
+    let text = re.replace_all(text, "$1");
+    let mut plain = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(char::is_ascii_punctuation) => {
+                plain.extend(chars.next());
+            }
+            '*' | '_' | '`' => {}
+            c => plain.push(c),
+        }
+    }
+    plain
+}
+
+/// Builds a xml id from the heading text
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.starts_with(|c: char| c.is_alphabetic()) {
+        slug.to_string()
+    } else {
+        format!("h-{}", slug)
+    }
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the spine item `index` converted to markdown, with its
+    /// headings, emphasis, lists, block quotes, code, links and images.
+    /// The links and the images keep the hrefs of the document. Needs the
+    /// `markdown` feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let markdown = doc.get_markdown(2).unwrap();
+    /// assert!(markdown.starts_with("# Despertar\n\nJosé Luís abrió los ojos"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index is out of the spine or if the document
+    /// can't be read.
+    pub fn get_markdown(&mut self, index: usize) -> Result<String, EpubError> {
+        let html = self.spine_str(index)?;
+        Ok(from_xhtml(&html))
+    }
+
+    /// Returns the current spine item converted to markdown, see
+    /// `get_markdown`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document can't be read.
+    pub fn get_current_markdown(&mut self) -> Result<String, EpubError> {
+        self.get_markdown(self.get_current_page())
+    }
+}
+
+/// Converts the content document `html` to markdown: the headings, the
+/// paragraphs, the emphasis, the lists, the block quotes, the code, the
+/// links and the images. The rest of the markup is dropped, keeping its
+/// text.
+pub(crate) fn from_xhtml(html: &str) -> String {
+    let mut writer = MarkdownWriter::default();
+    for (_, token) in ruby_tokens(tokens(html), RubyMode::Base) {
+        writer.token(token);
+    }
+    writer.flush();
+    writer.out
+}
+
+#[derive(Default)]
+struct MarkdownWriter {
+    out: String,
+    /// the inline text of the current block
+    text: String,
+    /// the marker of the current block, like "## " or "- "
+    marker: String,
+    /// the open lists, with the number of the next item of the ordered ones
+    lists: Vec<Option<usize>>,
+    /// the depth of the block quotes
+    quotes: usize,
+    /// the depth of the block quotes of the last block
+    last_quotes: usize,
+    /// the hrefs of the open links, None for the anchors
+    links: Vec<Option<String>>,
+    /// the content of the open `pre`, None outside of it
+    pre: Option<String>,
+    /// true if the last block was a list item, to keep the lists tight
+    in_list: bool,
+}
+
+impl MarkdownWriter {
+    fn token(&mut self, token: Token) {
+        if let Some(ref mut code) = self.pre {
+            match token {
+                Token::Text(text) => code.push_str(&text),
+                Token::Start { ref name, .. } if name == "br" => code.push('\n'),
+                Token::End(ref name) if name == "pre" => {
+                    let code = self.pre.take().unwrap_or_default();
+                    let code = code.trim_matches('\n');
+                    let fence = if code.contains("```") { "~~~" } else { "```" };
+                    self.block(&format!("{}\n{}\n{}", fence, code, fence), false);
+                }
+                _ => {}
+            }
+            return;
+        }
+        match token {
+            Token::Text(text) => self.text.push_str(&escape_markdown(&text)),
+            Token::Start { name, attrs, empty } => self.start(&name, &attrs, empty),
+            Token::End(name) => self.end(&name),
+            Token::Ruby(_) => {}
+        }
+    }
+
+    fn start(&mut self, name: &str, attrs: &str, empty: bool) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                self.flush();
+                let level = name[1..].parse().unwrap_or(1);
+                self.marker = format!("{} ", "#".repeat(level));
+            }
+            "p" | "div" | "section" | "article" | "aside" | "header" | "footer" | "main"
+            | "nav" | "figure" | "figcaption" | "dd" | "dt" | "table" | "tr" | "caption" => {
+                self.flush()
+            }
+            "ul" | "ol" if !empty => {
+                self.flush();
+                self.lists
+                    .push(Some(1).filter(|_| name == "ol").map(|first| {
+                        attr(attrs, "start")
+                            .and_then(|s| s.trim().parse().ok())
+                            .unwrap_or(first)
+                    }));
+            }
+            "li" => {
+                self.flush();
+                let indent = "   ".repeat(self.lists.len().saturating_sub(1));
+                self.marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}{}. ", indent, *number - 1)
+                    }
+                    _ => format!("{}- ", indent),
+                };
+            }
+            "blockquote" if !empty => {
+                self.flush();
+                self.quotes += 1;
+            }
+            "pre" if !empty => {
+                self.flush();
+                self.pre = Some(String::new());
+            }
+            "hr" => {
+                self.flush();
+                self.block("---", false);
+            }
+            "br" => self.text.push_str("  \n"),
+            "td" | "th" => self.text.push(' '),
+            "em" | "i" | "cite" | "dfn" => self.text.push('*'),
+            "strong" | "b" => self.text.push_str("**"),
+            "code" | "kbd" | "samp" => self.text.push('`'),
+            "a" if !empty => {
+                let href = attr(attrs, "href");
+                if href.is_some() {
+                    self.text.push('[');
+                }
+                self.links.push(href);
+            }
+            "img" => {
+                let alt = attr(attrs, "alt").unwrap_or_default();
+                if let Some(src) = attr(attrs, "src") {
+                    let alt = escape_markdown(&alt);
+                    self.text
+                        .push_str(&format!("![{}]({})", alt, link_target(&src)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, name: &str) {
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "p" | "div" | "section" | "article"
+            | "aside" | "header" | "footer" | "main" | "nav" | "figure" | "figcaption" | "dd"
+            | "dt" | "table" | "tr" | "caption" | "li" => self.flush(),
+            "ul" | "ol" => {
+                self.flush();
+                self.lists.pop();
+            }
+            "blockquote" => {
+                self.flush();
+                self.quotes = self.quotes.saturating_sub(1);
+            }
+            "em" | "i" | "cite" | "dfn" => self.text.push('*'),
+            "strong" | "b" => self.text.push_str("**"),
+            "code" | "kbd" | "samp" => self.text.push('`'),
+            "a" => {
+                if let Some(Some(href)) = self.links.pop() {
+                    self.text.push_str(&format!("]({})", link_target(&href)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Writes the current block, with its marker, if it has text
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let marker = std::mem::take(&mut self.marker);
+        let lines: Vec<String> = text
+            .split("  \n")
+            .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|line| !line.is_empty())
+            .collect();
+        if lines.is_empty() {
+            // the marker of a list item is for its first paragraph
+            self.marker = marker;
+            return;
+        }
+        let block = if marker.starts_with('#') {
+            format!("{}{}", marker, lines.join(" "))
+        } else {
+            // the lines after the marker are aligned with the text
+            let indent = " ".repeat(marker.chars().count());
+            let separator = format!("  \n{}", indent);
+            format!("{}{}", marker, lines.join(&separator))
+        };
+        let list_item = !marker.is_empty() && !marker.starts_with('#');
+        self.block(&block, list_item);
+    }
+
+    /// Adds the `block` to the output, quoted in the block quotes
+    fn block(&mut self, block: &str, list_item: bool) {
+        let quote = "> ".repeat(self.quotes);
+        if !self.out.is_empty() {
+            let tight = list_item && self.in_list;
+            // the empty line between the blocks of a quote is quoted too
+            let shared = "> ".repeat(self.quotes.min(self.last_quotes));
+            self.out.push('\n');
+            if !tight {
+                self.out.push_str(shared.trim_end());
+                self.out.push('\n');
+            }
+        }
+        for (i, line) in block.lines().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            self.out.push_str(&quote);
+            self.out.push_str(line);
+        }
+        self.in_list = list_item;
+        self.last_quotes = self.quotes;
+    }
+}
+
+/// Escapes the characters of `text` that are markdown syntax
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '`' | '[' | ']' | '<') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The target of a markdown link, in angle brackets if it has spaces or
+/// parentheses
+fn link_target(href: &str) -> String {
+    if href.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+        format!("<{}>", href)
+    } else {
+        href.to_string()
+    }
+}
@@ -0,0 +1,179 @@
+//! Detection of the media type of the resources from their content.
+//!
+//! The media type declared in the manifest is often wrong, like jpeg images
+//! declared as png. The content is sniffed with the magic bytes of the
+//! common binary formats: images, fonts and audio. Text formats, like
+//! xhtml or css, can't be told apart reliably and aren't detected.
+//!
+//! # Examples
+//!
+//! ```
+//! use epub::media;
+//!
+//! assert_eq!(Some("image/png"), media::sniff(b"\x89PNG\r\n\x1a\n...."));
+//! assert_eq!(None, media::sniff(b"body { margin: 0 }"));
+//! assert!(media::same_type("image/jpg", "image/jpeg"));
+//! assert!(!media::same_type("image/png", "image/jpeg"));
+//! ```
+
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+
+/// A resource whose content doesn't match the media type of the manifest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaTypeMismatch {
+    /// the manifest id of the resource
+    pub id: String,
+    /// the full path of the resource in the archive
+    pub path: PathBuf,
+    /// the media type declared in the manifest
+    pub declared: String,
+    /// the media type detected from the content
+    pub sniffed: &'static str,
+}
+
+/// Returns the media type of `content` from its first bytes, or None if
+/// it isn't a known binary format or an svg image
+pub fn sniff(content: &[u8]) -> Option<&'static str> {
+    let mime = match content {
+        [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', ..] => "image/png",
+        [0xff, 0xd8, 0xff, ..] => "image/jpeg",
+        [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => "image/gif",
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => "image/webp",
+        [0x00, 0x01, 0x00, 0x00, ..] | [b't', b'r', b'u', b'e', ..] => "font/ttf",
+        [b'O', b'T', b'T', b'O', ..] => "font/otf",
+        [b'w', b'O', b'F', b'F', ..] => "font/woff",
+        [b'w', b'O', b'F', b'2', ..] => "font/woff2",
+        [b'I', b'D', b'3', ..] => "audio/mpeg",
+        [0xff, 0xfb | 0xfa | 0xf3 | 0xf2, ..] => "audio/mpeg",
+        [_, _, _, _, b'f', b't', b'y', b'p', ..] => "audio/mp4",
+        [b'O', b'g', b'g', b'S', ..] => "audio/ogg",
+        [b'%', b'P', b'D', b'F', ..] => "application/pdf",
+        _ => return sniff_svg(content),
+    };
+    Some(mime)
+}
+
+/// Detects the svg images, with the root element in the first kilobyte
+fn sniff_svg(content: &[u8]) -> Option<&'static str> {
+    let start = &content[..content.len().min(1024)];
+    let start = String::from_utf8_lossy(start);
+    let root = start
+        .split('<')
+        .skip(1)
+        .find(|tag| !tag.starts_with(['?', '!']))?;
+    let name = root
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or_default();
+    match name.rsplit(':').next() {
+        Some("svg") => Some("image/svg+xml"),
+        _ => None,
+    }
+}
+
+/// Returns true if the media types `a` and `b` are the same format,
+/// ignoring the parameters and the legacy names, like "image/jpg" for
+/// "image/jpeg" or "application/vnd.ms-opentype" for "font/otf"
+pub fn same_type(a: &str, b: &str) -> bool {
+    canonical(a) == canonical(b)
+}
+
+fn canonical(mime: &str) -> String {
+    let mime = mime
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let canonical = match mime.as_str() {
+        "image/jpg" | "image/pjpeg" => "image/jpeg",
+        // truetype and opentype files are both sfnt fonts
+        "font/ttf"
+        | "font/otf"
+        | "font/sfnt"
+        | "application/font-sfnt"
+        | "application/x-font-ttf"
+        | "application/x-font-truetype"
+        | "application/x-font-opentype"
+        | "application/vnd.ms-opentype" => "font/sfnt",
+        "application/font-woff" | "application/x-font-woff" => "font/woff",
+        "application/font-woff2" => "font/woff2",
+        "audio/mp3" | "audio/x-mpeg" | "audio/mpeg3" => "audio/mpeg",
+        "audio/x-m4a" | "audio/m4a" | "video/mp4" | "audio/aac" => "audio/mp4",
+        "application/ogg" | "audio/opus" | "audio/vorbis" => "audio/ogg",
+        _ => return mime,
+    };
+    canonical.to_string()
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the media type of the resource `id` detected from its
+    /// content, or None if it can't be detected. See `media::sniff`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// assert_eq!(Some("image/png"), doc.sniff_media_type("portada.png").unwrap());
+    /// assert_eq!(None, doc.sniff_media_type("stylesheet.css").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the resource isn't in the manifest or can't be
+    /// read.
+    pub fn sniff_media_type(&mut self, id: &str) -> Result<Option<&'static str>, EpubError> {
+        let content = self.get_resource(id)?;
+        Ok(sniff(&content))
+    }
+
+    /// Returns the resources whose content doesn't match the media type
+    /// declared in the manifest, sorted by path. The resources that can't
+    /// be read or whose type can't be detected are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// assert!(doc.mismatches().is_empty());
+    ///
+    /// doc.modify_entry("OEBPS/Images/cc.png", b"\xff\xd8\xff\xe0 a jpeg").unwrap();
+    /// let mismatches = doc.mismatches();
+    /// assert_eq!("cc.png", mismatches[0].id);
+    /// assert_eq!("image/png", mismatches[0].declared);
+    /// assert_eq!("image/jpeg", mismatches[0].sniffed);
+    /// ```
+    pub fn mismatches(&mut self) -> Vec<MediaTypeMismatch> {
+        let mut resources: Vec<(PathBuf, String, String)> = self
+            .resources
+            .iter()
+            .map(|(id, (path, mime))| (path.clone(), id.clone(), mime.clone()))
+            .collect();
+        resources.sort();
+        let mut mismatches = vec![];
+        for (path, id, declared) in resources {
+            let sniffed = match self.archive.get_entry(&path) {
+                Ok(content) => sniff(&content),
+                Err(_) => continue,
+            };
+            match sniffed {
+                Some(sniffed) if !same_type(&declared, sniffed) => {
+                    mismatches.push(MediaTypeMismatch {
+                        id,
+                        path,
+                        declared,
+                        sniffed,
+                    })
+                }
+                _ => {}
+            }
+        }
+        mismatches
+    }
+}
@@ -0,0 +1,178 @@
+//! Merges several epubs in a new one.
+
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::builder::{new_uuid, EpubBuilder, ROOT_BASE};
+use crate::doc::{EpubDoc, NavPoint, NCX_MIME};
+use crate::error::EpubError;
+use crate::navigation;
+
+/// Metadata names that can have several values in the merged epub, the
+/// values of every doc are added. For the rest, only the first value is
+/// used.
+const MERGED_METADATA: &[&str] = &["contributor", "creator", "language", "subject"];
+
+/// Metadata names copied from the first doc that has them
+const FIRST_METADATA: &[&str] = &[
+    "coverage",
+    "date",
+    "description",
+    "publisher",
+    "relation",
+    "rights",
+    "source",
+    "title",
+    "type",
+];
+
+impl EpubBuilder {
+    /// Appends the content of `doc` to the epub, to create an omnibus.
+    ///
+    /// The spine items are added at the end of the spine, and every other
+    /// resource to the manifest, except the navigation documents and the
+    /// manifest items missing in the archive. If a
+    /// path is already in use, every resource of the doc is moved to a
+    /// "book-N" directory, so the references between them still work.
+    ///
+    /// The doc table of content is added as a new entry with the doc
+    /// title. Creators, contributors, languages and subjects of every doc
+    /// are added, the rest of metadata is only copied if it's not set yet.
+    /// A new identifier is generated if there's none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::builder::EpubBuilder;
+    /// use epub::doc::EpubDoc;
+    ///
+    /// let mut first = EpubDoc::new("test.epub").unwrap();
+    /// let mut second = EpubDoc::new("test.epub").unwrap();
+    /// let mut builder = EpubBuilder::new();
+    /// builder.metadata("title", "Omnibus");
+    /// builder.append_doc(&mut first).unwrap();
+    /// builder.append_doc(&mut second).unwrap();
+    ///
+    /// let doc = builder.build().unwrap().into_doc().unwrap();
+    /// assert_eq!(34, doc.spine.len());
+    /// assert_eq!("Omnibus", doc.mdata("title").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some resource can't be read.
+    pub fn append_doc<R: Read + Seek>(
+        &mut self,
+        doc: &mut EpubDoc<R>,
+    ) -> Result<&mut Self, EpubError> {
+        self.merge_metadata(doc);
+
+        let nav = doc.get_nav_path();
+        let mut resources: Vec<(PathBuf, String)> = doc
+            .resources
+            .values()
+            .filter(|(path, mime)| Some(path) != nav.as_ref() && mime != NCX_MIME)
+            .cloned()
+            .collect();
+        resources.sort();
+
+        let collides = resources
+            .iter()
+            .any(|(path, _)| self.has_path(&doc.relative_to_root(path)));
+        let mut prefix = PathBuf::new();
+        if collides {
+            let mut n = 1;
+            while self.has_path(&prefix) {
+                prefix = PathBuf::from(format!("book-{}", n));
+                n += 1;
+            }
+        }
+
+        let spine: Vec<(PathBuf, String)> = doc
+            .spine
+            .iter()
+            .filter_map(|item| doc.resources.get(&item.idref))
+            .filter(|res| resources.contains(res))
+            .cloned()
+            .collect();
+        let mut documents = vec![];
+        for (path, mime) in spine.iter() {
+            if let Some(content) = read_resource(doc, path)? {
+                self.add_spine_resource(&prefix.join(doc.relative_to_root(path)), content, mime);
+                documents.push(path);
+            }
+        }
+        for (path, mime) in resources.iter() {
+            if spine.iter().any(|(p, _)| p == path) {
+                continue;
+            }
+            if let Some(content) = read_resource(doc, path)? {
+                self.add_resource(prefix.join(doc.relative_to_root(path)), content, mime);
+            }
+        }
+
+        let relocate = |content: &Path| {
+            Path::new(ROOT_BASE)
+                .join(&prefix)
+                .join(doc.relative_to_root(content))
+        };
+        let first = match documents.first() {
+            Some(path) => relocate(path),
+            None => return Ok(self),
+        };
+        let entry = NavPoint {
+            label: doc.mdata("title").unwrap_or_default().trim().to_string(),
+            content: first,
+            children: relocate_toc(&doc.toc, &relocate),
+            play_order: 0,
+        };
+        self.toc.push(entry);
+        navigation::renumber(&mut self.toc, &mut 0);
+        Ok(self)
+    }
+
+    fn merge_metadata<R: Read + Seek>(&mut self, doc: &EpubDoc<R>) {
+        if self.get_metadata("identifier").is_none() {
+            self.metadata("identifier", &format!("urn:uuid:{}", new_uuid()));
+        }
+        for name in MERGED_METADATA.iter() {
+            for value in doc.metadata.get(*name).into_iter().flatten() {
+                if !self.has_metadata(name, value) {
+                    self.metadata(name, value);
+                }
+            }
+        }
+        for name in FIRST_METADATA.iter() {
+            if self.get_metadata(name).is_some() {
+                continue;
+            }
+            if let Some(value) = doc.mdata(name) {
+                self.metadata(name, &value);
+            }
+        }
+    }
+}
+
+/// Reads the resource content, or `None` if the manifest item doesn't
+/// exist in the archive
+fn read_resource<R: Read + Seek>(
+    doc: &mut EpubDoc<R>,
+    path: &Path,
+) -> Result<Option<Vec<u8>>, EpubError> {
+    match doc.archive.get_entry(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(EpubError::ResourceNotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn relocate_toc(toc: &[NavPoint], relocate: &dyn Fn(&Path) -> PathBuf) -> Vec<NavPoint> {
+    toc.iter()
+        .map(|nav| NavPoint {
+            label: nav.label.clone(),
+            content: relocate(&nav.content),
+            children: relocate_toc(&nav.children, relocate),
+            play_order: 0,
+        })
+        .collect()
+}
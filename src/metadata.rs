@@ -0,0 +1,1230 @@
+//! Structured model of the package metadata.
+//!
+//! `EpubDoc::metadata` flattens the metadata in lists of strings by name.
+//! The items here keep the relationships instead: each `MetadataItem` has
+//! the `meta` elements that refine it, like the role or the sort name of a
+//! creator, linked to that specific element.
+//!
+//! EPUB2 attributes of the dc elements, like `opf:role` or `opf:file-as`,
+//! are read as refinements too, so the same code works for both versions.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::builder::{DC_ELEMENTS, DC_NS};
+use crate::href;
+use crate::xmlutils::XMLNode;
+
+/// Attributes of the metadata elements that aren't refinements
+const ITEM_ATTRS: &[&str] = &[
+    "id", "lang", "dir", "scheme", "refines", "property", "name", "content",
+];
+
+/// A metadata element of the package document, with its refinements
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataItem {
+    /// The id attribute of the element
+    pub id: Option<String>,
+    /// The dc element name, like "title" or "creator", or the meta
+    /// property, like "dcterms:modified" or "role"
+    pub property: String,
+    /// The text of the element, the content of EPUB2 `meta` elements or the
+    /// href of `dcterms:conformsTo` links
+    pub value: String,
+    /// The `xml:lang` attribute
+    pub lang: Option<String>,
+    /// The scheme of the value, like "marc:relators" or "ISBN"
+    pub scheme: Option<String>,
+    /// The elements refining this one, in document order
+    pub refinements: Vec<MetadataItem>,
+}
+
+impl MetadataItem {
+    /// Returns the first refinement with this `property`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let creator = doc.mdata_item("creator").unwrap();
+    /// assert_eq!("Daniel Garcia", creator.value);
+    /// assert_eq!("aut", creator.refinement("role").unwrap().value);
+    /// assert_eq!(Some("Garcia, Daniel"), creator.refined_value("file-as"));
+    /// ```
+    pub fn refinement(&self, property: &str) -> Option<&MetadataItem> {
+        self.refinements.iter().find(|r| r.property == property)
+    }
+
+    /// Returns the value of the first refinement with this `property`
+    pub fn refined_value(&self, property: &str) -> Option<&str> {
+        self.refinement(property).map(|r| r.value.as_str())
+    }
+
+    /// Returns the value in every language: the value of the element and
+    /// its `alternate-script` refinements, with their language tags
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let variants = doc.mdata_item("title").unwrap().variants();
+    /// assert_eq!("Todo es mío", variants[0].value);
+    /// ```
+    pub fn variants(&self) -> Vec<LangString> {
+        let mut variants = vec![LangString {
+            value: self.value.clone(),
+            lang: self.lang.clone(),
+        }];
+        variants.extend(self.alternate_scripts());
+        variants
+    }
+
+    /// Returns the `alternate-script` refinements, the value written in
+    /// other scripts or languages, like a romanized title
+    pub fn alternate_scripts(&self) -> Vec<LangString> {
+        self.refinements
+            .iter()
+            .filter(|r| r.property == "alternate-script")
+            .map(|r| LangString {
+                value: r.value.clone(),
+                lang: r.lang.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A text with its language tag
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LangString {
+    pub value: String,
+    /// The language tag, like "en" or "ja-Latn", if it's known
+    pub lang: Option<String>,
+}
+
+/// Role of a creator or contributor, from the MARC relator codes
+///
+/// See <https://www.loc.gov/marc/relators/relaterm.html> for the full list
+/// of codes, the ones not listed here are kept in `Other`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// aut
+    Author,
+    /// edt
+    Editor,
+    /// ill
+    Illustrator,
+    /// trl
+    Translator,
+    /// nrt
+    Narrator,
+    /// ctb
+    Contributor,
+    /// aui, author of the introduction
+    IntroductionAuthor,
+    /// bkp
+    BookProducer,
+    /// cov
+    CoverDesigner,
+    /// pbl
+    Publisher,
+    /// pht
+    Photographer,
+    /// adp
+    Adapter,
+    /// ann
+    Annotator,
+    /// Any other code, in lowercase
+    Other(String),
+}
+
+impl Role {
+    /// Returns the role of the relator `code`, ignoring the case
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::metadata::Role;
+    ///
+    /// assert_eq!(Role::Translator, Role::from_code("trl"));
+    /// assert_eq!(Role::Author, Role::from_code("AUT"));
+    /// assert_eq!(Role::Other(String::from("lyr")), Role::from_code("lyr"));
+    /// assert_eq!("nrt", Role::Narrator.code());
+    /// ```
+    pub fn from_code(code: &str) -> Role {
+        match code.trim().to_lowercase().as_str() {
+            "aut" => Role::Author,
+            "edt" => Role::Editor,
+            "ill" => Role::Illustrator,
+            "trl" => Role::Translator,
+            "nrt" => Role::Narrator,
+            "ctb" => Role::Contributor,
+            "aui" => Role::IntroductionAuthor,
+            "bkp" => Role::BookProducer,
+            "cov" => Role::CoverDesigner,
+            "pbl" => Role::Publisher,
+            "pht" => Role::Photographer,
+            "adp" => Role::Adapter,
+            "ann" => Role::Annotator,
+            code => Role::Other(code.to_string()),
+        }
+    }
+
+    /// Returns the relator code of the role
+    pub fn code(&self) -> &str {
+        match self {
+            Role::Author => "aut",
+            Role::Editor => "edt",
+            Role::Illustrator => "ill",
+            Role::Translator => "trl",
+            Role::Narrator => "nrt",
+            Role::Contributor => "ctb",
+            Role::IntroductionAuthor => "aui",
+            Role::BookProducer => "bkp",
+            Role::CoverDesigner => "cov",
+            Role::Publisher => "pbl",
+            Role::Photographer => "pht",
+            Role::Adapter => "adp",
+            Role::Annotator => "ann",
+            Role::Other(code) => code,
+        }
+    }
+}
+
+/// A creator or contributor of the publication
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Contributor {
+    /// The name, as it's displayed
+    pub name: String,
+    /// The name for sorting, like "Doe, Jane"
+    pub file_as: Option<String>,
+    /// The roles, empty if the element doesn't have any
+    pub roles: Vec<Role>,
+    /// The language of the name
+    pub lang: Option<String>,
+    /// The name written in other scripts, see
+    /// `MetadataItem::alternate_scripts`
+    pub alternate_scripts: Vec<LangString>,
+}
+
+impl Contributor {
+    /// Reads the contributor from a `creator` or `contributor` item. Only
+    /// the roles without scheme or with the "marc:relators" scheme are
+    /// read.
+    pub fn from_item(item: &MetadataItem) -> Contributor {
+        let roles = item
+            .refinements
+            .iter()
+            .filter(|r| r.property == "role")
+            .filter(|r| matches!(r.scheme.as_deref(), None | Some("marc:relators")))
+            .map(|r| Role::from_code(&r.value))
+            .collect();
+        Contributor {
+            name: item.value.clone(),
+            file_as: item.refined_value("file-as").map(String::from),
+            roles,
+            lang: item.lang.clone(),
+            alternate_scripts: item.alternate_scripts(),
+        }
+    }
+
+    /// Returns true if the contributor has this `role`
+    pub fn has_role(&self, role: &Role) -> bool {
+        self.roles.contains(role)
+    }
+}
+
+/// Type of a title, from the `title-type` refinement
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TitleType {
+    Main,
+    Subtitle,
+    Short,
+    Collection,
+    Edition,
+    Expanded,
+    /// Any other type, in lowercase
+    Other(String),
+}
+
+impl TitleType {
+    /// Returns the title type by its name in the `title-type` refinement
+    pub fn from_name(name: &str) -> TitleType {
+        match name.trim().to_lowercase().as_str() {
+            "main" => TitleType::Main,
+            "subtitle" => TitleType::Subtitle,
+            "short" => TitleType::Short,
+            "collection" => TitleType::Collection,
+            "edition" => TitleType::Edition,
+            "expanded" => TitleType::Expanded,
+            name => TitleType::Other(name.to_string()),
+        }
+    }
+}
+
+/// A title of the publication
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Title {
+    pub value: String,
+    /// The language of the title
+    pub lang: Option<String>,
+    /// The `title-type` refinement, if any
+    pub title_type: Option<TitleType>,
+    /// The title for sorting, from the `file-as` refinement
+    pub file_as: Option<String>,
+    /// The position to display it, from the `display-seq` refinement
+    pub display_seq: Option<usize>,
+}
+
+impl Title {
+    /// Reads the title from a `title` item
+    pub fn from_item(item: &MetadataItem) -> Title {
+        Title {
+            value: item.value.clone(),
+            lang: item.lang.clone(),
+            title_type: item.refined_value("title-type").map(TitleType::from_name),
+            file_as: item.refined_value("file-as").map(String::from),
+            display_seq: item
+                .refined_value("display-seq")
+                .and_then(|n| n.trim().parse().ok()),
+        }
+    }
+}
+
+/// A series or collection the publication belongs to
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    pub name: String,
+    /// The position in the series, like 2 or 1.5
+    pub position: Option<f64>,
+}
+
+/// Reads the series from the `belongs-to-collection` items, with their
+/// `group-position`, and from the `calibre:series` and
+/// `calibre:series_index` metas, if it's not already there
+pub(crate) fn series(items: &[MetadataItem]) -> Vec<Series> {
+    let position = |n: &str| n.trim().parse::<f64>().ok();
+    let mut series: Vec<Series> = items
+        .iter()
+        .filter(|item| item.property == "belongs-to-collection")
+        .map(|item| Series {
+            name: item.value.clone(),
+            position: item.refined_value("group-position").and_then(position),
+        })
+        .collect();
+
+    let calibre = items.iter().find(|item| item.property == "calibre:series");
+    if let Some(calibre) = calibre {
+        if !series.iter().any(|s| s.name == calibre.value) {
+            let index = items
+                .iter()
+                .find(|item| item.property == "calibre:series_index");
+            series.push(Series {
+                name: calibre.value.clone(),
+                position: index.and_then(|i| position(&i.value)),
+            });
+        }
+    }
+    series
+}
+
+/// A date of the metadata, in the W3C date format. The date can be
+/// partial, only the year or the year and the month.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+    /// The hours, minutes and seconds, if the date has a time
+    pub time: Option<(u32, u32, u32)>,
+    /// The offset from UTC in minutes, if the time has a time zone
+    pub utc_offset: Option<i32>,
+}
+
+impl Date {
+    /// Parses a date like "2020", "2020-05", "2020-05-17" or
+    /// "2020-05-17T10:30:00Z". Returns None if it isn't a valid date.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::metadata::Date;
+    ///
+    /// let date = Date::parse("2015-08-10T18:12:03Z").unwrap();
+    /// assert_eq!((2015, Some(8), Some(10)), (date.year, date.month, date.day));
+    /// assert_eq!(Some((18, 12, 3)), date.time);
+    /// assert_eq!(Some(0), date.utc_offset);
+    ///
+    /// let date = Date::parse("1915-10").unwrap();
+    /// assert_eq!((1915, Some(10), None), (date.year, date.month, date.day));
+    /// assert!(Date::parse("2020-13-01").is_none());
+    /// ```
+    pub fn parse(text: &str) -> Option<Date> {
+        let text = text.trim();
+        let (date, time) = match text.find('T') {
+            Some(i) => (&text[..i], Some(&text[i + 1..])),
+            None => (text, None),
+        };
+        let mut parts = date.split('-');
+        let year = parts.next().filter(|y| y.len() == 4)?.parse().ok()?;
+        let mut number = |max: u32| -> Option<Option<u32>> {
+            match parts.next() {
+                None => Some(None),
+                Some(p) if p.len() == 2 => match p.parse() {
+                    Ok(n) if n >= 1 && n <= max => Some(Some(n)),
+                    _ => None,
+                },
+                Some(_) => None,
+            }
+        };
+        let month = number(12)?;
+        let day = number(31)?;
+        if parts.next().is_some() {
+            return None;
+        }
+        let mut date = Date {
+            year,
+            month,
+            day,
+            time: None,
+            utc_offset: None,
+        };
+        if let Some(time) = time {
+            day?;
+            let (time, offset) = split_offset(time)?;
+            date.time = Some(parse_time(time)?);
+            date.utc_offset = offset;
+        }
+        Some(date)
+    }
+
+    /// Returns the date as a system time. The missing month and day are
+    /// the first ones, the missing time is midnight and the missing time
+    /// zone is UTC.
+    pub fn to_system_time(&self) -> SystemTime {
+        let days = days_from_civil(self.year, self.month.unwrap_or(1), self.day.unwrap_or(1));
+        let (h, m, s) = self.time.unwrap_or((0, 0, 0));
+        let secs = days * 86400 + i64::from(h * 3600 + m * 60 + s)
+            - i64::from(self.utc_offset.unwrap_or(0)) * 60;
+        if secs >= 0 {
+            UNIX_EPOCH + Duration::from_secs(secs as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+        }
+    }
+}
+
+/// Splits the time zone of the time, returning the offset in minutes
+fn split_offset(time: &str) -> Option<(&str, Option<i32>)> {
+    if let Some(time) = time.strip_suffix('Z') {
+        return Some((time, Some(0)));
+    }
+    match time.rfind(['+', '-']) {
+        Some(i) => {
+            let (h, m) = time[i + 1..].split_once(':')?;
+            let (h, m): (i32, i32) = (h.parse().ok()?, m.parse().ok()?);
+            let sign = if time[i..].starts_with('-') { -1 } else { 1 };
+            Some((&time[..i], Some(sign * (h * 60 + m))))
+        }
+        None => Some((time, None)),
+    }
+}
+
+/// Parses "hh:mm", "hh:mm:ss" or "hh:mm:ss.fff", ignoring the fraction
+fn parse_time(time: &str) -> Option<(u32, u32, u32)> {
+    let time = time.split('.').next().unwrap_or_default();
+    let mut parts = time.split(':').map(|p| p.parse::<u32>().ok());
+    let h = parts.next()??;
+    let m = parts.next()??;
+    let s = parts.next().unwrap_or(Some(0))?;
+    if parts.next().is_some() || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    Some((h, m, s))
+}
+
+/// Days since the unix epoch of the civil date, from Howard Hinnant's
+/// algorithm
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let y = i64::from(year) - i64::from(month <= 2);
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = i64::from(month);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// What happened at a date of the metadata
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DateEvent {
+    Publication,
+    Creation,
+    Modification,
+    /// Any other EPUB2 `opf:event`, in lowercase
+    Other(String),
+}
+
+impl DateEvent {
+    /// Returns the event by the name of the EPUB2 `opf:event` attribute
+    pub fn from_name(name: &str) -> DateEvent {
+        match name.trim().to_lowercase().as_str() {
+            "publication" => DateEvent::Publication,
+            "creation" => DateEvent::Creation,
+            "modification" => DateEvent::Modification,
+            name => DateEvent::Other(name.to_string()),
+        }
+    }
+}
+
+/// A date of the metadata and its event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventDate {
+    /// The parsed date, None if the value isn't a valid date
+    pub date: Option<Date>,
+    pub event: DateEvent,
+    /// The value as it's written
+    pub value: String,
+}
+
+/// Reads the `date` items, with the EPUB2 `opf:event` of each one or the
+/// publication event, and the `dcterms:modified` items, with the
+/// modification event
+pub(crate) fn dates(items: &[MetadataItem]) -> Vec<EventDate> {
+    items
+        .iter()
+        .filter_map(|item| {
+            let event = match item.property.as_str() {
+                "date" => item
+                    .refined_value("event")
+                    .map(DateEvent::from_name)
+                    .unwrap_or(DateEvent::Publication),
+                "dcterms:modified" => DateEvent::Modification,
+                _ => return None,
+            };
+            Some(EventDate {
+                date: Date::parse(&item.value),
+                event,
+                value: item.value.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The schema.org accessibility metadata of the publication
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessibilityInfo {
+    /// The access modes of the content, like "textual" or "visual"
+    pub access_modes: Vec<String>,
+    /// Each set of access modes sufficient to read the whole publication,
+    /// like `["textual"]`
+    pub access_modes_sufficient: Vec<Vec<String>>,
+    /// The accessibility features, like "alternativeText" or
+    /// "tableOfContents"
+    pub features: Vec<String>,
+    /// The hazards, like "flashing", or "none"
+    pub hazards: Vec<String>,
+    /// The human readable summary
+    pub summary: Option<String>,
+    /// The specifications the publication conforms to, like
+    /// "EPUB Accessibility 1.1 - WCAG 2.1 Level AA" or the url of the
+    /// conformance level
+    pub conforms_to: Vec<String>,
+    /// The party that certified the conformance
+    pub certified_by: Option<String>,
+    /// The credential of the certifier
+    pub certifier_credential: Option<String>,
+}
+
+impl AccessibilityInfo {
+    /// Reads the accessibility metadata from the metadata items. The EPUB2
+    /// metas, like `<meta name="schema:accessMode" content="textual"/>`,
+    /// are read too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let a11y = doc.accessibility();
+    /// // test.epub doesn't declare any accessibility metadata
+    /// assert!(a11y.is_empty());
+    /// assert_eq!(None, a11y.wcag_level());
+    /// ```
+    pub fn from_items(items: &[MetadataItem]) -> AccessibilityInfo {
+        let mut info = AccessibilityInfo::default();
+        for item in items.iter() {
+            let value = item.value.clone();
+            if value.is_empty() {
+                continue;
+            }
+            let property = item.property.as_str();
+            let name = property.strip_prefix("schema:").unwrap_or(property);
+            match name {
+                "accessMode" => info.access_modes.push(value),
+                "accessModeSufficient" => info.access_modes_sufficient.push(
+                    value
+                        .split(',')
+                        .map(|m| m.trim().to_string())
+                        .filter(|m| !m.is_empty())
+                        .collect(),
+                ),
+                "accessibilityFeature" => info.features.push(value),
+                "accessibilityHazard" => info.hazards.push(value),
+                "accessibilitySummary" => {
+                    info.summary.get_or_insert(value);
+                }
+                "dcterms:conformsTo" => {
+                    for r in item.refinements.iter() {
+                        info.read_certification(r);
+                    }
+                    info.conforms_to.push(value);
+                }
+                _ => info.read_certification(item),
+            }
+        }
+        info
+    }
+
+    fn read_certification(&mut self, item: &MetadataItem) {
+        match item.property.as_str() {
+            "a11y:certifiedBy" => {
+                self.certified_by.get_or_insert(item.value.clone());
+                for r in item.refinements.iter() {
+                    self.read_certification(r);
+                }
+            }
+            "a11y:certifierCredential" => {
+                self.certifier_credential.get_or_insert(item.value.clone());
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns true if the publication doesn't declare any accessibility
+    /// metadata
+    pub fn is_empty(&self) -> bool {
+        *self == AccessibilityInfo::default()
+    }
+
+    /// Returns true if the publication declares the accessibility `feature`
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Returns true if the publication declares that it has no hazards
+    pub fn hazard_free(&self) -> bool {
+        self.hazards.iter().any(|h| h == "none")
+            || [
+                "noFlashingHazard",
+                "noMotionSimulationHazard",
+                "noSoundHazard",
+            ]
+            .iter()
+            .all(|h| self.hazards.iter().any(|hazard| hazard == h))
+    }
+
+    /// Returns the WCAG level the publication conforms to, "A", "AA" or
+    /// "AAA", from the EPUB Accessibility conformance statement
+    pub fn wcag_level(&self) -> Option<&'static str> {
+        self.conforms_to
+            .iter()
+            .filter_map(|c| {
+                let c = c.to_lowercase();
+                ["aaa", "aa", "a"].iter().copied().find(|level| {
+                    c.ends_with(&format!("#wcag-{}", level))
+                        || c.ends_with(&format!("level {}", level))
+                })
+            })
+            .map(|level| match level {
+                "aaa" => "AAA",
+                "aa" => "AA",
+                _ => "A",
+            })
+            .next()
+    }
+}
+
+/// Scheme of an identifier
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IdentifierScheme {
+    Isbn,
+    Uuid,
+    Doi,
+    /// Other urn, like "urn:calibre:1"
+    Urn,
+    /// The scheme declared in the package, like "AMAZON", for values
+    /// without a known form
+    Other(String),
+    /// No scheme declared or detected
+    Unknown,
+}
+
+/// An identifier of the publication, with its scheme
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identifier {
+    pub scheme: IdentifierScheme,
+    /// The value without the scheme prefix, like "urn:uuid:". ISBNs are only
+    /// the digits, without hyphens or spaces.
+    pub value: String,
+}
+
+impl Identifier {
+    /// Reads the identifier from an `identifier` item. The scheme is
+    /// detected from the value, the EPUB2 `opf:scheme` or the EPUB3
+    /// `identifier-type` refinement.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// use epub::metadata::IdentifierScheme;
+    ///
+    /// # let doc = EpubDoc::new("test.epub").unwrap();
+    /// let id = &doc.identifiers()[0];
+    /// assert_eq!(IdentifierScheme::Uuid, id.scheme);
+    /// assert_eq!("09132750-3601-4d19-b3a4-55fdf8639849", id.value);
+    /// ```
+    pub fn from_item(item: &MetadataItem) -> Identifier {
+        let value = item.value.trim();
+        let lower = value.to_lowercase();
+        for (prefix, scheme) in [
+            ("urn:isbn:", IdentifierScheme::Isbn),
+            ("isbn:", IdentifierScheme::Isbn),
+            ("urn:uuid:", IdentifierScheme::Uuid),
+            ("uuid:", IdentifierScheme::Uuid),
+            ("urn:doi:", IdentifierScheme::Doi),
+            ("doi:", IdentifierScheme::Doi),
+            ("https://doi.org/", IdentifierScheme::Doi),
+            ("http://dx.doi.org/", IdentifierScheme::Doi),
+        ]
+        .iter()
+        {
+            if lower.starts_with(prefix) {
+                return Identifier::new(scheme.clone(), &value[prefix.len()..]);
+            }
+        }
+        if lower.starts_with("urn:") {
+            return Identifier::new(IdentifierScheme::Urn, value);
+        }
+
+        // the declared scheme
+        let declared = match (item.scheme.as_deref(), item.refinement("identifier-type")) {
+            (_, Some(t)) if t.scheme.as_deref() == Some("onix:codelist5") => match t.value.trim() {
+                "02" | "03" | "15" => Some(IdentifierScheme::Isbn),
+                "06" => Some(IdentifierScheme::Doi),
+                _ => None,
+            },
+            (_, Some(t)) => Some(declared_scheme(&t.value)),
+            (Some(scheme), None) => Some(declared_scheme(scheme)),
+            (None, None) => None,
+        };
+        match declared {
+            Some(scheme) => Identifier::new(scheme, value),
+            None if is_valid_isbn(value) => Identifier::new(IdentifierScheme::Isbn, value),
+            None if is_uuid(value) => Identifier::new(IdentifierScheme::Uuid, value),
+            None if value.starts_with("10.") && value.contains('/') => {
+                Identifier::new(IdentifierScheme::Doi, value)
+            }
+            None => Identifier::new(IdentifierScheme::Unknown, value),
+        }
+    }
+
+    fn new(scheme: IdentifierScheme, value: &str) -> Identifier {
+        let value = match scheme {
+            IdentifierScheme::Isbn => isbn_digits(value),
+            _ => value.trim().to_string(),
+        };
+        Identifier { scheme, value }
+    }
+
+    /// Returns the ISBN-13 form of an ISBN identifier, converting the
+    /// ISBN-10 ones. Returns None if it isn't an ISBN or the checksum isn't
+    /// valid.
+    pub fn isbn13(&self) -> Option<String> {
+        match self.scheme {
+            IdentifierScheme::Isbn => isbn13(&self.value),
+            _ => None,
+        }
+    }
+}
+
+fn declared_scheme(scheme: &str) -> IdentifierScheme {
+    match scheme.trim().to_lowercase().as_str() {
+        "isbn" | "isbn-10" | "isbn-13" | "isbn10" | "isbn13" => IdentifierScheme::Isbn,
+        "uuid" => IdentifierScheme::Uuid,
+        "doi" => IdentifierScheme::Doi,
+        "urn" => IdentifierScheme::Urn,
+        _ => IdentifierScheme::Other(scheme.trim().to_string()),
+    }
+}
+
+/// Digits of the ISBN, and the final X of the ISBN-10, without hyphens or
+/// spaces
+fn isbn_digits(isbn: &str) -> String {
+    isbn.chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Returns true if `isbn` is an ISBN-10 or ISBN-13 with a valid checksum.
+/// Hyphens and spaces are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use epub::metadata::is_valid_isbn;
+///
+/// assert!(is_valid_isbn("978-0-306-40615-7"));
+/// assert!(is_valid_isbn("0-306-40615-2"));
+/// assert!(!is_valid_isbn("978-0-306-40615-8"));
+/// ```
+pub fn is_valid_isbn(isbn: &str) -> bool {
+    if isbn
+        .chars()
+        .any(|c| !(c.is_ascii_digit() || matches!(c, '-' | ' ' | 'X' | 'x')))
+    {
+        return false;
+    }
+    let digits = isbn_digits(isbn);
+    let values: Vec<u32> = digits
+        .chars()
+        .map(|c| c.to_digit(10).unwrap_or(10))
+        .collect();
+    match values.len() {
+        10 => {
+            // only the check digit can be X
+            if values[..9].contains(&10) {
+                return false;
+            }
+            let sum: u32 = values.iter().zip((1..=10).rev()).map(|(v, w)| v * w).sum();
+            sum % 11 == 0
+        }
+        13 => {
+            if values.contains(&10) {
+                return false;
+            }
+            let sum: u32 = values
+                .iter()
+                .enumerate()
+                .map(|(i, v)| if i % 2 == 0 { *v } else { v * 3 })
+                .sum();
+            sum % 10 == 0
+        }
+        _ => false,
+    }
+}
+
+/// Returns the ISBN-13 of a valid ISBN-10 or ISBN-13, only the digits.
+///
+/// # Examples
+///
+/// ```
+/// use epub::metadata::isbn13;
+///
+/// assert_eq!(Some(String::from("9780306406157")), isbn13("0-306-40615-2"));
+/// assert_eq!(None, isbn13("0-306-40615-3"));
+/// ```
+pub fn isbn13(isbn: &str) -> Option<String> {
+    if !is_valid_isbn(isbn) {
+        return None;
+    }
+    let digits = isbn_digits(isbn);
+    if digits.len() == 13 {
+        return Some(digits);
+    }
+    let mut isbn = format!("978{}", &digits[..9]);
+    let sum: u32 = isbn
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, v)| if i % 2 == 0 { v } else { v * 3 })
+        .sum();
+    isbn.push_str(&((10 - sum % 10) % 10).to_string());
+    Some(isbn)
+}
+
+fn is_uuid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups
+        .iter()
+        .map(|g| g.len())
+        .eq([8, 4, 4, 4, 12].iter().copied())
+        && groups
+            .iter()
+            .all(|g| g.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// A `link` element of the package metadata, like an ONIX record or a
+/// preview
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataLink {
+    /// The id attribute of the element
+    pub id: Option<String>,
+    /// The relationships, like "onix-record" or "dcterms:conformsTo"
+    pub rel: Vec<String>,
+    /// The href as written in the package document
+    pub href: String,
+    /// The `media-type` attribute
+    pub media_type: Option<String>,
+    /// The `hreflang` attribute
+    pub hreflang: Option<String>,
+    /// The `properties` attribute, like "onix"
+    pub properties: Vec<String>,
+    /// The id of the element the link refines, without the `#`
+    pub refines: Option<String>,
+    /// The full path in the archive, for the links to files of the epub
+    pub path: Option<PathBuf>,
+    /// The id of the manifest item, for the links to files of the epub
+    pub resource_id: Option<String>,
+}
+
+impl MetadataLink {
+    /// Returns true if the link has the relationship `rel`
+    pub fn has_rel(&self, rel: &str) -> bool {
+        self.rel.iter().any(|r| r == rel)
+    }
+}
+
+/// Reads the `link` elements of the metadata. The hrefs without a scheme
+/// are resolved from the package document `root_file`.
+pub(crate) fn parse_links(
+    metadata: &XMLNode,
+    max_items: usize,
+    root_file: &Path,
+) -> Vec<MetadataLink> {
+    metadata
+        .childs
+        .iter()
+        .take(max_items)
+        .filter_map(|r| {
+            let node = r.borrow();
+            if node.name.local_name != "link" {
+                return None;
+            }
+            let attr = |name: &str| node.get_attr(name).ok();
+            let href = attr("href")?;
+            let list = |name: &str| -> Vec<String> {
+                attr(name)
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(String::from)
+                    .collect()
+            };
+            let path = if has_scheme(&href) {
+                None
+            } else {
+                Some(href::resolve(root_file, &href))
+            };
+            Some(MetadataLink {
+                id: attr("id"),
+                rel: list("rel"),
+                media_type: attr("media-type"),
+                hreflang: attr("hreflang"),
+                properties: list("properties"),
+                refines: attr("refines").map(|r| r.trim_start_matches('#').to_string()),
+                path,
+                resource_id: None,
+                href,
+            })
+        })
+        .collect()
+}
+
+/// Returns true if the href is an absolute url, like "https://..." or
+/// "mailto:..."
+fn has_scheme(href: &str) -> bool {
+    match href.find(':') {
+        Some(i) => {
+            let scheme = &href[..i];
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+        }
+        None => false,
+    }
+}
+
+/// The prefixes reserved by EPUB3, usable without declaring them
+const RESERVED_PREFIXES: &[(&str, &str)] = &[
+    ("a11y", "http://www.idpf.org/epub/vocab/package/a11y/#"),
+    ("dcterms", "http://purl.org/dc/terms/"),
+    ("marc", "http://id.loc.gov/vocabulary/"),
+    ("media", "http://www.idpf.org/epub/vocab/overlays/#"),
+    ("msv", "http://www.idpf.org/epub/vocab/structure/magazine/#"),
+    (
+        "onix",
+        "http://www.editeur.org/ONIX/book/codelists/current.html#",
+    ),
+    (
+        "prism",
+        "http://www.prismstandard.org/specifications/3.0/PRISM_CV_Spec_3.0.htm#",
+    ),
+    ("rendition", "http://www.idpf.org/vocab/rendition/#"),
+    ("schema", "http://schema.org/"),
+    ("xsd", "http://www.w3.org/2001/XMLSchema#"),
+];
+
+/// The vocabulary of the meta properties without a prefix, like "role"
+pub const META_VOCAB: &str = "http://idpf.org/epub/vocab/package/meta/#";
+
+/// The prefixes of the package document: the reserved ones, and the ones
+/// declared in the `prefix` attribute of the package, which can override
+/// them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Prefixes {
+    prefixes: HashMap<String, String>,
+}
+
+impl Default for Prefixes {
+    fn default() -> Self {
+        Prefixes {
+            prefixes: RESERVED_PREFIXES
+                .iter()
+                .map(|(p, iri)| (p.to_string(), iri.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl Prefixes {
+    /// Reads the `prefix` attribute, a list of `prefix: iri` pairs. Returns
+    /// the prefixes and a warning for each malformed pair.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::metadata::Prefixes;
+    ///
+    /// let (prefixes, warnings) =
+    ///     Prefixes::parse("ibooks: http://vocabulary.itunes.apple.com/rdf/ibooks/vocabulary-extensions-1.0/");
+    /// assert!(warnings.is_empty());
+    /// assert_eq!(
+    ///     Some("http://vocabulary.itunes.apple.com/rdf/ibooks/vocabulary-extensions-1.0/version"),
+    ///     prefixes.expand("ibooks:version").as_deref()
+    /// );
+    /// assert_eq!(
+    ///     Some("http://www.idpf.org/vocab/rendition/#layout"),
+    ///     prefixes.expand("rendition:layout").as_deref()
+    /// );
+    /// ```
+    pub fn parse(attr: &str) -> (Prefixes, Vec<String>) {
+        let mut prefixes = Prefixes::default();
+        let mut warnings = vec![];
+        let mut tokens = attr.split_whitespace();
+        while let Some(token) = tokens.next() {
+            let prefix = match token.strip_suffix(':') {
+                Some(prefix) if !prefix.is_empty() && !prefix.contains(':') => prefix,
+                _ => {
+                    warnings.push(format!("malformed prefix declaration {}", token));
+                    continue;
+                }
+            };
+            match tokens.next() {
+                Some(iri) => {
+                    prefixes
+                        .prefixes
+                        .insert(prefix.to_string(), iri.to_string());
+                }
+                None => warnings.push(format!("the prefix {} doesn't have an iri", prefix)),
+            }
+        }
+        (prefixes, warnings)
+    }
+
+    /// Returns the iri of the `prefix`
+    pub fn iri(&self, prefix: &str) -> Option<&str> {
+        self.prefixes.get(prefix).map(|iri| iri.as_str())
+    }
+
+    /// Expands the metadata `property`, like "rendition:layout", to its full
+    /// iri. The dc elements expand to the Dublin Core namespace and the
+    /// properties without a prefix to `META_VOCAB`. Returns None if the
+    /// prefix isn't declared.
+    pub fn expand(&self, property: &str) -> Option<String> {
+        match property.find(':') {
+            Some(i) => {
+                let iri = self.iri(&property[..i])?;
+                Some(format!("{}{}", iri, &property[i + 1..]))
+            }
+            None if DC_ELEMENTS.contains(&property) => Some(format!("{}{}", DC_NS, property)),
+            None => Some(format!("{}{}", META_VOCAB, property)),
+        }
+    }
+}
+
+/// A metadata element before linking the refinements
+struct RawItem {
+    item: MetadataItem,
+    /// id of the refined element, without the `#`
+    refines: Option<String>,
+}
+
+/// Parses the first `max_items` childs of the `metadata` element. The
+/// elements without `xml:lang` get the `lang` of the package. Returns the
+/// items and the refinements pointing to missing elements.
+pub(crate) fn parse_items(
+    metadata: &XMLNode,
+    max_items: usize,
+    lang: Option<&str>,
+) -> (Vec<MetadataItem>, Vec<String>) {
+    let mut raw = vec![];
+    for r in metadata.childs.iter().take(max_items) {
+        let node = r.borrow();
+        let mut item = match raw_item(&node) {
+            Some(item) => item,
+            None => continue,
+        };
+        if item.item.lang.is_none() {
+            item.item.lang = lang.map(String::from);
+        }
+        // EPUB2 attributes, like opf:role, refine the element itself
+        if node.name.local_name != "meta" {
+            for attr in node.attrs.iter() {
+                let name = attr.name.local_name.as_str();
+                if ITEM_ATTRS.contains(&name) {
+                    continue;
+                }
+                item.item.refinements.push(MetadataItem {
+                    property: name.to_string(),
+                    value: attr.value.clone(),
+                    ..MetadataItem::default()
+                });
+            }
+        }
+        raw.push(item);
+    }
+
+    let ids: HashSet<&str> = raw.iter().filter_map(|r| r.item.id.as_deref()).collect();
+    let unresolved = raw
+        .iter()
+        .filter_map(|r| r.refines.as_deref())
+        .filter(|id| !ids.contains(id))
+        .map(|id| format!("the meta refines the missing element #{}", id))
+        .collect();
+
+    let mut used = vec![false; raw.len()];
+    let items = link(&raw, None, &mut used);
+    (items, unresolved)
+}
+
+fn raw_item(node: &XMLNode) -> Option<RawItem> {
+    let attr = |name: &str| node.get_attr(name).ok();
+    let text = node.text.clone().unwrap_or_default();
+    let (property, value) = if node.name.local_name == "meta" {
+        match (attr("property"), attr("name"), attr("content")) {
+            (Some(property), _, _) => (property, text),
+            (None, Some(name), Some(content)) => (name, content),
+            _ => return None,
+        }
+    } else if node.name.local_name == "link" {
+        // the conformance links of EPUB Accessibility 1.1 replace the
+        // dcterms:conformsTo metas
+        let rel = attr("rel").unwrap_or_default();
+        match attr("href") {
+            Some(href) if rel.split_whitespace().any(|r| r == "dcterms:conformsTo") => {
+                (String::from("dcterms:conformsTo"), href)
+            }
+            _ => return None,
+        }
+    } else {
+        (node.name.local_name.clone(), text)
+    };
+    Some(RawItem {
+        item: MetadataItem {
+            id: attr("id"),
+            property,
+            value: value.trim().to_string(),
+            lang: attr("lang"),
+            scheme: attr("scheme"),
+            refinements: vec![],
+        },
+        refines: attr("refines").map(|r| r.trim_start_matches('#').to_string()),
+    })
+}
+
+/// Returns the items refining the element with the id `parent`, or the
+/// top level items, with their own refinements. Each item is used once,
+/// so refinement cycles end.
+fn link(raw: &[RawItem], parent: Option<&str>, used: &mut [bool]) -> Vec<MetadataItem> {
+    let mut items = vec![];
+    for (i, r) in raw.iter().enumerate() {
+        if used[i] || r.refines.as_deref() != parent {
+            continue;
+        }
+        used[i] = true;
+        let mut item = r.item.clone();
+        if let Some(ref id) = item.id {
+            item.refinements.extend(link(raw, Some(id), used));
+        }
+        items.push(item);
+    }
+    items
+}
+
+/// The path of the container metadata file in the archive
+pub const CONTAINER_METADATA_FILE: &str = "META-INF/metadata.xml";
+
+/// The `META-INF/metadata.xml`, the metadata of the container, shared by
+/// its renditions or added by a vendor
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContainerMetadata {
+    /// the content of the file
+    pub raw: String,
+    /// the metadata elements, best-effort, read like the ones of the
+    /// package document. Empty if the content isn't xml.
+    pub items: Vec<MetadataItem>,
+}
+
+impl ContainerMetadata {
+    /// Parses the metadata.xml `raw` content, the items are the children
+    /// of its `metadata` element, or of the root
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::metadata::ContainerMetadata;
+    ///
+    /// let metadata = ContainerMetadata::parse(String::from(
+    ///     r#"<metadata xmlns="http://www.idpf.org/2013/metadata"
+    ///         xmlns:dc="http://purl.org/dc/elements/1.1/">
+    ///       <dc:identifier>urn:isbn:9780000000000</dc:identifier>
+    ///       <meta property="dcterms:modified">2020-01-01T00:00:00Z</meta>
+    ///     </metadata>"#,
+    /// ));
+    /// assert_eq!(Some("urn:isbn:9780000000000"), metadata.get("identifier"));
+    /// assert_eq!(Some("2020-01-01T00:00:00Z"), metadata.get("dcterms:modified"));
+    /// ```
+    pub fn parse(raw: String) -> ContainerMetadata {
+        let items = match crate::xmlutils::XMLReader::parse(raw.as_bytes()) {
+            Ok(root) => {
+                let root = root.borrow();
+                let lang = root.get_attr("lang").ok();
+                match root.name.local_name.as_str() {
+                    "metadata" => parse_items(&root, usize::MAX, lang.as_deref()).0,
+                    _ => match root.find("metadata") {
+                        Ok(metadata) => {
+                            parse_items(&metadata.borrow(), usize::MAX, lang.as_deref()).0
+                        }
+                        Err(_) => parse_items(&root, usize::MAX, lang.as_deref()).0,
+                    },
+                }
+            }
+            Err(_) => vec![],
+        };
+        ContainerMetadata { raw, items }
+    }
+
+    /// Returns the value of the first item with the `property`, like
+    /// "identifier" or "dcterms:modified"
+    pub fn get(&self, property: &str) -> Option<&str> {
+        self.items
+            .iter()
+            .find(|item| item.property == property)
+            .map(|item| item.value.as_str())
+    }
+}
@@ -0,0 +1,154 @@
+//! Read only memory map of a file, used to open big epubs without reading
+//! them in memory.
+//!
+//! On unix the file is mapped with `mmap`, on other platforms the file is
+//! read in memory.
+
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+#[cfg(unix)]
+mod sys {
+    use std::os::raw::{c_int, c_long, c_void};
+
+    #[cfg(target_os = "linux")]
+    pub type OffT = c_long;
+    #[cfg(not(target_os = "linux"))]
+    pub type OffT = i64;
+
+    pub const PROT_READ: c_int = 1;
+    pub const MAP_PRIVATE: c_int = 2;
+    pub const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+    extern "C" {
+        pub fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: OffT,
+        ) -> *mut c_void;
+        pub fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+}
+
+/// A file mapped in memory. The content is available as a byte slice.
+///
+/// Cloning it is cheap, the clones share the same map.
+#[derive(Clone)]
+pub struct Mmap(Arc<Map>);
+
+struct Map {
+    #[cfg(unix)]
+    ptr: *const u8,
+    #[cfg(unix)]
+    len: usize,
+    #[cfg(not(unix))]
+    data: Vec<u8>,
+}
+
+// the map is read only, so it can be shared between threads
+unsafe impl Send for Map {}
+unsafe impl Sync for Map {}
+
+impl Mmap {
+    /// Maps the file in `path`.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated, by this process or by
+    /// another one, while the map or one of its clones is alive. The
+    /// content of the slice would change under its borrows, which is
+    /// undefined behavior, and reading past the end of a truncated file
+    /// kills the process with `SIGBUS`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be opened or mapped, or if it's
+    /// bigger than the address space.
+    #[cfg(unix)]
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Mmap> {
+        use std::convert::TryFrom;
+        use std::os::unix::io::AsRawFd;
+
+        let file = File::open(path)?;
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "file too big to be mapped"))?;
+        if len == 0 {
+            return Ok(Mmap(Arc::new(Map {
+                ptr: std::ptr::NonNull::dangling().as_ptr(),
+                len,
+            })));
+        }
+        let ptr = sys::mmap(
+            std::ptr::null_mut(),
+            len,
+            sys::PROT_READ,
+            sys::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == sys::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Mmap(Arc::new(Map {
+            ptr: ptr as *const u8,
+            len,
+        })))
+    }
+
+    /// Reads the file in `path`, there's no memory map support for this
+    /// platform.
+    ///
+    /// # Safety
+    ///
+    /// Reading the file is safe, it's unsafe like the unix version, that
+    /// maps it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read.
+    #[cfg(not(unix))]
+    pub unsafe fn open<P: AsRef<Path>>(path: P) -> io::Result<Mmap> {
+        use std::io::Read;
+
+        let mut data = vec![];
+        File::open(path)?.read_to_end(&mut data)?;
+        Ok(Mmap(Arc::new(Map { data })))
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    #[cfg(unix)]
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.0.ptr, self.0.len) }
+    }
+
+    #[cfg(not(unix))]
+    fn deref(&self) -> &[u8] {
+        &self.0.data
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+#[cfg(unix)]
+impl Drop for Map {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            unsafe {
+                sys::munmap(self.ptr as *mut _, self.len);
+            }
+        }
+    }
+}
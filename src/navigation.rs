@@ -0,0 +1,513 @@
+//! Generates the navigation documents, the EPUB3 nav document and the
+//! EPUB2 toc.ncx, from a `NavPoint` tree, and builds that tree from the
+//! content documents headings. Also reads the other lists of the nav
+//! document, like the landmarks.
+
+use crate::error::EpubError;
+use percent_encoding::percent_decode_str;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use xml::reader::{ParserConfig, XmlEvent as ReaderEvent};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+use crate::builder::{encode_href, relative_path, zip_name, OPS_NS, XHTML_NS};
+use crate::doc::NavPoint;
+use crate::href;
+use crate::xmlutils::XMLNode;
+
+pub(crate) const NCX_NS: &str = "http://www.daisy.org/z3986/2005/ncx/";
+
+/// A heading found in a content document
+pub(crate) struct Heading {
+    /// heading level, from 1 to 6
+    pub level: usize,
+    /// heading text, without markup
+    pub text: String,
+    /// id attribute of the heading element
+    pub id: Option<String>,
+}
+
+/// Builds a complete EPUB3 navigation document with the `toc`. The nav
+/// document is placed in the `base` directory.
+pub(crate) fn nav_document(
+    title: &str,
+    toc: &[NavPoint],
+    base: &Path,
+) -> Result<Vec<u8>, EpubError> {
+    let mut b = Vec::new();
+    {
+        let mut w = writer(&mut b, true);
+        w.write(
+            XmlEvent::start_element("html")
+                .default_ns(XHTML_NS)
+                .ns("epub", OPS_NS),
+        )?;
+        w.write(XmlEvent::start_element("head"))?;
+        w.write(XmlEvent::start_element("title"))?;
+        w.write(XmlEvent::characters(title))?;
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::start_element("body"))?;
+        write_nav(&mut w, toc, base)?;
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::end_element())?;
+    }
+    Ok(b)
+}
+
+/// Builds the `<nav epub:type="toc">` element alone, to replace the toc in
+/// an existing nav document
+pub(crate) fn nav_element(toc: &[NavPoint], base: &Path) -> Result<Vec<u8>, EpubError> {
+    let mut b = Vec::new();
+    write_nav(&mut writer(&mut b, false), toc, base)?;
+    Ok(b)
+}
+
+/// Builds a complete toc.ncx document with the `toc`. The ncx is placed in
+/// the `base` directory.
+pub(crate) fn ncx_document(
+    uid: &str,
+    title: &str,
+    toc: &[NavPoint],
+    base: &Path,
+) -> Result<Vec<u8>, EpubError> {
+    let depth = depth(toc).to_string();
+    let mut b = Vec::new();
+    {
+        let mut w = writer(&mut b, true);
+        w.write(
+            XmlEvent::start_element("ncx")
+                .default_ns(NCX_NS)
+                .attr("version", "2005-1"),
+        )?;
+        w.write(XmlEvent::start_element("head"))?;
+        for (name, content) in [
+            ("dtb:uid", uid),
+            ("dtb:depth", &depth),
+            ("dtb:totalPageCount", "0"),
+            ("dtb:maxPageNumber", "0"),
+        ]
+        .iter()
+        {
+            w.write(
+                XmlEvent::start_element("meta")
+                    .attr("name", name)
+                    .attr("content", content),
+            )?;
+            w.write(XmlEvent::end_element())?;
+        }
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::start_element("docTitle"))?;
+        w.write(XmlEvent::start_element("text"))?;
+        w.write(XmlEvent::characters(title))?;
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::end_element())?;
+        write_nav_map(&mut w, toc, base)?;
+        w.write(XmlEvent::end_element())?;
+    }
+    Ok(b)
+}
+
+/// Builds the `<navMap>` element alone, to replace the navMap in an
+/// existing ncx
+pub(crate) fn nav_map_element(toc: &[NavPoint], base: &Path) -> Result<Vec<u8>, EpubError> {
+    let mut b = Vec::new();
+    write_nav_map(&mut writer(&mut b, false), toc, base)?;
+    Ok(b)
+}
+
+fn writer<W: Write>(sink: W, declaration: bool) -> EventWriter<W> {
+    EmitterConfig::new()
+        .perform_indent(true)
+        .write_document_declaration(declaration)
+        .create_writer(sink)
+}
+
+fn write_nav<W: Write>(
+    w: &mut EventWriter<W>,
+    toc: &[NavPoint],
+    base: &Path,
+) -> Result<(), EpubError> {
+    w.write(
+        XmlEvent::start_element("nav")
+            .attr("epub:type", "toc")
+            .attr("id", "toc"),
+    )?;
+    write_nav_list(w, toc, base)?;
+    w.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+fn write_nav_list<W: Write>(
+    w: &mut EventWriter<W>,
+    navpoints: &[NavPoint],
+    base: &Path,
+) -> Result<(), EpubError> {
+    w.write(XmlEvent::start_element("ol"))?;
+    for nav in navpoints.iter() {
+        let href = navpoint_href(base, &nav.content);
+        w.write(XmlEvent::start_element("li"))?;
+        w.write(XmlEvent::start_element("a").attr("href", &href))?;
+        w.write(XmlEvent::characters(&nav.label))?;
+        w.write(XmlEvent::end_element())?;
+        if !nav.children.is_empty() {
+            write_nav_list(w, &nav.children, base)?;
+        }
+        w.write(XmlEvent::end_element())?;
+    }
+    w.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+fn write_nav_map<W: Write>(
+    w: &mut EventWriter<W>,
+    toc: &[NavPoint],
+    base: &Path,
+) -> Result<(), EpubError> {
+    w.write(XmlEvent::start_element("navMap"))?;
+    write_navpoints(w, toc, base, &mut 0)?;
+    w.write(XmlEvent::end_element())?;
+    Ok(())
+}
+
+/// Writes the ncx navPoints, numbering the play order in reading order
+fn write_navpoints<W: Write>(
+    w: &mut EventWriter<W>,
+    navpoints: &[NavPoint],
+    base: &Path,
+    play_order: &mut usize,
+) -> Result<(), EpubError> {
+    for nav in navpoints.iter() {
+        *play_order += 1;
+        let id = format!("navPoint-{}", play_order);
+        let order = play_order.to_string();
+        let src = navpoint_href(base, &nav.content);
+        w.write(
+            XmlEvent::start_element("navPoint")
+                .attr("id", &id)
+                .attr("playOrder", &order),
+        )?;
+        w.write(XmlEvent::start_element("navLabel"))?;
+        w.write(XmlEvent::start_element("text"))?;
+        w.write(XmlEvent::characters(&nav.label))?;
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::end_element())?;
+        w.write(XmlEvent::start_element("content").attr("src", &src))?;
+        w.write(XmlEvent::end_element())?;
+        write_navpoints(w, &nav.children, base, play_order)?;
+        w.write(XmlEvent::end_element())?;
+    }
+    Ok(())
+}
+
+/// Returns the href to the navpoint content from a document in the `base`
+/// directory, keeping the fragment
+fn navpoint_href(base: &Path, content: &Path) -> String {
+    let content = zip_name(content);
+    let (path, fragment) = match content.find('#') {
+        Some(i) => content.split_at(i),
+        None => (content.as_str(), ""),
+    };
+    let href = relative_path(base, Path::new(path));
+    // the content may be already encoded if it was read from the epub
+    let href = percent_decode_str(&href).decode_utf8_lossy();
+    encode_href(&href) + fragment
+}
+
+/// Number of nesting levels in the tree
+pub(crate) fn depth(navpoints: &[NavPoint]) -> usize {
+    navpoints
+        .iter()
+        .map(|nav| 1 + depth(&nav.children))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Appends the navpoints of the tree in document order, each one with its
+/// nesting level
+pub(crate) fn flatten<'a>(
+    navpoints: &'a [NavPoint],
+    level: usize,
+    flat: &mut Vec<(usize, &'a NavPoint)>,
+) {
+    for nav in navpoints {
+        flat.push((level, nav));
+        flatten(&nav.children, level + 1, flat);
+    }
+}
+
+/// Returns the `h1` to `h{max_level}` headings in the xhtml `content`, in
+/// document order. Parsing stops at the first xml error.
+pub(crate) fn find_headings(content: &[u8], max_level: usize) -> Vec<Heading> {
+    let reader = ParserConfig::new()
+        .add_entity("nbsp", " ")
+        .add_entity("copy", "©")
+        .add_entity("reg", "®")
+        .create_reader(content);
+
+    let mut headings = vec![];
+    let mut current: Option<Heading> = None;
+    let mut depth = 0;
+    for e in reader {
+        match e {
+            Ok(ReaderEvent::StartElement {
+                name, attributes, ..
+            }) => {
+                if current.is_some() {
+                    depth += 1;
+                    continue;
+                }
+                let level = heading_level(&name.local_name).filter(|l| *l <= max_level);
+                if let Some(level) = level {
+                    let id = attributes
+                        .iter()
+                        .find(|a| a.name.local_name == "id")
+                        .map(|a| a.value.clone());
+                    current = Some(Heading {
+                        level,
+                        text: String::new(),
+                        id,
+                    });
+                }
+            }
+            Ok(ReaderEvent::EndElement { .. }) if current.is_some() => {
+                if depth > 0 {
+                    depth -= 1;
+                    continue;
+                }
+                let mut heading = current.take().expect("current heading");
+                heading.text = heading
+                    .text
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if !heading.text.is_empty() {
+                    headings.push(heading);
+                }
+            }
+            Ok(ReaderEvent::Characters(text)) | Ok(ReaderEvent::CData(text)) => {
+                if let Some(ref mut heading) = current {
+                    heading.text.push_str(&text);
+                }
+            }
+            Ok(ReaderEvent::Whitespace(_)) => {
+                if let Some(ref mut heading) = current {
+                    heading.text.push(' ');
+                }
+            }
+            Err(_) => break,
+            _ => {}
+        }
+    }
+    headings
+}
+
+fn heading_level(name: &str) -> Option<usize> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+/// Builds the nested table of content from a list of headings and the
+/// path of the document where each one is. Lower level headings are nested
+/// under the previous higher level heading.
+///
+/// The play order isn't set, use `renumber` for that.
+pub(crate) fn heading_navpoints(headings: &[(PathBuf, Heading)]) -> Vec<NavPoint> {
+    let mut navpoints = vec![];
+    let mut i = 0;
+    while i < headings.len() {
+        let (ref path, ref heading) = headings[i];
+        let end = headings[i + 1..]
+            .iter()
+            .position(|(_, h)| h.level <= heading.level)
+            .map(|p| i + 1 + p)
+            .unwrap_or(headings.len());
+        let mut content = path.as_os_str().to_os_string();
+        if let Some(ref id) = heading.id {
+            content.push(format!("#{}", id));
+        }
+        navpoints.push(NavPoint {
+            label: heading.text.clone(),
+            content: PathBuf::from(content),
+            children: heading_navpoints(&headings[i + 1..end]),
+            play_order: 0,
+        });
+        i = end;
+    }
+    navpoints
+}
+
+/// Sets the play order of every navpoint, in depth first order, after
+/// `play_order`
+pub(crate) fn renumber(navpoints: &mut [NavPoint], play_order: &mut usize) {
+    for nav in navpoints.iter_mut() {
+        *play_order += 1;
+        nav.play_order = *play_order;
+        renumber(&mut nav.children, play_order);
+    }
+}
+
+/// Returns the lists of every `nav` element of the nav document `root`
+/// with an epub:type, like "toc" or "lot", as trees up to `depth` levels.
+/// The hrefs are resolved from `nav`, the path of the nav document.
+pub(crate) fn nav_trees(root: &XMLNode, nav: &Path, depth: usize) -> Vec<(String, Vec<NavPoint>)> {
+    let mut trees = vec![];
+    collect_navs(root, nav, depth, &mut trees);
+    trees
+}
+
+fn collect_navs(
+    node: &XMLNode,
+    nav: &Path,
+    depth: usize,
+    trees: &mut Vec<(String, Vec<NavPoint>)>,
+) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name != "nav" {
+            collect_navs(&child, nav, depth, trees);
+            continue;
+        }
+        let nav_type = child.get_attr("type").unwrap_or_default();
+        let nav_type = match nav_type.split_whitespace().next() {
+            Some(t) => t.to_string(),
+            None => continue,
+        };
+        let mut navpoints = match child.find("ol") {
+            Ok(ol) => list_navpoints(&ol.borrow(), nav, depth),
+            Err(_) => vec![],
+        };
+        renumber(&mut navpoints, &mut 0);
+        trees.push((nav_type, navpoints));
+    }
+}
+
+/// The entries of an `ol` of the nav document. The entries without a link,
+/// headings of a group, point to the first link of the group.
+fn list_navpoints(ol: &XMLNode, nav: &Path, depth: usize) -> Vec<NavPoint> {
+    let mut navpoints = vec![];
+    if depth == 0 {
+        return navpoints;
+    }
+    for li in ol.childs.iter() {
+        let li = li.borrow();
+        if li.name.local_name != "li" {
+            continue;
+        }
+        let mut label = None;
+        let mut content = None;
+        let mut children = vec![];
+        for child in li.childs.iter() {
+            let child = child.borrow();
+            match child.name.local_name.as_str() {
+                "a" | "span" if label.is_none() => {
+                    let mut text = String::new();
+                    node_text(&child, &mut text);
+                    label = Some(text.split_whitespace().collect::<Vec<_>>().join(" "));
+                    content = child
+                        .get_attr("href")
+                        .ok()
+                        .map(|href| href::resolve_with_fragment(nav, &href));
+                }
+                "ol" => children = list_navpoints(&child, nav, depth - 1),
+                _ => {}
+            }
+        }
+        let content = content.or_else(|| {
+            li.find("a")
+                .ok()
+                .and_then(|a| a.borrow().get_attr("href").ok())
+                .map(|href| href::resolve_with_fragment(nav, &href))
+        });
+        if let Some(label) = label {
+            navpoints.push(NavPoint {
+                label,
+                content: content.unwrap_or_default(),
+                children,
+                play_order: 0,
+            });
+        }
+    }
+    navpoints
+}
+
+/// Returns the links of the first `nav` element with the epub:type
+/// `nav_type` in the nav document `root`, in document order, as the label,
+/// the href resolved from `nav` and the epub:type of the link
+pub(crate) fn nav_links(
+    root: &XMLNode,
+    nav_type: &str,
+    nav: &Path,
+) -> Vec<(String, PathBuf, Option<String>)> {
+    let mut links = vec![];
+    find_nav(root, nav_type, nav, &mut links);
+    links
+}
+
+/// Collects the links of the first `nav_type` nav under `node`, returns
+/// true if it's found
+fn find_nav(
+    node: &XMLNode,
+    nav_type: &str,
+    nav: &Path,
+    links: &mut Vec<(String, PathBuf, Option<String>)>,
+) -> bool {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        let is_nav = child.name.local_name == "nav"
+            && child
+                .get_attr("type")
+                .map(|t| t.split_whitespace().any(|t| t == nav_type))
+                .unwrap_or(false);
+        if is_nav {
+            collect_links(&child, nav, links);
+            return true;
+        }
+        if find_nav(&child, nav_type, nav, links) {
+            return true;
+        }
+    }
+    false
+}
+
+fn collect_links(node: &XMLNode, nav: &Path, links: &mut Vec<(String, PathBuf, Option<String>)>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name != "a" {
+            collect_links(&child, nav, links);
+            continue;
+        }
+        let href = match child.get_attr("href") {
+            Ok(href) => href,
+            Err(_) => continue,
+        };
+        let mut label = String::new();
+        node_text(&child, &mut label);
+        let mut label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+        if label.is_empty() {
+            label = child.get_attr("title").unwrap_or_default();
+        }
+        links.push((
+            label,
+            href::resolve_with_fragment(nav, &href),
+            child.get_attr("type").ok(),
+        ));
+    }
+}
+
+/// Appends the text of the element and its descendants
+fn node_text(node: &XMLNode, text: &mut String) {
+    if let Some(ref t) = node.text {
+        text.push_str(t);
+    }
+    for child in node.childs.iter() {
+        node_text(&child.borrow(), text);
+    }
+}
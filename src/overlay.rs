@@ -0,0 +1,407 @@
+//! EPUB3 media overlays, the SMIL documents that synchronize the text of a
+//! content document with its narration.
+//!
+//! A content document with a `media-overlay` attribute in the manifest
+//! points to a SMIL document. Its body is a sequence of `par` elements,
+//! each one with the fragment of the text and the clip of the audio file
+//! that reads it, grouped in nested `seq` elements like the sections of
+//! the text.
+//!
+//! # Examples
+//!
+//! ```
+//! use epub::overlay::parse_clock_value;
+//! use std::time::Duration;
+//!
+//! assert_eq!(Some(Duration::from_millis(83_500)), parse_clock_value("0:01:23.5"));
+//! assert_eq!(Some(Duration::from_millis(1_500)), parse_clock_value("1.5s"));
+//! assert_eq!(Some(Duration::from_millis(250)), parse_clock_value("250ms"));
+//! ```
+
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::doc::{EpubDoc, NavPoint};
+use crate::error::EpubError;
+use crate::href;
+use crate::xmlutils::{self, XMLNode};
+
+/// The media overlay of a content document, a parsed SMIL document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MediaOverlay {
+    /// the manifest id of the SMIL document
+    pub id: String,
+    /// the full path of the SMIL document in the archive
+    pub path: PathBuf,
+    /// the body of the SMIL document, the top level sequence
+    pub body: Seq,
+}
+
+impl MediaOverlay {
+    /// Returns the `par` elements of the overlay in reading order,
+    /// including the ones in nested sequences
+    pub fn pars(&self) -> Vec<&Par> {
+        let mut pars = vec![];
+        self.body.collect_pars(&mut pars);
+        pars
+    }
+
+    /// Returns the audio clip that reads the element of `href`, a full
+    /// path with a fragment like the `text` of the pars. The element can be
+    /// the text of a par or the textref of a sequence, whose first clip is
+    /// returned. An href without fragment is the start of the document.
+    pub fn clip_for(&self, href: &str) -> Option<&AudioClip> {
+        let (path, fragment) = href::split_fragment(href);
+        let path = href::normalize(path);
+        let target = match fragment {
+            Some(fragment) if !fragment.is_empty() => format!("{}#{}", path, fragment),
+            _ => return self.pars().into_iter().find_map(|par| par.audio.as_ref()),
+        };
+        let is_target = |p: &Path| p.to_string_lossy() == target;
+        if let Some(par) = self.pars().into_iter().find(|par| is_target(&par.text)) {
+            return par.audio.as_ref();
+        }
+        self.body.find_seq(&is_target).and_then(|seq| {
+            let mut pars = vec![];
+            seq.collect_pars(&mut pars);
+            pars.into_iter().find_map(|par| par.audio.as_ref())
+        })
+    }
+}
+
+/// A child of a sequence, a nested sequence or a synchronized text and
+/// audio pair
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OverlayNode {
+    Seq(Seq),
+    Par(Par),
+}
+
+/// A `seq` element, a group of pars and sequences played in order, like a
+/// section or a table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Seq {
+    /// the id of the element
+    pub id: Option<String>,
+    /// the epub:type of the element, like "chapter" or "table"
+    pub epub_type: Vec<String>,
+    /// the epub:textref, the element of the text that the sequence
+    /// reads, as the full path with the fragment
+    pub text_ref: Option<PathBuf>,
+    /// the pars and sequences of the sequence, in order
+    pub children: Vec<OverlayNode>,
+}
+
+impl Seq {
+    /// Returns the first sequence, this one or a nested one, with a
+    /// textref matching `is_target`
+    fn find_seq(&self, is_target: &dyn Fn(&Path) -> bool) -> Option<&Seq> {
+        if self.text_ref.as_deref().is_some_and(is_target) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| match child {
+            OverlayNode::Seq(seq) => seq.find_seq(is_target),
+            OverlayNode::Par(_) => None,
+        })
+    }
+
+    fn collect_pars<'a>(&'a self, pars: &mut Vec<&'a Par>) {
+        for child in self.children.iter() {
+            match child {
+                OverlayNode::Seq(seq) => seq.collect_pars(pars),
+                OverlayNode::Par(par) => pars.push(par),
+            }
+        }
+    }
+}
+
+/// A `par` element, a fragment of the text and the audio clip that reads
+/// it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Par {
+    /// the id of the element
+    pub id: Option<String>,
+    /// the epub:type of the element
+    pub epub_type: Vec<String>,
+    /// the src of the text element, as the full path with the fragment
+    pub text: PathBuf,
+    /// the clip of the audio, if any
+    pub audio: Option<AudioClip>,
+}
+
+/// A clip of an audio file, from an `audio` element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioClip {
+    /// the full path of the audio file in the archive
+    pub src: PathBuf,
+    /// the start of the clip, the start of the file if it's None
+    pub clip_begin: Option<Duration>,
+    /// the end of the clip, the end of the file if it's None
+    pub clip_end: Option<Duration>,
+}
+
+/// Parses a SMIL clock value, like "0:01:23.5", "01:23.5", "83.5s",
+/// "1.5min" or "250ms". A number without unit is in seconds.
+pub fn parse_clock_value(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    if value.contains(':') {
+        let parts: Vec<&str> = value.split(':').collect();
+        let (hours, minutes, seconds) = match parts.as_slice() {
+            [h, m, s] => (h.parse::<u64>().ok()?, m.parse::<u64>().ok()?, *s),
+            [m, s] => (0, m.parse::<u64>().ok()?, *s),
+            _ => return None,
+        };
+        let seconds = seconds.parse::<f64>().ok()?;
+        if minutes >= 60 || !(0.0..60.0).contains(&seconds) {
+            return None;
+        }
+        return Some(Duration::from_secs(hours * 3600 + minutes * 60) + secs(seconds)?);
+    }
+    let (number, scale) = match value {
+        v if v.ends_with("ms") => (&v[..v.len() - 2], 0.001),
+        v if v.ends_with("min") => (&v[..v.len() - 3], 60.0),
+        v if v.ends_with('h') => (&v[..v.len() - 1], 3600.0),
+        v if v.ends_with('s') => (&v[..v.len() - 1], 1.0),
+        v => (v, 1.0),
+    };
+    secs(number.trim().parse::<f64>().ok()? * scale)
+}
+
+fn secs(seconds: f64) -> Option<Duration> {
+    if seconds.is_finite() && seconds >= 0.0 {
+        Some(Duration::from_secs_f64(seconds))
+    } else {
+        None
+    }
+}
+
+/// Parses the SMIL document `content`, in the archive `path`
+pub(crate) fn parse_smil(content: &[u8], path: &Path) -> Result<Seq, EpubError> {
+    let root = xmlutils::XMLReader::parse(content)?;
+    let body = root.borrow().find("body")?;
+    let body = body.borrow();
+    Ok(parse_seq(&body, path))
+}
+
+fn parse_seq(node: &XMLNode, path: &Path) -> Seq {
+    let mut seq = Seq {
+        id: node.get_attr("id").ok(),
+        epub_type: epub_type(node),
+        text_ref: node
+            .get_attr("textref")
+            .ok()
+            .map(|href| href::resolve_with_fragment(path, &href)),
+        children: vec![],
+    };
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        match child.name.local_name.as_str() {
+            "seq" => seq.children.push(OverlayNode::Seq(parse_seq(&child, path))),
+            "par" => {
+                if let Some(par) = parse_par(&child, path) {
+                    seq.children.push(OverlayNode::Par(par));
+                }
+            }
+            _ => {}
+        }
+    }
+    seq
+}
+
+/// Parses a par element, None if it doesn't have a text
+fn parse_par(node: &XMLNode, path: &Path) -> Option<Par> {
+    let mut text = None;
+    let mut audio = None;
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        let src = match child.get_attr("src") {
+            Ok(src) => src,
+            Err(_) => continue,
+        };
+        match child.name.local_name.as_str() {
+            "text" => text = Some(href::resolve_with_fragment(path, &src)),
+            "audio" => {
+                let clip = |name: &str| {
+                    child
+                        .get_attr(name)
+                        .ok()
+                        .and_then(|v| parse_clock_value(&v))
+                };
+                audio = Some(AudioClip {
+                    src: href::resolve(path, &src),
+                    clip_begin: clip("clipBegin"),
+                    clip_end: clip("clipEnd"),
+                });
+            }
+            _ => {}
+        }
+    }
+    Some(Par {
+        id: node.get_attr("id").ok(),
+        epub_type: epub_type(node),
+        text: text?,
+        audio,
+    })
+}
+
+fn epub_type(node: &XMLNode) -> Vec<String> {
+    match node.get_attr("type") {
+        Ok(t) => t.split_whitespace().map(String::from).collect(),
+        Err(_) => vec![],
+    }
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Parses the media overlay of the content document `id`. Returns None
+    /// if the document doesn't have a media overlay.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// // test.epub doesn't have narration
+    /// assert_eq!(None, doc.get_media_overlay("000.xhtml").unwrap());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the SMIL document isn't in the manifest or can't
+    /// be parsed.
+    pub fn get_media_overlay(&mut self, id: &str) -> Result<Option<MediaOverlay>, EpubError> {
+        let smil_id = match self.media_overlay_id(id) {
+            Some(smil_id) => smil_id.to_string(),
+            None => return Ok(None),
+        };
+        let path = match self.resources.get(&smil_id) {
+            Some((path, _)) => path.clone(),
+            None => return Err(EpubError::ResourceNotFound(smil_id)),
+        };
+        let content = self.archive.get_entry(&path)?;
+        let body = parse_smil(&content, &path)?;
+        Ok(Some(MediaOverlay {
+            id: smil_id,
+            path,
+            body,
+        }))
+    }
+
+    /// Returns the audio clip that reads the element of `href`, a full
+    /// path in the archive with a fragment like the content of the toc
+    /// entries. See `MediaOverlay::clip_for`. Returns None if the document
+    /// doesn't have a media overlay or the element isn't narrated.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::ResourceNotFound` if the document isn't in the
+    /// manifest, or an error if its media overlay can't be parsed.
+    pub fn audio_clip_for(&mut self, href: &str) -> Result<Option<AudioClip>, EpubError> {
+        let (path, _) = href::split_fragment(href);
+        let path = PathBuf::from(href::normalize(path));
+        let id = self
+            .resources
+            .iter()
+            .find(|(_, (p, _))| *p == path)
+            .map(|(id, _)| id.clone())
+            .ok_or_else(|| EpubError::ResourceNotFound(href.to_string()))?;
+        let overlay = match self.get_media_overlay(&id)? {
+            Some(overlay) => overlay,
+            None => return Ok(None),
+        };
+        Ok(overlay.clip_for(href).cloned())
+    }
+
+    /// Returns the audio clip that reads the start of the toc entry `nav`.
+    /// See `audio_clip_for`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document of the entry isn't in the manifest
+    /// or its media overlay can't be parsed.
+    pub fn audio_clip_for_navpoint(
+        &mut self,
+        nav: &NavPoint,
+    ) -> Result<Option<AudioClip>, EpubError> {
+        self.audio_clip_for(&nav.content.to_string_lossy())
+    }
+
+    /// Returns the audio of the `clip`. A clip without begin and end is
+    /// the whole file. Compressed audio can't be cut without decoding it,
+    /// so only the clips of uncompressed wav files are cut, to a new wav
+    /// file with the samples of the clip.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the audio file can't be read, or
+    /// `EpubError::InvalidOperation` if the clip can't be cut because the
+    /// file isn't an uncompressed wav file.
+    pub fn get_audio_clip(&mut self, clip: &AudioClip) -> Result<Vec<u8>, EpubError> {
+        let content = self.archive.get_entry(&clip.src)?;
+        if clip.clip_begin.is_none() && clip.clip_end.is_none() {
+            return Ok(content);
+        }
+        cut_wav(&content, clip.clip_begin, clip.clip_end).ok_or_else(|| {
+            EpubError::InvalidOperation(format!(
+                "can't cut the audio of {}, only uncompressed wav files are supported",
+                clip.src.display()
+            ))
+        })
+    }
+}
+
+/// Cuts the samples from `begin` to `end` of the wav file `content`, to a
+/// new wav file. None if it isn't a pcm wav file.
+fn cut_wav(content: &[u8], begin: Option<Duration>, end: Option<Duration>) -> Option<Vec<u8>> {
+    if content.len() < 12 || &content[..4] != b"RIFF" || &content[8..12] != b"WAVE" {
+        return None;
+    }
+    let u16_at = |i: usize| Some(u16::from_le_bytes([*content.get(i)?, *content.get(i + 1)?]));
+    let u32_at = |i: usize| {
+        let bytes = content.get(i..i + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    };
+    let mut fmt = None;
+    let mut data = None;
+    let mut i = 12;
+    while i + 8 <= content.len() {
+        let size = u32_at(i + 4)? as usize;
+        let body = i + 8..(i + 8).checked_add(size)?.min(content.len());
+        let next = body.start + size + size % 2;
+        match &content[i..i + 4] {
+            b"fmt " => fmt = Some(body),
+            b"data" => data = Some(body),
+            _ => {}
+        }
+        // chunks are aligned to two bytes
+        i = next;
+    }
+    let fmt = fmt?;
+    let data = data?;
+    // pcm or ieee float samples
+    let format = u16_at(fmt.start)?;
+    if format != 1 && format != 3 {
+        return None;
+    }
+    let byte_rate = u32_at(fmt.start + 8)? as f64;
+    let block_align = (u16_at(fmt.start + 12)? as usize).max(1);
+    let offset = |time: Duration| {
+        let bytes = (time.as_secs_f64() * byte_rate) as usize;
+        (bytes - bytes % block_align).min(data.len())
+    };
+    let start = begin.map(offset).unwrap_or(0);
+    let stop = end.map(offset).unwrap_or(data.len()).max(start);
+    let samples = &content[data.start + start..data.start + stop];
+    let fmt = &content[fmt];
+
+    let mut wav = Vec::with_capacity(44 + samples.len());
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&((4 + 8 + fmt.len() + 8 + samples.len()) as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    wav.extend_from_slice(fmt);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    wav.extend_from_slice(samples);
+    Some(wav)
+}
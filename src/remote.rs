@@ -0,0 +1,418 @@
+//! Remote epubs, read with range requests, fetching only the parts of the
+//! file that are needed.
+//!
+//! `RangeReader` implements `Read + Seek` over any `RangeSource`, caching
+//! the last fetched blocks. Opening the epub only fetches the end of the
+//! file, with the zip central directory, and then each entry is fetched
+//! when it's read.
+//!
+//! `HttpRange`, with the "http" feature, is a `RangeSource` for plain http
+//! urls. For https, or to use a different http client, implement
+//! `RangeSource` and use `EpubDoc::from_reader(RangeReader::new(source)?)`.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(feature = "http")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "http")]
+use std::net::TcpStream;
+#[cfg(feature = "http")]
+use std::time::Duration;
+
+#[cfg(feature = "http")]
+use crate::archive::EpubArchive;
+#[cfg(feature = "http")]
+use crate::doc::EpubDoc;
+#[cfg(feature = "http")]
+use crate::error::EpubError;
+
+/// Size of the blocks fetched from the source
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// Number of blocks kept in the cache by default, 4 MiB
+const CACHE_BLOCKS: usize = 64;
+
+/// Redirects followed by a http request
+#[cfg(feature = "http")]
+const MAX_REDIRECTS: usize = 5;
+
+/// A file that can be read by ranges, like a remote file served with
+/// http range requests.
+pub trait RangeSource {
+    /// Returns the total size of the file
+    fn size(&mut self) -> io::Result<u64>;
+
+    /// Returns `len` bytes of the file, starting at `offset`. It can
+    /// return less bytes at the end of the file.
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>>;
+}
+
+/// Reader over a `RangeSource`. The file is fetched in blocks, and the
+/// last used blocks are cached, so they're fetched only once while they're
+/// in use.
+pub struct RangeReader<S: RangeSource> {
+    source: S,
+    size: u64,
+    pos: u64,
+    /// block index -> content
+    blocks: HashMap<u64, Vec<u8>>,
+    /// cached block indexes, from the least to the most recently used
+    used: VecDeque<u64>,
+    /// max number of cached blocks
+    capacity: usize,
+    fetched: u64,
+}
+
+impl<S: RangeSource> RangeReader<S> {
+    /// Creates the reader, asking the source for the file size
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the source fails.
+    pub fn new(mut source: S) -> io::Result<RangeReader<S>> {
+        let size = source.size()?;
+        Ok(RangeReader {
+            source,
+            size,
+            pos: 0,
+            blocks: HashMap::new(),
+            used: VecDeque::new(),
+            capacity: CACHE_BLOCKS,
+            fetched: 0,
+        })
+    }
+
+    /// Sets the number of 64 KiB blocks kept in the cache, 64 by default.
+    /// The least recently used block is dropped when the cache is full.
+    pub fn cache_blocks(mut self, blocks: usize) -> Self {
+        self.capacity = blocks.max(1);
+        while self.used.len() > self.capacity {
+            if let Some(index) = self.used.pop_front() {
+                self.blocks.remove(&index);
+            }
+        }
+        self
+    }
+
+    /// Total size of the file
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Number of bytes fetched from the source
+    pub fn fetched(&self) -> u64 {
+        self.fetched
+    }
+
+    /// Returns the inner source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    fn block(&mut self, index: u64) -> io::Result<&[u8]> {
+        if self.blocks.contains_key(&index) {
+            if let Some(i) = self.used.iter().position(|&used| used == index) {
+                self.used.remove(i);
+            }
+        } else {
+            let offset = index * BLOCK_SIZE;
+            let len = BLOCK_SIZE.min(self.size - offset);
+            let content = self.source.read_range(offset, len)?;
+            if (content.len() as u64) < len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "range shorter than requested",
+                ));
+            }
+            self.fetched += content.len() as u64;
+            if self.used.len() >= self.capacity {
+                if let Some(oldest) = self.used.pop_front() {
+                    self.blocks.remove(&oldest);
+                }
+            }
+            self.blocks.insert(index, content);
+        }
+        self.used.push_back(index);
+        Ok(&self.blocks[&index])
+    }
+}
+
+impl<S: RangeSource> Read for RangeReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = self.pos / BLOCK_SIZE;
+        let start = (self.pos % BLOCK_SIZE) as usize;
+        let block = self.block(index)?;
+        let n = buf.len().min(block.len() - start);
+        buf[..n].copy_from_slice(&block[start..start + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<S: RangeSource> Seek for RangeReader<S> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => offset(self.size, n),
+            SeekFrom::Current(n) => offset(self.pos, n),
+        };
+        match pos {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the file",
+            )),
+        }
+    }
+}
+
+fn offset(base: u64, n: i64) -> Option<u64> {
+    if n >= 0 {
+        base.checked_add(n as u64)
+    } else {
+        base.checked_sub(n.unsigned_abs())
+    }
+}
+
+/// A remote file served over plain http, read with range requests.
+///
+/// Every request uses a new connection. The server must support range
+/// requests, answering "206 Partial Content". Redirects to other http
+/// urls are followed, and the later requests go to the new url.
+#[cfg(feature = "http")]
+#[derive(Clone, Debug)]
+pub struct HttpRange {
+    host: String,
+    port: u16,
+    path: String,
+    timeout: Option<Duration>,
+}
+
+#[cfg(feature = "http")]
+impl HttpRange {
+    /// Creates the source for an "http://host[:port]/path" url. IPv6
+    /// hosts are written in brackets, like "http://[::1]:8080/book.epub".
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if the url isn't a valid http url.
+    pub fn new(url: &str) -> io::Result<HttpRange> {
+        let invalid = |reason: &str| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", reason, url))
+        };
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("only http urls are supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(ipv6) => {
+                let end = ipv6.find(']').ok_or_else(|| invalid("invalid ipv6 host"))?;
+                let port = match &ipv6[end + 1..] {
+                    "" => 80,
+                    port => port
+                        .strip_prefix(':')
+                        .and_then(|port| port.parse().ok())
+                        .ok_or_else(|| invalid("invalid port"))?,
+                };
+                (&ipv6[..end], port)
+            }
+            None => match authority.rfind(':') {
+                Some(i) => {
+                    let port = authority[i + 1..]
+                        .parse()
+                        .map_err(|_| invalid("invalid port"))?;
+                    (&authority[..i], port)
+                }
+                None => (authority, 80),
+            },
+        };
+        if host.is_empty() {
+            return Err(invalid("url without host"));
+        }
+        Ok(HttpRange {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            timeout: Some(Duration::from_secs(30)),
+        })
+    }
+
+    /// Sets the read and write timeout of the connections, 30 seconds by
+    /// default
+    pub fn timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Requests the range from `start` to `end`, both included, following
+    /// the redirects. Returns the response headers, in lowercase, and the
+    /// body.
+    fn request(&mut self, start: u64, end: u64) -> io::Result<(HashMap<String, String>, Vec<u8>)> {
+        for _ in 0..=MAX_REDIRECTS {
+            let (status, headers, reader) = self.send(start, end)?;
+            let code = status.split_whitespace().nth(1).unwrap_or_default();
+            if let ("301" | "302" | "303" | "307" | "308", Some(location)) =
+                (code, headers.get("location"))
+            {
+                self.redirect(location)?;
+                continue;
+            }
+            if code != "206" {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("range request not supported: {}", status.trim()),
+                ));
+            }
+            let body = read_body(reader, &headers)?;
+            return Ok((headers, body));
+        }
+        Err(io::Error::new(io::ErrorKind::Other, "too many redirects"))
+    }
+
+    /// Sends the range request, returns the status line, the headers and
+    /// the reader of the body
+    fn send(
+        &self,
+        start: u64,
+        end: u64,
+    ) -> io::Result<(String, HashMap<String, String>, BufReader<TcpStream>)> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port))?;
+        stream.set_read_timeout(self.timeout)?;
+        stream.set_write_timeout(self.timeout)?;
+        let mut host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        if self.port != 80 {
+            host = format!("{}:{}", host, self.port);
+        }
+        write!(
+            &stream,
+            "GET {} HTTP/1.1\r\nHost: {}\r\nRange: bytes={}-{}\r\nConnection: close\r\n\r\n",
+            self.path, host, start, end
+        )?;
+
+        let mut reader = BufReader::new(stream);
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(i) = line.find(':') {
+                let name = line[..i].trim().to_lowercase();
+                headers.insert(name, line[i + 1..].trim().to_string());
+            }
+        }
+        Ok((status, headers, reader))
+    }
+
+    /// Moves the source to the redirect `location`, an absolute http url
+    /// or an absolute path in the same host
+    fn redirect(&mut self, location: &str) -> io::Result<()> {
+        if location.starts_with('/') {
+            self.path = location.to_string();
+        } else {
+            let timeout = self.timeout;
+            *self = HttpRange::new(location)?.timeout(timeout);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "http")]
+fn read_body<R: BufRead>(mut reader: R, headers: &HashMap<String, String>) -> io::Result<Vec<u8>> {
+    let mut body = vec![];
+    let chunked = headers
+        .get("transfer-encoding")
+        .map(|v| v.eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false);
+    if chunked {
+        read_chunked(&mut reader, &mut body)?;
+    } else if let Some(len) = headers.get("content-length").and_then(|l| l.parse().ok()) {
+        reader.take(len).read_to_end(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok(body)
+}
+
+#[cfg(feature = "http")]
+fn read_chunked<R: BufRead>(reader: &mut R, body: &mut Vec<u8>) -> io::Result<()> {
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = u64::from_str_radix(size, 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad chunk size"))?;
+        if size == 0 {
+            return Ok(());
+        }
+        reader.by_ref().take(size).read_to_end(body)?;
+        line.clear();
+        reader.read_line(&mut line)?;
+    }
+}
+
+#[cfg(feature = "http")]
+impl RangeSource for HttpRange {
+    fn size(&mut self) -> io::Result<u64> {
+        let (headers, _) = self.request(0, 0)?;
+        // Content-Range: bytes 0-0/1234
+        headers
+            .get("content-range")
+            .and_then(|range| range.rsplit('/').next())
+            .and_then(|size| size.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown file size"))
+    }
+
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        let (_, body) = self.request(offset, offset + len - 1)?;
+        Ok(body)
+    }
+}
+
+#[cfg(feature = "http")]
+impl EpubArchive<RangeReader<HttpRange>> {
+    /// Opens the remote epub in the http `url`, using range requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the url isn't valid, if the server doesn't
+    /// support range requests or if the zip is broken.
+    pub fn from_url(url: &str) -> Result<EpubArchive<RangeReader<HttpRange>>, EpubError> {
+        EpubArchive::from_reader(RangeReader::new(HttpRange::new(url)?)?)
+    }
+}
+
+#[cfg(feature = "http")]
+impl EpubDoc<RangeReader<HttpRange>> {
+    /// Opens the remote epub in the http `url`, using range requests.
+    /// Only the zip central directory and the package documents are
+    /// fetched, each resource is fetched when it's read.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the url isn't valid, if the server doesn't
+    /// support range requests or if the epub is broken.
+    pub fn from_url(url: &str) -> Result<EpubDoc<RangeReader<HttpRange>>, EpubError> {
+        EpubDoc::from_reader(RangeReader::new(HttpRange::new(url)?)?)
+    }
+}
@@ -0,0 +1,531 @@
+//! The rendering of the publication, from the `rendition:*` properties of
+//! the package metadata, and the renditions of the container.
+//!
+//! The metadata sets the global settings, like
+//! `<meta property="rendition:layout">pre-paginated</meta>` for the fixed
+//! layout books, and the itemrefs of the spine override them for some
+//! documents, like `<itemref idref="map" properties="rendition:layout-reflowable"/>`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use epub::doc::EpubDoc;
+//! use epub::rendition::{Flow, Layout};
+//!
+//! # let doc = EpubDoc::new("test.epub").unwrap();
+//! // test.epub is a reflowable book
+//! let rendition = doc.rendition();
+//! assert_eq!(Layout::Reflowable, rendition.layout);
+//! assert_eq!(Flow::Auto, rendition.flow);
+//! assert!(!doc.spine_rendition(0).unwrap().is_fixed_layout());
+//! ```
+//!
+//! A publication can have several renditions, each one with its own
+//! package document, like a fixed layout and a reflowable version of the
+//! same book. They are the rootfiles of the `container.xml`, see
+//! `EpubDoc::renditions`. The rendition mapping document, linked from the
+//! container, lists the equivalent locations of the renditions, to keep
+//! the reading position when the reader switches from one to another.
+
+use percent_encoding::percent_decode_str;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::cfi;
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::href;
+use crate::xmlutils::{self, XMLNode};
+
+/// The media type of the rootfiles that are package documents
+pub const PACKAGE_MIME: &str = "application/oebps-package+xml";
+
+/// How the content is laid out, `rendition:layout`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// the text is paginated by the reading system
+    Reflowable,
+    /// each document is a fixed page, like comics or children books
+    PrePaginated,
+}
+
+/// The orientation of the device the content is intended for,
+/// `rendition:orientation`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Auto,
+    Landscape,
+    Portrait,
+}
+
+/// When two pages are shown side by side, `rendition:spread`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Spread {
+    Auto,
+    /// single pages only
+    None,
+    /// spreads in landscape orientation only
+    Landscape,
+    /// spreads in both orientations, also for the deprecated "portrait"
+    Both,
+}
+
+/// How the overflowing content of a reflowable document is presented,
+/// `rendition:flow`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Auto,
+    /// split in pages
+    Paginated,
+    /// the documents are scrolled one after another
+    ScrolledContinuous,
+    /// each document is scrolled on its own
+    ScrolledDoc,
+}
+
+impl Layout {
+    /// Parses a value of `rendition:layout`, like "pre-paginated"
+    pub fn from_value(value: &str) -> Option<Layout> {
+        match value.trim() {
+            "reflowable" => Some(Layout::Reflowable),
+            "pre-paginated" => Some(Layout::PrePaginated),
+            _ => None,
+        }
+    }
+}
+
+impl Orientation {
+    /// Parses a value of `rendition:orientation`, like "landscape"
+    pub fn from_value(value: &str) -> Option<Orientation> {
+        match value.trim() {
+            "auto" => Some(Orientation::Auto),
+            "landscape" => Some(Orientation::Landscape),
+            "portrait" => Some(Orientation::Portrait),
+            _ => None,
+        }
+    }
+}
+
+impl Spread {
+    /// Parses a value of `rendition:spread`, like "none"
+    pub fn from_value(value: &str) -> Option<Spread> {
+        match value.trim() {
+            "auto" => Some(Spread::Auto),
+            "none" => Some(Spread::None),
+            "landscape" => Some(Spread::Landscape),
+            "both" | "portrait" => Some(Spread::Both),
+            _ => None,
+        }
+    }
+}
+
+impl Flow {
+    /// Parses a value of `rendition:flow`, like "scrolled-doc"
+    pub fn from_value(value: &str) -> Option<Flow> {
+        match value.trim() {
+            "auto" => Some(Flow::Auto),
+            "paginated" => Some(Flow::Paginated),
+            "scrolled-continuous" => Some(Flow::ScrolledContinuous),
+            "scrolled-doc" => Some(Flow::ScrolledDoc),
+            _ => None,
+        }
+    }
+}
+
+/// The rendering of the publication or of a spine item. The settings that
+/// aren't declared, or have an unknown value, keep the defaults of the
+/// specification: reflowable, and auto for the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RenditionSettings {
+    pub layout: Layout,
+    pub orientation: Orientation,
+    pub spread: Spread,
+    pub flow: Flow,
+}
+
+impl Default for RenditionSettings {
+    fn default() -> RenditionSettings {
+        RenditionSettings {
+            layout: Layout::Reflowable,
+            orientation: Orientation::Auto,
+            spread: Spread::Auto,
+            flow: Flow::Auto,
+        }
+    }
+}
+
+impl RenditionSettings {
+    /// Returns true for the pre-paginated content, shown as fixed pages
+    pub fn is_fixed_layout(&self) -> bool {
+        self.layout == Layout::PrePaginated
+    }
+
+    /// Returns the settings with the overrides of the spine item
+    /// `properties`, like "rendition:layout-pre-paginated" or
+    /// "rendition:spread-none". The properties without prefix, like
+    /// "layout-pre-paginated", are accepted too.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::rendition::{Layout, RenditionSettings, Spread};
+    ///
+    /// let properties = vec![
+    ///     String::from("rendition:layout-pre-paginated"),
+    ///     String::from("spread-none"),
+    ///     String::from("page-spread-left"),
+    /// ];
+    /// let settings = RenditionSettings::default().with_overrides(&properties);
+    /// assert_eq!(Layout::PrePaginated, settings.layout);
+    /// assert_eq!(Spread::None, settings.spread);
+    /// ```
+    pub fn with_overrides(&self, properties: &[String]) -> RenditionSettings {
+        let mut settings = *self;
+        for property in properties.iter() {
+            let property = property.strip_prefix("rendition:").unwrap_or(property);
+            if let Some(value) = property.strip_prefix("layout-") {
+                settings.layout = Layout::from_value(value).unwrap_or(settings.layout);
+            } else if let Some(value) = property.strip_prefix("orientation-") {
+                settings.orientation =
+                    Orientation::from_value(value).unwrap_or(settings.orientation);
+            } else if let Some(value) = property.strip_prefix("spread-") {
+                settings.spread = Spread::from_value(value).unwrap_or(settings.spread);
+            } else if let Some(value) = property.strip_prefix("flow-") {
+                settings.flow = Flow::from_value(value).unwrap_or(settings.flow);
+            }
+        }
+        settings
+    }
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the rendering of the publication declared in the metadata.
+    /// See `RenditionSettings`.
+    pub fn rendition(&self) -> RenditionSettings {
+        let value = |property: &str| {
+            self.mdata_items_by_property(property)
+                .first()
+                .map(|item| item.value.clone())
+                .unwrap_or_default()
+        };
+        let defaults = RenditionSettings::default();
+        RenditionSettings {
+            layout: Layout::from_value(&value("rendition:layout")).unwrap_or(defaults.layout),
+            orientation: Orientation::from_value(&value("rendition:orientation"))
+                .unwrap_or(defaults.orientation),
+            spread: Spread::from_value(&value("rendition:spread")).unwrap_or(defaults.spread),
+            flow: Flow::from_value(&value("rendition:flow")).unwrap_or(defaults.flow),
+        }
+    }
+
+    /// Returns the rendering of the spine item `index`, the one of the
+    /// publication with the overrides of the itemref. None if the index
+    /// isn't valid.
+    pub fn spine_rendition(&self, index: usize) -> Option<RenditionSettings> {
+        let item = self.spine.get(index)?;
+        Some(self.rendition().with_overrides(&item.properties))
+    }
+}
+
+/// A rendition of the publication, a rootfile of the `container.xml`. The
+/// attributes of the EPUB Multiple-Rendition Publications spec tell the
+/// reading systems which rendition to show, like a fixed layout rendition
+/// for the tablets and a reflowable one for the phones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rendition {
+    /// the full path of the rootfile in the archive
+    pub path: PathBuf,
+    /// the media type of the rootfile, `PACKAGE_MIME` for the package
+    /// documents
+    pub media_type: String,
+    /// `rendition:label`, the human readable name of the rendition
+    pub label: Option<String>,
+    /// `rendition:layout`
+    pub layout: Option<Layout>,
+    /// `rendition:language`, the language of the content
+    pub language: Option<String>,
+    /// `rendition:media`, the css media query the device must match, like
+    /// "(orientation: landscape)"
+    pub media: Option<String>,
+    /// `rendition:accessMode`, like "textual" or "visual"
+    pub access_mode: Option<String>,
+}
+
+impl Rendition {
+    /// Returns true if the rootfile is a package document that can be
+    /// opened, and not another format like a pdf
+    pub fn is_package(&self) -> bool {
+        self.media_type.is_empty() || self.media_type == PACKAGE_MIME
+    }
+}
+
+/// Returns the rootfiles of the `container`, in document order
+pub(crate) fn get_rootfiles(container: &[u8]) -> Result<Vec<Rendition>, EpubError> {
+    let root =
+        xmlutils::XMLReader::parse(container).map_err(|e| EpubError::InvalidContainer(e.error))?;
+    let mut renditions = vec![];
+    collect_rootfiles(&root.borrow(), &mut renditions);
+    if renditions.is_empty() {
+        return Err(EpubError::InvalidContainer(String::from("tag not found")));
+    }
+    Ok(renditions)
+}
+
+fn collect_rootfiles(node: &XMLNode, renditions: &mut Vec<Rendition>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name != "rootfile" {
+            collect_rootfiles(&child, renditions);
+            continue;
+        }
+        // the rootfiles without path are skipped, like the parser did
+        // with the first one
+        let path = match child.get_attr("full-path") {
+            Ok(path) => path,
+            Err(_) => continue,
+        };
+        let attr = |name: &str| child.get_attr(name).ok().filter(|v| !v.trim().is_empty());
+        renditions.push(Rendition {
+            path: PathBuf::from(path),
+            media_type: attr("media-type").unwrap_or_default(),
+            label: attr("label"),
+            layout: attr("layout").and_then(|layout| Layout::from_value(&layout)),
+            language: attr("language"),
+            media: attr("media"),
+            access_mode: attr("accessMode"),
+        });
+    }
+}
+
+/// A location in a rendition of the mapping document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MappedLocation {
+    /// the full path of the package document of the rendition
+    pub rootfile: PathBuf,
+    /// the position in the rendition, like `epubcfi(/6/4[page2]!)`
+    pub cfi: String,
+}
+
+impl MappedLocation {
+    /// Returns the spine index of the location in its rendition, from the
+    /// itemref step of the CFI
+    pub fn spine_index(&self) -> Option<usize> {
+        cfi::spine_index(&self.cfi)
+    }
+}
+
+/// The rendition mapping document of a publication with several
+/// renditions. Each unit is a list of equivalent locations, one for each
+/// rendition, like a page of the fixed layout rendition and the chapter
+/// with its text in the reflowable one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RenditionMapping {
+    /// the full path of the mapping document in the archive
+    pub path: PathBuf,
+    /// the `ul` lists of the `resource-map` nav, in document order
+    pub units: Vec<Vec<MappedLocation>>,
+}
+
+impl RenditionMapping {
+    /// Parses the mapping document `content` at `path`
+    pub(crate) fn parse(content: &[u8], path: &Path) -> Result<RenditionMapping, EpubError> {
+        let root = xmlutils::XMLReader::parse(content)?;
+        let root = root.borrow();
+        let mut units = vec![];
+        if !find_resource_map(&root, path, &mut units) {
+            collect_units(&root, path, &mut units);
+        }
+        units.retain(|unit| !unit.is_empty());
+        Ok(RenditionMapping {
+            path: path.to_path_buf(),
+            units,
+        })
+    }
+
+    /// Returns the location in the rendition `to` equivalent to the spine
+    /// item `index` of the rendition `from`, both the paths of their
+    /// package documents. The locations are compared by spine item, so
+    /// without a unit for the item, the unit of the closest previous item
+    /// is used. None if no unit has locations in both renditions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::rendition::{MappedLocation, RenditionMapping};
+    /// # use std::path::{Path, PathBuf};
+    /// let location = |rootfile: &str, cfi: &str| MappedLocation {
+    ///     rootfile: PathBuf::from(rootfile),
+    ///     cfi: String::from(cfi),
+    /// };
+    /// let mapping = RenditionMapping {
+    ///     path: PathBuf::from("mapping.xhtml"),
+    ///     units: vec![
+    ///         vec![location("fixed.opf", "epubcfi(/6/2!)"), location("text.opf", "epubcfi(/6/2!)")],
+    ///         vec![location("fixed.opf", "epubcfi(/6/6!)"), location("text.opf", "epubcfi(/6/4!)")],
+    ///     ],
+    /// };
+    /// let (fixed, text) = (Path::new("fixed.opf"), Path::new("text.opf"));
+    /// assert_eq!(Some(1), mapping.translate(fixed, 2, text).unwrap().spine_index());
+    /// // the second page is still in the first chapter
+    /// assert_eq!(Some(0), mapping.translate(fixed, 1, text).unwrap().spine_index());
+    /// ```
+    pub fn translate(&self, from: &Path, index: usize, to: &Path) -> Option<&MappedLocation> {
+        let mut best: Option<(usize, &MappedLocation)> = None;
+        for unit in self.units.iter() {
+            let start = unit
+                .iter()
+                .filter(|location| location.rootfile == from)
+                .filter_map(|location| location.spine_index())
+                .find(|start| *start <= index);
+            let target = unit.iter().find(|location| location.rootfile == to);
+            if let (Some(start), Some(target)) = (start, target) {
+                if best.map_or(true, |(best, _)| start > best) {
+                    best = Some((start, target));
+                }
+            }
+        }
+        best.map(|(_, target)| target)
+    }
+}
+
+/// Collects the units of the first `nav` with the epub:type
+/// "resource-map" under `node`, returns true if it's found
+fn find_resource_map(node: &XMLNode, mapping: &Path, units: &mut Vec<Vec<MappedLocation>>) -> bool {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        let is_map = child.name.local_name == "nav"
+            && child
+                .get_attr("type")
+                .map(|t| t.split_whitespace().any(|t| t == "resource-map"))
+                .unwrap_or(false);
+        if is_map {
+            collect_units(&child, mapping, units);
+            return true;
+        }
+        if find_resource_map(&child, mapping, units) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Adds a unit for each `ul` under `node`, with its links
+fn collect_units(node: &XMLNode, mapping: &Path, units: &mut Vec<Vec<MappedLocation>>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name == "ul" {
+            let mut unit = vec![];
+            collect_locations(&child, mapping, &mut unit, units);
+            units.push(unit);
+        } else {
+            collect_units(&child, mapping, units);
+        }
+    }
+}
+
+/// Adds the links under `node` to the `unit`, and the nested lists to the
+/// `units`
+fn collect_locations(
+    node: &XMLNode,
+    mapping: &Path,
+    unit: &mut Vec<MappedLocation>,
+    units: &mut Vec<Vec<MappedLocation>>,
+) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        match child.name.local_name.as_str() {
+            "ul" => {
+                let mut nested = vec![];
+                collect_locations(&child, mapping, &mut nested, units);
+                units.push(nested);
+            }
+            "a" => {
+                let link = child.get_attr("href").unwrap_or_default();
+                if let (_, Some(fragment)) = href::split_fragment(&link) {
+                    let fragment = percent_decode_str(fragment).decode_utf8_lossy();
+                    if fragment.starts_with("epubcfi(") {
+                        unit.push(MappedLocation {
+                            rootfile: href::resolve(mapping, &link),
+                            cfi: fragment.to_string(),
+                        });
+                    }
+                }
+            }
+            _ => collect_locations(&child, mapping, unit, units),
+        }
+    }
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the rendition mapping document linked from the container,
+    /// None if it doesn't have one.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the mapping document can't be read or parsed.
+    pub fn rendition_mapping(&mut self) -> Result<Option<RenditionMapping>, EpubError> {
+        let container = self.archive.get_container_file()?;
+        let root = xmlutils::XMLReader::parse(container.as_slice())
+            .map_err(|e| EpubError::InvalidContainer(e.error))?;
+        let link = match find_mapping_link(&root.borrow()) {
+            Some(link) => link,
+            None => return Ok(None),
+        };
+        // the paths of the container are relative to the root
+        let (path, _) = href::split_fragment(&link);
+        let path = PathBuf::from(href::normalize(path));
+        let content = self.archive.get_entry(&path)?;
+        RenditionMapping::parse(&content, &path).map(Some)
+    }
+
+    /// Returns the spine index in the rendition `rendition` equivalent to
+    /// the current page of the open one, from the rendition mapping
+    /// document. See `RenditionMapping::translate`. None if the publication
+    /// doesn't have a mapping document or it doesn't map the current page.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::InvalidOperation` if the rendition doesn't
+    /// exist, or an error if the mapping document can't be read.
+    pub fn map_current_page(&mut self, rendition: usize) -> Result<Option<usize>, EpubError> {
+        let to = match self.renditions().get(rendition) {
+            Some(to) => to.path.clone(),
+            None => {
+                return Err(EpubError::InvalidOperation(format!(
+                    "the container doesn't have the rendition {}",
+                    rendition
+                )))
+            }
+        };
+        let mapping = match self.rendition_mapping()? {
+            Some(mapping) => mapping,
+            None => return Ok(None),
+        };
+        let from = self.root_file.clone();
+        Ok(mapping
+            .translate(&from, self.get_current_page(), &to)
+            .and_then(|location| location.spine_index()))
+    }
+}
+
+/// Returns the href of the `link` element of the container with the rel
+/// "mapping"
+fn find_mapping_link(node: &XMLNode) -> Option<String> {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        let is_mapping = child.name.local_name == "link"
+            && child
+                .get_attr("rel")
+                .map(|rel| rel.split_whitespace().any(|rel| rel == "mapping"))
+                .unwrap_or(false);
+        if is_mapping {
+            if let Ok(href) = child.get_attr("href") {
+                return Some(href);
+            }
+        }
+        if let Some(href) = find_mapping_link(&child) {
+            return Some(href);
+        }
+    }
+    None
+}
@@ -0,0 +1,250 @@
+//! Best effort repair of broken epubs.
+
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::archive::guess_media_type;
+use crate::builder::{encode_href, relative_path};
+use crate::doc::{EpubDoc, NavPoint, NCX_MIME};
+use crate::error::EpubError;
+use crate::href;
+use crate::navigation;
+use crate::xmlutils;
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Fixes the common defects of broken epubs and returns a description
+    /// of each fix:
+    ///
+    /// * writes the mimetype file if it's missing or wrong
+    /// * removes the spine items pointing to missing manifest items
+    /// * adds manifest items for the files of the archive that aren't in
+    ///   the manifest, and points the spine to the toc.ncx
+    /// * regenerates the toc.ncx, and the navigation document in EPUB3
+    ///   packages, if they are missing or point to missing files. Entries
+    ///   pointing to missing files are removed, and an empty toc is built
+    ///   from the headings of the spine documents.
+    ///
+    /// Like the other modifications, the fixes are kept in memory and the
+    /// corrected epub is written with `save_as`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let mut doc = EpubDoc::new("tests/docs/Metamorphosis-jackson.epub").unwrap();
+    /// doc.modify_entry("mimetype", "application/zip").unwrap();
+    /// let fixes = doc.repair().unwrap();
+    /// assert_eq!(vec!["wrote the mimetype file"], fixes);
+    /// // doc.save_as("fixed.epub") writes the corrected epub
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the package document can't be parsed or if a
+    /// document can't be read.
+    pub fn repair(&mut self) -> Result<Vec<String>, EpubError> {
+        let mut fixes = vec![];
+
+        let mimetype = self.archive.get_entry("mimetype").unwrap_or_default();
+        if mimetype != b"application/epub+zip" {
+            self.archive
+                .modify_entry("mimetype", "application/epub+zip");
+            fixes.push(String::from("wrote the mimetype file"));
+        }
+
+        self.repair_package(&mut fixes)?;
+        self.repair_navigation(&mut fixes)?;
+        Ok(fixes)
+    }
+
+    /// Fixes the manifest and the spine of the package document
+    fn repair_package(&mut self, fixes: &mut Vec<String>) -> Result<(), EpubError> {
+        let opf = self.archive.get_entry(&self.root_file)?;
+        let root = xmlutils::XMLReader::parse(opf.as_slice())?;
+        let root = root.borrow();
+        let manifest = root.find("manifest")?;
+        let spine = root.find("spine")?;
+        let ids: HashSet<String> = manifest
+            .borrow()
+            .childs
+            .iter()
+            .filter_map(|item| item.borrow().get_attr("id").ok())
+            .collect();
+
+        let mut changed = false;
+        let dangling: Vec<String> = spine
+            .borrow()
+            .childs
+            .iter()
+            .filter_map(|itemref| itemref.borrow().get_attr("idref").ok())
+            .filter(|idref| !ids.contains(idref))
+            .collect();
+        if !dangling.is_empty() {
+            spine.borrow_mut().childs.retain(|itemref| {
+                let idref = itemref.borrow().get_attr("idref");
+                matches!(idref, Ok(idref) if ids.contains(&idref))
+            });
+            for idref in dangling {
+                fixes.push(format!("removed the spine item {}", idref));
+            }
+            changed = true;
+        }
+
+        let known: HashSet<PathBuf> = self.resources.values().map(|(p, _)| p.clone()).collect();
+        let mut new_ids = ids.clone();
+        let mut names = self.archive.files.clone();
+        names.sort();
+        for name in names.iter() {
+            let path = PathBuf::from(href::normalize(name));
+            if name.ends_with('/')
+                || name == "mimetype"
+                || name.starts_with("META-INF/")
+                || path == self.root_file
+                || known.contains(&path)
+            {
+                continue;
+            }
+            let mut id = item_id(&path);
+            while new_ids.contains(&id) {
+                id.push('_');
+            }
+            new_ids.insert(id.clone());
+            let href = encode_href(&relative_path(&self.root_base, &path));
+            let mime = guess_media_type(name);
+            let item = manifest.borrow().new_child(
+                "item",
+                &[("id", &id), ("href", &href), ("media-type", mime)],
+            );
+            manifest.borrow_mut().add_child(item);
+            fixes.push(format!("added {} to the manifest", path.display()));
+            changed = true;
+        }
+
+        let toc = spine.borrow().get_attr("toc").ok();
+        let ncx = self
+            .resources
+            .iter()
+            .find(|(_, (_, mime))| mime == NCX_MIME);
+        if let (None, Some((id, _))) = (toc.filter(|toc| ids.contains(toc)), ncx) {
+            spine.borrow_mut().set_attr("toc", id);
+            fixes.push(format!("pointed the spine to the toc.ncx {}", id));
+            changed = true;
+        }
+
+        if !changed {
+            return Ok(());
+        }
+        let content = root.to_xml()?;
+        let root_file = self.root_file.clone();
+        self.modify_entry(root_file, content)
+    }
+
+    /// Regenerates the toc.ncx and the navigation document if they are
+    /// missing or stale
+    fn repair_navigation(&mut self, fixes: &mut Vec<String>) -> Result<(), EpubError> {
+        let opf = self.archive.get_entry(&self.root_file)?;
+        let root = xmlutils::XMLReader::parse(opf.as_slice())?;
+        let epub3 = root
+            .borrow()
+            .get_attr("version")
+            .map(|v| v.starts_with('3'))
+            .unwrap_or(false);
+
+        let has_ncx = self.resources.values().any(|(_, mime)| mime == NCX_MIME);
+        let has_nav = self.get_nav_path().is_some();
+        let paths: HashSet<PathBuf> = self.resources.values().map(|(p, _)| p.clone()).collect();
+        let toc = prune_toc(&self.toc, &paths);
+        let stale = count(&toc) != count(&self.toc);
+
+        if epub3 {
+            if has_nav && !stale {
+                return Ok(());
+            }
+        } else if has_ncx && !stale {
+            return Ok(());
+        }
+
+        let mut toc = toc;
+        if toc.is_empty() {
+            toc = self.toc_from_headings()?;
+        }
+        if toc.is_empty() {
+            toc = self.spine_navpoints();
+        }
+        self.toc = toc;
+        self.update_navigation()?;
+        fixes.push(match (stale, epub3 && !has_nav, !has_ncx) {
+            (true, _, _) => String::from("removed the toc entries pointing to missing files"),
+            (_, true, _) => String::from("created the navigation document"),
+            _ => String::from("created the toc.ncx"),
+        });
+        Ok(())
+    }
+
+    /// Toc with an entry for each spine document, named after the file
+    fn spine_navpoints(&self) -> Vec<NavPoint> {
+        let mut toc: Vec<NavPoint> = self
+            .spine
+            .iter()
+            .filter_map(|item| self.resources.get(&item.idref))
+            .map(|(path, _)| NavPoint {
+                label: path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                content: path.clone(),
+                children: vec![],
+                play_order: 0,
+            })
+            .collect();
+        navigation::renumber(&mut toc, &mut 0);
+        toc
+    }
+}
+
+/// Copies the toc without the entries pointing to files that aren't in
+/// `paths`. Entries nested under a removed entry are moved up.
+fn prune_toc(toc: &[NavPoint], paths: &HashSet<PathBuf>) -> Vec<NavPoint> {
+    let mut navpoints = vec![];
+    for nav in toc {
+        let children = prune_toc(&nav.children, paths);
+        let content = nav.content.display().to_string();
+        let (path, _) = href::split_fragment(&content);
+        if paths.contains(Path::new(path)) {
+            navpoints.push(NavPoint {
+                label: nav.label.clone(),
+                content: nav.content.clone(),
+                children,
+                play_order: nav.play_order,
+            });
+        } else {
+            navpoints.extend(children);
+        }
+    }
+    navpoints
+}
+
+/// Number of entries in the toc, including the nested ones
+fn count(toc: &[NavPoint]) -> usize {
+    toc.iter().map(|nav| 1 + count(&nav.children)).sum()
+}
+
+/// Manifest id for the file in `path`, from the file name
+fn item_id(path: &Path) -> String {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let id: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' || c == '.' => c,
+            _ => '_',
+        })
+        .collect();
+    match id.chars().next() {
+        Some(c) if c.is_alphabetic() || c == '_' => id,
+        _ => format!("id-{}", id),
+    }
+}
@@ -0,0 +1,88 @@
+//! Detection of the scripted content documents.
+
+use regex::Regex;
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+
+use crate::doc::{EpubDoc, ScriptedDocument};
+use crate::error::EpubError;
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the documents with scripts, the xhtml and svg documents of
+    /// the manifest with `script` elements, event handler attributes or
+    /// `javascript:` urls, and the ones with the "scripted" property, sorted
+    /// by path. The reading systems can sandbox or refuse them before
+    /// rendering.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// assert!(doc.scripted_report().unwrap().is_empty());
+    ///
+    /// let chapter = "<html><body onload=\"start()\"><script src=\"../js/app.js\"/></body></html>";
+    /// doc.modify_entry("OEBPS/Text/001.xhtml", chapter).unwrap();
+    /// let report = doc.scripted_report().unwrap();
+    /// assert_eq!("001.xhtml", report[0].id);
+    /// assert_eq!(1, report[0].scripts);
+    /// assert_eq!(vec!["../js/app.js"], report[0].external_scripts);
+    /// assert_eq!(1, report[0].event_handlers);
+    /// // the manifest doesn't declare the scripts
+    /// assert!(report[0].is_mismatch());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn scripted_report(&mut self) -> Result<Vec<ScriptedDocument>, EpubError> {
+        let mut documents: Vec<(PathBuf, String)> = self
+            .resources
+            .iter()
+            .filter(|(_, (_, mime))| mime.contains("html") || mime.contains("svg"))
+            .map(|(id, (path, _))| (path.clone(), id.clone()))
+            .collect();
+        documents.sort();
+
+        let scripts =
+            Regex::new(r"(?i)<(?:[a-z][\w.-]*:)?script\b([^<>]*)>").expect("valid script regex");
+        let src = Regex::new(r#"(?i)\s(?:xlink:)?(?:src|href)\s*=\s*["']([^"']*)["']"#)
+            .expect("valid src regex");
+        let tags = Regex::new(r"<[A-Za-z][^<>]*>").expect("valid tag regex");
+        let handler = Regex::new(r#"(?i)\son[a-z]+\s*=\s*["']"#).expect("valid handler regex");
+        let javascript = Regex::new(r#"(?i)=\s*["']\s*javascript:"#).expect("valid url regex");
+
+        let mut report = vec![];
+        for (path, id) in documents {
+            let declared = self.resource_has_property(&id, "scripted");
+            let content = match self.archive.get_entry(&path) {
+                Ok(content) => content,
+                Err(_) if !declared => continue,
+                Err(e) => return Err(e),
+            };
+            let content = String::from_utf8_lossy(&content);
+            let mut document = ScriptedDocument {
+                id,
+                path,
+                declared,
+                scripts: 0,
+                external_scripts: vec![],
+                event_handlers: 0,
+                javascript_urls: javascript.find_iter(&content).count(),
+            };
+            for script in scripts.captures_iter(&content) {
+                document.scripts += 1;
+                if let Some(src) = src.captures(&script[1]) {
+                    document.external_scripts.push(src[1].to_string());
+                }
+            }
+            for tag in tags.find_iter(&content) {
+                document.event_handlers += handler.find_iter(tag.as_str()).count();
+            }
+            if document.declared || document.has_scripts() {
+                report.push(document);
+            }
+        }
+        Ok(report)
+    }
+}
@@ -0,0 +1,294 @@
+//! The full text search of the book, in the plain text of the linear spine
+//! items, see `text::plain_text`.
+//!
+//! The hits have their offset in the text of their spine item, like
+//! `EpubDoc::get_text` returns it, and an href to the closest element with
+//! an id before them, to link to them from a search result. The search of
+//! a regex can also be in the source of the documents, see
+//! `SearchOptions::raw`.
+
+use std::io::{Read, Seek};
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::href;
+use crate::text::{attr, paragraphs, tokens, TextOptions, Token};
+
+/// Options of `EpubDoc::search_with` and `EpubDoc::search_regex_with`
+///
+/// # Examples
+///
+/// ```
+/// use epub::search::SearchOptions;
+///
+/// let mut options = SearchOptions::new();
+/// options
+///     .case_sensitive(true)
+///     .context(20)
+///     .highlight("**", "**");
+/// ```
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    case_sensitive: bool,
+    context: usize,
+    raw: bool,
+    highlight: (String, String),
+}
+
+impl Default for SearchOptions {
+    fn default() -> SearchOptions {
+        SearchOptions {
+            case_sensitive: false,
+            context: 40,
+            raw: false,
+            highlight: (String::from("<mark>"), String::from("</mark>")),
+        }
+    }
+}
+
+impl SearchOptions {
+    /// Creates the default options: the text of the documents is searched,
+    /// the case is ignored, the snippets have 40 characters on each side of
+    /// the hit and the highlighted hits are in a `mark` element
+    pub fn new() -> SearchOptions {
+        SearchOptions::default()
+    }
+
+    /// Matches the case of the query. The regexes have their own flags.
+    pub fn case_sensitive(&mut self, case_sensitive: bool) -> &mut Self {
+        self.case_sensitive = case_sensitive;
+        self
+    }
+
+    /// Sets the number of characters of the snippets before and after the
+    /// hit
+    pub fn context(&mut self, chars: usize) -> &mut Self {
+        self.context = chars;
+        self
+    }
+
+    /// Searches the xhtml source of the documents, as returned by
+    /// `EpubDoc::get_resource_str`, instead of their text. The offsets of
+    /// the hits are in the source.
+    pub fn raw(&mut self, raw: bool) -> &mut Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Sets the text around the hits in the highlighted snippets
+    pub fn highlight(&mut self, before: &str, after: &str) -> &mut Self {
+        self.highlight = (before.to_string(), after.to_string());
+        self
+    }
+}
+
+/// A hit of a search
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchHit {
+    /// the index of the spine item of the hit
+    pub spine_index: usize,
+    /// the full path of the document, with the fragment of the closest
+    /// element with an id before the hit
+    pub href: PathBuf,
+    /// the offset of the hit in the text of the spine item, or in its
+    /// source with `SearchOptions::raw`, in characters
+    pub char_offset: usize,
+    /// the text of the hit
+    pub text: String,
+    /// the capture groups of the regex, from the first one, None for the
+    /// groups that didn't match
+    pub captures: Vec<Option<String>>,
+    /// the text around the hit, on a single line
+    pub snippet: String,
+    /// the snippet, with the hit between the marks of
+    /// `SearchOptions::highlight`
+    pub highlighted: String,
+}
+
+/// Returns the text before `start` and after `end` in `text`, up to
+/// `context` characters on each side, with the hit between `marks`, on a
+/// single line
+fn snippet(text: &str, start: usize, end: usize, context: usize, marks: (&str, &str)) -> String {
+    let before: Vec<char> = text[..start].chars().rev().take(context).collect();
+    let before: String = before.into_iter().rev().collect();
+    let after: String = text[end..].chars().take(context).collect();
+    let snippet = format!(
+        "{}{}{}{}{}",
+        before,
+        marks.0,
+        &text[start..end],
+        marks.1,
+        after
+    );
+    snippet.replace(['\r', '\n'], " ")
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Searches the `query` in the linear spine items, ignoring the case,
+    /// see `search_with`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let hits = doc.search("abrió los ojos").unwrap();
+    /// assert_eq!(2, hits[0].spine_index);
+    /// assert!(hits[0].snippet.contains("José Luís abrió los ojos"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn search(&mut self, query: &str) -> Result<Vec<SearchHit>, EpubError> {
+        self.search_with(query, &SearchOptions::default())
+    }
+
+    /// Searches the `query` in the linear spine items with the `options`,
+    /// and returns the hits in reading order. An empty query has no hits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn search_with(
+        &mut self,
+        query: &str,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>, EpubError> {
+        if query.is_empty() {
+            return Ok(vec![]);
+        }
+        let case = if options.case_sensitive { "" } else { "(?i)" };
+        let re =
+            Regex::new(&format!("{}{}", case, regex::escape(query))).expect("valid escaped query");
+        self.search_regex_with(&re, options)
+    }
+
+    /// Searches the regex `re` in the linear spine items, see
+    /// `search_regex_with`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// use regex::Regex;
+    ///
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let re = Regex::new(r"abrió los (\w+)").unwrap();
+    /// let hits = doc.search_regex(&re).unwrap();
+    /// assert_eq!(Some("ojos"), hits[0].captures[0].as_deref());
+    /// assert!(hits[0].highlighted.contains("<mark>abrió los ojos</mark>"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn search_regex(&mut self, re: &Regex) -> Result<Vec<SearchHit>, EpubError> {
+        self.search_regex_with(re, &SearchOptions::default())
+    }
+
+    /// Searches the regex `re` in the linear spine items with the `options`,
+    /// in their text or in their source, and returns the hits in reading
+    /// order with their capture groups. The empty matches aren't hits.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn search_regex_with(
+        &mut self,
+        re: &Regex,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>, EpubError> {
+        let mut hits = vec![];
+        for index in 0..self.spine.len() {
+            if !self.spine[index].linear {
+                continue;
+            }
+            hits.extend(self.search_spine_item(index, re, options)?);
+        }
+        Ok(hits)
+    }
+
+    /// The hits of `re` in the text of the spine item `index`
+    fn search_spine_item(
+        &mut self,
+        index: usize,
+        re: &Regex,
+        options: &SearchOptions,
+    ) -> Result<Vec<SearchHit>, EpubError> {
+        let path = match self.resources.get(&self.spine[index].idref) {
+            Some((path, _)) => path.clone(),
+            None => return Ok(vec![]),
+        };
+        let html = self.spine_str(index)?;
+        // the start of the paragraphs in the text, and in the source
+        let mut text = String::new();
+        let mut starts = vec![];
+        if options.raw {
+            text.push_str(&html);
+        } else {
+            for (paragraph, source) in paragraphs(&html, &TextOptions::default()) {
+                if !text.is_empty() {
+                    text.push_str("\n\n");
+                }
+                starts.push((text.len(), source));
+                text.push_str(&paragraph);
+            }
+        }
+        let ids: Vec<(usize, String)> = tokens(&html)
+            .into_iter()
+            .filter_map(|(range, token)| match token {
+                Token::Start { attrs, .. } => attr(&attrs, "id").map(|id| (range.start, id)),
+                _ => None,
+            })
+            .collect();
+
+        let mut hits = vec![];
+        let (mut byte, mut chars) = (0, 0);
+        for captures in re.captures_iter(&text) {
+            let found = match captures.get(0) {
+                Some(found) if !found.is_empty() => found,
+                _ => continue,
+            };
+            chars += text[byte..found.start()].chars().count();
+            byte = found.start();
+            let source = if options.raw {
+                found.start()
+            } else {
+                starts
+                    .iter()
+                    .rev()
+                    .find(|(start, _)| *start <= found.start())
+                    .map(|(_, source)| *source)
+                    .unwrap_or(0)
+            };
+            let href = match ids.iter().rev().find(|(start, _)| *start <= source) {
+                Some((_, id)) => href::resolve_with_fragment(&path, &format!("#{}", id)),
+                None => path.clone(),
+            };
+            hits.push(SearchHit {
+                spine_index: index,
+                href,
+                char_offset: chars,
+                text: found.as_str().to_string(),
+                captures: captures
+                    .iter()
+                    .skip(1)
+                    .map(|group| group.map(|group| group.as_str().to_string()))
+                    .collect(),
+                snippet: snippet(&text, found.start(), found.end(), options.context, ("", "")),
+                highlighted: snippet(
+                    &text,
+                    found.start(),
+                    found.end(),
+                    options.context,
+                    (&options.highlight.0, &options.highlight.1),
+                ),
+            });
+        }
+        Ok(hits)
+    }
+}
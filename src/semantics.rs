@@ -0,0 +1,367 @@
+//! The structural semantics of the content documents, the `epub:type`
+//! attributes of their elements.
+//!
+//! The types tell what an element is in the book, like
+//! `<section epub:type="chapter">`, `<aside epub:type="footnote">` or
+//! `<body epub:type="frontmatter titlepage">`, so the readers can show the
+//! notes in pop-ups or skip the front matter.
+//!
+//! # Examples
+//!
+//! ```
+//! # use epub::doc::EpubDoc;
+//! # let mut doc = EpubDoc::new("test.epub").unwrap();
+//! let chapter = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+//!   <body epub:type="bodymatter"><section id="c1" epub:type="chapter">
+//!   <p>The storm<a href="notes.xhtml#n1" epub:type="noteref">1</a></p></section></body></html>"#;
+//! doc.modify_entry("OEBPS/Text/001.xhtml", chapter).unwrap();
+//!
+//! let elements = doc.semantic_elements("001.xhtml").unwrap();
+//! assert_eq!(3, elements.len());
+//! assert_eq!("section", elements[1].element);
+//! assert_eq!(vec!["chapter"], elements[1].types);
+//! assert_eq!(Some("OEBPS/Text/notes.xhtml#n1".into()), elements[2].href);
+//!
+//! let index = doc.semantic_index().unwrap();
+//! let start = index.first_of_type("bodymatter").unwrap();
+//! assert_eq!(std::path::Path::new("OEBPS/Text/001.xhtml"), start.href_to());
+//! ```
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::anchor::element_source;
+use crate::builder::OPS_NS;
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::href;
+use crate::text::decode_entities;
+use crate::xmlutils::{self, XMLNode};
+
+/// An element of a content document with an `epub:type`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SemanticElement {
+    /// the manifest id of the document
+    pub document: String,
+    /// the full path of the document in the archive
+    pub path: PathBuf,
+    /// the local name of the element, like "section" or "aside"
+    pub element: String,
+    /// the id of the element
+    pub id: Option<String>,
+    /// the types of the element, like "chapter" or "footnote", with the
+    /// prefix if they have one
+    pub types: Vec<String>,
+    /// the href of the element, for the links like the noterefs, resolved
+    /// to the full path with the fragment. The external urls aren't
+    /// resolved.
+    pub href: Option<PathBuf>,
+}
+
+impl SemanticElement {
+    /// Returns true if the element has the `epub_type`
+    pub fn has_type(&self, epub_type: &str) -> bool {
+        self.types.iter().any(|t| t == epub_type)
+    }
+
+    /// Returns the full path of the element, with its id as fragment, to
+    /// use with `EpubDoc::go_to_href`. The path of the document for the
+    /// elements without id.
+    pub fn href_to(&self) -> PathBuf {
+        match self.id {
+            Some(ref id) => PathBuf::from(format!("{}#{}", self.path.display(), id)),
+            None => self.path.clone(),
+        }
+    }
+}
+
+/// A note reference of the content, with the note it points to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Footnote {
+    /// the manifest id of the document with the reference
+    pub document: String,
+    /// the full path of the reference, with its id as fragment if it has
+    /// one, see `SemanticElement::href_to`
+    pub reference: PathBuf,
+    /// the text of the reference, like "1" or "*"
+    pub label: String,
+    /// the full path of the note, with the fragment
+    pub target: PathBuf,
+    /// the epub:types of the note element, like "footnote" or "endnote"
+    pub note_types: Vec<String>,
+    /// the source of the note element, from its start tag to its end tag
+    pub html: String,
+    /// the text of the note, with the whitespace collapsed
+    pub text: String,
+}
+
+/// The semantic elements of the documents of the spine. See
+/// `EpubDoc::semantic_index`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SemanticIndex {
+    /// the elements of every document, in spine and document order
+    pub elements: Vec<SemanticElement>,
+}
+
+impl SemanticIndex {
+    /// Returns the elements with the `epub_type`, in reading order
+    pub fn of_type(&self, epub_type: &str) -> Vec<&SemanticElement> {
+        self.elements
+            .iter()
+            .filter(|element| element.has_type(epub_type))
+            .collect()
+    }
+
+    /// Returns the first element with the `epub_type`, like "bodymatter"
+    /// for the start of the main content
+    pub fn first_of_type(&self, epub_type: &str) -> Option<&SemanticElement> {
+        self.elements
+            .iter()
+            .find(|element| element.has_type(epub_type))
+    }
+
+    /// Returns the elements of the document `id`
+    pub fn of_document(&self, id: &str) -> Vec<&SemanticElement> {
+        self.elements
+            .iter()
+            .filter(|element| element.document == id)
+            .collect()
+    }
+
+    /// Returns the ids of the documents with an element of the `epub_type`,
+    /// in spine order, without duplicates
+    pub fn documents_with_type(&self, epub_type: &str) -> Vec<&str> {
+        let mut documents: Vec<&str> = vec![];
+        for element in self.of_type(epub_type) {
+            if !documents.contains(&element.document.as_str()) {
+                documents.push(&element.document);
+            }
+        }
+        documents
+    }
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the elements with an `epub:type` of the content document
+    /// `id`, in document order.
+    ///
+    /// # Errors
+    ///
+    /// Returns `EpubError::ResourceNotFound` if the document isn't in the
+    /// manifest, or an error if it can't be read or parsed.
+    pub fn semantic_elements(&mut self, id: &str) -> Result<Vec<SemanticElement>, EpubError> {
+        let path = match self.resources.get(id) {
+            Some((path, _)) => path.clone(),
+            None => return Err(EpubError::ResourceNotFound(id.to_string())),
+        };
+        let mut elements = vec![];
+        self.visit_semantic_elements(id, &path, &mut |_, element| elements.push(element))?;
+        Ok(elements)
+    }
+
+    /// Returns the semantic elements of every document of the spine, linear
+    /// or not. See `semantic_elements`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read or parsed.
+    pub fn semantic_index(&mut self) -> Result<SemanticIndex, EpubError> {
+        let ids = self.spine_documents();
+        let mut index = SemanticIndex::default();
+        for id in ids.iter() {
+            index.elements.extend(self.semantic_elements(id)?);
+        }
+        Ok(index)
+    }
+
+    /// Returns the note references of the spine documents, the links with
+    /// the `noteref` epub:type, with the content of the notes they point
+    /// to, in reading order. The notes can be in the same document, like
+    /// footnotes, or in another one, like endnotes. The references to
+    /// elements that don't exist are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let chapter = r##"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+    ///   <body><p>The storm<a href="#n1" epub:type="noteref">1</a></p>
+    ///   <aside id="n1" epub:type="footnote"><p>It was <em>cold</em>.</p></aside></body></html>"##;
+    /// doc.modify_entry("OEBPS/Text/001.xhtml", chapter).unwrap();
+    ///
+    /// let notes = doc.footnotes().unwrap();
+    /// assert_eq!(1, notes.len());
+    /// assert_eq!("1", notes[0].label);
+    /// assert_eq!(vec!["footnote"], notes[0].note_types);
+    /// assert_eq!("It was cold.", notes[0].text);
+    /// assert!(notes[0].html.starts_with("<aside id=\"n1\""));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a spine document can't be read or parsed.
+    pub fn footnotes(&mut self) -> Result<Vec<Footnote>, EpubError> {
+        let mut sources: HashMap<PathBuf, Option<String>> = HashMap::new();
+        let mut footnotes = vec![];
+        for (reference, label) in self.note_references()? {
+            let target = match reference.href {
+                Some(ref target) => target.clone(),
+                None => continue,
+            };
+            let target_str = target.display().to_string();
+            let (path, fragment) = href::split_fragment(&target_str);
+            let fragment = match fragment {
+                Some(fragment) if !fragment.is_empty() => href::normalize(fragment),
+                _ => continue,
+            };
+            let path = PathBuf::from(path);
+            if !sources.contains_key(&path) {
+                let source = self.get_resource_str_by_path(&path).ok();
+                sources.insert(path.clone(), source);
+            }
+            let source = match sources.get(&path) {
+                Some(Some(source)) => source,
+                _ => continue,
+            };
+            let html = match element_source(source, &fragment) {
+                Some(html) => html.to_string(),
+                None => continue,
+            };
+            let note_types = match xmlutils::XMLReader::parse(html.as_bytes()) {
+                Ok(note) => epub_types(&note.borrow()),
+                // the prefix is declared out of the note
+                Err(_) => start_tag_types(&html),
+            };
+            footnotes.push(Footnote {
+                document: reference.document.clone(),
+                reference: reference.href_to(),
+                label,
+                target,
+                note_types,
+                text: html_text(&html),
+                html,
+            });
+        }
+        Ok(footnotes)
+    }
+
+    /// Returns the noterefs of the spine documents, with their text
+    fn note_references(&mut self) -> Result<Vec<(SemanticElement, String)>, EpubError> {
+        let ids = self.spine_documents();
+        let mut references = vec![];
+        for id in ids.iter() {
+            let path = self.resources[id].0.clone();
+            self.visit_semantic_elements(id, &path, &mut |node, element| {
+                if element.has_type("noteref") {
+                    let mut label = String::new();
+                    node_text(node, &mut label);
+                    let label = label.split_whitespace().collect::<Vec<_>>().join(" ");
+                    references.push((element, label));
+                }
+            })?;
+        }
+        Ok(references)
+    }
+
+    /// Returns the ids of the documents of the spine, linear or not,
+    /// without duplicates
+    fn spine_documents(&self) -> Vec<String> {
+        let mut ids: Vec<String> = vec![];
+        for item in self.spine.iter() {
+            if !ids.contains(&item.idref) && self.resources.contains_key(&item.idref) {
+                ids.push(item.idref.clone());
+            }
+        }
+        ids
+    }
+
+    /// Parses the document `id` at `path` and calls `visit` with each
+    /// element with an epub:type
+    fn visit_semantic_elements(
+        &mut self,
+        id: &str,
+        path: &Path,
+        visit: &mut dyn FnMut(&XMLNode, SemanticElement),
+    ) -> Result<(), EpubError> {
+        let content = self.get_resource_by_path(path)?;
+        let (root, _) = xmlutils::XMLReader::parse_recover(&content)?;
+        collect_elements(&root.borrow(), id, path, visit);
+        Ok(())
+    }
+}
+
+/// Returns the values of the `epub:type` attribute of the `node`. The
+/// `type` attributes of html, like the one of the inputs, are ignored.
+pub(crate) fn epub_types(node: &XMLNode) -> Vec<String> {
+    node.attrs
+        .iter()
+        .filter(|attr| {
+            attr.name.local_name == "type"
+                && (attr.name.namespace.as_deref() == Some(OPS_NS)
+                    || attr.name.prefix.as_deref() == Some("epub"))
+        })
+        .flat_map(|attr| attr.value.split_whitespace().map(String::from))
+        .collect()
+}
+
+fn collect_elements(
+    node: &XMLNode,
+    document: &str,
+    path: &Path,
+    visit: &mut dyn FnMut(&XMLNode, SemanticElement),
+) {
+    let types = epub_types(node);
+    if !types.is_empty() {
+        let element = SemanticElement {
+            document: document.to_string(),
+            path: path.to_path_buf(),
+            element: node.name.local_name.clone(),
+            id: node.get_attr("id").ok(),
+            types,
+            href: node.get_attr("href").ok().map(|link| {
+                // external urls are kept as they are
+                if link.contains(':') {
+                    PathBuf::from(link)
+                } else {
+                    href::resolve_with_fragment(path, &link)
+                }
+            }),
+        };
+        visit(node, element);
+    }
+    for child in node.childs.iter() {
+        collect_elements(&child.borrow(), document, path, visit);
+    }
+}
+
+/// Appends the text of the element and its descendants
+pub(crate) fn node_text(node: &XMLNode, text: &mut String) {
+    if let Some(ref t) = node.text {
+        text.push_str(t);
+    }
+    for child in node.childs.iter() {
+        node_text(&child.borrow(), text);
+    }
+}
+
+/// The epub:types of the start tag of `html`, for the elements that can't
+/// be parsed alone
+fn start_tag_types(html: &str) -> Vec<String> {
+    let re = Regex::new(r#"^<[^<>]*?\s[A-Za-z_][\w.-]*:type\s*=\s*["']([^"']*)["']"#)
+        .expect("valid type regex");
+    match re.captures(html) {
+        Some(types) => types[1].split_whitespace().map(String::from).collect(),
+        None => vec![],
+    }
+}
+
+/// Returns the text of the `html`, without the tags, with the entities
+/// decoded and the whitespace collapsed
+pub(crate) fn html_text(html: &str) -> String {
+    let tags = Regex::new(r"<[^<>]*>").expect("valid tag regex");
+    let text = decode_entities(&tags.replace_all(html, ""));
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,226 @@
+//! The digital signatures of `META-INF/signatures.xml`, the XML-DSig
+//! signatures of the container and its entries.
+//!
+//! Each `Signature` has references to the signed entries, with their
+//! digests, in the `SignedInfo` or in a signed `Manifest`:
+//!
+//! ```xml
+//! <signatures xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+//!   <Signature Id="sig" xmlns="http://www.w3.org/2000/09/xmldsig#">
+//!     <SignedInfo>
+//!       <CanonicalizationMethod Algorithm="http://www.w3.org/TR/2001/REC-xml-c14n-20010315"/>
+//!       <SignatureMethod Algorithm="http://www.w3.org/2000/09/xmldsig#rsa-sha1"/>
+//!       <Reference URI="OEBPS/chapter1.xhtml">
+//!         <DigestMethod Algorithm="http://www.w3.org/2000/09/xmldsig#sha1"/>
+//!         <DigestValue>j6lwx3rvEPO0vKtMup4NbeVu8nk=</DigestValue>
+//!       </Reference>
+//!     </SignedInfo>
+//!     <SignatureValue>...</SignatureValue>
+//!   </Signature>
+//! </signatures>
+//! ```
+//!
+//! With the `integrity` feature, `EpubDoc::check_integrity` compares the
+//! digests of the referenced entries, to find the entries modified after
+//! the signature. It's only an integrity check: the `SignedInfo` and the
+//! `SignatureValue` aren't verified, that needs the canonicalization of
+//! the `SignedInfo` and the key of the signer, so a forged signatures.xml
+//! with the digests of forged entries passes it.
+
+use std::path::{Path, PathBuf};
+
+use crate::error::EpubError;
+use crate::href;
+use crate::xmlutils::{self, XMLNode};
+
+/// The path of the signatures file in the archive
+pub const SIGNATURES_FILE: &str = "META-INF/signatures.xml";
+
+/// The SHA-1 digest algorithm of XML-DSig
+pub const SHA1_DIGEST: &str = "http://www.w3.org/2000/09/xmldsig#sha1";
+
+/// The SHA-256 digest algorithm of XML-Enc
+pub const SHA256_DIGEST: &str = "http://www.w3.org/2001/04/xmlenc#sha256";
+
+/// A `Signature` element of the signatures.xml
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    /// the `Id` of the signature
+    pub id: Option<String>,
+    /// the `Algorithm` of the `CanonicalizationMethod`
+    pub canonicalization_method: String,
+    /// the `Algorithm` of the `SignatureMethod`, like
+    /// "http://www.w3.org/2000/09/xmldsig#rsa-sha1"
+    pub signature_method: String,
+    /// the `SignatureValue`, in base64
+    pub signature_value: String,
+    /// the references of the `SignedInfo` and of the signed manifests, in
+    /// document order
+    pub references: Vec<SignatureReference>,
+}
+
+impl Signature {
+    /// Returns the paths of the entries referenced by the signature,
+    /// without the references to the elements of the signatures.xml. The
+    /// signature isn't verified.
+    pub fn referenced_paths(&self) -> Vec<&Path> {
+        self.references
+            .iter()
+            .filter_map(|reference| reference.path.as_deref())
+            .collect()
+    }
+
+    /// Returns true if the entry `path` is referenced by the signature
+    pub fn is_referenced<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path = PathBuf::from(href::normalize(&path.as_ref().to_string_lossy()));
+        self.referenced_paths().contains(&path.as_path())
+    }
+}
+
+/// A `Reference` of a signature, to an entry or to an element
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignatureReference {
+    /// the `URI` of the reference
+    pub uri: String,
+    /// the full path of the entry, None if the reference is to an element
+    /// of the signatures.xml, like "#Manifest1"
+    pub path: Option<PathBuf>,
+    /// the `Algorithm` of the `Transform` elements
+    pub transforms: Vec<String>,
+    /// the `Algorithm` of the `DigestMethod`
+    pub digest_method: String,
+    /// the `DigestValue`, in base64
+    pub digest_value: String,
+}
+
+/// The result of the integrity check of a reference, comparing its
+/// digest, see `EpubDoc::check_integrity`
+#[cfg(feature = "integrity")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DigestStatus {
+    /// the digest of the entry matches
+    Valid,
+    /// the entry was modified after the signature
+    Mismatch,
+    /// the entry isn't in the archive
+    Missing,
+    /// the digest algorithm or the transforms aren't supported, or the
+    /// reference is to an element that needs a canonicalization
+    Unsupported,
+}
+
+/// The integrity check of a reference of a signature
+#[cfg(feature = "integrity")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IntegrityCheck {
+    /// the `Id` of the signature
+    pub signature: Option<String>,
+    /// the checked reference
+    pub reference: SignatureReference,
+    /// the result of the check
+    pub status: DigestStatus,
+}
+
+/// Parses the signatures.xml `content`
+pub(crate) fn parse_signatures(content: &[u8]) -> Result<Vec<Signature>, EpubError> {
+    let root = xmlutils::XMLReader::parse(content)?;
+    let mut signatures = vec![];
+    collect_signatures(&root.borrow(), &mut signatures);
+    Ok(signatures)
+}
+
+fn collect_signatures(node: &XMLNode, signatures: &mut Vec<Signature>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name != "Signature" {
+            collect_signatures(&child, signatures);
+            continue;
+        }
+        let algorithm = |element: &str| {
+            let element = child.find(element).ok()?;
+            let value = element.borrow().get_attr("Algorithm").ok();
+            value
+        };
+        let mut references = vec![];
+        collect_references(&child, &mut references);
+        signatures.push(Signature {
+            id: child.get_attr("Id").ok(),
+            canonicalization_method: algorithm("CanonicalizationMethod").unwrap_or_default(),
+            signature_method: algorithm("SignatureMethod").unwrap_or_default(),
+            signature_value: text(&child, "SignatureValue"),
+            references,
+        });
+    }
+}
+
+fn collect_references(node: &XMLNode, references: &mut Vec<SignatureReference>) {
+    for child in node.childs.iter() {
+        let child = child.borrow();
+        if child.name.local_name != "Reference" {
+            collect_references(&child, references);
+            continue;
+        }
+        let uri = child.get_attr("URI").unwrap_or_default();
+        // the uris are relative to the root of the container
+        let path = if uri.starts_with('#') {
+            None
+        } else {
+            let (path, _) = href::split_fragment(&uri);
+            Some(PathBuf::from(href::normalize(path)))
+        };
+        let mut transforms = vec![];
+        if let Ok(parent) = child.find("Transforms") {
+            for transform in parent.borrow().childs.iter() {
+                transforms.extend(transform.borrow().get_attr("Algorithm").ok());
+            }
+        }
+        references.push(SignatureReference {
+            uri,
+            path,
+            transforms,
+            digest_method: child
+                .find("DigestMethod")
+                .ok()
+                .and_then(|method| method.borrow().get_attr("Algorithm").ok())
+                .unwrap_or_default(),
+            digest_value: text(&child, "DigestValue"),
+        });
+    }
+}
+
+/// The text of the `element` under `node`, without the whitespace
+fn text(node: &XMLNode, element: &str) -> String {
+    node.find(element)
+        .ok()
+        .and_then(|e| e.borrow().text.clone())
+        .map(|text| text.split_whitespace().collect())
+        .unwrap_or_default()
+}
+
+/// Checks the digest of the entry `content` of the `reference`
+#[cfg(feature = "integrity")]
+pub(crate) fn check_digest(reference: &SignatureReference, content: Option<&[u8]>) -> DigestStatus {
+    use crate::digest;
+
+    if reference.path.is_none() || !reference.transforms.is_empty() {
+        return DigestStatus::Unsupported;
+    }
+    let expected = match digest::base64_decode(&reference.digest_value) {
+        Some(expected) => expected,
+        None => return DigestStatus::Mismatch,
+    };
+    let content = match content {
+        Some(content) => content,
+        None => return DigestStatus::Missing,
+    };
+    let actual = match reference.digest_method.as_str() {
+        SHA1_DIGEST => digest::sha1(content).to_vec(),
+        SHA256_DIGEST => digest::sha256(content).to_vec(),
+        _ => return DigestStatus::Unsupported,
+    };
+    if actual == expected {
+        DigestStatus::Valid
+    } else {
+        DigestStatus::Mismatch
+    }
+}
@@ -0,0 +1,156 @@
+//! Splits an epub in several smaller epubs.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::builder::{new_uuid, EpubBuilder, EpubOutput, DC_ELEMENTS, ROOT_BASE};
+use crate::doc::{find_references, EpubDoc, NavPoint};
+use crate::error::EpubError;
+use crate::href;
+use crate::navigation;
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Splits the epub in several epubs, one for each range of spine
+    /// positions.
+    ///
+    /// Each new epub contains the spine items in the range and only the
+    /// resources they reference, like images, stylesheets and fonts. Links
+    /// to spine items in other parts are kept, but they are broken. The
+    /// manifest and the navigation are generated again, keeping the entries
+    /// of the table of content that point to the included documents.
+    ///
+    /// The metadata is copied, except the identifiers. Each part gets a new
+    /// identifier, the doc unique identifier with the "-part-N" suffix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let parts = doc.split(&[0..9, 9..17]).unwrap();
+    /// let part = parts.into_iter().last().unwrap().into_doc().unwrap();
+    /// assert_eq!(8, part.spine.len());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if some range is empty or out of the spine, or if
+    /// some document can't be read.
+    pub fn split(&mut self, ranges: &[Range<usize>]) -> Result<Vec<EpubOutput>, EpubError> {
+        for range in ranges.iter() {
+            if range.start >= range.end || range.end > self.spine.len() {
+                return Err(EpubError::InvalidOperation(format!(
+                    "spine range not valid: {:?}",
+                    range
+                )));
+            }
+        }
+        ranges
+            .iter()
+            .enumerate()
+            .map(|(n, range)| self.split_part(n + 1, range.clone()))
+            .collect()
+    }
+
+    fn split_part(&mut self, n: usize, range: Range<usize>) -> Result<EpubOutput, EpubError> {
+        let mut builder = EpubBuilder::new();
+        self.copy_metadata(&mut builder, n);
+
+        // resource path -> mime
+        let mimes: HashMap<PathBuf, String> = self.resources.values().cloned().collect();
+        let spine_paths: HashSet<PathBuf> = self
+            .spine
+            .iter()
+            .filter_map(|item| self.resources.get(&item.idref))
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut included = HashSet::new();
+        // documents to look for references, as path, mime and content
+        let mut pending = vec![];
+        for item in self.spine[range].iter() {
+            let (path, mime) = match self.resources.get(&item.idref) {
+                Some(res) => res.clone(),
+                None => continue,
+            };
+            let content = self.archive.get_entry(&path)?;
+            builder.add_spine_resource(&self.relative_to_root(&path), content.clone(), &mime);
+            included.insert(path.clone());
+            pending.push((path, mime, content));
+        }
+        let documents = included.clone();
+
+        while let Some((path, mime, content)) = pending.pop() {
+            for href in find_references(&content, &mime) {
+                let target = href::resolve(&path, &href);
+                if included.contains(&target)
+                    || spine_paths.contains(&target)
+                    || builder.has_path(&self.relative_to_root(&target))
+                {
+                    continue;
+                }
+                let mime = match mimes.get(&target) {
+                    Some(mime) => mime.clone(),
+                    None => continue,
+                };
+                let content = self.archive.get_entry(&target)?;
+                builder.add_resource(self.relative_to_root(&target), content.clone(), &mime);
+                included.insert(target.clone());
+                pending.push((target, mime, content));
+            }
+        }
+
+        builder.toc = self.split_toc(&self.toc, &documents);
+        navigation::renumber(&mut builder.toc, &mut 0);
+        builder.build()
+    }
+
+    fn copy_metadata(&self, builder: &mut EpubBuilder, n: usize) {
+        let uid = self
+            .unique_identifier
+            .clone()
+            .or_else(|| self.mdata("identifier"))
+            .unwrap_or_else(|| format!("urn:uuid:{}", new_uuid()));
+        builder.metadata("identifier", &format!("{}-part-{}", uid, n));
+        for name in DC_ELEMENTS.iter().filter(|n| **n != "identifier") {
+            for value in self.metadata.get(*name).into_iter().flatten() {
+                builder.metadata(name, value);
+            }
+        }
+        if !self.metadata.contains_key("language") {
+            builder.metadata("language", "und");
+        }
+    }
+
+    /// Returns the path relative to the package document
+    pub(crate) fn relative_to_root(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root_base)
+            .unwrap_or(path)
+            .to_path_buf()
+    }
+
+    /// Copies the toc entries pointing to the `documents`, with the paths
+    /// of the generated epub. Entries nested under a removed entry are
+    /// moved up.
+    fn split_toc(&self, toc: &[NavPoint], documents: &HashSet<PathBuf>) -> Vec<NavPoint> {
+        let mut navpoints = vec![];
+        for nav in toc.iter() {
+            let children = self.split_toc(&nav.children, documents);
+            let content = nav.content.to_string_lossy();
+            let path = content.split('#').next().unwrap_or_default();
+            if documents.contains(Path::new(path)) {
+                navpoints.push(NavPoint {
+                    label: nav.label.clone(),
+                    content: Path::new(ROOT_BASE).join(self.relative_to_root(&nav.content)),
+                    children,
+                    play_order: 0,
+                });
+            } else {
+                navpoints.extend(children);
+            }
+        }
+        navpoints
+    }
+}
@@ -0,0 +1,161 @@
+//! The word and character counts of the book, with an estimation of the
+//! reading time.
+//!
+//! The counts are of the plain text of the linear spine items, see
+//! `text::plain_text`. The words are the runs of letters and digits, with
+//! the apostrophes and hyphens inside of them, and each ideograph, kana or
+//! hangul syllable counts as a word.
+
+use std::io::{Read, Seek};
+use std::time::Duration;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::text::plain_text;
+
+/// The reading speed of `reading_time`, in words per minute, when no other
+/// speed is given
+pub const DEFAULT_WPM: u32 = 250;
+
+/// The counts of a spine item
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChapterStats {
+    /// the index of the item in the spine
+    pub spine_index: usize,
+    /// the manifest id of the item
+    pub id: String,
+    /// the label of the toc entry, if the item starts a chapter
+    pub title: Option<String>,
+    /// the number of words
+    pub words: usize,
+    /// the number of characters, without the whitespace
+    pub characters: usize,
+}
+
+impl ChapterStats {
+    /// Returns the time to read the item at `wpm` words per minute
+    pub fn reading_time(&self, wpm: u32) -> Duration {
+        reading_time(self.words, wpm)
+    }
+}
+
+/// The counts of the whole book, see `EpubDoc::stats`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BookStats {
+    /// the counts of the linear spine items, in reading order
+    pub chapters: Vec<ChapterStats>,
+    /// the number of words of the book
+    pub words: usize,
+    /// the number of characters of the book, without the whitespace
+    pub characters: usize,
+}
+
+impl BookStats {
+    /// Returns the time to read the book at `wpm` words per minute, like
+    /// `DEFAULT_WPM`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::stats::BookStats;
+    /// use std::time::Duration;
+    ///
+    /// let stats = BookStats {
+    ///     words: 1000,
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(Duration::from_secs(240), stats.reading_time(250));
+    /// ```
+    pub fn reading_time(&self, wpm: u32) -> Duration {
+        reading_time(self.words, wpm)
+    }
+}
+
+fn reading_time(words: usize, wpm: u32) -> Duration {
+    if wpm == 0 {
+        return Duration::from_secs(0);
+    }
+    Duration::from_millis((words as u64).saturating_mul(60_000) / u64::from(wpm))
+}
+
+/// Returns the number of words of `text`, see the module documentation
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(5, epub::stats::word_count("It's a well-known fact, 42."));
+/// assert_eq!(3, epub::stats::word_count("Hello 世界 !"));
+/// ```
+pub fn word_count(text: &str) -> usize {
+    let mut words = 0;
+    let mut in_word = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if is_ideographic(c) {
+            words += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() {
+            if !in_word {
+                words += 1;
+            }
+            in_word = true;
+        } else if in_word && matches!(c, '\'' | '’' | '-') {
+            // inside of a word only if a letter follows
+            in_word = chars.peek().is_some_and(|next| next.is_alphanumeric());
+        } else {
+            in_word = false;
+        }
+    }
+    words
+}
+
+/// True for the characters of the scripts written without spaces
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30ff // hiragana and katakana
+        | 0x3400..=0x4dbf // cjk extension a
+        | 0x4e00..=0x9fff // cjk unified ideographs
+        | 0xac00..=0xd7af // hangul syllables
+        | 0xf900..=0xfaff // cjk compatibility ideographs
+        | 0x20000..=0x2fa1f) // cjk extensions b to f
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the word and character counts of the linear spine items and
+    /// of the whole book
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let stats = doc.stats().unwrap();
+    /// let words: usize = stats.chapters.iter().map(|c| c.words).sum();
+    /// assert_eq!(stats.words, words);
+    /// assert!(stats.reading_time(epub::stats::DEFAULT_WPM).as_secs() > 0);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn stats(&mut self) -> Result<BookStats, EpubError> {
+        let mut stats = BookStats::default();
+        for index in 0..self.spine.len() {
+            if !self.spine[index].linear {
+                continue;
+            }
+            let text = plain_text(&self.spine_str(index)?);
+            let chapter = ChapterStats {
+                spine_index: index,
+                id: self.spine[index].idref.clone(),
+                title: self.chapter_title(index),
+                words: word_count(&text),
+                characters: text.chars().filter(|c| !c.is_whitespace()).count(),
+            };
+            stats.words += chapter.words;
+            stats.characters += chapter.characters;
+            stats.chapters.push(chapter);
+        }
+        Ok(stats)
+    }
+}
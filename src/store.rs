@@ -0,0 +1,139 @@
+//! Storage backends for the epub archive.
+//!
+//! An epub is usually a zip file, but the same files can be stored in
+//! other places, like an unpacked directory, a database or an object
+//! store. Implementing `ResourceStore` for those places allows opening
+//! them with `EpubDoc::from_store`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek};
+use std::path::{Path, PathBuf};
+
+/// A place where the files of an epub are stored.
+///
+/// File names are like zip entry names, relative to the epub root and with
+/// "/" as separator, for example "META-INF/container.xml".
+pub trait ResourceStore: Send {
+    /// Returns the names of every file in the store
+    fn names(&self) -> Vec<String>;
+
+    /// Returns the content of the file by the `name`.
+    ///
+    /// # Errors
+    ///
+    /// Must fail with `io::ErrorKind::NotFound` if the file doesn't exist.
+    fn read(&mut self, name: &str) -> io::Result<Vec<u8>>;
+}
+
+/// Files of an unpacked epub, in a directory
+#[derive(Clone, Debug)]
+pub struct DirectoryStore {
+    root: PathBuf,
+    names: Vec<String>,
+}
+
+impl DirectoryStore {
+    /// Creates the store with the files under the `root` directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be read.
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<DirectoryStore> {
+        let root = root.as_ref().to_path_buf();
+        let mut names = vec![];
+        collect_files(&root, "", &mut names)?;
+        names.sort();
+        Ok(DirectoryStore { root, names })
+    }
+}
+
+impl ResourceStore for DirectoryStore {
+    fn names(&self) -> Vec<String> {
+        self.names.clone()
+    }
+
+    fn read(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        if !self.names.iter().any(|n| n == name) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        fs::read(self.root.join(name))
+    }
+}
+
+/// Adds to `names` the entry names of the files in `dir`, recursively.
+/// `prefix` is the entry name of the directory.
+pub(crate) fn collect_files(dir: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, &format!("{}/", name), names)?;
+        } else {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
+
+/// Files of an epub in memory, by name
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStore {
+    files: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.files.contains_key(name)
+    }
+
+    /// Adds or replaces the file by the `name`
+    pub fn insert<C: Into<Vec<u8>>>(&mut self, name: &str, content: C) -> &mut Self {
+        self.files.insert(name.to_string(), content.into());
+        self
+    }
+}
+
+impl From<HashMap<String, Vec<u8>>> for MemoryStore {
+    fn from(files: HashMap<String, Vec<u8>>) -> MemoryStore {
+        MemoryStore { files }
+    }
+}
+
+impl ResourceStore for MemoryStore {
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.files.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn read(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        self.files
+            .get(name)
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+}
+
+/// A zip file, the usual epub storage
+impl<R: Read + Seek + Send> ResourceStore for zip::ZipArchive<R> {
+    fn names(&self) -> Vec<String> {
+        self.file_names().map(|f| f.to_string()).collect()
+    }
+
+    fn read(&mut self, name: &str) -> io::Result<Vec<u8>> {
+        let mut file = self.by_name(name).map_err(|e| match e {
+            zip::result::ZipError::FileNotFound => io::ErrorKind::NotFound.into(),
+            zip::result::ZipError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        })?;
+        let mut content = vec![];
+        file.read_to_end(&mut content)?;
+        Ok(content)
+    }
+}
@@ -0,0 +1,117 @@
+//! Reads epubs from non seekable readers, like sockets or the standard
+//! input, in one pass.
+//!
+//! The zip entries are read in the order they are stored, without reading
+//! the central directory at the end of the file. Zip files where the entry
+//! sizes are only written after the content, in a data descriptor, can't
+//! be read this way.
+
+use std::io::{self, Read};
+
+use crate::doc::{get_root_file, EpubDoc};
+use crate::error::EpubError;
+use crate::store::MemoryStore;
+
+/// Epub read as a stream of zip entries. Created with
+/// `EpubArchive::from_stream`.
+pub struct EpubStream<R: Read> {
+    reader: R,
+}
+
+/// An entry of the epub stream. Implements `Read` to get the content.
+pub struct StreamEntry<'a> {
+    file: zip::read::ZipFile<'a>,
+}
+
+impl<'a> StreamEntry<'a> {
+    /// Entry name in the zip
+    pub fn name(&self) -> &str {
+        self.file.name()
+    }
+
+    /// Uncompressed size
+    pub fn size(&self) -> u64 {
+        self.file.size()
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file.is_dir()
+    }
+}
+
+impl<'a> Read for StreamEntry<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl<R: Read> EpubStream<R> {
+    pub(crate) fn new(reader: R) -> EpubStream<R> {
+        EpubStream { reader }
+    }
+
+    /// Returns the next entry, or `None` at the end of the entries. The
+    /// entry content must be read before getting the next one, the unread
+    /// content is skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken.
+    pub fn next_entry(&mut self) -> Result<Option<StreamEntry<'_>>, EpubError> {
+        let file = zip::read::read_zipfile_from_stream(&mut self.reader)?;
+        Ok(file.map(|file| StreamEntry { file }))
+    }
+
+    /// Reads the entries until the package document, and returns a doc
+    /// with its metadata, manifest and spine.
+    ///
+    /// The doc doesn't have the content of the resources, and the table
+    /// of content is only loaded if the navigation documents are stored
+    /// before the package document. The read entries are consumed, only
+    /// the xml documents are kept in memory, and the next entry is the one
+    /// after the package document.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the zip is broken or if the package document
+    /// isn't found.
+    pub fn read_package(&mut self) -> Result<EpubDoc<io::Empty>, EpubError> {
+        let mut store = MemoryStore::new();
+        let mut root_file = None;
+        while let Some(mut entry) = self.next_entry()? {
+            if entry.is_dir() {
+                continue;
+            }
+            let name = entry.name().to_string();
+            if !is_package_file(&name) {
+                continue;
+            }
+            let mut content = vec![];
+            entry.read_to_end(&mut content)?;
+            if name == "META-INF/container.xml" {
+                root_file = Some(get_root_file(content.clone())?);
+            }
+            store.insert(&name, content);
+
+            let found = root_file
+                .as_ref()
+                .map(|root| store.contains(&root.display().to_string()))
+                .unwrap_or(false);
+            if found {
+                return EpubDoc::from_store(store);
+            }
+        }
+        Err(EpubError::InvalidContainer(String::from(
+            "package document not found",
+        )))
+    }
+}
+
+/// Files that can be needed to load the package: the container, the
+/// package document and the navigation documents
+fn is_package_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    [".xml", ".opf", ".ncx", ".xhtml", ".html", ".htm"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+}
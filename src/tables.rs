@@ -0,0 +1,309 @@
+//! The tables of the content documents as rows of cells, that the plain
+//! text loses, with an export to CSV.
+//!
+//! The header rows are the rows of the `thead`, or the rows with only `th`
+//! cells. The nested tables are tables of their own, their text isn't in
+//! the cell that holds them.
+
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+use crate::text::{attr, ruby_tokens, tokens, RubyMode, Token};
+
+/// The largest `colspan` or `rowspan`, like in the browsers
+const MAX_SPAN: usize = 1000;
+
+/// A cell of a table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableCell {
+    /// the text of the cell, with the whitespace collapsed and the `br` as
+    /// line breaks
+    pub text: String,
+    /// true for the `th` cells
+    pub header: bool,
+    /// the number of columns of the cell, at least 1
+    pub colspan: usize,
+    /// the number of rows of the cell, at least 1
+    pub rowspan: usize,
+}
+
+/// A row of a table
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TableRow {
+    /// the cells, in document order
+    pub cells: Vec<TableCell>,
+    /// true for the rows of the `thead` and the rows with only `th` cells
+    pub header: bool,
+}
+
+/// A table of a content document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Table {
+    /// the manifest id of the document
+    pub document: String,
+    /// the text of the `caption`
+    pub caption: Option<String>,
+    /// the rows, of the `thead`, the `tbody` and the `tfoot` in document
+    /// order
+    pub rows: Vec<TableRow>,
+    /// the byte range of the `table` element in the document
+    pub range: Range<usize>,
+}
+
+impl Table {
+    /// Returns the text of the cells laid out in rows and columns, with
+    /// the spanned positions empty, so that all the rows have as many
+    /// columns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::tables::parse_tables;
+    ///
+    /// let html = "<table><tr><th colspan=\"2\">Name</th></tr>\
+    ///     <tr><td>Ada</td><td>Lovelace</td></tr></table>";
+    /// let tables = parse_tables(html, "c1");
+    /// assert_eq!(vec![vec!["Name", ""], vec!["Ada", "Lovelace"]], tables[0].grid());
+    /// ```
+    pub fn grid(&self) -> Vec<Vec<String>> {
+        let mut grid: Vec<Vec<Option<String>>> = vec![];
+        for (r, row) in self.rows.iter().enumerate() {
+            if grid.len() <= r {
+                grid.resize(r + 1, vec![]);
+            }
+            let mut column = 0;
+            for cell in row.cells.iter() {
+                // skips the positions of the cells spanning from the rows
+                // above
+                while grid[r].get(column).is_some_and(Option::is_some) {
+                    column += 1;
+                }
+                // like the browsers, the cells don't span past the last row
+                let rowspan = cell.rowspan.min(self.rows.len() - r);
+                for dr in 0..rowspan {
+                    if grid.len() <= r + dr {
+                        grid.resize(r + dr + 1, vec![]);
+                    }
+                    let line = &mut grid[r + dr];
+                    if line.len() < column + cell.colspan {
+                        line.resize(column + cell.colspan, None);
+                    }
+                    for dc in 0..cell.colspan {
+                        line[column + dc] = Some(String::new());
+                    }
+                }
+                grid[r][column] = Some(cell.text.clone());
+                column += cell.colspan;
+            }
+        }
+        let columns = grid.iter().map(Vec::len).max().unwrap_or(0);
+        grid.into_iter()
+            .map(|line| {
+                let mut line: Vec<String> =
+                    line.into_iter().map(Option::unwrap_or_default).collect();
+                line.resize(columns, String::new());
+                line
+            })
+            .collect()
+    }
+
+    /// Returns the table as CSV, the rows of `grid` separated by line
+    /// breaks and the cells by commas, quoted when they have a comma, a
+    /// quote or a line break
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::tables::parse_tables;
+    ///
+    /// let html = "<table><tr><td>1,5</td><td>say \"hi\"</td></tr></table>";
+    /// let tables = parse_tables(html, "c1");
+    /// assert_eq!("\"1,5\",\"say \"\"hi\"\"\"\n", tables[0].to_csv());
+    /// ```
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for line in self.grid() {
+            let cells: Vec<String> = line
+                .iter()
+                .map(|cell| {
+                    if cell.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell.clone()
+                    }
+                })
+                .collect();
+            csv.push_str(&cells.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// A table being read
+struct OpenTable {
+    /// the index of the table in the tables of the document
+    index: usize,
+    /// true inside of the `thead`
+    head: bool,
+    /// the text of the open cell or caption
+    text: Option<String>,
+}
+
+/// Returns the tables of the content document `html`, the document `id`,
+/// in the order of their start tags
+pub fn parse_tables(html: &str, id: &str) -> Vec<Table> {
+    let mut tables: Vec<Table> = vec![];
+    let mut open: Vec<OpenTable> = vec![];
+    for (range, token) in ruby_tokens(tokens(html), RubyMode::Base) {
+        let current = match token {
+            Token::Start {
+                ref name, empty, ..
+            } if name == "table" && !empty => {
+                open.push(OpenTable {
+                    index: tables.len(),
+                    head: false,
+                    text: None,
+                });
+                tables.push(Table {
+                    document: id.to_string(),
+                    caption: None,
+                    rows: vec![],
+                    range,
+                });
+                continue;
+            }
+            Token::End(ref name) if name == "table" => {
+                if let Some(current) = open.pop() {
+                    tables[current.index].range.end = range.end;
+                }
+                continue;
+            }
+            _ => match open.last_mut() {
+                Some(current) => current,
+                None => continue,
+            },
+        };
+        let table = &mut tables[current.index];
+        match token {
+            Token::Start {
+                name, attrs, empty, ..
+            } => match name.as_str() {
+                "thead" => current.head = !empty,
+                "tbody" | "tfoot" => current.head = false,
+                "tr" => table.rows.push(TableRow {
+                    cells: vec![],
+                    header: current.head,
+                }),
+                "td" | "th" => {
+                    if table.rows.is_empty() {
+                        table.rows.push(TableRow::default());
+                    }
+                    let span = |name| {
+                        attr(&attrs, name)
+                            .and_then(|s| s.trim().parse().ok())
+                            .unwrap_or(1)
+                            .clamp(1, MAX_SPAN)
+                    };
+                    let cell = TableCell {
+                        text: String::new(),
+                        header: name == "th",
+                        colspan: span("colspan"),
+                        rowspan: span("rowspan"),
+                    };
+                    if let Some(row) = table.rows.last_mut() {
+                        row.cells.push(cell);
+                    }
+                    current.text = Some(String::new()).filter(|_| !empty);
+                }
+                "caption" if !empty => current.text = Some(String::new()),
+                "br" => {
+                    if let Some(ref mut text) = current.text {
+                        text.push('\n');
+                    }
+                }
+                _ => {
+                    if let Some(ref mut text) = current.text {
+                        text.push(' ');
+                    }
+                }
+            },
+            Token::Text(t) => {
+                if let Some(ref mut text) = current.text {
+                    text.push_str(&t.replace('\n', " "));
+                }
+            }
+            Token::End(name) => match name.as_str() {
+                "thead" => current.head = false,
+                "td" | "th" => {
+                    let text = collapse(&current.text.take().unwrap_or_default());
+                    let cell = table.rows.last_mut().and_then(|row| row.cells.last_mut());
+                    if let Some(cell) = cell {
+                        cell.text = text;
+                    }
+                }
+                "caption" => {
+                    let text = collapse(&current.text.take().unwrap_or_default());
+                    table.caption = Some(text).filter(|t| !t.is_empty());
+                }
+                "tr" => {
+                    if let Some(row) = table.rows.last_mut() {
+                        row.header = row.header
+                            || (!row.cells.is_empty() && row.cells.iter().all(|c| c.header));
+                    }
+                }
+                _ => {
+                    if let Some(ref mut text) = current.text {
+                        text.push(' ');
+                    }
+                }
+            },
+            Token::Ruby(_) => {}
+        }
+    }
+    tables
+}
+
+/// Collapses the whitespace of the lines of `text`
+fn collapse(text: &str) -> String {
+    let lines: Vec<String> = text
+        .split('\n')
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    lines.join("\n")
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the tables of the content document `id`, see `parse_tables`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document isn't in the manifest or can't be
+    /// read.
+    pub fn tables_of(&mut self, id: &str) -> Result<Vec<Table>, EpubError> {
+        let html = self.get_resource_str(id)?;
+        Ok(parse_tables(&html, id))
+    }
+
+    /// Returns the tables of the spine documents, in reading order
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn tables(&mut self) -> Result<Vec<Table>, EpubError> {
+        let mut ids: Vec<String> = vec![];
+        for item in self.spine.iter() {
+            if !ids.contains(&item.idref) && self.resources.contains_key(&item.idref) {
+                ids.push(item.idref.clone());
+            }
+        }
+        let mut tables = vec![];
+        for id in ids {
+            tables.extend(self.tables_of(&id)?);
+        }
+        Ok(tables)
+    }
+}
@@ -0,0 +1,836 @@
+//! The plain text of the content documents, for search, text to speech or
+//! text processing.
+//!
+//! The tags are stripped and the entities decoded. The block elements,
+//! like paragraphs or headings, are separated by an empty line, the `br`
+//! elements by a line break, and the rest of the whitespace is collapsed.
+//! The `head`, the scripts and the styles aren't text. The ruby
+//! annotations, like the furigana, are dropped by default, see `RubyMode`.
+//!
+//! The text can also be read as a list of typed blocks, see `text_blocks`,
+//! to keep the structure of the document.
+
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+use regex::Regex;
+
+use crate::doc::EpubDoc;
+use crate::error::EpubError;
+
+thread_local! {
+    /// The tags and the text of `tokens`
+    static TOKENS: Regex = Regex::new(
+        r"(?s)<!--.*?-->|<!\[CDATA\[(.*?)\]\]>|<[!?][^>]*>|<(/?)([A-Za-z][^\s/>]*)([^>]*?)(/?)>|[^<]+|<",
+    )
+    .expect("valid token regex");
+    /// The attributes of a start tag, with their name and their value
+    static ATTRIBUTES: Regex =
+        Regex::new(r#"([^\s=/"'>]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#)
+            .expect("valid attribute regex");
+    /// The character references and the xml entities
+    static ENTITIES: Regex =
+        Regex::new(r"&(#[0-9]+|#[xX][0-9a-fA-F]+|[A-Za-z]+);").expect("valid entity regex");
+}
+
+/// The elements that start a paragraph of the text
+const BLOCKS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// The elements whose content isn't text
+const SKIPPED: &[&str] = &["head", "script", "style", "template"];
+
+/// The elements without content, that may have no end tag
+const VOID: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// How the text extraction handles the ruby annotations, the `rt` of the
+/// `ruby` elements
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RubyMode {
+    /// keeps the base text only, "漢字"
+    Base,
+    /// keeps the readings in parentheses after their base text, "漢字(かんじ)"
+    Parentheses,
+    /// keeps the base text only, and the annotations in the `ruby` of the
+    /// blocks of `text_blocks_with`
+    Annotations,
+}
+
+/// A ruby annotation: a base text and its reading
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ruby {
+    /// the annotated text
+    pub base: String,
+    /// the reading, the text of the `rt`
+    pub reading: String,
+}
+
+/// Options of the text extraction, of `plain_text_with` and of the text of
+/// the whole book with `EpubDoc::extract_text_with`
+///
+/// # Examples
+///
+/// ```
+/// use epub::text::{RubyMode, TextOptions};
+///
+/// let mut options = TextOptions::new();
+/// options
+///     .separator("\n\n* * *\n\n")
+///     .chapter_titles(true)
+///     .ruby(RubyMode::Parentheses);
+/// ```
+#[derive(Clone, Debug)]
+pub struct TextOptions {
+    separator: String,
+    chapter_titles: bool,
+    ruby: RubyMode,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            separator: String::from("\n\n"),
+            chapter_titles: false,
+            ruby: RubyMode::Base,
+        }
+    }
+}
+
+impl TextOptions {
+    /// Creates the default options: the spine items separated by an empty
+    /// line, without headers, and the base text of the ruby only
+    pub fn new() -> TextOptions {
+        TextOptions::default()
+    }
+
+    /// Sets the text between the spine items
+    pub fn separator(&mut self, separator: &str) -> &mut Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Adds the label of the toc entry before the text of the spine items
+    /// that start a chapter
+    pub fn chapter_titles(&mut self, titles: bool) -> &mut Self {
+        self.chapter_titles = titles;
+        self
+    }
+
+    /// Sets how the ruby annotations are extracted
+    pub fn ruby(&mut self, mode: RubyMode) -> &mut Self {
+        self.ruby = mode;
+        self
+    }
+}
+
+/// Returns the plain text of the xhtml `html`
+///
+/// # Examples
+///
+/// ```
+/// let html = "<html><head><title>T</title></head><body>\
+///     <h1>Chapter&#160;1</h1><p>It was   a <em>dark</em>\nnight.<br/>Really.</p>\
+///     </body></html>";
+/// assert_eq!("Chapter 1\n\nIt was a dark night.\nReally.", epub::text::plain_text(html));
+/// ```
+pub fn plain_text(html: &str) -> String {
+    plain_text_with(html, &TextOptions::default())
+}
+
+/// Returns the plain text of the xhtml `html` with the `options`, see
+/// `plain_text`
+///
+/// # Examples
+///
+/// ```
+/// use epub::text::{plain_text_with, RubyMode, TextOptions};
+///
+/// let html = "<p><ruby>漢<rp>(</rp><rt>かん</rt><rp>)</rp>字<rt>じ</rt></ruby>です</p>";
+/// assert_eq!("漢字です", epub::text::plain_text(html));
+/// let mut options = TextOptions::new();
+/// options.ruby(RubyMode::Parentheses);
+/// assert_eq!("漢(かん)字(じ)です", plain_text_with(html, &options));
+/// ```
+pub fn plain_text_with(html: &str, options: &TextOptions) -> String {
+    let paragraphs: Vec<String> = paragraphs(html, options)
+        .into_iter()
+        .map(|(paragraph, _)| paragraph)
+        .collect();
+    paragraphs.join("\n\n")
+}
+
+/// Returns the paragraphs of the plain text of `html`, see
+/// `plain_text_with`, with the byte offset of their first text in `html`
+pub(crate) fn paragraphs(html: &str, options: &TextOptions) -> Vec<(String, usize)> {
+    let mut paragraphs: Vec<(String, usize)> = vec![];
+    let mut current = String::new();
+    let mut start: Option<usize> = None;
+    let mut pre: usize = 0;
+    let mut end_paragraph = |current: &mut String, start: &mut Option<usize>| {
+        let mut text = vec![];
+        flush(current, &mut text);
+        if let (Some(text), Some(start)) = (text.pop(), start.take()) {
+            paragraphs.push((text, start));
+        }
+    };
+    for (range, token) in ruby_tokens(tokens(html), options.ruby) {
+        if let Token::Text(ref text) = token {
+            if start.is_none() && !text.trim().is_empty() {
+                start = Some(range.start);
+            }
+        }
+        match token {
+            Token::Text(text) if pre == 0 => current.push_str(&text.replace('\n', " ")),
+            Token::Text(text) => current.push_str(&text),
+            Token::Start { ref name, .. } if name == "br" => current.push('\n'),
+            Token::Start { ref name, .. } if name == "td" || name == "th" => current.push(' '),
+            Token::Start { name, empty, .. } if BLOCKS.contains(&name.as_str()) => {
+                end_paragraph(&mut current, &mut start);
+                if name == "pre" && !empty {
+                    pre += 1;
+                }
+            }
+            Token::End(name) if BLOCKS.contains(&name.as_str()) => {
+                end_paragraph(&mut current, &mut start);
+                if name == "pre" {
+                    pre = pre.saturating_sub(1);
+                }
+            }
+            _ => {}
+        }
+    }
+    end_paragraph(&mut current, &mut start);
+    paragraphs
+}
+
+/// The kind of a block of text, see `text_blocks`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// a `h1` to `h6` heading, with its level from 1 to 6
+    Heading { level: usize },
+    /// a paragraph, or the text of any other block element
+    Paragraph,
+    /// a paragraph of a list item
+    ListItem,
+    /// a paragraph of a block quote
+    BlockQuote,
+    /// a figure, with the caption as text and the alt text of its image
+    Figure { alt: Option<String> },
+    /// a `pre` element, with its lines and indentation kept
+    CodeBlock,
+}
+
+/// A block of the text of a content document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextBlock {
+    /// the kind of the block
+    pub kind: BlockKind,
+    /// the text of the block, like the text of `plain_text`
+    pub text: String,
+    /// the byte range of the block in the source, from its first text, or
+    /// image for the figures, to its last inline tag
+    pub range: Range<usize>,
+    /// the ruby annotations of the block, with `RubyMode::Annotations`
+    pub ruby: Vec<Ruby>,
+    /// the language of the block, the `xml:lang` or `lang` of the closest
+    /// element that has one
+    pub lang: Option<String>,
+}
+
+/// Returns the blocks of text of the xhtml `html`, in document order. The
+/// text of the blocks nested in a list item or a block quote has the kind
+/// of the item or the quote, and a figure is a single block. The empty
+/// blocks are skipped, but for the figures with an image.
+///
+/// # Examples
+///
+/// ```
+/// use epub::text::{text_blocks, BlockKind};
+///
+/// let html = "<body><h2>Title</h2><p>Some text.</p>\
+///     <ul><li><p>An item</p></li></ul></body>";
+/// let blocks = text_blocks(html);
+/// assert_eq!(BlockKind::Heading { level: 2 }, blocks[0].kind);
+/// assert_eq!("Some text.", blocks[1].text);
+/// assert_eq!("Some text.", &html[blocks[1].range.clone()]);
+/// assert_eq!(BlockKind::ListItem, blocks[2].kind);
+/// ```
+pub fn text_blocks(html: &str) -> Vec<TextBlock> {
+    text_blocks_with(html, &TextOptions::default())
+}
+
+/// Returns the blocks of text of the xhtml `html` with the `options`, see
+/// `text_blocks`
+///
+/// # Examples
+///
+/// ```
+/// use epub::text::{text_blocks_with, Ruby, RubyMode, TextOptions};
+///
+/// let html = "<p><ruby>漢字<rt>かんじ</rt></ruby>です</p>";
+/// let mut options = TextOptions::new();
+/// options.ruby(RubyMode::Annotations);
+/// let blocks = text_blocks_with(html, &options);
+/// assert_eq!("漢字です", blocks[0].text);
+/// let ruby = Ruby {
+///     base: String::from("漢字"),
+///     reading: String::from("かんじ"),
+/// };
+/// assert_eq!(vec![ruby], blocks[0].ruby);
+/// ```
+pub fn text_blocks_with(html: &str, options: &TextOptions) -> Vec<TextBlock> {
+    let mut reader = BlockReader::default();
+    for (range, token) in ruby_tokens(tokens(html), options.ruby) {
+        reader.token(range, token);
+    }
+    reader.flush(BlockKind::Paragraph);
+    reader.blocks
+}
+
+#[derive(Default)]
+struct BlockReader {
+    blocks: Vec<TextBlock>,
+    /// the open block elements, with the kind they give to their text
+    open: Vec<(String, Option<BlockKind>)>,
+    /// the text of the current block
+    text: String,
+    /// the range of the current block
+    range: Option<Range<usize>>,
+    /// the alt text of the image of the open figure, None outside of it
+    figure: Option<Option<String>>,
+    /// true inside of a `pre`
+    pre: bool,
+    /// the ruby annotations of the current block
+    ruby: Vec<Ruby>,
+    /// the open elements, with their language
+    langs: Vec<(String, Option<String>)>,
+}
+
+impl BlockReader {
+    fn token(&mut self, range: Range<usize>, token: Token) {
+        let closed = match token {
+            Token::Start {
+                ref name,
+                ref attrs,
+                empty: false,
+            } if !VOID.contains(&name.as_str()) => {
+                let lang = attr(attrs, "xml:lang").or_else(|| attr(attrs, "lang"));
+                self.langs.push((name.clone(), lang));
+                None
+            }
+            Token::End(ref name) => self.langs.iter().rposition(|(open, _)| open == name),
+            _ => None,
+        };
+        self.block_token(range, token);
+        // the element is closed after its block is flushed
+        if let Some(i) = closed {
+            self.langs.truncate(i);
+        }
+    }
+
+    fn block_token(&mut self, range: Range<usize>, token: Token) {
+        match token {
+            Token::Text(text) => {
+                if !text.trim().is_empty() {
+                    self.extend(range);
+                }
+                if self.pre {
+                    self.text.push_str(&text);
+                } else {
+                    self.text.push_str(&text.replace('\n', " "));
+                }
+            }
+            Token::Start { ref name, .. } if name == "br" => self.text.push('\n'),
+            Token::Start { ref name, .. } if name == "td" || name == "th" => self.text.push(' '),
+            Token::Start {
+                ref name,
+                ref attrs,
+                ..
+            } if name == "img" => {
+                if let Some(None) = self.figure {
+                    self.extend(range);
+                    self.figure = Some(attr(attrs, "alt"));
+                }
+            }
+            Token::Start { name, empty, .. } if BLOCKS.contains(&name.as_str()) => {
+                if self.figure.is_some() || self.pre {
+                    return;
+                }
+                self.flush(self.kind());
+                if empty {
+                    return;
+                }
+                let kind = match name.as_str() {
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Some(BlockKind::Heading {
+                        level: name[1..].parse().unwrap_or(1),
+                    }),
+                    "li" => Some(BlockKind::ListItem),
+                    "blockquote" => Some(BlockKind::BlockQuote),
+                    "figure" => {
+                        self.figure = Some(None);
+                        None
+                    }
+                    "pre" => {
+                        self.pre = true;
+                        None
+                    }
+                    _ => None,
+                };
+                self.open.push((name, kind));
+            }
+            Token::End(name) if BLOCKS.contains(&name.as_str()) => {
+                let kind = match name.as_str() {
+                    "figure" => match self.figure.take() {
+                        Some(alt) => BlockKind::Figure { alt },
+                        None => return,
+                    },
+                    "pre" if self.pre && self.figure.is_none() => {
+                        self.pre = false;
+                        BlockKind::CodeBlock
+                    }
+                    _ if self.figure.is_some() || self.pre => return,
+                    _ => self.kind(),
+                };
+                self.flush(kind);
+                if let Some(i) = self.open.iter().rposition(|(open, _)| *open == name) {
+                    self.open.truncate(i);
+                }
+            }
+            Token::Ruby(ruby) => self.ruby.push(ruby),
+            // the inline tags after the text, like the end of an emphasis
+            _ if self.range.is_some() => self.extend(range),
+            _ => {}
+        }
+    }
+
+    /// The kind of the text of the open blocks
+    fn kind(&self) -> BlockKind {
+        self.open
+            .iter()
+            .rev()
+            .find_map(|(_, kind)| kind.clone())
+            .unwrap_or(BlockKind::Paragraph)
+    }
+
+    /// Extends the range of the current block to `range`
+    fn extend(&mut self, range: Range<usize>) {
+        self.range = match self.range.take() {
+            Some(current) => Some(current.start..range.end),
+            None => Some(range),
+        };
+    }
+
+    /// Adds the current block with the `kind`, if it has a range
+    fn flush(&mut self, kind: BlockKind) {
+        let text = std::mem::take(&mut self.text);
+        let ruby = std::mem::take(&mut self.ruby);
+        let range = match self.range.take() {
+            Some(range) => range,
+            None => return,
+        };
+        let text = match kind {
+            BlockKind::CodeBlock => text.trim_matches('\n').to_string(),
+            _ => {
+                let mut paragraphs = vec![];
+                let mut text = text;
+                flush(&mut text, &mut paragraphs);
+                paragraphs.join("\n")
+            }
+        };
+        // an empty language is an unknown one
+        let lang = self
+            .langs
+            .iter()
+            .rev()
+            .find_map(|(_, lang)| lang.clone())
+            .filter(|lang| !lang.is_empty());
+        self.blocks.push(TextBlock {
+            kind,
+            text,
+            range,
+            ruby,
+            lang,
+        });
+    }
+}
+
+/// A token of a content document, see `tokens`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Token {
+    /// text, with the entities decoded
+    Text(String),
+    /// a start tag, with the lowercase local name and the source of the
+    /// attributes, or an empty element
+    Start {
+        name: String,
+        attrs: String,
+        empty: bool,
+    },
+    /// an end tag, with the lowercase local name
+    End(String),
+    /// a ruby annotation, after its base text, see `ruby_tokens`
+    Ruby(Ruby),
+}
+
+/// Splits the `html` in tags and text, with their byte range in the
+/// source. The comments, the declarations and the content of the `head`,
+/// the scripts and the styles are skipped, the CDATA sections are text.
+pub(crate) fn tokens(html: &str) -> Vec<(Range<usize>, Token)> {
+    let re = TOKENS.with(Regex::clone);
+    let mut tokens = vec![];
+    let mut skipped: usize = 0;
+    for captures in re.captures_iter(html) {
+        let range = captures.get(0).map(|m| m.range()).unwrap_or_default();
+        let token = match captures.get(3) {
+            Some(name) => {
+                let name = name.as_str().to_lowercase();
+                let name = name.rsplit(':').next().unwrap_or_default().to_string();
+                let empty = &captures[5] == "/";
+                if &captures[2] == "/" {
+                    Token::End(name)
+                } else {
+                    Token::Start {
+                        name,
+                        attrs: captures[4].to_string(),
+                        empty,
+                    }
+                }
+            }
+            None => match captures.get(1) {
+                Some(cdata) => Token::Text(cdata.as_str().to_string()),
+                None if captures[0].starts_with("<!") || captures[0].starts_with("<?") => continue,
+                None => Token::Text(decode_entities(&captures[0])),
+            },
+        };
+        match token {
+            Token::Start {
+                ref name, empty, ..
+            } if SKIPPED.contains(&name.as_str()) => skipped += usize::from(!empty),
+            Token::End(ref name) if SKIPPED.contains(&name.as_str()) => {
+                skipped = skipped.saturating_sub(1);
+            }
+            token if skipped == 0 => tokens.push((range, token)),
+            _ => {}
+        }
+    }
+    tokens
+}
+
+/// Applies the ruby `mode` to the `tokens`: the `rp` fallback parentheses
+/// and the `ruby`, `rb` and `rtc` tags are dropped, and the `rt` readings
+/// are dropped, put in parentheses or replaced by a `Token::Ruby`.
+pub(crate) fn ruby_tokens(
+    tokens: Vec<(Range<usize>, Token)>,
+    mode: RubyMode,
+) -> Vec<(Range<usize>, Token)> {
+    let mut out = vec![];
+    let mut ruby: usize = 0;
+    let mut rp: usize = 0;
+    // the base text since the start of the ruby or the last reading
+    let mut base = String::new();
+    let mut reading: Option<(usize, String)> = None;
+    for (range, token) in tokens {
+        match token {
+            Token::Start {
+                ref name, empty, ..
+            } if name == "ruby" => {
+                ruby += usize::from(!empty);
+                base.clear();
+            }
+            Token::End(ref name) if name == "ruby" => ruby = ruby.saturating_sub(1),
+            token if ruby == 0 => out.push((range, token)),
+            Token::Start {
+                ref name, empty, ..
+            } if name == "rp" => rp += usize::from(!empty),
+            Token::End(ref name) if name == "rp" => rp = rp.saturating_sub(1),
+            Token::Start {
+                ref name, empty, ..
+            } if name == "rt" && !empty => reading = Some((range.start, String::new())),
+            Token::End(ref name) if name == "rt" => {
+                let (start, text) = match reading.take() {
+                    Some(reading) => reading,
+                    None => continue,
+                };
+                let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+                let range = start..range.end;
+                match mode {
+                    RubyMode::Base => {}
+                    RubyMode::Parentheses => out.push((range, Token::Text(format!("({})", text)))),
+                    RubyMode::Annotations => {
+                        let ruby = Ruby {
+                            base: base.trim().to_string(),
+                            reading: text,
+                        };
+                        out.push((range, Token::Ruby(ruby)));
+                    }
+                }
+                base.clear();
+            }
+            Token::Start { ref name, .. } | Token::End(ref name)
+                if name == "rb" || name == "rtc" => {}
+            Token::Text(_) if rp > 0 => {}
+            Token::Text(text) => match reading {
+                Some((_, ref mut reading)) => reading.push_str(&text),
+                None => {
+                    base.push_str(&text);
+                    out.push((range, Token::Text(text)));
+                }
+            },
+            token => out.push((range, token)),
+        }
+    }
+    out
+}
+
+/// Returns the value of the attribute `name` in the `attrs` of a start
+/// tag, with the entities decoded
+pub(crate) fn attr(attrs: &str, name: &str) -> Option<String> {
+    if !attrs.contains(name) {
+        return None;
+    }
+    let re = ATTRIBUTES.with(Regex::clone);
+    let captures = re
+        .captures_iter(attrs)
+        .find(|captures| &captures[1] == name)?;
+    let value = captures
+        .get(2)
+        .or_else(|| captures.get(3))
+        .or_else(|| captures.get(4))?;
+    Some(decode_entities(value.as_str()))
+}
+
+/// Adds the `current` paragraph, with the whitespace of its lines
+/// collapsed, if it isn't empty
+fn flush(current: &mut String, paragraphs: &mut Vec<String>) {
+    let lines: Vec<String> = current
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect();
+    if !lines.is_empty() {
+        paragraphs.push(lines.join("\n"));
+    }
+    current.clear();
+}
+
+/// Decodes the character references and the xml entities of `text`, the
+/// unknown entities are kept as they are
+pub(crate) fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let entities = ENTITIES.with(Regex::clone);
+    let decoded = entities.replace_all(text, |entity: &regex::Captures<'_>| {
+        let name = &entity[1];
+        let c = match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ if name.starts_with("#x") || name.starts_with("#X") => {
+                u32::from_str_radix(&name[2..], 16)
+                    .ok()
+                    .and_then(char::from_u32)
+            }
+            _ if name.starts_with('#') => name[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        };
+        match c {
+            Some(c) => c.to_string(),
+            None => entity[0].to_string(),
+        }
+    });
+    decoded.into_owned()
+}
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Returns the plain text of the spine item `index`, see `plain_text`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let text = doc.get_text(2).unwrap();
+    /// assert!(text.starts_with("Despertar\n\nJosé Luís abrió los ojos"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index is out of the spine or if the document
+    /// can't be read.
+    pub fn get_text(&mut self, index: usize) -> Result<String, EpubError> {
+        self.get_text_with(index, &TextOptions::default())
+    }
+
+    /// Returns the plain text of the spine item `index` with the `options`,
+    /// see `plain_text_with`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index is out of the spine or if the document
+    /// can't be read.
+    pub fn get_text_with(
+        &mut self,
+        index: usize,
+        options: &TextOptions,
+    ) -> Result<String, EpubError> {
+        let html = self.spine_str(index)?;
+        Ok(plain_text_with(&html, options))
+    }
+
+    /// Returns the blocks of text of the spine item `index`, see
+    /// `text_blocks`. The ranges are byte offsets in the source of the
+    /// document, as returned by `get_resource_str`, and the blocks without
+    /// a language have the language of the publication.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index is out of the spine or if the document
+    /// can't be read.
+    pub fn get_text_blocks(&mut self, index: usize) -> Result<Vec<TextBlock>, EpubError> {
+        self.get_text_blocks_with(index, &TextOptions::default())
+    }
+
+    /// Returns the blocks of text of the spine item `index` with the
+    /// `options`, see `text_blocks_with`. The blocks without a language
+    /// have the language of the publication.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the index is out of the spine or if the document
+    /// can't be read.
+    pub fn get_text_blocks_with(
+        &mut self,
+        index: usize,
+        options: &TextOptions,
+    ) -> Result<Vec<TextBlock>, EpubError> {
+        let html = self.spine_str(index)?;
+        let mut blocks = text_blocks_with(&html, options);
+        if let Some(language) = self.mdata("language") {
+            for block in blocks.iter_mut().filter(|block| block.lang.is_none()) {
+                block.lang = Some(language.clone());
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// Returns the text of the whole book, the text of the linear spine
+    /// items in reading order, see `extract_text_with`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn extract_text(&mut self) -> Result<String, EpubError> {
+        self.extract_text_with(&TextOptions::default())
+    }
+
+    /// Returns the text of the whole book with the `options`, the text of
+    /// the linear spine items in reading order. The items without text,
+    /// like the images, are skipped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use epub::doc::EpubDoc;
+    /// use epub::text::TextOptions;
+    ///
+    /// let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let mut options = TextOptions::new();
+    /// options.separator("\n---\n").chapter_titles(true);
+    /// let text = doc.extract_text_with(&options).unwrap();
+    /// assert!(text.contains("\n---\nDespertar\n\nDespertar\n\nJosé Luís"));
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a document can't be read.
+    pub fn extract_text_with(&mut self, options: &TextOptions) -> Result<String, EpubError> {
+        let mut chapters = vec![];
+        for index in 0..self.spine.len() {
+            if !self.spine[index].linear {
+                continue;
+            }
+            let mut text = self.get_text_with(index, options)?;
+            if text.is_empty() {
+                continue;
+            }
+            let title = self.chapter_title(index);
+            if let Some(title) = title.filter(|_| options.chapter_titles) {
+                text = format!("{}\n\n{}", title, text);
+            }
+            chapters.push(text);
+        }
+        Ok(chapters.join(&options.separator))
+    }
+
+    /// Returns the label of the toc entry of the spine item `index`, if the
+    /// item starts a chapter
+    pub(crate) fn chapter_title(&self, index: usize) -> Option<String> {
+        match self.chapter_for_spine_index(index) {
+            Some(nav) if self.resource_uri_to_chapter(&nav.content) == Some(index) => {
+                Some(nav.label.trim().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the source of the spine item `index`
+    pub(crate) fn spine_str(&mut self, index: usize) -> Result<String, EpubError> {
+        let id = match self.spine.get(index) {
+            Some(item) => item.idref.clone(),
+            None => {
+                return Err(EpubError::InvalidOperation(format!(
+                    "spine index {} out of range",
+                    index
+                )))
+            }
+        };
+        self.get_resource_str(&id)
+    }
+
+    /// Returns the plain text of the current spine item, see `get_text`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the document can't be read.
+    pub fn get_current_text(&mut self) -> Result<String, EpubError> {
+        self.get_text(self.get_current_page())
+    }
+}
@@ -0,0 +1,152 @@
+//! Detection of the files of the archive that aren't used by the book.
+
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+
+use crate::archive::guess_media_type;
+use crate::doc::{find_references, EpubDoc, NavPoint, ResourceUsage, NCX_MIME};
+use crate::error::EpubError;
+use crate::href;
+
+impl<R: Read + Seek> EpubDoc<R> {
+    /// Cross-references the manifest, the spine and the references inside
+    /// the documents, to find the files that can be removed and the
+    /// manifest items that are broken.
+    ///
+    /// A file is used if it's in the spine, it's the navigation document,
+    /// the toc.ncx or the cover, it's pointed by the toc, the guide or a
+    /// metadata link, it's the fallback or the media overlay of a used
+    /// resource, or a used document links it, like the images and the
+    /// style sheets of the chapters or the fonts of the style sheets. The
+    /// mimetype file, the META-INF directory and the package document are
+    /// always used.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use epub::doc::EpubDoc;
+    /// # use std::path::PathBuf;
+    /// # let mut doc = EpubDoc::new("test.epub").unwrap();
+    /// let usage = doc.resource_usage().unwrap();
+    /// // the file is at the root of the archive, but the manifest item
+    /// // points to the OEBPS directory
+    /// assert!(usage.unreferenced.contains(&PathBuf::from("a normal item.xml")));
+    /// assert!(usage.not_in_manifest.contains(&PathBuf::from("a normal item.xml")));
+    /// assert_eq!("normal.xml", usage.missing[1].0);
+    /// assert_eq!(PathBuf::from("OEBPS/a normal item.xml"), usage.missing[1].1);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a used document can't be read.
+    pub fn resource_usage(&mut self) -> Result<ResourceUsage, EpubError> {
+        let by_path: HashMap<PathBuf, (String, String)> = self
+            .resources
+            .iter()
+            .map(|(id, (path, mime))| (path.clone(), (id.clone(), mime.clone())))
+            .collect();
+        let files: HashSet<PathBuf> = self
+            .archive
+            .files
+            .iter()
+            .filter(|name| !name.ends_with('/'))
+            .map(|name| PathBuf::from(href::normalize(name)))
+            .collect();
+
+        let mut pending: Vec<PathBuf> = vec![];
+        for item in self.spine.iter() {
+            pending.extend(self.resources.get(&item.idref).map(|(p, _)| p.clone()));
+        }
+        let mut ids = self.resources_with_property("nav");
+        ids.extend(self.resources_with_property("cover-image"));
+        ids.extend(self.get_cover_id());
+        ids.extend(
+            self.resources
+                .iter()
+                .filter(|(_, (_, mime))| mime == NCX_MIME)
+                .map(|(id, _)| id.clone()),
+        );
+        for id in ids.iter() {
+            pending.extend(self.resources.get(id).map(|(p, _)| p.clone()));
+        }
+        toc_paths(&self.toc, &mut pending);
+        for reference in self.guide() {
+            pending.push(without_fragment(&reference.content));
+        }
+        for link in self.metadata_links() {
+            pending.extend(link.path.clone());
+        }
+
+        let mut used: HashSet<PathBuf> = HashSet::new();
+        while let Some(path) = pending.pop() {
+            if !used.insert(path.clone()) {
+                continue;
+            }
+            let (id, mime) = match by_path.get(&path) {
+                Some((id, mime)) => (Some(id.as_str()), mime.clone()),
+                None => (
+                    None,
+                    guess_media_type(&path.display().to_string()).to_string(),
+                ),
+            };
+            if let Some(id) = id {
+                let related = self
+                    .fallback_chain(id)
+                    .into_iter()
+                    .skip(1)
+                    .chain(self.media_overlay_id(id).map(String::from));
+                for related in related {
+                    pending.extend(self.resources.get(&related).map(|(p, _)| p.clone()));
+                }
+            }
+            let is_document = mime == "text/css" || mime.contains("xml") || mime.contains("html");
+            if !is_document || !files.contains(&path) {
+                continue;
+            }
+            let content = self.archive.get_entry(&path)?;
+            for reference in find_references(&content, &mime) {
+                pending.push(href::resolve(&path, &reference));
+            }
+        }
+
+        let mut usage = ResourceUsage::default();
+        for path in files.iter() {
+            let name = path.display().to_string();
+            if name == "mimetype" || name.starts_with("META-INF/") || *path == self.root_file {
+                continue;
+            }
+            if !used.contains(path) {
+                usage.unreferenced.push(path.clone());
+            }
+            if !by_path.contains_key(path) {
+                usage.not_in_manifest.push(path.clone());
+            }
+        }
+        for (path, (id, _)) in by_path.iter() {
+            if !files.contains(path) {
+                usage.missing.push((id.clone(), path.clone()));
+            }
+        }
+        usage.unreferenced.sort();
+        usage.not_in_manifest.sort();
+        usage
+            .missing
+            .sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+        Ok(usage)
+    }
+}
+
+/// Adds the documents of the toc entries, without the fragments
+fn toc_paths(toc: &[NavPoint], paths: &mut Vec<PathBuf>) {
+    for nav in toc {
+        paths.push(without_fragment(&nav.content));
+        toc_paths(&nav.children, paths);
+    }
+}
+
+fn without_fragment(content: &Path) -> PathBuf {
+    let content = content.display().to_string();
+    let (path, _) = href::split_fragment(&content);
+    PathBuf::from(path)
+}
@@ -10,10 +10,15 @@ use xml::writer::XmlEvent as WriterEvent;
 
 use std::error::Error;
 use std::fmt;
+use xml::name::OwnedName;
 use xml::writer::EmitterConfig;
 use xml::writer::Error as EmitterError;
+use xml::writer::EventWriter;
 
 use std::borrow::Cow;
+use std::io::Write;
+
+use crate::encoding;
 
 // Using RefCell because we need to edit the children vec during the parsing.
 // Using rc because a Node will be referenced by its parent and by its childs.
@@ -26,26 +31,17 @@ pub struct XMLReader<'a> {
 
 impl<'a> XMLReader<'a> {
     pub fn parse(content: &[u8]) -> Result<RefCell<XMLNode>, XMLError> {
-        let content_str;
-        //If there is a UTF-8 BOM marker, ignore it
-        let content_slice = if content[0..3] == [0xefu8, 0xbbu8, 0xbfu8] {
-            &content[3..]
-        } else if content[0..2] == [0xfeu8, 0xffu8] || content[0..2] == [0xffu8, 0xfeu8] { //handle utf-16
-            let (big_byte, small_byte) = if content[0] == 0xfeu8 {
-                (1,0) //big endian utf-16
-            } else {
-                (0,1) //little endian utf-16
-            };
-            let content_u16: Vec<u16> = content[2..]
-                .chunks_exact(2)
-                .into_iter()
-                .map(|a| u16::from_ne_bytes([a[big_byte], a[small_byte]]))
-                .collect();
-            content_str = String::from_utf16_lossy(content_u16.as_slice());
-            content_str.as_bytes()
-        } else {
-            content
-        };
+        XMLReader::parse_recover(content).map(|(root, _)| root)
+    }
+
+    /// Parses the document, keeping the elements read before the first
+    /// syntax error, like an unclosed tag. Returns the root and the error.
+    pub fn parse_recover(content: &[u8]) -> Result<(RefCell<XMLNode>, Option<XMLError>), XMLError> {
+        // byte order marks, utf-16 and legacy encodings
+        let content_str = encoding::decode(content).map_err(|e| XMLError {
+            error: e.to_string(),
+        })?;
+        let content_slice = content_str.as_bytes();
 
         let reader = XMLReader {
             reader: ParserConfig::new()
@@ -58,9 +54,10 @@ impl<'a> XMLReader<'a> {
         reader.parse_xml()
     }
 
-    fn parse_xml(self) -> Result<RefCell<XMLNode>, XMLError> {
+    fn parse_xml(self) -> Result<(RefCell<XMLNode>, Option<XMLError>), XMLError> {
         let mut root: Option<ChildNodeRef> = None;
         let mut parents: Vec<ChildNodeRef> = vec![];
+        let mut error = None;
 
         for e in self.reader {
             match e {
@@ -84,7 +81,7 @@ impl<'a> XMLReader<'a> {
                         let current = parents.last();
                         if let Some(c) = current {
                             c.borrow_mut().childs.push(arnode.clone());
-                            arnode.borrow_mut().parent = Some(Rc::downgrade(&c));
+                            arnode.borrow_mut().parent = Some(Rc::downgrade(c));
                         }
                     }
                     parents.push(arnode.clone());
@@ -110,14 +107,20 @@ impl<'a> XMLReader<'a> {
                         c.borrow_mut().cdata = Some(text);
                     }
                 }
+                Err(e) => {
+                    error = Some(XMLError::from(e));
+                    break;
+                }
                 _ => continue,
             }
         }
 
+        // unclosed elements
+        parents.clear();
         if let Some(r) = root {
             let a = Rc::try_unwrap(r);
             match a {
-                Ok(n) => return Ok(n),
+                Ok(n) => return Ok((n, error)),
                 Err(_) => {
                     return Err(XMLError {
                         error: String::from("Unknown error"),
@@ -157,9 +160,9 @@ impl From<EmitterError> for XMLError {
 }
 
 impl From<ReaderError> for XMLError {
-    fn from(_: ReaderError) -> XMLError {
+    fn from(e: ReaderError) -> XMLError {
         XMLError {
-            error: String::from("Problem reading"),
+            error: format!("Problem reading: {}", e),
         }
     }
 }
@@ -200,6 +203,89 @@ impl XMLNode {
             error: String::from("tag not found"),
         })
     }
+
+    /// Creates a new element in the same namespace as this node, to be
+    /// added as a child
+    pub fn new_child(&self, local_name: &str, attrs: &[(&str, &str)]) -> XMLNode {
+        let mut name = self.name.clone();
+        name.local_name = local_name.to_string();
+        XMLNode {
+            name,
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| xml::attribute::OwnedAttribute::new(OwnedName::local(*k), *v))
+                .collect(),
+            namespace: self.namespace.clone(),
+            text: None,
+            cdata: None,
+            parent: None,
+            childs: vec![],
+        }
+    }
+
+    /// Creates a new element in the `uri` namespace, using `prefix`, to
+    /// be added as a child
+    pub fn new_child_ns(
+        &self,
+        prefix: &str,
+        uri: &str,
+        local_name: &str,
+        attrs: &[(&str, &str)],
+    ) -> XMLNode {
+        let mut node = self.new_child(local_name, attrs);
+        node.name.prefix = Some(prefix.to_string());
+        node.name.namespace = Some(uri.to_string());
+        node.namespace.put(prefix, uri);
+        node
+    }
+
+    /// Sets the attribute value, adding the attribute if it doesn't exists
+    pub fn set_attr(&mut self, name: &str, value: &str) {
+        match self.attrs.iter_mut().find(|a| a.name.local_name == name) {
+            Some(attr) => attr.value = value.to_string(),
+            None => self.attrs.push(xml::attribute::OwnedAttribute::new(
+                OwnedName::local(name),
+                value,
+            )),
+        }
+    }
+
+    pub fn add_child(&mut self, node: XMLNode) -> ChildNodeRef {
+        let child = Rc::new(RefCell::new(node));
+        self.childs.push(child.clone());
+        child
+    }
+
+    /// Serializes this node and all its childs as a xml document
+    pub fn to_xml(&self) -> Result<Vec<u8>, XMLError> {
+        let mut b = Vec::new();
+        {
+            let mut writer = EmitterConfig::default()
+                .perform_indent(true)
+                .create_writer(&mut b);
+            self.write(&mut writer)?;
+        }
+        Ok(b)
+    }
+
+    fn write<W: Write>(&self, writer: &mut EventWriter<W>) -> Result<(), XMLError> {
+        writer.write(WriterEvent::StartElement {
+            name: self.name.borrow(),
+            attributes: Cow::Owned(self.attrs.iter().map(|x| x.borrow()).collect()),
+            namespace: Cow::Borrowed(&self.namespace),
+        })?;
+        if let Some(ref text) = self.text {
+            writer.write(WriterEvent::characters(text))?;
+        }
+        if let Some(ref cdata) = self.cdata {
+            writer.write(WriterEvent::cdata(cdata))?;
+        }
+        for c in self.childs.iter() {
+            c.borrow().write(writer)?;
+        }
+        writer.write(WriterEvent::end_element())?;
+        Ok(())
+    }
 }
 
 impl fmt::Display for XMLNode {
@@ -260,8 +346,7 @@ where
                     {
                         for i in 0..attributes.len() {
                             let mut attr = attributes[i].to_owned();
-                            let repl =
-                                closure(&name.local_name, &attr.name.local_name, &attr.value);
+                            let repl = closure(name.local_name, &attr.name.local_name, &attr.value);
                             attr.value = repl;
                             attrs.push(attr);
                         }
@@ -1,6 +1,7 @@
-use epub::archive::EpubArchive;
+use epub::archive::{ArchiveLimits, Compression, EpubArchive, Overwrite, WriteOptions};
+use epub::error::EpubError;
 use std::fs;
-use std::io::Write;
+use std::io::{Cursor, Read, Write};
 
 #[test]
 fn archive_open() {
@@ -58,3 +59,549 @@ fn archive_bin_entry() {
     let resp = f.write_all(&content);
     assert!(resp.is_ok());
 }
+
+#[test]
+fn archive_modify_entry() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    archive.modify_entry("OEBPS/Text/000.xhtml", "<html>modified</html>");
+    archive.modify_entry("OEBPS/Text/new.xhtml", "<html>new</html>");
+    assert_eq!(
+        "<html>modified</html>",
+        archive.get_entry_as_str("OEBPS/Text/000.xhtml").unwrap()
+    );
+    assert_eq!(33, archive.files.len());
+
+    let path = std::env::temp_dir().join("epub-rs-archive-modify.epub");
+    archive.save_as(&path).unwrap();
+    assert!(archive.save_as("test.epub").is_err());
+    assert!(archive.save_as("./tests/../test.epub").is_err());
+    let absolute = std::env::current_dir().unwrap().join("test.epub");
+    assert!(archive.save_as(&absolute).is_err());
+
+    let mut saved = EpubArchive::new(&path).unwrap();
+    assert_eq!(33, saved.files.len());
+    assert_eq!(
+        "<html>modified</html>",
+        saved.get_entry_as_str("OEBPS/Text/000.xhtml").unwrap()
+    );
+    assert_eq!(
+        "<html>new</html>",
+        saved.get_entry_as_str("OEBPS/Text/new.xhtml").unwrap()
+    );
+    assert_eq!(
+        archive.get_entry("OEBPS/Images/portada.png").unwrap(),
+        saved.get_entry("OEBPS/Images/portada.png").unwrap()
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn archive_modify_bin_entry() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    let image: Vec<u8> = vec![
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0xff, 0xfe, 0x00,
+    ];
+    archive.modify_entry("OEBPS/Images/portada.png", &image);
+
+    let path = std::env::temp_dir().join("epub-rs-archive-modify-bin.epub");
+    archive.save_as(&path).unwrap();
+    let mut saved = EpubArchive::new(&path).unwrap();
+    assert_eq!(image, saved.get_entry("OEBPS/Images/portada.png").unwrap());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn archive_deterministic_write() {
+    let mut options = WriteOptions::new();
+    options.deterministic(true);
+
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    archive.modify_entry("OEBPS/Text/new.xhtml", "<html/>");
+    archive.remove_entry("OEBPS/Text/001.xhtml").unwrap();
+    let first = archive
+        .write_to_with(Cursor::new(vec![]), &options)
+        .unwrap();
+
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    archive.remove_entry("OEBPS/Text/001.xhtml").unwrap();
+    archive.modify_entry("OEBPS/Text/new.xhtml", "<html/>");
+    let second = archive
+        .write_to_with(Cursor::new(vec![]), &options)
+        .unwrap();
+    assert_eq!(first.get_ref(), second.get_ref());
+
+    let mut zip = zip::ZipArchive::new(first).unwrap();
+    let names: Vec<String> = (0..zip.len())
+        .map(|i| zip.by_index(i).unwrap().name().to_string())
+        .collect();
+    assert_eq!("mimetype", names[0]);
+    assert_eq!("META-INF/container.xml", names[1]);
+    let mut rest = names[2..].to_vec();
+    rest.sort();
+    assert_eq!(rest, names[2..]);
+    assert!(names.contains(&String::from("OEBPS/Text/new.xhtml")));
+    assert!(!names.contains(&String::from("OEBPS/Text/001.xhtml")));
+
+    let mimetype = zip.by_name("mimetype").unwrap();
+    assert_eq!(zip::CompressionMethod::Stored, mimetype.compression());
+    assert_eq!(1980, mimetype.last_modified().year());
+}
+
+#[test]
+fn archive_write_compression() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    let epub = archive.write_to(Cursor::new(vec![])).unwrap();
+    let mut zip = zip::ZipArchive::new(epub).unwrap();
+    let mimetype = zip.by_index(0).unwrap();
+    assert_eq!("mimetype", mimetype.name());
+    assert_eq!(zip::CompressionMethod::Stored, mimetype.compression());
+    drop(mimetype);
+    assert_eq!(32, zip.len());
+
+    let mut options = WriteOptions::new();
+    options
+        .compression("image/*", Compression::Stored)
+        .compression("image/png", Compression::DeflatedLevel(1))
+        .compression("text/css", Compression::Stored);
+    let epub = archive
+        .write_to_with(Cursor::new(vec![]), &options)
+        .unwrap();
+    let mut zip = zip::ZipArchive::new(epub).unwrap();
+    let css = zip.by_name("OEBPS/Styles/stylesheet.css").unwrap();
+    assert_eq!(zip::CompressionMethod::Stored, css.compression());
+    assert_eq!(188, css.compressed_size());
+    drop(css);
+    let png = zip.by_name("OEBPS/Images/cc.png").unwrap();
+    assert_eq!(zip::CompressionMethod::Deflated, png.compression());
+    drop(png);
+    let chapter = zip.by_name("OEBPS/Text/001.xhtml").unwrap();
+    assert_eq!(zip::CompressionMethod::Deflated, chapter.compression());
+    assert_eq!(2189, chapter.compressed_size());
+}
+
+#[test]
+fn archive_write_missing_mimetype() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    archive.remove_entry("mimetype").unwrap();
+    let epub = archive.write_to(Cursor::new(vec![])).unwrap();
+    let mut archive = EpubArchive::from_reader(epub).unwrap();
+    assert_eq!(
+        "application/epub+zip",
+        archive.get_entry_as_str("mimetype").unwrap()
+    );
+}
+
+#[test]
+fn archive_from_directory() {
+    let dir = std::env::temp_dir().join("epub-rs-archive-directory");
+    let _ = fs::remove_dir_all(&dir);
+    let mut zip = zip::ZipArchive::new(fs::File::open("test.epub").unwrap()).unwrap();
+    zip.extract(&dir).unwrap();
+    fs::remove_file(dir.join("mimetype")).unwrap();
+
+    let mut archive = EpubArchive::from_directory(&dir).unwrap();
+    // the directory entries aren't added
+    assert_eq!(27, archive.files.len());
+    assert_eq!(
+        "application/epub+zip",
+        archive.get_entry_as_str("mimetype").unwrap()
+    );
+    let content = archive.get_entry("OEBPS/Text/titlepage.xhtml").unwrap();
+    assert_eq!(
+        fs::read(dir.join("OEBPS/Text/titlepage.xhtml")).unwrap(),
+        content
+    );
+
+    let epub = archive.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut zip = zip::ZipArchive::new(Cursor::new(epub)).unwrap();
+    let mimetype = zip.by_index(0).unwrap();
+    assert_eq!("mimetype", mimetype.name());
+    assert_eq!(zip::CompressionMethod::Stored, mimetype.compression());
+    drop(mimetype);
+    assert_eq!("META-INF/container.xml", zip.by_index(1).unwrap().name());
+
+    fs::remove_file(dir.join("OEBPS/content.opf")).unwrap();
+    assert!(EpubArchive::from_directory(&dir).is_err());
+    fs::remove_dir_all(dir.join("META-INF")).unwrap();
+    assert!(EpubArchive::from_directory(&dir).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn archive_mmap() {
+    let mut archive = unsafe { EpubArchive::new_mmap("test.epub") }.unwrap();
+    let mut buffered = EpubArchive::new("test.epub").unwrap();
+    assert_eq!("test.epub", archive.path.display().to_string());
+    assert_eq!(buffered.files.len(), archive.files.len());
+    assert_eq!(
+        buffered.get_entry("OEBPS/Images/portada.png").unwrap(),
+        archive.get_entry("OEBPS/Images/portada.png").unwrap()
+    );
+
+    assert_eq!(
+        b"application/epub+zip",
+        archive.get_mapped_entry("mimetype").unwrap()
+    );
+    // compressed
+    assert!(archive.get_mapped_entry("OEBPS/content.opf").is_none());
+    assert!(archive.get_mapped_entry("missing").is_none());
+    archive.modify_entry("mimetype", b"text/plain");
+    assert!(archive.get_mapped_entry("mimetype").is_none());
+
+    // the stored mimetype is encrypted, its content in the map is the
+    // ciphertext
+    let path = "tests/docs/protected.epub";
+    let mut archive = unsafe { EpubArchive::new_mmap(path) }.unwrap();
+    assert!(archive.get_mapped_entry("mimetype").is_none());
+    archive.password("secret").unwrap();
+    assert!(archive.get_mapped_entry("mimetype").is_none());
+
+    let mut limits = ArchiveLimits::new();
+    limits.max_entry_size(10);
+    let mut archive = unsafe { EpubArchive::new_mmap_with_limits("test.epub", &limits) }.unwrap();
+    assert!(archive.get_mapped_entry("mimetype").is_none());
+    limits.max_entry_size(20);
+    let mut archive = unsafe { EpubArchive::new_mmap_with_limits("test.epub", &limits) }.unwrap();
+    assert!(archive.get_mapped_entry("mimetype").is_some());
+}
+
+#[test]
+fn archive_from_stream() {
+    /// Reader without seek
+    struct Stream(fs::File);
+    impl std::io::Read for Stream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    let file = fs::File::open("test.epub").unwrap();
+    let mut stream = EpubArchive::from_stream(Stream(file));
+    let doc = stream.read_package().unwrap();
+    assert_eq!("Todo es mío", doc.mdata("title").unwrap());
+    assert_eq!(17, doc.spine.len());
+    assert_eq!(23, doc.resources.len());
+
+    let mut names = vec![];
+    while let Some(mut entry) = stream.next_entry().unwrap() {
+        if entry.name() == "OEBPS/Styles/stylesheet.css" {
+            let mut css = String::new();
+            entry.read_to_string(&mut css).unwrap();
+            assert!(css.contains("text-align: center"));
+        }
+        names.push(entry.name().to_string());
+    }
+    assert!(names.contains(&String::from("OEBPS/Images/portada.png")));
+
+    let epub = fs::read("test.epub").unwrap();
+    let mut stream = EpubArchive::from_stream(&epub[..100]);
+    assert!(stream.read_package().is_err());
+}
+
+#[test]
+fn archive_entry_reader() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    let expected = archive.get_entry("OEBPS/Text/titlepage.xhtml").unwrap();
+    let mut content = vec![];
+    archive
+        .get_entry_reader("OEBPS/Text/titlepage.xhtml")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(expected, content);
+
+    let mut content = String::new();
+    archive
+        .get_entry_reader("a%20%25%20encoded%20item.xml")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert!(content.is_empty());
+    assert!(archive.get_entry_reader("missing").is_err());
+
+    archive.modify_entry("OEBPS/Text/titlepage.xhtml", b"modified");
+    let mut content = vec![];
+    archive
+        .get_entry_reader("OEBPS/Text/titlepage.xhtml")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(b"modified", &content[..]);
+    archive.remove_entry("OEBPS/Text/titlepage.xhtml").unwrap();
+    assert!(archive
+        .get_entry_reader("OEBPS/Text/titlepage.xhtml")
+        .is_err());
+}
+
+#[test]
+fn archive_entry_info() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    let info = archive.entry_info("OEBPS/Images/portada.png").unwrap();
+    assert_eq!("OEBPS/Images/portada.png", info.name);
+    assert_eq!(1186183, info.size);
+    assert_eq!(1181285, info.compressed_size);
+    assert_eq!(0xce7218ec, info.crc32);
+    assert_eq!(zip::CompressionMethod::Deflated, info.compression);
+    assert_eq!(2012, info.last_modified.year());
+    assert_eq!(2, info.last_modified.month());
+
+    let info = archive.entry_info("a%20%25%20encoded%20item.xml").unwrap();
+    assert_eq!("a % encoded item.xml", info.name);
+    assert_eq!(0, info.size);
+    assert!(archive.entry_info("missing").is_err());
+
+    archive.modify_entry("OEBPS/Images/portada.png", b"modified");
+    assert!(archive.entry_info("OEBPS/Images/portada.png").is_err());
+}
+
+#[test]
+fn archive_extract_to() {
+    let dir = std::env::temp_dir().join("epub-rs-archive-extract");
+    let _ = fs::remove_dir_all(&dir);
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    archive.modify_entry("OEBPS/new.txt", b"new");
+    archive.remove_entry("OEBPS/Text/000.xhtml").unwrap();
+
+    let written = archive.extract_to(&dir).unwrap();
+    assert_eq!(27, written.len());
+    assert_eq!(b"new", &fs::read(dir.join("OEBPS/new.txt")).unwrap()[..]);
+    assert_eq!(
+        archive.get_entry("OEBPS/Images/portada.png").unwrap(),
+        fs::read(dir.join("OEBPS/Images/portada.png")).unwrap()
+    );
+    assert!(!dir.join("OEBPS/Text/000.xhtml").exists());
+
+    fs::write(dir.join("mimetype"), "changed").unwrap();
+    assert!(archive.extract_to_with(&dir, Overwrite::Fail).is_err());
+    assert!(archive
+        .extract_to_with(&dir, Overwrite::Skip)
+        .unwrap()
+        .is_empty());
+    assert_eq!("changed", fs::read_to_string(dir.join("mimetype")).unwrap());
+    archive.extract_to(&dir).unwrap();
+    assert_eq!(
+        "application/epub+zip",
+        fs::read_to_string(dir.join("mimetype")).unwrap()
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    // zip slip
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    let options = zip::write::FileOptions::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    zip.start_file("../epub-rs-zip-slip.txt", options).unwrap();
+    zip.write_all(b"outside").unwrap();
+    let epub = zip.finish().unwrap().into_inner();
+    let mut archive = EpubArchive::from_bytes(epub).unwrap();
+    assert!(matches!(
+        archive.extract_to(&dir),
+        Err(EpubError::UnsafePath(_))
+    ));
+    assert!(!dir.exists());
+    assert!(!std::env::temp_dir().join("epub-rs-zip-slip.txt").exists());
+}
+
+#[test]
+fn archive_entries_matching() {
+    let mut archive = EpubArchive::new("test.epub").unwrap();
+    assert_eq!(
+        vec!["OEBPS/Images/cc.png", "OEBPS/Images/portada.png"],
+        archive.names_matching("OEBPS/Images/*")
+    );
+    assert_eq!(vec!["mimetype"], archive.names_matching("mimetype"));
+    assert_eq!(6, archive.names_matching("OEBPS/Text/01?.xhtml").len());
+    // `*` doesn't match directories
+    assert!(archive.names_matching("OEBPS/*.png").is_empty());
+    assert_eq!(2, archive.names_matching("OEBPS/**.png").len());
+    assert!(archive.names_matching("OEBPS/Images").is_empty());
+    assert!(archive.names_matching("OEBPS/Images/[a-z].png").is_empty());
+
+    let mut entries = archive.entries_matching("OEBPS/Styles/**");
+    assert_eq!((1, Some(1)), entries.size_hint());
+    let (name, content) = entries.next().unwrap().unwrap();
+    assert_eq!("OEBPS/Styles/stylesheet.css", name);
+    assert_eq!(188, content.len());
+    assert!(entries.next().is_none());
+}
+
+#[test]
+fn archive_limits() {
+    let mut archive =
+        EpubArchive::new_with_limits("test.epub", &ArchiveLimits::untrusted()).unwrap();
+    assert_eq!(
+        188,
+        archive
+            .get_entry("OEBPS/Styles/stylesheet.css")
+            .unwrap()
+            .len()
+    );
+
+    let mut limits = ArchiveLimits::new();
+    limits.max_entries(32);
+    assert!(EpubArchive::new_with_limits("test.epub", &limits).is_ok());
+    limits.max_entries(31);
+    let err = EpubArchive::new_with_limits("test.epub", &limits)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("32 entries"));
+    assert!(matches!(err, EpubError::LimitExceeded(_)));
+
+    let mut limits = ArchiveLimits::new();
+    limits.max_total_size(1024 * 1024);
+    assert!(EpubArchive::new_with_limits("test.epub", &limits).is_err());
+
+    // a small zip with 1 MiB of zeros
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("zeros", zip::write::FileOptions::default())
+        .unwrap();
+    zip.write_all(&vec![0; 1024 * 1024]).unwrap();
+    let bomb = zip.finish().unwrap().into_inner();
+    assert!(bomb.len() < 10 * 1024);
+    let mut limits = ArchiveLimits::new();
+    limits.max_ratio(100);
+    let err = EpubArchive::from_reader_with_limits(Cursor::new(bomb.clone()), &limits)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("zeros"));
+    let mut archive = EpubArchive::from_bytes(bomb).unwrap();
+    assert_eq!(1024 * 1024, archive.get_entry("zeros").unwrap().len());
+}
+
+#[test]
+fn archive_entry_reader_limits() {
+    let mut limits = ArchiveLimits::new();
+    limits.max_entry_size(200);
+    let mut archive = EpubArchive::new_with_limits("test.epub", &limits).unwrap();
+    let mut content = vec![];
+    archive
+        .get_entry_reader("OEBPS/Styles/stylesheet.css")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(188, content.len());
+    assert!(matches!(
+        archive.get_entry_reader("OEBPS/Images/portada.png").err(),
+        Some(EpubError::LimitExceeded(_))
+    ));
+
+    // a zip declaring 10 bytes for an entry of 1000
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("liar", zip::write::FileOptions::default())
+        .unwrap();
+    zip.write_all(&[b'a'; 1000]).unwrap();
+    let mut bytes = zip.finish().unwrap().into_inner();
+    for (signature, offset) in [(b"PK\x03\x04", 22), (b"PK\x01\x02", 24)].iter() {
+        let header = bytes.windows(4).position(|w| w == *signature).unwrap();
+        bytes[header + offset..header + offset + 4].copy_from_slice(&10u32.to_le_bytes());
+    }
+    let mut archive = EpubArchive::from_bytes(bytes.clone()).unwrap();
+    assert_eq!(1000, archive.get_entry("liar").unwrap().len());
+    let mut limits = ArchiveLimits::new();
+    limits.max_total_size(1024);
+    let mut archive = EpubArchive::from_reader_with_limits(Cursor::new(bytes), &limits).unwrap();
+    let mut content = vec![];
+    let err = archive
+        .get_entry_reader("liar")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap_err();
+    let err = err.get_ref().unwrap().downcast_ref::<EpubError>();
+    assert!(matches!(err, Some(EpubError::LimitExceeded(_))));
+    assert!(matches!(
+        archive.get_entry("liar"),
+        Err(EpubError::LimitExceeded(_))
+    ));
+}
+
+#[test]
+fn archive_entry_encoding() {
+    let utf16 = |text: &str, bom: bool| {
+        let mut content = if bom { vec![0xff, 0xfe] } else { vec![] };
+        content.extend(text.encode_utf16().flat_map(|u| u.to_le_bytes()));
+        content
+    };
+    let files: Vec<(&str, Vec<u8>)> = vec![
+        (
+            "latin1.xhtml",
+            b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><p>caf\xe9</p>".to_vec(),
+        ),
+        (
+            "meta.html",
+            b"<html><head><meta charset=\"windows-1252\"></head><p>\x93hi\x94</p></html>".to_vec(),
+        ),
+        ("legacy.xhtml", b"<p>na\xefve</p>".to_vec()),
+        ("utf16.xhtml", utf16("<p>\u{f1}and\u{fa}</p>", true)),
+        ("utf16-nobom.xhtml", utf16("<p>\u{f1}</p>", false)),
+        ("bom.xhtml", b"\xef\xbb\xbf<p>\xc3\xb1</p>".to_vec()),
+        (
+            "koi8.xhtml",
+            b"<?xml version=\"1.0\" encoding=\"KOI8-R\"?><p/>".to_vec(),
+        ),
+    ];
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    for (name, content) in files.iter() {
+        zip.start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(content).unwrap();
+    }
+    let mut archive = EpubArchive::from_bytes(zip.finish().unwrap().into_inner()).unwrap();
+
+    assert_eq!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><p>café</p>",
+        archive.get_entry_as_str("latin1.xhtml").unwrap()
+    );
+    assert!(archive
+        .get_entry_as_str("meta.html")
+        .unwrap()
+        .contains("<p>\u{201c}hi\u{201d}</p>"));
+    assert_eq!(
+        "<p>naïve</p>",
+        archive.get_entry_as_str("legacy.xhtml").unwrap()
+    );
+    assert_eq!(
+        "<p>ñandú</p>",
+        archive.get_entry_as_str("utf16.xhtml").unwrap()
+    );
+    assert_eq!(
+        "<p>ñ</p>",
+        archive.get_entry_as_str("utf16-nobom.xhtml").unwrap()
+    );
+    assert_eq!("<p>ñ</p>", archive.get_entry_as_str("bom.xhtml").unwrap());
+    assert!(matches!(
+        archive.get_entry_as_str("koi8.xhtml"),
+        Err(EpubError::Encoding(_))
+    ));
+}
+
+#[test]
+fn archive_password() {
+    let path = "tests/docs/protected.epub";
+    let mut archive = EpubArchive::new_with_password(path, "secret").unwrap();
+    let chapter = archive.get_entry_as_str("c1.xhtml").unwrap();
+    assert!(chapter.contains("The secret chapter."));
+    let mut content = String::new();
+    archive
+        .get_entry_reader("content.opf")
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert!(content.contains("<dc:title>Protected</dc:title>"));
+
+    match EpubArchive::new_with_password(path, "wrong") {
+        Err(EpubError::InvalidPassword(name)) => assert_eq!("mimetype", name),
+        other => panic!("expected a wrong password, got {:?}", other.err()),
+    }
+    // without the password the entries can't be read
+    let mut archive = EpubArchive::new(path).unwrap();
+    assert!(matches!(
+        archive.get_entry("c1.xhtml"),
+        Err(EpubError::InvalidPassword(_))
+    ));
+    archive.password("secret").unwrap();
+    assert!(archive.get_entry("c1.xhtml").is_ok());
+
+    // the archives without encrypted entries take any password
+    assert!(EpubArchive::new_with_password("test.epub", "secret").is_ok());
+}
@@ -0,0 +1,115 @@
+#![cfg(feature = "async")]
+
+use epub::archive::Overwrite;
+use epub::error::EpubError;
+use epub::r#async::{set_spawner, EpubDoc, Job};
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn async_open() {
+    block_on(async {
+        let doc = EpubDoc::open("test.epub").await.unwrap();
+        assert_eq!("Todo es mío", doc.mdata("title").await.unwrap());
+        assert_eq!(17, doc.with(|doc| doc.spine.len()).await.unwrap());
+        let cover = doc.get_cover().await.unwrap();
+        assert_eq!(1186183, cover.len());
+        let css = doc.get_resource_str("stylesheet.css").await.unwrap();
+        assert!(css.contains("text-align: center"));
+        assert!(doc.get_resource("missing").await.is_err());
+
+        assert!(EpubDoc::open("missing.epub").await.is_err());
+    });
+}
+
+#[test]
+fn async_extract_to() {
+    let dir = std::env::temp_dir().join("epub-rs-async-extract");
+    let _ = std::fs::remove_dir_all(&dir);
+    block_on(async {
+        let content = std::fs::read("test.epub").unwrap();
+        let doc = EpubDoc::from_bytes(content).await.unwrap();
+        let written = doc.extract_to(&dir, Overwrite::Replace).await.unwrap();
+        assert_eq!(27, written.len());
+        assert!(dir.join("mimetype").is_file());
+        let css = std::fs::read(dir.join("OEBPS/Styles/stylesheet.css")).unwrap();
+        assert_eq!(
+            doc.get_resource_by_path("OEBPS/Styles/stylesheet.css")
+                .await
+                .unwrap(),
+            css
+        );
+    });
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn async_poisoned_doc() {
+    block_on(async {
+        let doc = EpubDoc::open("test.epub").await.unwrap();
+        let other = doc.clone();
+        assert!(doc.with(|_| panic!("broken task")).await.is_err());
+        // the doc may be half modified, it isn't used anymore
+        assert!(matches!(
+            other.get_cover().await,
+            Err(EpubError::InvalidOperation(_))
+        ));
+        assert_eq!(None, other.mdata("title").await);
+    });
+}
+
+#[test]
+fn async_many_tasks() {
+    block_on(async {
+        let doc = EpubDoc::open("test.epub").await.unwrap();
+        // the tasks run in the threads of the pool, not in new ones
+        let mut threads = std::collections::HashSet::new();
+        for _ in 0..100 {
+            threads.insert(doc.with(|_| thread::current().id()).await.unwrap());
+        }
+        assert!(threads.len() <= 16);
+    });
+}
+
+#[test]
+fn async_spawner() {
+    // every task of the test runs in the same thread from now on
+    let (jobs, receiver) = std::sync::mpsc::channel::<Job>();
+    let worker = thread::spawn(move || {
+        for job in receiver {
+            job();
+        }
+    })
+    .thread()
+    .id();
+    let jobs = std::sync::Mutex::new(jobs);
+    set_spawner(move |job| jobs.lock().unwrap().send(job).unwrap()).unwrap();
+    assert!(set_spawner(|job| job()).is_err());
+
+    block_on(async {
+        let doc = EpubDoc::open("test.epub").await.unwrap();
+        assert_eq!(worker, doc.with(|_| thread::current().id()).await.unwrap());
+    });
+}
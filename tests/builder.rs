@@ -0,0 +1,361 @@
+use epub::archive::EpubArchive;
+use epub::builder::EpubBuilder;
+use epub::doc::{EpubDoc, NavPoint};
+use epub::error::EpubError;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+const CHAPTER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>Chapter 1</title></head>
+<body><p>Hello world</p></body>
+</html>"#;
+
+fn book() -> EpubBuilder {
+    let mut builder = EpubBuilder::new();
+    builder
+        .metadata("title", "Testing book")
+        .metadata("creator", "Me")
+        .metadata("language", "en")
+        .metadata(
+            "identifier",
+            "urn:uuid:6d4b38c7-8aea-4b5e-b1a6-0f51d2c8a7b4",
+        )
+        .add_resource("style.css", b"p { color: red }".to_vec(), "text/css")
+        .add_content("Text/chapter1.xhtml", CHAPTER.as_bytes().to_vec())
+        .add_toc_entry("Chapter 1", "Text/chapter1.xhtml");
+    builder
+}
+
+#[test]
+fn builder_write() {
+    let epub = book().write_to(Cursor::new(vec![])).unwrap().into_inner();
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(epub.clone())).unwrap();
+    let mimetype = zip.by_index(0).unwrap();
+    assert_eq!("mimetype", mimetype.name());
+    assert_eq!(zip::CompressionMethod::Stored, mimetype.compression());
+    drop(mimetype);
+
+    let mut archive = EpubArchive::from_reader(Cursor::new(epub.clone())).unwrap();
+    assert_eq!(
+        "application/epub+zip",
+        archive.get_entry_as_str("mimetype").unwrap()
+    );
+
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+    assert_eq!(Path::new("OEBPS/content.opf"), doc.root_file);
+    assert_eq!(doc.mdata("title").unwrap(), "Testing book");
+    assert_eq!(doc.mdata("creator").unwrap(), "Me");
+    assert_eq!(
+        doc.unique_identifier.as_ref().unwrap(),
+        "urn:uuid:6d4b38c7-8aea-4b5e-b1a6-0f51d2c8a7b4"
+    );
+    assert!(doc.mdata("dcterms:modified").is_some());
+
+    assert_eq!(1, doc.spine.len());
+    assert_eq!(
+        Path::new("OEBPS/Text/chapter1.xhtml"),
+        doc.get_current_path().unwrap()
+    );
+    assert_eq!(CHAPTER, doc.get_current_str().unwrap());
+    assert_eq!(
+        "text/css",
+        doc.get_resource_mime_by_path("OEBPS/style.css").unwrap()
+    );
+
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(nav.contains(r#"<a href="Text/chapter1.xhtml">Chapter 1</a>"#));
+}
+
+#[test]
+fn builder_missing_metadata() {
+    let mut builder = EpubBuilder::new();
+    builder.metadata("title", "No language");
+    match builder.write_to(Cursor::new(vec![])) {
+        Err(EpubError::MissingMetadata(name)) => assert_eq!("identifier", name),
+        other => panic!("expected a missing metadata, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn builder_duplicate_path() {
+    let mut builder = book();
+    builder.add_content("Text/chapter1.xhtml", CHAPTER.as_bytes().to_vec());
+    match builder.build() {
+        Err(EpubError::AlreadyExists(path)) => assert_eq!("Text/chapter1.xhtml", path),
+        other => panic!("expected an existing path, got {:?}", other.err()),
+    }
+
+    let mut builder = book();
+    builder.add_resource(
+        "nav.xhtml",
+        CHAPTER.as_bytes().to_vec(),
+        "application/xhtml+xml",
+    );
+    match builder.build() {
+        Err(EpubError::AlreadyExists(path)) => assert_eq!("nav.xhtml", path),
+        other => panic!("expected an existing path, got {:?}", other.err()),
+    }
+}
+
+#[test]
+fn builder_set_cover() {
+    let epub = book().write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+    assert!(doc.get_cover().is_err());
+
+    let png = vec![0x89, 0x50, 0x4e, 0x47];
+    doc.set_cover(&png, "image/png").unwrap();
+    assert_eq!("cover-image", doc.get_cover_id().unwrap());
+    assert_eq!(png, doc.get_cover().unwrap());
+    assert_eq!(
+        Path::new("OEBPS/cover.png"),
+        doc.resources.get("cover-image").unwrap().0
+    );
+    let opf = doc.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    assert!(opf.contains(r#"properties="cover-image""#));
+}
+
+#[test]
+fn builder_add_chapter() {
+    let mut builder = book();
+    builder
+        .add_chapter("Chapter <2>", "<p>Second chapter</p>")
+        .add_chapter("Chapter 3", CHAPTER);
+    let epub = builder.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+
+    assert_eq!(3, doc.spine.len());
+    doc.set_current_page(1).unwrap();
+    assert_eq!(
+        Path::new("OEBPS/chapter_2.xhtml"),
+        doc.get_current_path().unwrap()
+    );
+    let content = doc.get_current_str().unwrap();
+    assert!(content.contains("<title>Chapter &lt;2&gt;</title>"));
+    assert!(content.contains("<body>\n<p>Second chapter</p>\n</body>"));
+    doc.go_next().unwrap();
+    assert_eq!(CHAPTER, doc.get_current_str().unwrap());
+
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert!(nav.contains(r#"<a href="chapter_2.xhtml">Chapter &lt;2&gt;</a>"#));
+    assert!(nav.contains(r#"<a href="chapter_3.xhtml">Chapter 3</a>"#));
+}
+
+#[cfg(feature = "markdown")]
+#[test]
+fn builder_add_chapter_md() {
+    let markdown = "# Chapter 2\n\
+        \n\
+        Some *emphasis* and **strong** text with `<code>`.\n\
+        \n\
+        ## First part\n\
+        \n\
+        - one\n\
+        - [two](https://example.com)\n\
+        \n\
+        ### Detail\n\
+        \n\
+        ```\n\
+        let a = 1 < 2;\n\
+        ```\n\
+        \n\
+        ## Second part\n\
+        \n\
+        > quoted\n";
+    let mut builder = book();
+    builder.add_chapter_md("Chapter 2", markdown);
+
+    assert_eq!(2, builder.toc.len());
+    let chapter = &builder.toc[1];
+    assert_eq!(2, chapter.play_order);
+    assert_eq!(2, chapter.children.len());
+    assert_eq!("First part", chapter.children[0].label);
+    assert_eq!(
+        Path::new("OEBPS/chapter_2.xhtml#first-part"),
+        chapter.children[0].content
+    );
+    assert_eq!("Detail", chapter.children[0].children[0].label);
+    assert_eq!(4, chapter.children[0].children[0].play_order);
+    assert_eq!("Second part", chapter.children[1].label);
+
+    let epub = builder.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+    doc.set_current_page(1).unwrap();
+    let content = doc.get_current_str().unwrap();
+    assert!(content.contains(r#"<h1 id="chapter-2">Chapter 2</h1>"#));
+    assert!(content.contains("<em>emphasis</em> and <strong>strong</strong>"));
+    assert!(content.contains("<code>&lt;code&gt;</code>"));
+    assert!(content.contains(r#"<li><a href="https://example.com">two</a></li>"#));
+    assert!(content.contains("<pre><code>let a = 1 &lt; 2;</code></pre>"));
+    assert!(content.contains("<blockquote>\n<p>quoted</p>\n</blockquote>"));
+
+    let markdown = "Setext \\*title\\*\n\
+        ===\n\
+        \n\
+        Not \\*emphasis\\*, `a\\*b` and \\[no link\\](x).\n\
+        \n\
+        - outer\n  \
+        - inner\n\
+        - last\n";
+    let mut builder = book();
+    builder.add_chapter_md("Setext", markdown);
+    assert_eq!("Setext *title*", builder.toc[1].children[0].label);
+    let mut doc = builder.build().unwrap().into_doc().unwrap();
+    doc.set_current_page(1).unwrap();
+    let content = doc.get_current_str().unwrap();
+    assert!(content.contains(r#"<h1 id="setext-title">Setext *title*</h1>"#));
+    assert!(content.contains("<p>Not *emphasis*, <code>a\\*b</code> and [no link](x).</p>"));
+    assert!(content.contains("<li>outer<ul>\n<li>inner</li>\n</ul>\n</li>\n<li>last</li>"));
+}
+
+#[test]
+fn builder_navigation() {
+    let mut builder = book();
+    builder.add_chapter("Chapter 2", "<p>Second chapter</p>");
+    builder.toc[1].children.push(NavPoint {
+        label: String::from("Section"),
+        content: PathBuf::from("OEBPS/chapter_2.xhtml#section"),
+        children: vec![],
+        play_order: 3,
+    });
+    let epub = builder.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+
+    assert_eq!(2, doc.toc.len());
+    assert_eq!("Section", doc.toc[1].children[0].label);
+    assert_eq!(
+        Path::new("OEBPS/chapter_2.xhtml#section"),
+        doc.toc[1].children[0].content
+    );
+    let nav = doc.get_resource_str("nav").unwrap();
+    assert!(nav.contains(r##"<a href="chapter_2.xhtml#section">Section</a>"##));
+
+    // regenerated when missing
+    doc.remove_resource("nav").unwrap();
+    doc.remove_resource("ncx").unwrap();
+    assert!(doc.toc.is_empty());
+    doc.toc.push(NavPoint {
+        label: String::from("Only chapter"),
+        content: PathBuf::from("OEBPS/Text/chapter1.xhtml"),
+        children: vec![],
+        play_order: 1,
+    });
+    doc.update_navigation().unwrap();
+    assert_eq!(1, doc.toc.len());
+    let nav = doc.get_resource_str("nav").unwrap();
+    assert!(nav.contains(r#"<nav epub:type="toc" id="toc">"#));
+    assert!(nav.contains(r#"<a href="Text/chapter1.xhtml">Only chapter</a>"#));
+    let ncx = doc.get_resource_str("ncx").unwrap();
+    assert!(ncx.contains(r#"<content src="Text/chapter1.xhtml" />"#));
+    let opf = doc.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    assert!(opf.contains(r#"<spine toc="ncx">"#));
+}
+
+#[test]
+fn builder_toc_from_headings() {
+    let mut builder = book();
+    builder
+        .add_chapter(
+            "Chapter 2",
+            r#"<h1 id="c2">Chapter <em>2</em></h1><h2 id="s1">First</h2>
+            <h3>Detail</h3><h4 id="ignored">Ignored</h4><h2 id="s2">Second</h2>"#,
+        )
+        .add_chapter("Chapter 3", r#"<h2 id="c3">Chapter 3</h2>"#)
+        .toc_from_headings();
+
+    // the first chapter doesn't have headings
+    assert_eq!(1, builder.toc.len());
+    let chapter = &builder.toc[0];
+    assert_eq!("Chapter 2", chapter.label);
+    assert_eq!(Path::new("OEBPS/chapter_2.xhtml#c2"), chapter.content);
+    assert_eq!(3, chapter.children.len());
+    assert_eq!("Detail", chapter.children[0].children[0].label);
+    assert_eq!(
+        Path::new("OEBPS/chapter_2.xhtml"),
+        chapter.children[0].children[0].content
+    );
+    assert_eq!(3, chapter.children[0].children[0].play_order);
+    assert_eq!(
+        Path::new("OEBPS/chapter_3.xhtml#c3"),
+        chapter.children[2].content
+    );
+
+    let epub = builder.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+    let nav = doc.get_resource_str("nav").unwrap();
+    assert!(nav.contains(r##"<a href="chapter_2.xhtml#s2">Second</a>"##));
+}
+
+#[test]
+fn builder_minimal() {
+    let open = |builder: EpubBuilder| {
+        let epub = builder.write_to(Cursor::new(vec![])).unwrap().into_inner();
+        EpubDoc::from_reader(Cursor::new(epub)).unwrap()
+    };
+    let mut doc = open(EpubBuilder::minimal("Skeleton", "Me", "en"));
+
+    assert_eq!(doc.mdata("title").unwrap(), "Skeleton");
+    assert_eq!(doc.mdata("language").unwrap(), "en");
+    let uid = doc.unique_identifier.clone().unwrap();
+    assert!(uid.starts_with("urn:uuid:"));
+    assert_eq!(45, uid.len());
+    let other = open(EpubBuilder::minimal("Skeleton", "Me", "en"));
+    assert_ne!(uid, other.unique_identifier.unwrap());
+
+    assert_eq!(1, doc.spine.len());
+    assert_eq!(1, doc.toc.len());
+    let chapter = doc.get_current_str().unwrap();
+    assert!(chapter.contains("<title>Skeleton</title>"));
+    assert!(doc.get_resource_str("nav").is_ok());
+}
+
+#[test]
+fn builder_append_doc() {
+    let mut builder = EpubBuilder::new();
+    builder.metadata("language", "en");
+    for path in [
+        "test.epub",
+        "test.epub",
+        "tests/docs/Metamorphosis-jackson.epub",
+    ]
+    .iter()
+    {
+        let mut doc = EpubDoc::new(path).unwrap();
+        builder.append_doc(&mut doc).unwrap();
+    }
+    let epub = builder.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+
+    assert_eq!("Todo es mío", doc.mdata("title").unwrap());
+    assert!(doc
+        .unique_identifier
+        .as_ref()
+        .unwrap()
+        .starts_with("urn:uuid:"));
+    let creators = &doc.metadata["creator"];
+    assert!(creators.contains(&String::from("Franz Kafka")));
+    assert_eq!(2, doc.metadata["language"].len());
+
+    assert_eq!(3, doc.toc.len());
+    assert_eq!("Todo es mío", doc.toc[1].label);
+    assert_eq!(16, doc.toc[1].children.len());
+    assert_eq!("Metamorphosis", doc.toc[2].label);
+
+    // the second copy is moved to a new directory
+    doc.set_current_page(17).unwrap();
+    assert_eq!(
+        Path::new("OEBPS/book-1/Text/titlepage.xhtml"),
+        doc.get_current_path().unwrap()
+    );
+    assert!(doc
+        .get_current_str()
+        .unwrap()
+        .contains("../Images/portada.png"));
+    assert!(doc
+        .get_resource_by_path("OEBPS/book-1/Images/portada.png")
+        .is_ok());
+    let nav = doc.get_resource_str("nav").unwrap();
+    assert!(nav.contains(r#"href="book-1/Text/titlepage.xhtml""#));
+}
@@ -1,5 +1,17 @@
-use epub::doc::EpubDoc;
-use std::path::Path;
+use epub::archive::{Compression, WriteOptions};
+use epub::builder::EpubBuilder;
+use epub::chunk::{chunk_text, ChunkOptions};
+use epub::doc::{EpubDoc, NavPoint, OpenOptions, ParseMode, ReadingDirection, Resource};
+use epub::encryption::{deobfuscate, Drm, ADOBE_OBFUSCATION, IDPF_OBFUSCATION};
+use epub::error::EpubError;
+use epub::metadata::{Date, DateEvent, IdentifierScheme, Role, TitleType};
+use epub::overlay::{parse_clock_value, OverlayNode};
+use epub::rendition::{Flow, Layout, Orientation, Spread};
+use epub::search::SearchOptions;
+use epub::text::{BlockKind, Ruby, RubyMode, TextBlock, TextOptions};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
 
 #[test]
 fn doc_open() {
@@ -18,7 +30,7 @@ fn doc_open() {
 
     {
         assert_eq!(17, doc.spine.len());
-        assert_eq!("titlepage.xhtml", doc.spine[0]);
+        assert_eq!("titlepage.xhtml", doc.spine[0].idref);
     }
 
     {
@@ -72,10 +84,4156 @@ fn toc_test() {
     assert!(doc.is_ok());
     let doc = doc.unwrap();
 
-    assert!(doc.toc.len() > 0);
+    assert!(!doc.toc.is_empty());
     for nav in doc.toc.iter() {
         let chapter = doc.resource_uri_to_chapter(&nav.content);
         assert!(chapter.is_some());
         assert_eq!(nav.play_order, chapter.unwrap());
     }
 }
+
+#[test]
+fn doc_modify_entry() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let opf = doc.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    let opf = opf.replace(
+        "<dc:title>Todo es mío</dc:title>",
+        "<dc:title>Modified</dc:title>",
+    );
+    doc.modify_entry("OEBPS/content.opf", &opf).unwrap();
+    assert_eq!("Modified", doc.mdata("title").unwrap());
+    assert_eq!(17, doc.spine.len());
+
+    let path = std::env::temp_dir().join("epub-rs-doc-modify.epub");
+    doc.save_as(&path).unwrap();
+    let saved = EpubDoc::new(&path).unwrap();
+    assert_eq!("Modified", saved.mdata("title").unwrap());
+    assert_eq!(23, saved.resources.len());
+    std::fs::remove_file(&path).unwrap();
+
+    let opf = opf.replace(
+        "<dc:title>Modified</dc:title>",
+        "<dc:title>Again</dc:title>",
+    );
+    doc.modify_entry("OEBPS/./Text/../content%2Eopf", &opf)
+        .unwrap();
+    assert_eq!("Again", doc.mdata("title").unwrap());
+
+    // the toc is reloaded from the nav document
+    let mut builder = EpubBuilder::minimal("Title", "Me", "en");
+    builder.add_chapter("One", "<p>one</p>");
+    let mut doc = builder.build().unwrap().into_doc().unwrap();
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    doc.modify_entry("OEBPS/nav.xhtml", nav.replace(">One<", ">Uno<"))
+        .unwrap();
+    assert_eq!("Uno", doc.navs()["toc"][1].label);
+}
+
+#[test]
+fn doc_add_resource() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    doc.add_resource(
+        "notes.xhtml",
+        "Text/notes.xhtml",
+        "application/xhtml+xml",
+        "<html>notes</html>",
+    )
+    .unwrap();
+    assert!(doc
+        .add_resource(
+            "notes.xhtml",
+            "Text/other.xhtml",
+            "application/xhtml+xml",
+            ""
+        )
+        .is_err());
+    match doc.add_resource("other", "Text/notes.xhtml", "application/xhtml+xml", "") {
+        Err(EpubError::AlreadyExists(href)) => assert_eq!("Text/notes.xhtml", href),
+        other => panic!("expected an existing href, got {:?}", other),
+    }
+    assert_eq!(
+        "<html>notes</html>",
+        doc.get_resource_str("notes.xhtml").unwrap()
+    );
+    doc.add_spine_item("notes.xhtml").unwrap();
+    assert!(doc.add_spine_item("missing").is_err());
+
+    doc.add_resource(
+        "extra",
+        "Text/extra notes.xhtml",
+        "application/xhtml+xml",
+        "",
+    )
+    .unwrap();
+    let opf = doc.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    assert!(opf.contains(r#"href="Text/extra%20notes.xhtml""#));
+    assert_eq!(
+        Path::new("OEBPS/Text/extra notes.xhtml"),
+        doc.resources["extra"].0
+    );
+    doc.remove_resource("extra").unwrap();
+
+    assert_eq!(24, doc.resources.len());
+    assert_eq!(18, doc.spine.len());
+    assert_eq!("notes.xhtml", doc.spine[17].idref);
+
+    let path = std::env::temp_dir().join("epub-rs-doc-add-resource.epub");
+    doc.save_as(&path).unwrap();
+    let mut saved = EpubDoc::new(&path).unwrap();
+    assert_eq!(24, saved.resources.len());
+    assert_eq!(18, saved.spine.len());
+    assert_eq!("Todo es mío", saved.mdata("title").unwrap());
+    assert_eq!(
+        "urn:uuid:09132750-3601-4d19-b3a4-55fdf8639849",
+        saved.unique_identifier.clone().unwrap()
+    );
+    saved.set_current_page(17).unwrap();
+    assert_eq!("<html>notes</html>", saved.get_current_str().unwrap());
+    assert_eq!(
+        Path::new("OEBPS/Text/notes.xhtml"),
+        saved.get_current_path().unwrap()
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn doc_remove_resource() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let toc_len = doc.toc.len();
+    let removed = Path::new("OEBPS/Text/001.xhtml");
+    doc.remove_resource("001.xhtml").unwrap();
+    assert!(doc.remove_resource("001.xhtml").is_err());
+
+    assert_eq!(22, doc.resources.len());
+    assert_eq!(16, doc.spine.len());
+    assert!(doc.spine.iter().all(|item| item.idref != "001.xhtml"));
+    assert_eq!(toc_len - 1, doc.toc.len());
+    assert!(doc.toc.iter().all(|nav| nav.content != removed));
+
+    let path = std::env::temp_dir().join("epub-rs-doc-remove-resource.epub");
+    doc.save_as(&path).unwrap();
+    let mut saved = EpubDoc::new(&path).unwrap();
+    assert_eq!(16, saved.spine.len());
+    assert_eq!(toc_len - 1, saved.toc.len());
+    assert!(saved.get_resource_by_path(removed).is_err());
+    std::fs::remove_file(&path).unwrap();
+
+    // removing the cover also removes the cover meta
+    doc.remove_resource("portada.png").unwrap();
+    assert!(doc.get_cover_id().is_err());
+}
+
+#[test]
+fn doc_rename_resource() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    assert!(doc
+        .rename_resource("Text/000.xhtml", "Text/001.xhtml")
+        .is_err());
+    assert!(doc
+        .rename_resource("Text/missing.xhtml", "Text/new.xhtml")
+        .is_err());
+
+    doc.rename_resource("Styles/stylesheet.css", "style.css")
+        .unwrap();
+    doc.rename_resource("Text/001.xhtml", "chapters/one.xhtml")
+        .unwrap();
+
+    assert_eq!(
+        Path::new("OEBPS/style.css"),
+        doc.resources.get("stylesheet.css").unwrap().0
+    );
+    let one = Path::new("OEBPS/chapters/one.xhtml");
+    assert_eq!(one, doc.resources.get("001.xhtml").unwrap().0);
+    assert!(doc.toc.iter().any(|nav| nav.content == one));
+
+    let content = doc.get_resource_str("001.xhtml").unwrap();
+    assert!(content.contains(r#"href="../style.css""#));
+    let content = doc.get_resource_str("002.xhtml").unwrap();
+    assert!(content.contains(r#"href="../style.css""#));
+    assert!(doc
+        .get_resource_by_path("OEBPS/Styles/stylesheet.css")
+        .is_err());
+
+    let path = std::env::temp_dir().join("epub-rs-doc-rename-resource.epub");
+    doc.save_as(&path).unwrap();
+    let mut saved = EpubDoc::new(&path).unwrap();
+    saved.set_current_page(2).unwrap();
+    assert_eq!(one, saved.get_current_path().unwrap());
+    assert!(saved.get_current_str().is_ok());
+    std::fs::remove_file(&path).unwrap();
+
+    // added entries aren't replaced and the css must be valid utf-8
+    doc.add_resource("notes", "notes.xhtml", "application/xhtml+xml", "<html/>")
+        .unwrap();
+    match doc.rename_resource("style.css", "notes.xhtml") {
+        Err(EpubError::AlreadyExists(href)) => assert_eq!("notes.xhtml", href),
+        other => panic!("expected an existing href, got {:?}", other),
+    }
+    doc.add_resource(
+        "latin1",
+        "latin1.css",
+        "text/css",
+        b"p { content: \"\xe9\" }",
+    )
+    .unwrap();
+    match doc.rename_resource("Images/portada.png", "cover.png") {
+        Err(EpubError::Encoding(_)) => {}
+        other => panic!("expected an encoding error, got {:?}", other),
+    }
+    assert_eq!(
+        Path::new("OEBPS/Images/portada.png"),
+        doc.resources["portada.png"].0
+    );
+}
+
+#[test]
+fn doc_metadata_write() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    doc.set_title("Nuevo título").unwrap();
+    doc.set_creator("Someone").unwrap();
+    doc.set_language("en").unwrap();
+    doc.add_identifier("urn:isbn:9780000000000").unwrap();
+    doc.set_metadata("custom", "value").unwrap();
+
+    assert_eq!("Nuevo título", doc.mdata("title").unwrap());
+    assert_eq!("en", doc.mdata("language").unwrap());
+    assert_eq!(2, doc.metadata.get("identifier").unwrap().len());
+    assert_eq!(
+        "urn:uuid:09132750-3601-4d19-b3a4-55fdf8639849",
+        doc.unique_identifier.clone().unwrap()
+    );
+    assert_ne!(
+        "2015-08-10T18:12:03Z",
+        doc.mdata("dcterms:modified").unwrap()
+    );
+
+    let path = std::env::temp_dir().join("epub-rs-doc-metadata-write.epub");
+    doc.save_as(&path).unwrap();
+    let saved = EpubDoc::new(&path).unwrap();
+    assert_eq!("Nuevo título", saved.mdata("title").unwrap());
+    assert_eq!("Someone", saved.mdata("creator").unwrap());
+    assert_eq!("value", saved.mdata("custom").unwrap());
+    assert_eq!("portada.png", saved.get_cover_id().unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn doc_set_cover() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    match doc.set_cover("<html/>", "application/xhtml+xml") {
+        Err(EpubError::InvalidOperation(_)) => {}
+        other => panic!("expected an invalid operation, got {:?}", other),
+    }
+
+    let jpeg = vec![0xff, 0xd8, 0xff, 0xe0];
+    doc.set_cover(&jpeg, "image/jpeg").unwrap();
+    assert_eq!(
+        Path::new("OEBPS/Images/portada.jpg"),
+        doc.resources["portada.png"].0
+    );
+    assert_eq!(jpeg, doc.get_cover().unwrap());
+    let title_page = doc.get_resource_str("titlepage.xhtml").unwrap();
+    assert!(title_page.contains("../Images/portada.jpg"));
+    // no EPUB3 properties in an EPUB2 package
+    let opf = doc.get_resource_str_by_path("OEBPS/content.opf").unwrap();
+    assert!(!opf.contains("cover-image"));
+
+    // a xhtml cover page isn't replaced
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title>
+          <meta name="cover" content="cover"/></metadata>
+        <manifest>
+          <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="cover"/></spine>
+        </package>"#;
+    let files = [("cover.xhtml", "<html>cover</html>")];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    doc.set_cover(&jpeg, "image/jpeg").unwrap();
+    assert_eq!("cover-image", doc.get_cover_id().unwrap());
+    assert_eq!(Path::new("cover.jpg"), doc.resources["cover-image"].0);
+    assert_eq!(jpeg, doc.get_cover().unwrap());
+    assert_eq!("<html>cover</html>", doc.get_resource_str("cover").unwrap());
+    let opf = doc.get_resource_str_by_path("content.opf").unwrap();
+    assert!(opf.contains(r#"properties="cover-image""#));
+}
+
+#[test]
+fn doc_spine_order() {
+    let mut doc = EpubDoc::new("tests/docs/Metamorphosis-jackson.epub").unwrap();
+    doc.set_current_page(5).unwrap();
+    assert_eq!("chapter-001", doc.get_current_id().unwrap());
+
+    assert!(doc.move_spine_item(0, 8).is_err());
+    assert!(doc.set_spine_order(&["front-cover"]).is_err());
+
+    let mut order = doc.spine.clone();
+    order.reverse();
+    doc.set_spine_order(&order).unwrap();
+    assert_eq!(order, doc.spine);
+    assert_eq!("chapter-001", doc.get_current_id().unwrap());
+    assert_eq!(2, doc.get_current_page());
+
+    doc.move_spine_item(7, 0).unwrap();
+    assert_eq!("front-cover", doc.spine[0].idref);
+    assert_eq!("chapter-003", doc.spine[1].idref);
+
+    let path = std::env::temp_dir().join("epub-rs-doc-spine-order.epub");
+    doc.save_as(&path).unwrap();
+    let saved = EpubDoc::new(&path).unwrap();
+    assert_eq!(doc.spine, saved.spine);
+    let opf = doc.get_resource_str_by_path("book.opf").unwrap();
+    assert!(opf.contains(r#"<itemref idref="front-cover" linear="no" />"#));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn doc_update_navigation() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let nested = doc.toc.remove(1);
+    doc.toc[0].children.push(nested);
+    doc.update_navigation().unwrap();
+    assert_eq!(15, doc.toc.len());
+
+    let path = std::env::temp_dir().join("epub-rs-doc-update-navigation.epub");
+    doc.save_as(&path).unwrap();
+    let mut saved = EpubDoc::new(&path).unwrap();
+    assert_eq!(15, saved.toc.len());
+    assert_eq!("Despertar", saved.toc[0].children[0].label);
+    assert_eq!(
+        Path::new("OEBPS/Text/001.xhtml"),
+        saved.toc[0].children[0].content
+    );
+    assert_eq!(3, saved.toc[1].play_order);
+    let ncx = saved.get_resource_str("ncx").unwrap();
+    assert!(
+        ncx.contains(r#"<meta content="09132750-3601-4d19-b3a4-55fdf8639849" name="dtb:uid"/>"#)
+    );
+    // no nav document for EPUB2
+    assert_eq!(23, saved.resources.len());
+    std::fs::remove_file(&path).unwrap();
+
+    // the nav document of EPUB3 has its toc replaced, not repeated
+    let mut builder = EpubBuilder::minimal("Title", "Me", "en");
+    builder
+        .add_chapter("One", "<p>one</p>")
+        .add_chapter("Two", "<p>two</p>");
+    let mut doc = builder.build().unwrap().into_doc().unwrap();
+    doc.toc.truncate(2);
+    doc.update_navigation().unwrap();
+    let nav = doc.get_resource_str_by_path("OEBPS/nav.xhtml").unwrap();
+    assert_eq!(1, nav.matches("<nav ").count());
+    assert_eq!(1, nav.matches(r#"epub:type="toc""#).count());
+    assert!(nav.contains("One"));
+    assert!(!nav.contains("Two"));
+}
+
+#[test]
+fn doc_toc_from_headings() {
+    let mut doc = EpubDoc::new("tests/docs/Metamorphosis-jackson.epub").unwrap();
+    doc.toc = doc.toc_from_headings().unwrap();
+    assert_eq!(2, doc.toc.len());
+    assert_eq!("Metamorphosis", doc.toc[0].label);
+    assert_eq!("Franz Kafka", doc.toc[0].children[0].label);
+    let contents = &doc.toc[1];
+    assert_eq!(3, contents.play_order);
+    assert_eq!(3, contents.children.len());
+    assert_eq!("CHAPTER II", contents.children[1].label);
+    assert_eq!(
+        Path::new("OEBPS/chapter-002-chapter-ii.html"),
+        contents.children[1].content
+    );
+
+    doc.update_navigation().unwrap();
+    let ncx = doc.get_resource_str_by_path("toc.ncx").unwrap();
+    assert!(ncx.contains("<text>CHAPTER III</text>"));
+}
+
+#[test]
+fn doc_save_compression() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let mut options = WriteOptions::new();
+    options.compression("image/png", Compression::Stored);
+    let path = std::env::temp_dir().join("epub-rs-doc-save-compression.epub");
+    doc.save_as_with(&path, &options).unwrap();
+
+    let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let cover = zip.by_name("OEBPS/Images/portada.png").unwrap();
+    assert_eq!(zip::CompressionMethod::Stored, cover.compression());
+    assert_eq!(1186183, cover.compressed_size());
+    drop(cover);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn doc_split() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    assert!(matches!(
+        doc.split(&[0..2, 2..18]),
+        Err(EpubError::InvalidOperation(_))
+    ));
+    assert!(doc.split(&[0..3, 3..3]).is_err());
+
+    let parts = doc.split(&[0..2, 2..17]).unwrap();
+    assert_eq!(2, parts.len());
+    let mut parts = parts.into_iter().map(|p| p.into_doc().unwrap());
+    let mut first = parts.next().unwrap();
+    let second = parts.next().unwrap();
+
+    assert_eq!(2, first.spine.len());
+    assert_eq!(15, second.spine.len());
+    assert_eq!(first.mdata("title").unwrap(), "Todo es mío");
+    assert_eq!(
+        first.unique_identifier.as_ref().unwrap(),
+        "urn:uuid:09132750-3601-4d19-b3a4-55fdf8639849-part-1"
+    );
+
+    // titlepage and 000.xhtml use the stylesheet and the images
+    let mut paths: Vec<_> = first.resources.values().map(|(p, _)| p.clone()).collect();
+    paths.sort();
+    assert_eq!(
+        vec![
+            Path::new("OEBPS/Images/cc.png"),
+            Path::new("OEBPS/Images/portada.png"),
+            Path::new("OEBPS/Styles/stylesheet.css"),
+            Path::new("OEBPS/Text/000.xhtml"),
+            Path::new("OEBPS/Text/titlepage.xhtml"),
+            Path::new("OEBPS/nav.xhtml"),
+            Path::new("OEBPS/toc.ncx"),
+        ],
+        paths
+    );
+    assert_eq!(18, second.resources.len());
+    assert_eq!("Despertar", second.toc[0].label);
+    assert_eq!(1, first.toc.len());
+    assert_eq!(Path::new("OEBPS/Text/000.xhtml"), first.toc[0].content);
+    let chapter = first.get_resource_str_by_path("OEBPS/Text/000.xhtml");
+    assert!(chapter.is_ok());
+}
+
+#[test]
+fn doc_from_bytes() {
+    let content = std::fs::read("test.epub").unwrap();
+    let mut doc = EpubDoc::from_bytes(content).unwrap();
+    assert_eq!(17, doc.spine.len());
+    assert!(doc.get_current_str().is_ok());
+
+    assert!(EpubDoc::from_bytes(b"not an epub".to_vec()).is_err());
+}
+
+#[test]
+fn doc_resources_with_mime() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let css: Vec<(String, Vec<u8>)> = doc
+        .resources_with_mime("text/css")
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(1, css.len());
+    assert_eq!("stylesheet.css", css[0].0);
+    assert_eq!(doc.get_resource("stylesheet.css").unwrap(), css[0].1);
+
+    assert_eq!(19, doc.resources_with_mime("application/xhtml+xml").count());
+    assert_eq!(0, doc.resources_with_mime("video/").count());
+}
+
+#[test]
+fn doc_open_options() {
+    let mut options = OpenOptions::new();
+    options.max_metadata_items(1).max_toc_depth(0);
+    let doc = EpubDoc::new_with_options("test.epub", &options).unwrap();
+    assert_eq!(1, doc.metadata.values().map(|v| v.len()).sum::<usize>());
+    assert!(doc.toc.is_empty());
+    assert_eq!(17, doc.spine.len());
+
+    let mut options = OpenOptions::new();
+    options.max_resource_size(100_000);
+    let mut doc = EpubDoc::new_with_options("test.epub", &options).unwrap();
+    assert!(doc
+        .get_resource_by_path("OEBPS/Styles/stylesheet.css")
+        .is_ok());
+    let err = doc.get_cover().err().unwrap();
+    assert!(err.to_string().contains("portada.png"));
+
+    let mut doc = EpubDoc::new_with_options("test.epub", &OpenOptions::untrusted()).unwrap();
+    assert!(doc.get_cover().is_ok());
+    assert_eq!(EpubDoc::new("test.epub").unwrap().toc.len(), doc.toc.len());
+}
+
+/// Builds an epub with the package document `opf` and the `files`
+fn epub_with(mimetype: &str, opf: &str, files: &[(&str, &str)]) -> Vec<u8> {
+    let container = r#"<container version="1.0"><rootfiles>
+        <rootfile full-path="content.opf" media-type="application/oebps-package+xml"/>
+        </rootfiles></container>"#;
+    let mut all = vec![("content.opf", opf)];
+    all.extend_from_slice(files);
+    epub_with_container(mimetype, container, &all)
+}
+
+/// An epub with this container.xml, the package documents are in `files`
+fn epub_with_container(mimetype: &str, container: &str, files: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    let options = zip::write::FileOptions::default();
+    zip.start_file("mimetype", options).unwrap();
+    zip.write_all(mimetype.as_bytes()).unwrap();
+    zip.start_file("META-INF/container.xml", options).unwrap();
+    zip.write_all(container.as_bytes()).unwrap();
+    for (name, content) in files {
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(content.as_bytes()).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+/// The idrefs of the spine items
+fn spine_ids<R: Read + std::io::Seek>(doc: &EpubDoc<R>) -> Vec<&str> {
+    doc.spine.iter().map(|item| item.idref.as_str()).collect()
+}
+
+#[test]
+fn doc_epub_uris() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let c1 = r#"<html xmlns="http://www.w3.org/1999/xhtml"><body>
+        <a href="my%20notes.xhtml#n1">notes</a><img src="../Images/a b.png"/>
+        </body></html>"#;
+    let files = [("Text/c1.xhtml", c1)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    let content = String::from_utf8(doc.get_current_with_epub_uris().unwrap()).unwrap();
+    // the hrefs are kept as they're written
+    assert!(content.contains("epub://Text/my%20notes.xhtml#n1"));
+    assert!(content.contains("epub://Images/a b.png"));
+}
+
+#[test]
+fn doc_parse_mode() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c1" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="missing"/></spine>
+        </package>"#;
+    let files = [("c1.xhtml", "<html/>"), ("c2.xhtml", "<html/>")];
+    let epub = epub_with("application/epub+zip", opf, &files);
+
+    let doc = EpubDoc::from_reader(Cursor::new(epub.clone())).unwrap();
+    assert_eq!(vec!["c1"], spine_ids(&doc));
+    assert!(doc.toc.is_empty());
+
+    let mut strict = OpenOptions::new();
+    strict.mode(ParseMode::Strict);
+    let err = EpubDoc::from_reader_with_options(Cursor::new(epub), &strict)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("duplicate manifest id c1"));
+
+    let epub = epub_with("text/plain", opf, &files);
+    let err = EpubDoc::from_reader_with_options(Cursor::new(epub), &strict)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("mimetype"));
+
+    let opf = opf.replace(r#"id="c1" href="c2"#, r#"id="c2" href="c2"#);
+    let epub = epub_with("application/epub+zip", &opf, &files);
+    let err = EpubDoc::from_reader_with_options(Cursor::new(epub), &strict)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("missing"));
+
+    let opf = opf.replace(r#"<itemref idref="missing"/>"#, "");
+    let epub = epub_with("application/epub+zip", &opf, &files);
+    let err = EpubDoc::from_reader_with_options(Cursor::new(epub), &strict)
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("OPF 2.0.1"));
+
+    assert!(EpubDoc::new_with_options("tests/docs/Metamorphosis-jackson.epub", &strict).is_ok());
+}
+
+#[test]
+fn doc_warnings() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <unknown/>
+        </manifest>
+        <spine toc="ncx"><itemref idref="c1"/></spine>
+        <extra/>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+        <navPoint id="n1" playOrder="1"><navLabel><text>One</text></navLabel><content src="c1.xhtml#top"/></navPoint>
+        <navPoint id="n2" playOrder="2"><navLabel><text>Two</text></navLabel><content src="c2.xhtml"/></navPoint>
+        </navMap></ncx>"#;
+    let files = [("c1.xhtml", "<html/>"), ("toc.ncx", ncx)];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+    assert_eq!(2, doc.toc.len());
+    assert_eq!(
+        vec![
+            "unknown package element extra",
+            "unknown manifest element unknown",
+            "the package doesn't have a unique-identifier (EPUB 3.3, the package element: \
+             unique-identifier must be a dc:identifier id)",
+            "no cover meta",
+            "the toc entry c2.xhtml isn't in the manifest",
+        ],
+        doc.warnings()
+    );
+
+    assert!(EpubDoc::new("tests/docs/Metamorphosis-jackson.epub")
+        .unwrap()
+        .warnings()
+        .is_empty());
+}
+
+#[test]
+fn doc_errors() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    match doc.get_resource("missing") {
+        Err(EpubError::ResourceNotFound(id)) => assert_eq!("missing", id),
+        _ => panic!("expected ResourceNotFound"),
+    }
+    assert!(matches!(
+        doc.set_current_page(100),
+        Err(EpubError::InvalidOperation(_))
+    ));
+
+    let epub = epub_with("application/epub+zip", "not xml", &[]);
+    match EpubDoc::from_reader(Cursor::new(epub)) {
+        Err(EpubError::InvalidOpf { path, .. }) => assert_eq!(Path::new("content.opf"), path),
+        _ => panic!("expected InvalidOpf"),
+    }
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(vec![]));
+    zip.start_file("mimetype", zip::write::FileOptions::default())
+        .unwrap();
+    zip.write_all(b"application/epub+zip").unwrap();
+    let epub = zip.finish().unwrap().into_inner();
+    assert!(matches!(
+        EpubDoc::from_reader(Cursor::new(epub)),
+        Err(EpubError::MissingContainer)
+    ));
+    assert!(matches!(
+        EpubDoc::from_bytes(b"not an epub".to_vec()),
+        Err(EpubError::ZipError(_))
+    ));
+
+    let mut strict = OpenOptions::new();
+    strict.mode(ParseMode::Strict);
+    assert!(matches!(
+        EpubDoc::new_with_options("test.epub", &strict),
+        Err(EpubError::SpecViolation { .. })
+    ));
+}
+
+#[test]
+fn doc_malformed_opf() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title><dc:identifier id="id">urn:x</dc:identifier>
+        </metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/><itemref idref="gone"/>"#;
+    let epub = epub_with("application/epub+zip", opf, &[("c1.xhtml", "<html/>")]);
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub.clone())).unwrap();
+    assert_eq!(vec!["c1"], spine_ids(&doc));
+    assert_eq!("T", doc.mdata("title").unwrap());
+    assert_eq!(Some("urn:x"), doc.unique_identifier.as_deref());
+    assert!(doc.get_current_str().is_ok());
+    let warnings = doc.warnings();
+    assert!(warnings[0].starts_with("the package document is malformed"));
+    assert!(warnings.iter().any(|w| w.contains("spine item gone")));
+
+    let mut strict = OpenOptions::new();
+    strict.mode(ParseMode::Strict);
+    assert!(matches!(
+        EpubDoc::from_reader_with_options(Cursor::new(epub), &strict),
+        Err(EpubError::InvalidOpf { .. })
+    ));
+
+    // no spine, and the unique-identifier isn't an identifier
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="other">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:identifier>urn:y</dc:identifier></metadata>
+        <manifest/>
+        </package>"#;
+    let epub = epub_with("application/epub+zip", opf, &[]);
+    let doc = EpubDoc::from_reader(Cursor::new(epub)).unwrap();
+    assert!(doc.spine.is_empty());
+    assert_eq!(Some("urn:y"), doc.unique_identifier.as_deref());
+    assert!(doc
+        .warnings()
+        .iter()
+        .any(|w| w.starts_with("the package document doesn't have a spine element")));
+    assert!(doc
+        .warnings()
+        .iter()
+        .any(|w| w.starts_with("the unique-identifier other isn't an identifier")));
+}
+
+#[test]
+fn doc_href_resolution() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title><dc:identifier id="id">urn:uuid:1</dc:identifier>
+        </metadata>
+        <manifest>
+          <item id="c1" href="Text/./ch%201.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="Text/../Text/ch2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="ncx" href="nav/toc.ncx" media-type="application/x-dtbncx+xml"/>
+        </manifest>
+        <spine toc="ncx"><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+        <navPoint id="n1" playOrder="1"><navLabel><text>One</text></navLabel>
+          <content src="../Text/ch%201.xhtml#s1"/></navPoint>
+        <navPoint id="n2" playOrder="2"><navLabel><text>Two</text></navLabel>
+          <content src="../Text/ch2.xhtml"/></navPoint>
+        </navMap></ncx>"#;
+    let files = [
+        ("Text/ch 1.xhtml", "<html>one</html>"),
+        ("Text/ch2.xhtml", "<html>two</html>"),
+        ("nav/toc.ncx", ncx),
+    ];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let mut doc = EpubDoc::from_bytes(epub).unwrap();
+
+    assert_eq!(Path::new("Text/ch 1.xhtml"), doc.resources["c1"].0);
+    assert_eq!(Path::new("Text/ch2.xhtml"), doc.resources["c2"].0);
+    assert_eq!("<html>one</html>", doc.get_current_str().unwrap());
+    assert_eq!(vec!["no cover meta"], doc.warnings());
+
+    assert_eq!(Path::new("Text/ch 1.xhtml#s1"), doc.toc[0].content);
+    assert_eq!(Some(0), doc.resource_uri_to_chapter(&doc.toc[0].content));
+    assert_eq!(Some(1), doc.resource_uri_to_chapter(&doc.toc[1].content));
+
+    for path in [
+        "Text//ch%201.xhtml",
+        "./Text/ch 1.xhtml",
+        "nav/../Text/ch 1.xhtml",
+    ]
+    .iter()
+    {
+        assert_eq!(
+            b"<html>one</html>".to_vec(),
+            doc.get_resource_by_path(path).unwrap()
+        );
+        assert_eq!(
+            "application/xhtml+xml",
+            doc.get_resource_mime_by_path(path).unwrap()
+        );
+    }
+    assert!(matches!(
+        doc.get_resource_by_path("Text/ch3.xhtml"),
+        Err(EpubError::ResourceNotFound(_))
+    ));
+}
+
+#[test]
+fn doc_fuzzy_lookup() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="text/ch1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="Text/ch2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let files = [
+        ("Text\\CH1.xhtml", "<html>one</html>"),
+        ("Text/ch2.xhtml", "<html>two</html>"),
+        ("text/CH2.xhtml", "<html>other</html>"),
+    ];
+    let epub = epub_with("application/epub+zip", opf, &files);
+
+    let mut doc = EpubDoc::from_bytes(epub.clone()).unwrap();
+    assert!(matches!(
+        doc.get_current_str(),
+        Err(EpubError::ResourceNotFound(_))
+    ));
+
+    let mut options = OpenOptions::new();
+    options.fuzzy_lookup(true);
+    let mut doc = EpubDoc::from_reader_with_options(Cursor::new(epub), &options).unwrap();
+    assert_eq!("<html>one</html>", doc.get_current_str().unwrap());
+    // the exact name is preferred
+    doc.go_next().unwrap();
+    assert_eq!("<html>two</html>", doc.get_current_str().unwrap());
+    assert!(doc.get_resource_by_path("text/ch3.xhtml").is_err());
+}
+
+#[test]
+fn doc_repair() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title><dc:identifier id="id">urn:uuid:1</dc:identifier>
+        </metadata>
+        <manifest>
+          <item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="gone"/></spine>
+        </package>"#;
+    let files = [
+        (
+            "Text/c1.xhtml",
+            "<html><body><h1 id=\"t\">Title</h1></body></html>",
+        ),
+        ("Images/my cover.png", "png"),
+        ("Styles/style.css", "p {}"),
+    ];
+    let epub = epub_with("application/zip", opf, &files);
+    let mut doc = EpubDoc::from_bytes(epub).unwrap();
+    assert!(!doc.warnings().is_empty());
+
+    let fixes = doc.repair().unwrap();
+    assert_eq!(
+        vec![
+            "wrote the mimetype file",
+            "removed the spine item gone",
+            "added Images/my cover.png to the manifest",
+            "added Styles/style.css to the manifest",
+            "created the toc.ncx",
+        ],
+        fixes
+    );
+    assert_eq!("image/png", doc.get_resource_mime("my_cover.png").unwrap());
+    assert_eq!(1, doc.toc.len());
+    assert_eq!("Title", doc.toc[0].label);
+    assert!(doc.repair().unwrap().is_empty());
+
+    let path = std::env::temp_dir().join("epub-rs-test-repair.epub");
+    doc.save_as(&path).unwrap();
+    let mut options = OpenOptions::new();
+    options.mode(ParseMode::Strict);
+    let doc = EpubDoc::new_with_options(&path, &options).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(vec!["c1"], spine_ids(&doc));
+    assert_eq!(4, doc.resources.len());
+    assert_eq!(vec!["no cover meta"], doc.warnings());
+}
+
+#[test]
+fn doc_metadata_refines() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier>
+          <dc:title id="t1" xml:lang="en">The Book</dc:title>
+          <meta refines="#t1" property="title-type">main</meta>
+          <meta refines="#t1" property="display-seq">1</meta>
+          <dc:creator id="c1">Jane Doe</dc:creator>
+          <dc:creator id="c2">John Roe</dc:creator>
+          <meta refines="#c2" property="role" scheme="marc:relators" id="r2">ill</meta>
+          <meta refines="#r2" property="alternate-script" xml:lang="ja">挿絵</meta>
+          <meta refines="#c1" property="role" scheme="marc:relators">aut</meta>
+          <meta refines="#c1" property="file-as">Doe, Jane</meta>
+          <meta refines="#nothing" property="file-as">Nobody</meta>
+          <meta property="dcterms:modified">2020-01-01T00:00:00Z</meta>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+        </manifest>
+        <spine><itemref idref="nav"/></spine>
+        </package>"##;
+    let files = [("nav.xhtml", "<html/>")];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let doc = EpubDoc::from_bytes(epub).unwrap();
+
+    let title = doc.mdata_item("title").unwrap();
+    assert_eq!("The Book", title.value);
+    assert_eq!(Some("en"), title.lang.as_deref());
+    assert_eq!(Some("main"), title.refined_value("title-type"));
+    assert_eq!(Some("1"), title.refined_value("display-seq"));
+
+    let creators: Vec<_> = doc
+        .metadata_items()
+        .iter()
+        .filter(|item| item.property == "creator")
+        .collect();
+    assert_eq!(2, creators.len());
+    assert_eq!(Some("aut"), creators[0].refined_value("role"));
+    assert_eq!(Some("Doe, Jane"), creators[0].refined_value("file-as"));
+    let role = creators[1].refinement("role").unwrap();
+    assert_eq!("ill", role.value);
+    assert_eq!(Some("marc:relators"), role.scheme.as_deref());
+    assert_eq!(Some("挿絵"), role.refined_value("alternate-script"));
+    assert_eq!(None, creators[1].refined_value("file-as"));
+
+    // refinements aren't top level items, the flat metadata is unchanged
+    assert_eq!(5, doc.metadata_items().len());
+    assert_eq!(vec!["ill", "aut"], doc.metadata["role"]);
+    assert!(doc.warnings().contains(&String::from(
+        "the meta refines the missing element #nothing"
+    )));
+}
+
+#[test]
+fn doc_contributor_roles() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier>
+          <dc:title>T</dc:title>
+          <dc:creator id="c1">Jane Doe</dc:creator>
+          <meta refines="#c1" property="role" scheme="marc:relators">aut</meta>
+          <meta refines="#c1" property="role" scheme="marc:relators">ill</meta>
+          <meta refines="#c1" property="role" scheme="other:scheme">writer</meta>
+          <dc:contributor opf:role="trl" opf:file-as="Roe, John">John Roe</dc:contributor>
+          <dc:contributor id="c3">Ann Smith</dc:contributor>
+          <meta refines="#c3" property="role">NRT</meta>
+          <dc:contributor>Nobody</dc:contributor>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+        </manifest>
+        <spine><itemref idref="nav"/></spine>
+        </package>"##;
+    let files = [("nav.xhtml", "<html/>")];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let doc = EpubDoc::from_bytes(epub).unwrap();
+
+    let creators = doc.creators();
+    assert_eq!(1, creators.len());
+    assert_eq!(vec![Role::Author, Role::Illustrator], creators[0].roles);
+
+    let contributors = doc.contributors();
+    assert_eq!(3, contributors.len());
+    assert_eq!("John Roe", contributors[0].name);
+    assert_eq!(Some("Roe, John"), contributors[0].file_as.as_deref());
+    assert!(contributors[0].has_role(&Role::Translator));
+    assert_eq!(vec![Role::Narrator], contributors[1].roles);
+    assert!(contributors[2].roles.is_empty());
+}
+
+#[test]
+fn doc_metadata_languages() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id" xml:lang="ja">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier>
+          <dc:title id="t1">吾輩は猫である</dc:title>
+          <meta refines="#t1" property="alternate-script" xml:lang="ja-Latn">Wagahai wa Neko de Aru</meta>
+          <dc:title xml:lang="en">I Am a Cat</dc:title>
+          <dc:creator id="c1">夏目漱石</dc:creator>
+          <meta refines="#c1" property="alternate-script" xml:lang="en">Natsume Soseki</meta>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+        </manifest>
+        <spine><itemref idref="nav"/></spine>
+        </package>"##;
+    let files = [("nav.xhtml", "<html/>")];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let doc = EpubDoc::from_bytes(epub).unwrap();
+
+    let titles: Vec<(String, Option<String>)> = doc
+        .mdata_variants("title")
+        .into_iter()
+        .map(|t| (t.value, t.lang))
+        .collect();
+    let expected = [
+        ("吾輩は猫である", "ja"),
+        ("Wagahai wa Neko de Aru", "ja-Latn"),
+        ("I Am a Cat", "en"),
+    ];
+    let expected: Vec<(String, Option<String>)> = expected
+        .iter()
+        .map(|(v, l)| (v.to_string(), Some(l.to_string())))
+        .collect();
+    assert_eq!(expected, titles);
+
+    let creator = &doc.creators()[0];
+    assert_eq!(Some("ja"), creator.lang.as_deref());
+    assert_eq!("Natsume Soseki", creator.alternate_scripts[0].value);
+    assert_eq!(Some("en"), creator.alternate_scripts[0].lang.as_deref());
+}
+
+#[test]
+fn doc_title_types() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier>
+          <dc:title id="t1">A Journey</dc:title>
+          <meta refines="#t1" property="title-type">subtitle</meta>
+          <meta refines="#t1" property="display-seq">2</meta>
+          <dc:title id="t2">The Great Book</dc:title>
+          <meta refines="#t2" property="title-type">main</meta>
+          <meta refines="#t2" property="display-seq">1</meta>
+          <meta refines="#t2" property="file-as">Great Book, The</meta>
+          <dc:title id="t3">Classics</dc:title>
+          <meta refines="#t3" property="title-type">collection</meta>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+        </manifest>
+        <spine><itemref idref="nav"/></spine>
+        </package>"##;
+    let files = [("nav.xhtml", "<html/>")];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let doc = EpubDoc::from_bytes(epub).unwrap();
+
+    assert_eq!("The Great Book", doc.main_title().unwrap());
+    assert_eq!("A Journey", doc.subtitle().unwrap());
+    assert_eq!("Great Book, The", doc.sort_title().unwrap());
+
+    let titles = doc.titles();
+    let types: Vec<_> = titles
+        .iter()
+        .map(|t| t.title_type.clone().unwrap())
+        .collect();
+    assert_eq!(
+        vec![TitleType::Main, TitleType::Subtitle, TitleType::Collection],
+        types
+    );
+    // the flat metadata keeps the document order
+    assert_eq!("A Journey", doc.mdata("title").unwrap());
+}
+
+#[test]
+fn doc_series() {
+    let opf = |metadata: &str| {
+        format!(
+            r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+              {}
+            </metadata>
+            <manifest>
+              <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+            </manifest>
+            <spine><itemref idref="nav"/></spine>
+            </package>"#,
+            metadata
+        )
+    };
+    let files = [("nav.xhtml", "<html/>")];
+
+    let epub3 = opf(
+        r##"<meta property="belongs-to-collection" id="c1">The Saga</meta>
+        <meta refines="#c1" property="collection-type">series</meta>
+        <meta refines="#c1" property="group-position">2</meta>
+        <meta name="calibre:series" content="The Saga"/>
+        <meta name="calibre:series_index" content="2.0"/>"##,
+    );
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", &epub3, &files)).unwrap();
+    let series = doc.series();
+    assert_eq!(1, series.len());
+    assert_eq!("The Saga", series[0].name);
+    assert_eq!(Some(2.0), series[0].position);
+
+    let calibre = opf(r#"<meta name="calibre:series_index" content="1.5"/>
+        <meta name="calibre:series" content="Tales"/>"#);
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", &calibre, &files)).unwrap();
+    let series = doc.series();
+    assert_eq!(1, series.len());
+    assert_eq!("Tales", series[0].name);
+    assert_eq!(Some(1.5), series[0].position);
+
+    let doc = EpubDoc::new("test.epub").unwrap();
+    assert!(doc.series().is_empty());
+}
+
+#[test]
+fn doc_dates() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+          <dc:date opf:event="publication">1915</dc:date>
+          <dc:date opf:event="creation">1912-11</dc:date>
+          <dc:date opf:event="modification">2020-05-17T10:30:00+02:00</dc:date>
+          <dc:date opf:event="conversion">2021-01-01</dc:date>
+          <dc:date>someday</dc:date>
+          <meta property="dcterms:modified">2021-02-03T04:05:06Z</meta>
+        </metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let files = [("c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let dates = doc.dates();
+    let events: Vec<_> = dates.iter().map(|d| d.event.clone()).collect();
+    assert_eq!(
+        vec![
+            DateEvent::Publication,
+            DateEvent::Creation,
+            DateEvent::Modification,
+            DateEvent::Other(String::from("conversion")),
+            DateEvent::Publication,
+            DateEvent::Modification,
+        ],
+        events
+    );
+
+    let published = dates[0].date.unwrap();
+    assert_eq!(
+        (1915, None, None),
+        (published.year, published.month, published.day)
+    );
+    let created = dates[1].date.unwrap();
+    assert_eq!(
+        (1912, Some(11), None),
+        (created.year, created.month, created.day)
+    );
+    let modified = dates[2].date.unwrap();
+    assert_eq!(Some((10, 30, 0)), modified.time);
+    assert_eq!(Some(120), modified.utc_offset);
+    assert_eq!(None, dates[4].date);
+    assert_eq!("someday", dates[4].value);
+
+    let secs = |d: Date| {
+        d.to_system_time()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    };
+    assert_eq!(1589704200, secs(modified));
+    assert_eq!(1612325106, secs(dates[5].date.unwrap()));
+}
+
+#[test]
+fn doc_identifiers() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:09132750-3601-4d19-b3a4-55fdf8639849</dc:identifier>
+          <dc:identifier>urn:isbn:0-306-40615-2</dc:identifier>
+          <dc:identifier id="isbn">978 0 306 40615 7</dc:identifier>
+          <meta refines="#isbn" property="identifier-type" scheme="onix:codelist5">15</meta>
+          <dc:identifier>doi:10.1000/182</dc:identifier>
+          <dc:identifier>urn:calibre:42</dc:identifier>
+          <dc:identifier id="asin">B00ABC</dc:identifier>
+          <meta refines="#asin" property="identifier-type">AMAZON</meta>
+          <dc:identifier>0-306-40615-3</dc:identifier>
+          <dc:title>T</dc:title>
+        </metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"##;
+    let files = [("c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let ids: Vec<_> = doc
+        .identifiers()
+        .into_iter()
+        .map(|id| (id.scheme, id.value))
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                IdentifierScheme::Uuid,
+                String::from("09132750-3601-4d19-b3a4-55fdf8639849")
+            ),
+            (IdentifierScheme::Isbn, String::from("0306406152")),
+            (IdentifierScheme::Isbn, String::from("9780306406157")),
+            (IdentifierScheme::Doi, String::from("10.1000/182")),
+            (IdentifierScheme::Urn, String::from("urn:calibre:42")),
+            (
+                IdentifierScheme::Other(String::from("AMAZON")),
+                String::from("B00ABC")
+            ),
+            (IdentifierScheme::Unknown, String::from("0-306-40615-3")),
+        ],
+        ids
+    );
+
+    // the isbn-10 and the isbn-13 are the same book
+    let isbns: Vec<_> = doc
+        .identifiers()
+        .iter()
+        .filter_map(|id| id.isbn13())
+        .collect();
+    assert_eq!(vec!["9780306406157", "9780306406157"], isbns);
+    assert!(epub::metadata::is_valid_isbn("080442957X"));
+    assert_eq!(
+        Some(String::from("9780804429573")),
+        epub::metadata::isbn13("0-8044-2957-X")
+    );
+}
+
+#[test]
+fn doc_accessibility() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+          <meta property="schema:accessMode">textual</meta>
+          <meta property="schema:accessMode">visual</meta>
+          <meta property="schema:accessModeSufficient">textual</meta>
+          <meta property="schema:accessModeSufficient">textual, visual</meta>
+          <meta property="schema:accessibilityFeature">alternativeText</meta>
+          <meta property="schema:accessibilityFeature">tableOfContents</meta>
+          <meta property="schema:accessibilityHazard">none</meta>
+          <meta property="schema:accessibilitySummary">Fully accessible.</meta>
+          <link rel="dcterms:conformsTo" id="conf"
+                href="http://www.idpf.org/epub/a11y/accessibility-20170105.html#wcag-aa"/>
+          <meta property="a11y:certifiedBy" refines="#conf" id="cert">Accessible Books Inc.</meta>
+          <meta property="a11y:certifierCredential" refines="#cert">Certified Publisher</meta>
+        </metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"##;
+    let files = [("c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let a11y = doc.accessibility();
+    assert!(!a11y.is_empty());
+    assert_eq!(vec!["textual", "visual"], a11y.access_modes);
+    assert_eq!(
+        vec![vec!["textual"], vec!["textual", "visual"]],
+        a11y.access_modes_sufficient
+    );
+    assert!(a11y.has_feature("alternativeText"));
+    assert!(!a11y.has_feature("MathML"));
+    assert!(a11y.hazard_free());
+    assert_eq!(Some("Fully accessible."), a11y.summary.as_deref());
+    assert_eq!(Some("AA"), a11y.wcag_level());
+    assert_eq!(Some("Accessible Books Inc."), a11y.certified_by.as_deref());
+    assert_eq!(
+        Some("Certified Publisher"),
+        a11y.certifier_credential.as_deref()
+    );
+
+    // EPUB2 metas and the conformance statement of EPUB Accessibility 1.1
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+          <meta name="schema:accessibilityHazard" content="flashing"/>
+          <meta name="dcterms:conformsTo" content="EPUB Accessibility 1.1 - WCAG 2.1 Level A"/>
+        </metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    let a11y = doc.accessibility();
+    assert_eq!(vec!["flashing"], a11y.hazards);
+    assert!(!a11y.hazard_free());
+    assert_eq!(Some("A"), a11y.wcag_level());
+}
+
+#[test]
+fn doc_metadata_links() {
+    let opf = r##"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+          <link rel="record" href="meta/onix.xml" media-type="application/xml" properties="onix"/>
+          <link rel="voicing" href="https://example.com/preview.mp3" hreflang="en"
+                refines="#id" media-type="audio/mpeg"/>
+          <link rel="acquire" href="../shop.html"/>
+        </metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="onix" href="meta/onix.xml" media-type="application/xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"##;
+    let files = [("c1.xhtml", "<html/>"), ("meta/onix.xml", "<ONIXMessage/>")];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let links = doc.metadata_links().to_vec();
+    assert_eq!(3, links.len());
+    assert!(links[0].has_rel("record"));
+    assert_eq!(vec!["onix"], links[0].properties);
+    assert_eq!(Some("application/xml"), links[0].media_type.as_deref());
+    assert_eq!(Some(Path::new("meta/onix.xml")), links[0].path.as_deref());
+    assert_eq!(Some("onix"), links[0].resource_id.as_deref());
+    assert_eq!(
+        b"<ONIXMessage/>".to_vec(),
+        doc.get_resource(links[0].resource_id.as_ref().unwrap())
+            .unwrap()
+    );
+
+    assert_eq!("https://example.com/preview.mp3", links[1].href);
+    assert_eq!(None, links[1].path);
+    assert_eq!(Some("id"), links[1].refines.as_deref());
+    assert_eq!(Some("en"), links[1].hreflang.as_deref());
+
+    // a file that isn't in the manifest
+    assert_eq!(Some(Path::new("shop.html")), links[2].path.as_deref());
+    assert_eq!(None, links[2].resource_id);
+}
+
+#[test]
+fn doc_metadata_prefixes() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id"
+        prefix="rend: http://www.idpf.org/vocab/rendition/# ibooks: http://vocabulary.itunes.apple.com/rdf/ibooks/vocabulary-extensions-1.0/ broken">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+          <meta property="rend:layout">pre-paginated</meta>
+          <meta property="ibooks:version">1.2</meta>
+          <meta property="unknown:thing">x</meta>
+        </metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let files = [("c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    assert!(doc
+        .warnings()
+        .contains(&String::from("malformed prefix declaration broken")));
+
+    // the reserved prefix finds the property declared with another prefix
+    let layout = doc.mdata_items_by_property("rendition:layout");
+    assert_eq!(1, layout.len());
+    assert_eq!("pre-paginated", layout[0].value);
+    assert_eq!(
+        layout,
+        doc.mdata_items_by_property("http://www.idpf.org/vocab/rendition/#layout")
+    );
+
+    let version = doc.mdata_items_by_property(
+        "http://vocabulary.itunes.apple.com/rdf/ibooks/vocabulary-extensions-1.0/version",
+    );
+    assert_eq!("1.2", version[0].value);
+
+    // undeclared prefixes are only found as written
+    assert_eq!(1, doc.mdata_items_by_property("unknown:thing").len());
+    assert_eq!(None, doc.prefixes().expand("unknown:thing"));
+
+    assert_eq!(
+        Some("http://purl.org/dc/elements/1.1/title"),
+        doc.prefixes().expand("title").as_deref()
+    );
+    assert_eq!(
+        Some("http://idpf.org/epub/vocab/package/meta/#role"),
+        doc.prefixes().expand("role").as_deref()
+    );
+    assert_eq!(
+        "T",
+        doc.mdata_items_by_property("http://purl.org/dc/elements/1.1/title")[0].value
+    );
+}
+
+#[test]
+fn doc_landmarks_page_list() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav/nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body>
+          <nav epub:type="toc"><ol><li><a href="../text/c1.xhtml">One</a></li></ol></nav>
+          <nav epub:type="landmarks" hidden="">
+            <ol>
+              <li><a epub:type="toc" href="nav.xhtml">Contents</a></li>
+              <li><a epub:type="bodymatter" href="../text/c1.xhtml#start">Start <em>here</em></a></li>
+            </ol>
+          </nav>
+          <nav epub:type="page-list" hidden="">
+            <ol>
+              <li><a href="../text/c1.xhtml#p1">1</a></li>
+              <li><a href="../text/c1.xhtml#p2">2</a></li>
+            </ol>
+          </nav>
+        </body></html>"#;
+    let files = [("nav/nav.xhtml", nav), ("text/c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let landmarks = doc.landmarks();
+    assert_eq!(2, landmarks.len());
+    assert_eq!("Contents", landmarks[0].label);
+    assert_eq!("toc", landmarks[0].epub_type);
+    assert_eq!(Path::new("nav/nav.xhtml"), landmarks[0].content);
+    assert_eq!("Start here", landmarks[1].label);
+    assert_eq!("bodymatter", landmarks[1].epub_type);
+    assert_eq!(Path::new("text/c1.xhtml#start"), landmarks[1].content);
+
+    let pages: Vec<_> = doc
+        .page_list()
+        .iter()
+        .map(|p| (p.label.as_str(), p.content.clone()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("1", Path::new("text/c1.xhtml#p1").to_path_buf()),
+            ("2", Path::new("text/c1.xhtml#p2").to_path_buf()),
+        ],
+        pages
+    );
+
+    // EPUB2 books don't have them
+    let doc = EpubDoc::new("test.epub").unwrap();
+    assert!(doc.landmarks().is_empty());
+    assert!(doc.page_list().is_empty());
+}
+
+#[test]
+fn doc_guide() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest><item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        <guide>
+          <reference type="title-page" title="Title" href="text/c1.xhtml"/>
+          <reference type="text" title="Start" href="text/c1.xhtml#ch1"/>
+          <reference title="No type" href="text/c1.xhtml"/>
+        </guide>
+        </package>"#;
+    let files = [("text/c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let guide = doc.guide();
+    assert_eq!(2, guide.len());
+    assert_eq!("text", guide[1].reference_type);
+    assert_eq!("Start", guide[1].title);
+    assert_eq!(Path::new("text/c1.xhtml#ch1"), guide[1].content);
+
+    // without landmarks, the guide is used
+    assert!(doc.landmarks().is_empty());
+    let types: Vec<_> = doc
+        .landmarks_or_guide()
+        .into_iter()
+        .map(|l| l.epub_type)
+        .collect();
+    assert_eq!(vec!["titlepage", "bodymatter"], types);
+    let start = doc.find_landmark("bodymatter").unwrap();
+    assert_eq!(Path::new("text/c1.xhtml#ch1"), start.content);
+    assert_eq!(None, doc.find_landmark("cover"));
+}
+
+#[test]
+fn doc_ncx_page_list() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="ncx" href="toc/toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx"><itemref idref="c1"/></spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+        <navMap>
+          <navPoint id="n1" playOrder="1">
+            <navLabel><text>One</text></navLabel><content src="../text/c1.xhtml"/>
+          </navPoint>
+        </navMap>
+        <pageList>
+          <navLabel><text>Pages</text></navLabel>
+          <pageTarget id="p2" type="normal" value="2" playOrder="3">
+            <navLabel><text>2</text></navLabel><content src="../text/c1.xhtml#page2"/>
+          </pageTarget>
+          <pageTarget id="p1" type="normal" value="1" playOrder="2">
+            <navLabel><text>1</text></navLabel><content src="../text/c1.xhtml#page1"/>
+          </pageTarget>
+          <pageTarget id="p3" type="normal" value="3" playOrder="4">
+            <content src="../text/c1.xhtml#page3"/>
+          </pageTarget>
+        </pageList>
+        </ncx>"#;
+    let files = [("toc/toc.ncx", ncx), ("text/c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let pages: Vec<_> = doc
+        .page_list()
+        .iter()
+        .map(|p| (p.label.as_str(), p.content.to_str().unwrap()))
+        .collect();
+    assert_eq!(
+        vec![
+            ("1", "text/c1.xhtml#page1"),
+            ("2", "text/c1.xhtml#page2"),
+            ("3", "text/c1.xhtml#page3"),
+        ],
+        pages
+    );
+}
+
+#[test]
+fn doc_toc_helpers() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx"><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+          <navPoint playOrder="1"><navLabel><text>Part</text></navLabel><content src="c1.xhtml"/>
+            <navPoint playOrder="2"><navLabel><text>One</text></navLabel><content src="c1.xhtml#one"/>
+              <navPoint playOrder="3"><navLabel><text>One.A</text></navLabel><content src="c1.xhtml#a"/></navPoint>
+            </navPoint>
+            <navPoint playOrder="4"><navLabel><text>Two</text></navLabel><content src="c2.xhtml"/></navPoint>
+          </navPoint>
+        </navMap></ncx>"#;
+    let files = [
+        ("toc.ncx", ncx),
+        ("c1.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+    ];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let flat: Vec<_> = doc
+        .toc_flat()
+        .into_iter()
+        .map(|(level, nav)| (level, nav.label.as_str()))
+        .collect();
+    assert_eq!(
+        vec![(0, "Part"), (1, "One"), (2, "One.A"), (1, "Two")],
+        flat
+    );
+    assert_eq!(3, doc.toc_depth());
+
+    let label = |href: &str| doc.find_toc_entry(href).map(|nav| nav.label.clone());
+    assert_eq!(Some(String::from("One.A")), label("c1.xhtml#a"));
+    assert_eq!(Some(String::from("Part")), label("c1.xhtml"));
+    assert_eq!(Some(String::from("Part")), label("./c1.xhtml#missing"));
+    assert_eq!(Some(String::from("Two")), label("c2.xhtml#x"));
+    assert_eq!(None, label("c3.xhtml"));
+}
+
+#[test]
+fn doc_chapter_for_spine_index() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c1b" href="c1b.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx">
+          <itemref idref="cover"/><itemref idref="c1"/><itemref idref="c1b"/><itemref idref="c2"/>
+        </spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+          <navPoint playOrder="1"><navLabel><text>Part</text></navLabel><content src="c1.xhtml"/>
+            <navPoint playOrder="2"><navLabel><text>One</text></navLabel><content src="c1.xhtml#one"/></navPoint>
+            <navPoint playOrder="3"><navLabel><text>Storm</text></navLabel><content src="c1.xhtml#storm"/></navPoint>
+          </navPoint>
+          <navPoint playOrder="4"><navLabel><text>Two</text></navLabel><content src="c2.xhtml"/></navPoint>
+        </navMap></ncx>"#;
+    let files = [
+        ("toc.ncx", ncx),
+        ("cover.xhtml", "<html/>"),
+        ("c1.xhtml", "<html/>"),
+        ("c1b.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let label = |doc: &EpubDoc<_>, i| doc.chapter_for_spine_index(i).map(|nav| nav.label.clone());
+    // the cover is before the first entry
+    assert_eq!(None, label(&doc, 0));
+    assert_eq!(Some(String::from("Part")), label(&doc, 1));
+    // the continuation of the chapter, after the last section
+    assert_eq!(Some(String::from("Storm")), label(&doc, 2));
+    assert_eq!(Some(String::from("Two")), label(&doc, 3));
+    assert_eq!(Some(String::from("Two")), label(&doc, 10));
+
+    assert!(doc.current_chapter().is_none());
+    doc.set_current_page(2).unwrap();
+    assert_eq!("Storm", doc.current_chapter().unwrap().label);
+}
+
+#[test]
+fn doc_find_anchor() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest><item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let chapter = "<html><body><p>Ñandú</p><section id='s-2'><p>Two</p></section>\
+                   <a name=\"old\"/><p data-id=\"x\" id=\"x\">X</p></body></html>";
+    let files = [("text/c1.xhtml", chapter)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let anchor = doc.find_anchor("text/c1.xhtml#s-2").unwrap().unwrap();
+    assert_eq!(Path::new("text/c1.xhtml"), anchor.path);
+    assert_eq!("s-2", anchor.id);
+    assert_eq!(chapter.find("<section").unwrap(), anchor.byte_offset);
+    // the Ñ and the ú are two bytes each
+    assert_eq!(anchor.byte_offset - 2, anchor.char_offset);
+
+    let old = doc.find_anchor("text/./c1.xhtml#old").unwrap().unwrap();
+    assert_eq!(chapter.find("<a name").unwrap(), old.byte_offset);
+    let x = doc.find_anchor("text/c1.xhtml#x").unwrap().unwrap();
+    assert_eq!(chapter.find("<p data-id").unwrap(), x.byte_offset);
+
+    let top = doc.find_anchor("text/c1.xhtml").unwrap().unwrap();
+    assert_eq!(0, top.byte_offset);
+    assert!(doc.find_anchor("text/c1.xhtml#nope").unwrap().is_none());
+    assert!(doc.find_anchor("text/c2.xhtml#s-2").is_err());
+
+    let rest = doc.get_resource_from_anchor("text/c1.xhtml#s-2").unwrap();
+    assert!(rest.starts_with("<section id='s-2'><p>Two</p>"));
+    assert!(matches!(
+        doc.get_resource_from_anchor("text/c1.xhtml#nope"),
+        Err(EpubError::ResourceNotFound(_))
+    ));
+}
+
+#[test]
+fn doc_go_to_href() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav/nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="css" href="style.css" media-type="text/css"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body><nav epub:type="toc"><ol>
+          <li><a href="../text/c2.xhtml#storm">Storm</a></li>
+        </ol></nav></body></html>"#;
+    let c2 = r#"<html><body><p>calm</p><h2 id="storm">Storm</h2></body></html>"#;
+    let files = [
+        ("nav/nav.xhtml", nav),
+        ("text/c1.xhtml", "<html/>"),
+        ("text/c2.xhtml", c2),
+        ("style.css", "p {}"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    // relative to the nav document
+    doc.go_to_href("../text/c2.xhtml#storm").unwrap();
+    assert_eq!(1, doc.get_current_page());
+    assert_eq!(Some("storm"), doc.get_current_fragment());
+    let anchor = doc.get_current_anchor().unwrap().unwrap();
+    assert_eq!(c2.find("<h2").unwrap(), anchor.byte_offset);
+
+    // changing the page forgets the fragment
+    doc.go_prev().unwrap();
+    assert_eq!(None, doc.get_current_fragment());
+    assert_eq!(None, doc.get_current_anchor().unwrap());
+    doc.go_next().unwrap();
+    assert_eq!(None, doc.get_current_fragment());
+
+    doc.go_to_path("text/c1.xhtml").unwrap();
+    assert_eq!(0, doc.get_current_page());
+    assert_eq!(None, doc.get_current_fragment());
+
+    assert!(matches!(
+        doc.go_to_path("style.css"),
+        Err(EpubError::InvalidOperation(_))
+    ));
+    assert!(matches!(
+        doc.go_to_href("../text/c3.xhtml#x"),
+        Err(EpubError::ResourceNotFound(_))
+    ));
+    assert_eq!(0, doc.get_current_page());
+}
+
+#[test]
+fn doc_navs() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body>
+          <nav epub:type="toc"><h1>Contents</h1><ol>
+            <li><span>Part <em>I</em></span><ol>
+              <li><a href="text/c1.xhtml#one">One</a></li>
+              <li><a href="text/c1.xhtml#two">Two</a></li>
+            </ol></li>
+          </ol></nav>
+          <nav epub:type="lot"><ol>
+            <li><a href="text/c1.xhtml#table1">Table 1</a></li>
+          </ol></nav>
+          <nav epub:type="x-maps"><ol>
+            <li><a href="text/c1.xhtml#map">Map</a></li>
+          </ol></nav>
+          <nav><ol><li><a href="text/c1.xhtml">Untyped</a></li></ol></nav>
+        </body></html>"#;
+    let files = [("nav.xhtml", nav), ("text/c1.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let navs = doc.navs();
+    let mut types: Vec<_> = navs.keys().map(|t| t.as_str()).collect();
+    types.sort_unstable();
+    assert_eq!(vec!["lot", "toc", "x-maps"], types);
+
+    let toc = &navs["toc"];
+    assert_eq!(1, toc.len());
+    assert_eq!("Part I", toc[0].label);
+    // the heading points to the first link of the group
+    assert_eq!(Path::new("text/c1.xhtml#one"), toc[0].content);
+    let children: Vec<_> = toc[0]
+        .children
+        .iter()
+        .map(|nav| (nav.label.as_str(), nav.play_order))
+        .collect();
+    assert_eq!(vec![("One", 2), ("Two", 3)], children);
+
+    assert_eq!("Table 1", navs["lot"][0].label);
+    assert_eq!(Path::new("text/c1.xhtml#map"), navs["x-maps"][0].content);
+}
+
+#[test]
+fn doc_toc_json() {
+    let doc = EpubDoc::new("test.epub").unwrap();
+    let json = NavPoint::list_to_json(&doc.toc);
+    let toc = NavPoint::list_from_json(&json).unwrap();
+    assert_eq!(doc.toc.len(), toc.len());
+    for (a, b) in doc.toc.iter().zip(toc.iter()) {
+        assert_eq!(a.label, b.label);
+        assert_eq!(a.content, b.content);
+        assert_eq!(a.play_order, b.play_order);
+    }
+
+    // nested entries, escapes and optional fields
+    let json = r#"[ {"label": "Part \"I\"\n", "content": "a.xhtml", "play_order": 1,
+        "children": [{"label": "café 😀", "content": "a.xhtml#b"}]} ]"#;
+    let toc = NavPoint::list_from_json(json).unwrap();
+    assert_eq!("Part \"I\"\n", toc[0].label);
+    assert_eq!("café 😀", toc[0].children[0].label);
+    assert_eq!(Path::new("a.xhtml#b"), toc[0].children[0].content);
+    assert_eq!(0, toc[0].children[0].play_order);
+    assert_eq!(
+        r#"{"label":"Part \"I\"\n","content":"a.xhtml","play_order":1,"children":[{"label":"café 😀","content":"a.xhtml#b","play_order":0,"children":[]}]}"#,
+        toc[0].to_json()
+    );
+
+    for broken in &[
+        "",
+        "[",
+        r#"[{"label": "x"}]"#,
+        r#"[{"label": "x", "content": "y"} x]"#,
+        r#"{"label": "x", "content": "y"}"#,
+        r#"["unclosed]"#,
+    ] {
+        assert!(matches!(
+            NavPoint::list_from_json(broken),
+            Err(EpubError::Json(_))
+        ));
+    }
+    let deep = format!("{}{}", "[".repeat(1000), "]".repeat(1000));
+    assert!(NavPoint::list_from_json(&deep).is_err());
+
+    let landmark = epub::doc::Landmark {
+        label: String::from("Start"),
+        content: "c1.xhtml".into(),
+        epub_type: String::from("bodymatter"),
+    };
+    assert_eq!(
+        r#"{"label":"Start","content":"c1.xhtml","epub_type":"bodymatter"}"#,
+        landmark.to_json()
+    );
+}
+
+#[test]
+fn doc_toc_links() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="id">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:identifier id="id">urn:uuid:1</dc:identifier><dc:title>T</dc:title>
+        </metadata>
+        <manifest>
+          <item id="ncx" href="toc/toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx"><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+          <navPoint playOrder="1"><navLabel><text>One</text></navLabel><content src="../text/c1.xhtml"/>
+            <navPoint playOrder="3"><navLabel><text>One.A</text></navLabel><content src="../text/./c1.xhtml#a"/></navPoint>
+          </navPoint>
+          <navPoint playOrder="2"><navLabel><text>Two</text></navLabel><content src="../text/c2.xhtml"/></navPoint>
+          <navPoint playOrder="4"><navLabel><text>Notes</text></navLabel><content src="../text/notes.xhtml"/></navPoint>
+        </navMap></ncx>"#;
+    let files = [
+        ("toc/toc.ncx", ncx),
+        ("text/c1.xhtml", "<html/>"),
+        ("text/c2.xhtml", "<html/>"),
+        ("text/notes.xhtml", "<html/>"),
+    ];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let links: Vec<_> = doc
+        .toc_links()
+        .into_iter()
+        .map(|l| {
+            (
+                l.label,
+                l.play_order,
+                l.level,
+                l.path.display().to_string(),
+                l.fragment,
+                l.spine_index,
+            )
+        })
+        .collect();
+    let link = |label: &str, order, level, path: &str, fragment: Option<&str>, index| {
+        (
+            String::from(label),
+            order,
+            level,
+            String::from(path),
+            fragment.map(String::from),
+            index,
+        )
+    };
+    assert_eq!(
+        vec![
+            link("One", 1, 0, "text/c1.xhtml", None, Some(0)),
+            link("Two", 2, 0, "text/c2.xhtml", None, Some(1)),
+            link("One.A", 3, 1, "text/c1.xhtml", Some("a"), Some(0)),
+            // not in the spine
+            link("Notes", 4, 0, "text/notes.xhtml", None, None),
+        ],
+        links
+    );
+}
+
+#[test]
+fn doc_spine_items() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="notes.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine>
+          <itemref idref="c1" id="ref1" properties="page-spread-left"/>
+          <itemref idref="c2" properties="rendition:page-spread-right other"/>
+          <itemref idref="notes" linear="no"/>
+        </spine>
+        </package>"#;
+    let files = [
+        ("c1.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+        ("notes.xhtml", "<html/>"),
+    ];
+    let doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    assert_eq!(vec!["c1", "c2", "notes"], spine_ids(&doc));
+    assert_eq!(Some("ref1"), doc.spine[0].id.as_deref());
+    assert_eq!(None, doc.spine[1].id);
+    assert!(doc.spine[0].linear && doc.spine[1].linear);
+    assert!(!doc.spine[2].linear);
+    assert_eq!(Some("left"), doc.spine[0].page_spread());
+    assert_eq!(Some("right"), doc.spine[1].page_spread());
+    assert_eq!(None, doc.spine[2].page_spread());
+    assert!(doc.spine[1].has_property("other"));
+    assert!(doc.spine[2].properties.is_empty());
+}
+
+#[test]
+fn doc_skip_non_linear() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="note" href="note.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="end" href="end.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine>
+          <itemref idref="c1"/><itemref idref="note" linear="no"/>
+          <itemref idref="c2"/><itemref idref="end" linear="no"/>
+        </spine>
+        </package>"#;
+    let files = [
+        ("c1.xhtml", "<html/>"),
+        ("note.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+        ("end.xhtml", "<html/>"),
+    ];
+    let epub = epub_with("application/epub+zip", opf, &files);
+
+    let mut doc = EpubDoc::from_reader(Cursor::new(epub.clone())).unwrap();
+    let linear: Vec<&str> = doc.spine_linear().map(|item| item.idref.as_str()).collect();
+    assert_eq!(vec!["c1", "c2"], linear);
+    assert_eq!(4, doc.spine_all().count());
+    doc.go_next().unwrap();
+    assert_eq!("c2", doc.get_current_id().unwrap());
+    assert!(doc.go_next().is_err());
+    assert_eq!("c2", doc.get_current_id().unwrap());
+    doc.go_prev().unwrap();
+    assert_eq!("c1", doc.get_current_id().unwrap());
+
+    // the non-linear items can still be opened directly
+    doc.set_current_page(3).unwrap();
+    doc.go_prev().unwrap();
+    assert_eq!("c2", doc.get_current_id().unwrap());
+
+    let mut options = OpenOptions::new();
+    options.include_non_linear(true);
+    let mut doc = EpubDoc::from_reader_with_options(Cursor::new(epub), &options).unwrap();
+    doc.go_next().unwrap();
+    assert_eq!("note", doc.get_current_id().unwrap());
+}
+
+#[test]
+fn doc_resource_properties() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml" properties="scripted mathml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml" properties="svg  remote-resources scripted"/>
+          <item id="img" href="cover.png" media-type="image/png" properties="cover-image"/>
+          <item id="css" href="style.css" media-type="text/css"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let files = [
+        ("nav.xhtml", "<html/>"),
+        ("c1.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+        ("cover.png", ""),
+        ("style.css", ""),
+    ];
+    let doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    assert_eq!(&["scripted", "mathml"], doc.resource_properties("c1"));
+    assert_eq!(3, doc.resource_properties("c2").len());
+    assert!(doc.resource_properties("css").is_empty());
+    assert!(doc.resource_properties("missing").is_empty());
+    assert!(doc.resource_has_property("img", "cover-image"));
+    assert!(!doc.resource_has_property("img", "nav"));
+
+    assert_eq!(vec!["c1", "c2"], doc.scripted_resources());
+    assert_eq!(vec!["c1"], doc.mathml_resources());
+    assert_eq!(vec!["c2"], doc.svg_resources());
+    assert_eq!(vec!["c2"], doc.remote_resource_users());
+    assert_eq!(vec!["nav"], doc.resources_with_property("nav"));
+}
+
+#[test]
+fn doc_fallback_chain() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="svg" href="fig.svg" media-type="image/svg+xml" fallback="jxl"/>
+          <item id="jxl" href="fig.jxl" media-type="image/jxl" fallback="png"/>
+          <item id="png" href="fig.png" media-type="image/png"/>
+          <item id="a" href="a.foo" media-type="application/x-foo" fallback="b"/>
+          <item id="b" href="b.foo" media-type="application/x-bar" fallback="a"/>
+          <item id="broken" href="c.foo" media-type="application/x-foo" fallback="missing"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let files = [("c1.xhtml", "<html/>")];
+    let doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    assert_eq!(vec!["svg", "jxl", "png"], doc.fallback_chain("svg"));
+    assert_eq!(vec!["png"], doc.fallback_chain("png"));
+    assert!(doc.fallback_chain("missing").is_empty());
+    assert_eq!(vec!["broken"], doc.fallback_chain("broken"));
+
+    let png = |mime: &str| mime == "image/png";
+    assert_eq!(Some(String::from("png")), doc.resolve_fallback("svg", png));
+    let any_image = |mime: &str| mime.starts_with("image/");
+    assert_eq!(
+        Some(String::from("svg")),
+        doc.resolve_fallback("svg", any_image)
+    );
+
+    // circular chains end
+    assert_eq!(vec!["a", "b"], doc.fallback_chain("a"));
+    assert_eq!(None, doc.resolve_fallback("a", png));
+    assert!(doc
+        .warnings()
+        .iter()
+        .any(|w| w == "the fallback chain of a is circular"));
+    assert!(!doc.warnings().iter().any(|w| w.contains("of svg")));
+}
+
+#[test]
+fn doc_media_type_mismatches() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="gif" href="a.png" media-type="image/png"/>
+          <item id="svg" href="b.jpg" media-type="image/jpeg"/>
+          <item id="ogg" href="c.ogg" media-type="application/ogg"/>
+          <item id="font" href="d.woff" media-type="application/font-woff"/>
+          <item id="missing" href="e.png" media-type="image/png"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let files = [
+        ("c1.xhtml", "<?xml version=\"1.0\"?><html/>"),
+        ("a.png", "GIF89a...."),
+        (
+            "b.jpg",
+            "<?xml version=\"1.0\"?><!DOCTYPE svg><svg xmlns=\"http://www.w3.org/2000/svg\"/>",
+        ),
+        ("c.ogg", "OggS...."),
+        ("d.woff", "wOFF...."),
+    ];
+    let mut doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    assert_eq!(Some("image/gif"), doc.sniff_media_type("gif").unwrap());
+    assert_eq!(None, doc.sniff_media_type("c1").unwrap());
+    assert!(doc.sniff_media_type("missing").is_err());
+
+    let mismatches = doc.mismatches();
+    assert_eq!(2, mismatches.len());
+    assert_eq!("gif", mismatches[0].id);
+    assert_eq!(Path::new("a.png"), mismatches[0].path);
+    assert_eq!("image/png", mismatches[0].declared);
+    assert_eq!("image/gif", mismatches[0].sniffed);
+    assert_eq!("svg", mismatches[1].id);
+    assert_eq!("image/svg+xml", mismatches[1].sniffed);
+}
+
+#[test]
+fn doc_typed_resources() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/c%201.xhtml" media-type="application/xhtml+xml" properties="scripted"/>
+          <item id="img" href="fig.jxl" media-type="image/jxl" fallback="png"/>
+          <item id="png" href="fig.png" media-type="image/png"/>
+          <item id="missing" href="gone.css" media-type="text/css"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let files = [
+        ("Text/c 1.xhtml", "<html/>"),
+        ("fig.jxl", "jxl"),
+        ("fig.png", "png!"),
+    ];
+    let mut doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    let resources: Vec<Resource> = doc.resources().collect();
+    let ids: Vec<&str> = resources.iter().map(|r| r.id.as_str()).collect();
+    assert_eq!(vec!["c1", "img", "png", "missing"], ids);
+
+    assert_eq!("Text/c%201.xhtml", resources[0].href);
+    assert_eq!(Path::new("Text/c 1.xhtml"), resources[0].path);
+    assert_eq!("application/xhtml+xml", resources[0].media_type);
+    assert_eq!(vec!["scripted"], resources[0].properties);
+    assert_eq!(Some(7), resources[0].size);
+    assert_eq!(Some("png"), resources[1].fallback.as_deref());
+    assert_eq!(None, resources[2].fallback);
+    assert_eq!(Some(4), resources[2].size);
+    assert_eq!(None, resources[3].size);
+
+    doc.modify_entry("fig.png", "a bigger png").unwrap();
+    let png = doc.resources().find(|r| r.id == "png").unwrap();
+    assert_eq!(Some(12), png.size);
+}
+
+#[test]
+fn doc_resource_reader() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let expected = doc.get_resource("portada.png").unwrap();
+
+    let (mut reader, mime) = doc.get_resource_reader("portada.png").unwrap();
+    let mut content = vec![];
+    reader.read_to_end(&mut content).unwrap();
+    drop(reader);
+    assert_eq!("image/png", mime);
+    assert_eq!(expected, content);
+
+    // files outside of the manifest
+    let (_, mime) = doc
+        .get_resource_by_path_reader("META-INF/container.xml")
+        .unwrap();
+    assert_eq!("application/xml", mime);
+
+    // pending modifications are streamed too
+    doc.modify_entry("OEBPS/Text/000.xhtml", "<html/>").unwrap();
+    let (mut reader, mime) = doc.get_resource_reader("000.xhtml").unwrap();
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+    drop(reader);
+    assert_eq!("application/xhtml+xml", mime);
+    assert_eq!("<html/>", text);
+
+    assert!(doc.get_resource_reader("missing").is_err());
+    assert!(doc
+        .get_resource_by_path_reader("OEBPS/missing.png")
+        .is_err());
+}
+
+#[test]
+fn doc_reading_direction() {
+    let opf = |language: &str, direction: &str| {
+        format!(
+            r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <dc:title>T</dc:title><dc:language>{}</dc:language>
+            </metadata>
+            <manifest>
+              <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+              <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+              <item id="c3" href="c3.xhtml" media-type="application/xhtml+xml"/>
+              <item id="c4" href="c4.xhtml" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine {}>
+              <itemref idref="c1"/>
+              <itemref idref="c2" properties="page-spread-center"/>
+              <itemref idref="c3"/>
+              <itemref idref="c4" properties="page-spread-left"/>
+            </spine>
+            </package>"#,
+            language, direction
+        )
+    };
+    let files = [
+        ("c1.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+        ("c3.xhtml", "<html/>"),
+        ("c4.xhtml", "<html/>"),
+    ];
+    let open = |language: &str, direction: &str| {
+        let epub = epub_with("application/epub+zip", &opf(language, direction), &files);
+        EpubDoc::from_reader(Cursor::new(epub)).unwrap()
+    };
+
+    let doc = open("ja", r#"page-progression-direction="rtl""#);
+    assert_eq!(
+        Some(ReadingDirection::Rtl),
+        doc.page_progression_direction()
+    );
+    assert_eq!(ReadingDirection::Rtl, doc.reading_direction());
+    let spreads: Vec<&str> = (0..4).filter_map(|i| doc.page_spread(i)).collect();
+    assert_eq!(vec!["left", "center", "left", "left"], spreads);
+
+    let doc = open("he", "");
+    assert_eq!(None, doc.page_progression_direction());
+    assert_eq!(ReadingDirection::Rtl, doc.reading_direction());
+    let doc = open("az-Arab", r#"page-progression-direction="default""#);
+    assert_eq!(ReadingDirection::Rtl, doc.reading_direction());
+    let doc = open("ar", r#"page-progression-direction="ltr""#);
+    assert_eq!(ReadingDirection::Ltr, doc.reading_direction());
+
+    let doc = open("en", "");
+    assert_eq!(ReadingDirection::Ltr, doc.reading_direction());
+    let spreads: Vec<&str> = (0..4).filter_map(|i| doc.page_spread(i)).collect();
+    assert_eq!(vec!["right", "center", "right", "left"], spreads);
+}
+
+#[test]
+fn doc_spine_cfi() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c3" href="c3.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine>
+          <itemref idref="c1" id="ref1"/><itemref idref="c2"/><itemref idref="c3" id="a[b]"/>
+        </spine>
+        </package>"#;
+    let files = [
+        ("c1.xhtml", "<html/>"),
+        ("c2.xhtml", "<html/>"),
+        ("c3.xhtml", "<html/>"),
+    ];
+    let mut doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    assert_eq!("epubcfi(/6/2[ref1]!)", doc.spine_cfi(0).unwrap());
+    assert_eq!("epubcfi(/6/4!)", doc.spine_cfi(1).unwrap());
+    assert_eq!("epubcfi(/6/6[a^[b^]]!)", doc.spine_cfi(2).unwrap());
+    for i in 0..3 {
+        let cfi = doc.spine_cfi(i).unwrap();
+        assert_eq!(i, doc.cfi_spine_index(&cfi).unwrap());
+    }
+
+    // the id assertion wins over a stale position
+    assert_eq!(
+        0,
+        doc.cfi_spine_index("epubcfi(/6/4[ref1]!/4/2:3)").unwrap()
+    );
+    assert_eq!(1, doc.cfi_spine_index("/6/4[unknown;s=a]!/4").unwrap());
+    assert_eq!(
+        2,
+        doc.cfi_spine_index("epubcfi(/6/6!/4,/2:1,/3:4)").unwrap()
+    );
+
+    assert!(doc.cfi_spine_index("epubcfi(/6/8!)").is_err());
+    assert!(doc.cfi_spine_index("epubcfi(/6/3!)").is_err());
+    assert!(doc.cfi_spine_index("epubcfi(/4/2!)").is_err());
+    assert!(doc.cfi_spine_index("epubcfi(/6/2").is_err());
+    assert!(doc.cfi_spine_index("chapter 2").is_err());
+
+    doc.go_to_cfi("epubcfi(/6/4!/4/10/1:0)").unwrap();
+    assert_eq!("c2", doc.get_current_id().unwrap());
+    assert!(doc.go_to_cfi("epubcfi(/6/20!)").is_err());
+    assert_eq!("c2", doc.get_current_id().unwrap());
+}
+
+#[test]
+fn doc_resource_usage() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+          <item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="css" href="Styles/main.css" media-type="text/css"/>
+          <item id="font" href="Fonts/serif.woff" media-type="font/woff"/>
+          <item id="img" href="Images/fig.jxl" media-type="image/jxl" fallback="png"/>
+          <item id="png" href="Images/fig.png" media-type="image/png"/>
+          <item id="old" href="Images/old.png" media-type="image/png"/>
+          <item id="gone" href="Images/gone.png" media-type="image/png"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let chapter = r#"<html><head><link rel="stylesheet" href="../Styles/main.css"/></head>
+        <body><img src="../Images/fig.jxl"/><a href="http://example.com/x.png">x</a></body></html>"#;
+    let files = [
+        (
+            "nav.xhtml",
+            "<html><body><nav epub:type=\"toc\"><ol/></nav></body></html>",
+        ),
+        ("Text/c1.xhtml", chapter),
+        (
+            "Styles/main.css",
+            "@font-face { src: url(../Fonts/serif.woff) }",
+        ),
+        ("Fonts/serif.woff", "wOFF"),
+        ("Images/fig.jxl", ""),
+        ("Images/fig.png", ""),
+        ("Images/old.png", ""),
+        ("Images/stray.png", ""),
+    ];
+    let mut doc =
+        EpubDoc::from_reader(Cursor::new(epub_with("application/epub+zip", opf, &files))).unwrap();
+
+    let usage = doc.resource_usage().unwrap();
+    let paths = |paths: &[std::path::PathBuf]| -> Vec<String> {
+        paths.iter().map(|p| p.display().to_string()).collect()
+    };
+    assert_eq!(
+        vec!["Images/old.png", "Images/stray.png"],
+        paths(&usage.unreferenced)
+    );
+    assert_eq!(vec!["Images/stray.png"], paths(&usage.not_in_manifest));
+    assert_eq!(
+        vec![(
+            String::from("gone"),
+            std::path::PathBuf::from("Images/gone.png")
+        )],
+        usage.missing
+    );
+}
+
+/// An epub with a narrated chapter, ch1.xhtml with the overlay ch1.smil
+fn narrated_epub() -> Vec<u8> {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/ch1.xhtml" media-type="application/xhtml+xml" media-overlay="s1"/>
+          <item id="c2" href="Text/ch2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="s1" href="Smil/ch1.smil" media-type="application/smil+xml"/>
+          <item id="a1" href="Audio/ch1.mp3" media-type="audio/mpeg"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let smil = r#"<smil xmlns="http://www.w3.org/ns/SMIL" xmlns:epub="http://www.idpf.org/2007/ops" version="3.0">
+        <body epub:textref="../Text/ch1.xhtml">
+          <par id="p1"><text src="../Text/ch1.xhtml#title"/>
+            <audio src="../Audio/ch1.mp3" clipBegin="0:00:00.000" clipEnd="0:00:02.500"/></par>
+          <seq epub:type="table" epub:textref="../Text/ch1.xhtml#t1">
+            <par id="p2"><text src="../Text/ch1.xhtml#r1"/>
+              <audio src="../Audio/ch1.mp3" clipBegin="2.5s" clipEnd="4s"/></par>
+            <par id="p3" epub:type="note"><text src="../Text/ch1.xhtml#r2"/>
+              <audio src="../Audio/ch1.mp3" clipBegin="4000ms"/></par>
+          </seq>
+          <par id="silent"><text src="../Text/ch1.xhtml#end"/></par>
+        </body></smil>"#;
+    let chapter = r#"<html><body><h1 id="title">One</h1><table id="t1"><tr id="r1"/><tr id="r2"/></table><p id="end">.</p></body></html>"#;
+    let files = [
+        ("Text/ch1.xhtml", chapter),
+        ("Text/ch2.xhtml", "<html/>"),
+        ("Smil/ch1.smil", smil),
+        ("Audio/ch1.mp3", "ID3 0123456789"),
+    ];
+    epub_with("application/epub+zip", opf, &files)
+}
+
+#[test]
+fn doc_media_overlay() {
+    let mut doc = EpubDoc::from_reader(Cursor::new(narrated_epub())).unwrap();
+    assert_eq!(Some("s1"), doc.media_overlay_id("c1"));
+    assert_eq!(None, doc.media_overlay_id("c2"));
+    assert_eq!(None, doc.get_media_overlay("c2").unwrap());
+
+    let overlay = doc.get_media_overlay("c1").unwrap().unwrap();
+    assert_eq!("s1", overlay.id);
+    assert_eq!(Path::new("Smil/ch1.smil"), overlay.path);
+    assert_eq!(
+        Some(Path::new("Text/ch1.xhtml")),
+        overlay.body.text_ref.as_deref()
+    );
+    assert_eq!(3, overlay.body.children.len());
+    match &overlay.body.children[1] {
+        OverlayNode::Seq(seq) => {
+            assert_eq!(vec!["table"], seq.epub_type);
+            assert_eq!(
+                Some(Path::new("Text/ch1.xhtml#t1")),
+                seq.text_ref.as_deref()
+            );
+            assert_eq!(2, seq.children.len());
+        }
+        OverlayNode::Par(_) => panic!("expected a seq"),
+    }
+
+    let pars = overlay.pars();
+    let ids: Vec<&str> = pars.iter().filter_map(|p| p.id.as_deref()).collect();
+    assert_eq!(vec!["p1", "p2", "p3", "silent"], ids);
+    assert_eq!(Path::new("Text/ch1.xhtml#title"), pars[0].text);
+    let audio = pars[0].audio.as_ref().unwrap();
+    assert_eq!(Path::new("Audio/ch1.mp3"), audio.src);
+    assert_eq!(Some(Duration::from_secs(0)), audio.clip_begin);
+    assert_eq!(Some(Duration::from_millis(2500)), audio.clip_end);
+    let audio = pars[2].audio.as_ref().unwrap();
+    assert_eq!(vec!["note"], pars[2].epub_type);
+    assert_eq!(Some(Duration::from_secs(4)), audio.clip_begin);
+    assert_eq!(None, audio.clip_end);
+    assert_eq!(None, pars[3].audio);
+
+    assert_eq!(Some(Duration::from_secs(90)), parse_clock_value("1.5min"));
+    assert_eq!(
+        Some(Duration::from_secs(3723)),
+        parse_clock_value("1:02:03")
+    );
+    assert_eq!(None, parse_clock_value("0:61:00"));
+    assert_eq!(None, parse_clock_value("soon"));
+}
+
+#[test]
+fn doc_audio_clips() {
+    let mut doc = EpubDoc::from_reader(Cursor::new(narrated_epub())).unwrap();
+    let clip = doc.audio_clip_for("Text/ch1.xhtml#r1").unwrap().unwrap();
+    assert_eq!(Path::new("Audio/ch1.mp3"), clip.src);
+    assert_eq!(Some(Duration::from_millis(2500)), clip.clip_begin);
+    assert_eq!(Some(Duration::from_secs(4)), clip.clip_end);
+    // the textref of a sequence starts with its first clip
+    let clip = doc.audio_clip_for("Text/ch1.xhtml#t1").unwrap().unwrap();
+    assert_eq!(Some(Duration::from_millis(2500)), clip.clip_begin);
+    let clip = doc.audio_clip_for("Text/ch1.xhtml").unwrap().unwrap();
+    assert_eq!(Some(Duration::from_secs(0)), clip.clip_begin);
+    assert_eq!(None, doc.audio_clip_for("Text/ch1.xhtml#end").unwrap());
+    assert_eq!(None, doc.audio_clip_for("Text/ch2.xhtml").unwrap());
+    assert!(doc.audio_clip_for("Text/missing.xhtml").is_err());
+
+    let nav = NavPoint {
+        label: String::from("Row 2"),
+        content: PathBuf::from("Text/ch1.xhtml#r2"),
+        children: vec![],
+        play_order: 1,
+    };
+    let clip = doc.audio_clip_for_navpoint(&nav).unwrap().unwrap();
+    assert_eq!(Some(Duration::from_secs(4)), clip.clip_begin);
+    assert_eq!(None, clip.clip_end);
+
+    // mp3 clips can't be cut
+    let r1 = doc.audio_clip_for("Text/ch1.xhtml#r1").unwrap().unwrap();
+    assert!(doc.get_audio_clip(&r1).is_err());
+
+    // 8 bit mono wav at 1000 samples per second, 5 seconds long
+    let mut wav = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&[1, 0, 1, 0]);
+    wav.extend_from_slice(&1000u32.to_le_bytes());
+    wav.extend_from_slice(&1000u32.to_le_bytes());
+    wav.extend_from_slice(&[1, 0, 8, 0]);
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&5000u32.to_le_bytes());
+    wav.extend((0..5000).map(|i| (i / 1000) as u8));
+    doc.modify_entry("Audio/ch1.mp3", &wav).unwrap();
+
+    let audio = doc.get_audio_clip(&r1).unwrap();
+    assert_eq!(b"RIFF", &audio[..4]);
+    assert_eq!(44 + 1500, audio.len());
+    assert_eq!(&1500u32.to_le_bytes(), &audio[40..44]);
+    assert_eq!(2, audio[44]);
+    assert_eq!(3, audio[audio.len() - 1]);
+    let audio = doc.get_audio_clip(&clip).unwrap();
+    assert_eq!(44 + 1000, audio.len());
+    assert_eq!(4, audio[44]);
+}
+
+#[test]
+fn doc_rendition_settings() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title>
+          <meta property="rendition:layout">pre-paginated</meta>
+          <meta property="rendition:orientation">landscape</meta>
+          <meta property="rendition:spread">portrait</meta>
+          <meta property="rendition:flow">sideways</meta>
+        </metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine>
+          <itemref idref="c1" properties="page-spread-right"/>
+          <itemref idref="c2" properties="rendition:layout-reflowable rendition:flow-scrolled-doc rendition:spread-none"/>
+        </spine>
+        </package>"#;
+    let files = [("c1.xhtml", "<html/>"), ("c2.xhtml", "<html/>")];
+    let doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let rendition = doc.rendition();
+    assert!(rendition.is_fixed_layout());
+    assert_eq!(Orientation::Landscape, rendition.orientation);
+    // the deprecated portrait spread is both
+    assert_eq!(Spread::Both, rendition.spread);
+    // unknown values keep the default
+    assert_eq!(Flow::Auto, rendition.flow);
+
+    assert_eq!(Some(rendition), doc.spine_rendition(0));
+    let second = doc.spine_rendition(1).unwrap();
+    assert_eq!(Layout::Reflowable, second.layout);
+    assert_eq!(Flow::ScrolledDoc, second.flow);
+    assert_eq!(Spread::None, second.spread);
+    assert_eq!(Orientation::Landscape, second.orientation);
+    assert_eq!(None, doc.spine_rendition(2));
+}
+
+/// An epub with a reflowable rendition and a fixed layout one
+fn multi_rendition_epub() -> Vec<u8> {
+    let container = r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+        xmlns:rendition="http://www.idpf.org/2013/rendition"><rootfiles>
+        <rootfile full-path="reflow/content.opf" media-type="application/oebps-package+xml"
+          rendition:label="Text" rendition:layout="reflowable" rendition:language="en"/>
+        <rootfile full-path="fixed/content.opf" media-type="application/oebps-package+xml"
+          rendition:label="Pages" rendition:layout="pre-paginated" rendition:media="(orientation: landscape)"
+          rendition:accessMode="visual"/>
+        <rootfile full-path="book.pdf" media-type="application/pdf"/>
+        </rootfiles>
+        <links><link href="mapping.xhtml" rel="mapping" media-type="application/xhtml+xml"/></links>
+        </container>"#;
+    let mapping = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <head><meta name="epub.multiple.renditions.version" content="1.0"/></head>
+        <body><nav epub:type="resource-map">
+          <ul>
+            <li><a href="reflow/content.opf#epubcfi(/6/2!)">Text</a></li>
+            <li><a href="fixed/content.opf#epubcfi(/6/2[f1]!)">Pages</a></li>
+          </ul>
+          <ul>
+            <li><a href="reflow/content.opf#epubcfi(%2F6%2F4!)"/></li>
+            <li><a href="fixed/content.opf#epubcfi(/6/6!)"/></li>
+          </ul>
+        </nav></body></html>"#;
+    let reflow = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Text</dc:title></metadata>
+        <manifest>
+          <item id="r1" href="ch1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="r2" href="ch2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="r1"/><itemref idref="r2"/></spine>
+        </package>"#;
+    let fixed = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Pages</dc:title>
+          <meta property="rendition:layout">pre-paginated</meta></metadata>
+        <manifest>
+          <item id="f1" href="p1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="f2" href="p2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="f3" href="p3.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="f1"/><itemref idref="f2"/><itemref idref="f3"/></spine>
+        </package>"#;
+    let files = [
+        ("reflow/content.opf", reflow),
+        ("reflow/ch1.xhtml", "<html/>"),
+        ("reflow/ch2.xhtml", "<html/>"),
+        ("fixed/content.opf", fixed),
+        ("fixed/p1.xhtml", "<html/>"),
+        ("fixed/p2.xhtml", "<html/>"),
+        ("fixed/p3.xhtml", "<html/>"),
+        ("book.pdf", "%PDF"),
+        ("mapping.xhtml", mapping),
+    ];
+    epub_with_container("application/epub+zip", container, &files)
+}
+
+#[test]
+fn doc_renditions() {
+    let mut doc = EpubDoc::from_bytes(multi_rendition_epub()).unwrap();
+    let renditions = doc.renditions().to_vec();
+    assert_eq!(3, renditions.len());
+    assert_eq!(Path::new("reflow/content.opf"), renditions[0].path);
+    assert_eq!(Some("Text"), renditions[0].label.as_deref());
+    assert_eq!(Some(Layout::Reflowable), renditions[0].layout);
+    assert_eq!(Some("en"), renditions[0].language.as_deref());
+    assert_eq!(Some(Layout::PrePaginated), renditions[1].layout);
+    assert_eq!(
+        Some("(orientation: landscape)"),
+        renditions[1].media.as_deref()
+    );
+    assert_eq!(Some("visual"), renditions[1].access_mode.as_deref());
+    assert!(!renditions[2].is_package());
+
+    // the first rendition is open
+    assert_eq!(0, doc.current_rendition());
+    assert_eq!(vec!["r1", "r2"], spine_ids(&doc));
+    doc.go_next().unwrap();
+
+    doc.open_rendition(1).unwrap();
+    assert_eq!(1, doc.current_rendition());
+    assert_eq!(Path::new("fixed/content.opf"), doc.root_file);
+    assert_eq!(Path::new("fixed"), doc.root_base);
+    assert_eq!(vec!["f1", "f2", "f3"], spine_ids(&doc));
+    assert_eq!(0, doc.get_current_page());
+    assert_eq!(Some(String::from("Pages")), doc.mdata("title"));
+    assert!(doc.rendition().is_fixed_layout());
+
+    assert!(doc.open_rendition(2).is_err());
+    assert!(doc.open_rendition(3).is_err());
+    assert_eq!(1, doc.current_rendition());
+    assert_eq!(3, doc.spine.len());
+
+    doc.open_rendition(0).unwrap();
+    assert_eq!(Some(String::from("Text")), doc.mdata("title"));
+}
+
+#[test]
+fn doc_rendition_mapping() {
+    let mut doc = EpubDoc::from_bytes(multi_rendition_epub()).unwrap();
+    let mapping = doc.rendition_mapping().unwrap().unwrap();
+    assert_eq!(Path::new("mapping.xhtml"), mapping.path);
+    assert_eq!(2, mapping.units.len());
+    assert_eq!(Path::new("fixed/content.opf"), mapping.units[0][1].rootfile);
+    assert_eq!("epubcfi(/6/2[f1]!)", mapping.units[0][1].cfi);
+    // the fragments are percent-decoded
+    assert_eq!("epubcfi(/6/4!)", mapping.units[1][0].cfi);
+    assert_eq!(Some(2), mapping.units[1][1].spine_index());
+
+    // the second chapter is the third page
+    assert_eq!(Some(0), doc.map_current_page(1).unwrap());
+    doc.go_next().unwrap();
+    assert_eq!(Some(2), doc.map_current_page(1).unwrap());
+    assert!(doc.map_current_page(5).is_err());
+
+    // the second page is still in the first chapter
+    doc.open_rendition(1).unwrap();
+    doc.go_next().unwrap();
+    assert_eq!(Some(0), doc.map_current_page(0).unwrap());
+    doc.go_next().unwrap();
+    assert_eq!(Some(1), doc.map_current_page(0).unwrap());
+    // the pdf isn't in the mapping
+    assert_eq!(None, doc.map_current_page(2).unwrap());
+
+    let epub = EpubDoc::new("test.epub")
+        .unwrap()
+        .rendition_mapping()
+        .unwrap();
+    assert_eq!(None, epub);
+}
+
+#[test]
+fn doc_semantic_index() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="title" href="Text/title.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c1" href="Text/ch1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="Text/notes.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="title"/><itemref idref="c1"/><itemref idref="notes" linear="no"/></spine>
+        </package>"#;
+    let title = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body epub:type="frontmatter titlepage"><h1>T</h1><input type="text"/></body></html>"#;
+    let chapter = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body epub:type="bodymatter"><section id="s1" epub:type="chapter">
+        <p>One<a id="r1" href="notes.xhtml#n1" epub:type="noteref">1</a>
+        <a href="https://example.com" epub:type="z3998:link">site</a></p>
+        <ol type="a"><li>x</li></ol></section></body></html>"#;
+    let notes = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:e="http://www.idpf.org/2007/ops">
+        <body><aside id="n1" e:type="footnote"><p>A note</p></aside></body></html>"#;
+    let files = [
+        ("Text/title.xhtml", title),
+        ("Text/ch1.xhtml", chapter),
+        ("Text/notes.xhtml", notes),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let elements = doc.semantic_elements("c1").unwrap();
+    let types: Vec<&str> = elements.iter().map(|e| e.types[0].as_str()).collect();
+    // the type of the ol isn't an epub:type
+    assert_eq!(
+        vec!["bodymatter", "chapter", "noteref", "z3998:link"],
+        types
+    );
+    assert_eq!("c1", elements[1].document);
+    assert_eq!(Some("s1"), elements[1].id.as_deref());
+    assert_eq!(Path::new("Text/ch1.xhtml#s1"), elements[1].href_to());
+    assert_eq!(Some(PathBuf::from("Text/notes.xhtml#n1")), elements[2].href);
+    assert_eq!(Some(PathBuf::from("https://example.com")), elements[3].href);
+    assert!(doc.semantic_elements("missing").is_err());
+
+    let index = doc.semantic_index().unwrap();
+    assert_eq!(6, index.elements.len());
+    assert_eq!(vec!["frontmatter", "titlepage"], index.elements[0].types);
+    assert_eq!(vec!["title"], index.documents_with_type("frontmatter"));
+    // the notes aren't linear, but they are in the spine, with any prefix
+    let footnotes = index.of_type("footnote");
+    assert_eq!(1, footnotes.len());
+    assert_eq!("notes", footnotes[0].document);
+    assert_eq!("aside", footnotes[0].element);
+    assert_eq!(4, index.of_document("c1").len());
+
+    let start = index.first_of_type("bodymatter").unwrap();
+    doc.go_to_href(&start.href_to().display().to_string())
+        .unwrap();
+    assert_eq!(1, doc.get_current_page());
+}
+
+#[test]
+fn doc_footnotes() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/ch1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="Text/notes.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="notes" linear="no"/></spine>
+        </package>"#;
+    let chapter = r##"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body><p>One<a id="r1" href="#f1" epub:type="noteref"><sup>1</sup></a>
+        two<a href="notes.xhtml#e1" epub:type="noteref">[2]</a>
+        three<a href="notes.xhtml#gone" epub:type="noteref">3</a></p>
+        <aside id="f1" epub:type="footnote"><p>A <aside>nested</aside> &amp; &#169; note</p></aside>
+        </body></html>"##;
+    let notes = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body><ol><li id="e1" epub:type="endnote"><p>The end
+        note.</p></li></ol></body></html>"#;
+    let files = [("Text/ch1.xhtml", chapter), ("Text/notes.xhtml", notes)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    // the reference to a missing note is skipped
+    let footnotes = doc.footnotes().unwrap();
+    assert_eq!(2, footnotes.len());
+
+    let note = &footnotes[0];
+    assert_eq!("c1", note.document);
+    assert_eq!(Path::new("Text/ch1.xhtml#r1"), note.reference);
+    assert_eq!("1", note.label);
+    assert_eq!(Path::new("Text/ch1.xhtml#f1"), note.target);
+    assert_eq!(vec!["footnote"], note.note_types);
+    assert!(note.html.starts_with("<aside id=\"f1\""));
+    assert!(note.html.ends_with("note</p></aside>"));
+    assert_eq!("A nested & © note", note.text);
+
+    let note = &footnotes[1];
+    assert_eq!(Path::new("Text/ch1.xhtml"), note.reference);
+    assert_eq!("[2]", note.label);
+    assert_eq!(Path::new("Text/notes.xhtml#e1"), note.target);
+    // the prefix is declared in the html element
+    assert_eq!(vec!["endnote"], note.note_types);
+    assert_eq!("The end note.", note.text);
+}
+
+#[test]
+fn doc_dictionary() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>D</dc:title>
+          <dc:type>dictionary</dc:type></metadata>
+        <manifest>
+          <item id="dict" href="dict.xhtml" media-type="application/xhtml+xml"/>
+          <item id="skm" href="skm.xml" media-type="application/vnd.epub.search-key-map+xml"
+            properties="dictionary search-key-map"/>
+        </manifest>
+        <spine><itemref idref="dict"/></spine>
+        </package>"#;
+    let skm = r##"<search-key-map xmlns="http://www.idpf.org/2007/ops" xml:lang="en">
+        <search-key-group href="dict.xhtml#run">
+          <match value="run"><value value="ran"/><value value="running"/></match>
+        </search-key-group>
+        <search-key-group href="dict.xhtml#set">
+          <match value="set"/>
+          <match value="setting" href="dict.xhtml#setting"/>
+        </search-key-group>
+        <search-key-group href="dict.xhtml#gone"><match value="gone"/></search-key-group>
+        </search-key-map>"##;
+    let dict = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body>
+        <article id="run" epub:type="dictentry"><dfn>run</dfn> <p>To move fast.</p></article>
+        <article id="set" epub:type="dictentry"><dfn>set</dfn> <p>To put.</p></article>
+        <article id="setting" epub:type="dictentry"><dfn>setting</dfn> <p>A place.</p></article>
+        </body></html>"#;
+    let files = [("dict.xhtml", dict), ("skm.xml", skm)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    assert!(doc.is_dictionary());
+    assert!(doc.glossaries().is_empty());
+
+    let maps = doc.search_key_maps().unwrap();
+    assert_eq!(1, maps.len());
+    assert_eq!("skm", maps[0].id);
+    assert_eq!(Some("en"), maps[0].lang.as_deref());
+    assert_eq!(4, maps[0].keys.len());
+    assert_eq!(vec!["ran", "running"], maps[0].keys[0].inflections);
+    assert_eq!(Path::new("dict.xhtml#set"), maps[0].keys[1].href);
+    assert_eq!(Path::new("dict.xhtml#setting"), maps[0].keys[2].href);
+
+    let definitions = doc.lookup("Running").unwrap();
+    assert_eq!(1, definitions.len());
+    assert_eq!("run", definitions[0].headword);
+    assert_eq!(Path::new("dict.xhtml#run"), definitions[0].href);
+    assert!(definitions[0].html.starts_with("<article id=\"run\""));
+    assert_eq!("run To move fast.", definitions[0].text);
+    assert_eq!("set To put.", doc.lookup("set").unwrap()[0].text);
+    // the definition doesn't exist
+    assert!(doc.lookup("gone").unwrap().is_empty());
+    assert!(doc.lookup("walk").unwrap().is_empty());
+
+    let doc = EpubDoc::new("test.epub").unwrap();
+    assert!(!doc.is_dictionary());
+}
+
+#[test]
+fn doc_indexes() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/ch1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="ix" href="Text/index.xhtml" media-type="application/xhtml+xml" properties="index"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="ix"/></spine>
+        </package>"#;
+    let index = r##"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+        <body><section epub:type="index"><h1>Index</h1>
+        <section epub:type="index-group"><h2>S</h2>
+        <ul epub:type="index-entry-list">
+          <li epub:type="index-entry"><span epub:type="index-term">storms</span>,
+            <a epub:type="index-locator" href="ch1.xhtml#p3">3</a>,
+            <span epub:type="index-locator-range"><a href="ch1.xhtml#p7">7</a>-<a href="ch1.xhtml#p9">9</a></span>
+            <ul epub:type="index-entry-list">
+              <li epub:type="index-entry"><span epub:type="index-term">at sea</span>
+                <a epub:type="index-locator" href="ch1.xhtml#p8">8</a></li>
+            </ul>
+            see also <a epub:type="index-xref-related" href="#wind">wind</a></li>
+          <li epub:type="index-entry"><span epub:type="index-term">squalls</span>
+            see <a epub:type="index-xref-preferred" href="#storms">storms</a></li>
+        </ul></section></section></body></html>"##;
+    let files = [("Text/ch1.xhtml", "<html/>"), ("Text/index.xhtml", index)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let indexes = doc.indexes().unwrap();
+    assert_eq!(1, indexes.len());
+    let index = &indexes[0];
+    assert_eq!("ix", index.document);
+    assert_eq!(Path::new("Text/index.xhtml"), index.path);
+    assert_eq!(2, index.entries.len());
+
+    let storms = &index.entries[0];
+    assert_eq!("storms", storms.term);
+    assert_eq!(2, storms.locators.len());
+    assert_eq!("3", storms.locators[0].label);
+    assert_eq!(Path::new("Text/ch1.xhtml#p3"), storms.locators[0].href);
+    assert_eq!(None, storms.locators[0].end);
+    assert_eq!("7-9", storms.locators[1].label);
+    assert_eq!(
+        Some(PathBuf::from("Text/ch1.xhtml#p9")),
+        storms.locators[1].end
+    );
+    assert_eq!(vec!["wind"], storms.see_also);
+    assert_eq!("at sea", storms.children[0].term);
+    assert_eq!(vec!["storms"], index.entries[1].see);
+
+    let locations = index.locations("At Sea");
+    assert_eq!(1, locations.len());
+    assert_eq!(Path::new("Text/ch1.xhtml#p8"), locations[0].href);
+    assert!(index.locations("calm").is_empty());
+
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    assert!(doc.indexes().unwrap().is_empty());
+}
+
+#[test]
+fn doc_scripted_report() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml" properties="scripted"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c3" href="c3.xhtml" media-type="application/xhtml+xml" properties="scripted"/>
+          <item id="map" href="map.svg" media-type="image/svg+xml"/>
+          <item id="plain" href="plain.xhtml" media-type="application/xhtml+xml"/>
+          <item id="js" href="app.js" media-type="application/javascript"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/><itemref idref="c3"/><itemref idref="plain"/></spine>
+        </package>"#;
+    let c1 = r#"<html><head><script src="app.js"></script><SCRIPT>go()</SCRIPT></head>
+        <body><button onclick="a()" onmouseover="b()">x</button></body></html>"#;
+    let c2 = r#"<html><body><a href="javascript:void(0)">x</a></body></html>"#;
+    let map = r#"<svg xmlns="http://www.w3.org/2000/svg"><svg:script xlink:href="map.js"/></svg>"#;
+    let plain = r#"<html><body><p>No scripts, just a description of the onclick attribute.</p></body></html>"#;
+    let files = [
+        ("c1.xhtml", c1),
+        ("c2.xhtml", c2),
+        ("c3.xhtml", "<html/>"),
+        ("map.svg", map),
+        ("plain.xhtml", plain),
+        ("app.js", "go()"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let report = doc.scripted_report().unwrap();
+    let ids: Vec<&str> = report.iter().map(|d| d.id.as_str()).collect();
+    assert_eq!(vec!["c1", "c2", "c3", "map"], ids);
+
+    assert!(report[0].declared);
+    assert_eq!(2, report[0].scripts);
+    assert_eq!(vec!["app.js"], report[0].external_scripts);
+    assert_eq!(2, report[0].event_handlers);
+    assert!(!report[0].is_mismatch());
+
+    assert_eq!(1, report[1].javascript_urls);
+    assert!(report[1].is_mismatch());
+    // declared, but without scripts
+    assert!(!report[2].has_scripts());
+    assert!(report[2].is_mismatch());
+    assert_eq!(vec!["map.js"], report[3].external_scripts);
+}
+
+#[test]
+fn doc_encrypted_resources() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="font" href="Fonts/serif.otf" media-type="font/otf"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let encryption = r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+        xmlns:enc="http://www.w3.org/2001/04/xmlenc#" xmlns:ds="http://www.w3.org/2000/09/xmldsig#">
+        <enc:EncryptedData>
+          <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+          <enc:CipherData><enc:CipherReference URI="Fonts/serif.otf"/></enc:CipherData>
+        </enc:EncryptedData>
+        <enc:EncryptedData>
+          <enc:EncryptionMethod Algorithm="http://www.w3.org/2001/04/xmlenc#aes256-cbc"/>
+          <ds:KeyInfo>
+            <ds:RetrievalMethod URI="license.lcpl#/encryption/content_key"/>
+            <ds:KeyName> content key </ds:KeyName>
+          </ds:KeyInfo>
+          <enc:CipherData><enc:CipherReference URI="Text/c1.xhtml"/></enc:CipherData>
+          <enc:EncryptionProperties><enc:EncryptionProperty>
+            <Compression xmlns="http://www.idpf.org/2016/encryption#compression" Method="8" OriginalLength="42"/>
+          </enc:EncryptionProperty></enc:EncryptionProperties>
+        </enc:EncryptedData>
+        </encryption>"#;
+    let files = [
+        ("META-INF/encryption.xml", encryption),
+        ("Text/c1.xhtml", "encrypted"),
+        ("Fonts/serif.otf", "obfuscated"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let encrypted = doc.encrypted_resources().to_vec();
+    assert_eq!(2, encrypted.len());
+    assert_eq!(Path::new("Fonts/serif.otf"), encrypted[0].path);
+    assert!(encrypted[0].is_obfuscation());
+    assert_eq!(
+        "http://www.w3.org/2001/04/xmlenc#aes256-cbc",
+        encrypted[1].algorithm
+    );
+    assert_eq!(
+        Some("license.lcpl#/encryption/content_key"),
+        encrypted[1].retrieval_method.as_deref()
+    );
+    assert_eq!(Some("content key"), encrypted[1].key_name.as_deref());
+    assert_eq!(Some(8), encrypted[1].compression_method);
+    assert_eq!(Some(42), encrypted[1].original_length);
+    assert_eq!(Some(&encrypted[1]), doc.encryption_of("Text/./c1.xhtml"));
+    assert_eq!(None, doc.encryption_of("Text/c2.xhtml"));
+
+    // the encrypted document fails with a clear error
+    match doc.get_resource("c1") {
+        Err(EpubError::Encrypted { path, algorithm }) => {
+            assert_eq!(Path::new("Text/c1.xhtml"), path);
+            assert!(algorithm.ends_with("aes256-cbc"));
+        }
+        other => panic!("expected an encryption error, got {:?}", other),
+    }
+    assert!(doc.get_current_str().is_err());
+    assert!(doc.get_resource_reader("c1").is_err());
+    assert!(doc.get_resource("font").is_ok());
+
+    let doc = EpubDoc::new("test.epub").unwrap();
+    assert!(doc.encrypted_resources().is_empty());
+}
+
+/// An epub with this encryption.xml, a c1.xhtml and the font
+/// Fonts/serif.otf
+fn epub_with_font(opf: &str, encryption: &str, font: &[u8]) -> Vec<u8> {
+    let files = [
+        ("META-INF/encryption.xml", encryption),
+        ("c1.xhtml", "<html/>"),
+    ];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let mut zip = zip::ZipWriter::new_append(Cursor::new(epub)).unwrap();
+    let options = zip::write::FileOptions::default();
+    zip.start_file("Fonts/serif.otf", options).unwrap();
+    zip.write_all(font).unwrap();
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn doc_font_deobfuscation() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title>
+          <dc:identifier id="uid"> urn:uuid:1234 </dc:identifier>
+        </metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="font" href="Fonts/serif.otf" media-type="font/otf"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let encryption = r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+        xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+        <enc:EncryptedData>
+          <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+          <enc:CipherData><enc:CipherReference URI="Fonts/serif.otf"/></enc:CipherData>
+        </enc:EncryptedData>
+        </encryption>"#;
+    let font: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+    // the SHA-1 of "urn:uuid:1234"
+    let key = [
+        0xcb, 0xae, 0x3e, 0x12, 0xbc, 0x09, 0x2a, 0xa5, 0x61, 0xb8, 0xfa, 0xcb, 0xbd, 0x9e, 0x61,
+        0x0d, 0xca, 0x57, 0xf4, 0xfe,
+    ];
+    let mut obfuscated = font.clone();
+    for (i, byte) in obfuscated.iter_mut().take(1040).enumerate() {
+        *byte ^= key[i % key.len()];
+    }
+
+    let mut doc = EpubDoc::from_bytes(epub_with_font(opf, encryption, &obfuscated)).unwrap();
+
+    assert_eq!(font, doc.get_resource("font").unwrap());
+    assert_eq!(font, doc.get_resource_by_path("Fonts/serif.otf").unwrap());
+    let mut content = vec![];
+    let (mut reader, mime) = doc.get_resource_reader("font").unwrap();
+    reader.read_to_end(&mut content).unwrap();
+    assert_eq!(font, content);
+    assert_eq!("font/otf", mime);
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn doc_mmap_obfuscated_font() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title>
+          <dc:identifier id="uid">urn:uuid:1234</dc:identifier>
+        </metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="font" href="Fonts/serif.otf" media-type="font/otf"/>
+          <item id="image" href="image.png" media-type="image/png"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let encryption = r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+        xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+        <enc:EncryptedData>
+          <enc:EncryptionMethod Algorithm="http://www.idpf.org/2008/embedding"/>
+          <enc:CipherData><enc:CipherReference URI="Fonts/serif.otf"/></enc:CipherData>
+        </enc:EncryptedData>
+        </encryption>"#;
+    let files = [
+        ("META-INF/encryption.xml", encryption),
+        ("c1.xhtml", "<html/>"),
+    ];
+    let epub = epub_with("application/epub+zip", opf, &files);
+    let mut zip = zip::ZipWriter::new_append(Cursor::new(epub)).unwrap();
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    zip.start_file("Fonts/serif.otf", options).unwrap();
+    zip.write_all(&[0x55; 2000]).unwrap();
+    zip.start_file("image.png", options).unwrap();
+    zip.write_all(b"png").unwrap();
+    let path = std::env::temp_dir().join("epub-rs-doc-mmap-font.epub");
+    std::fs::write(&path, zip.finish().unwrap().into_inner()).unwrap();
+
+    let mut doc = unsafe { EpubDoc::new_mmap(&path) }.unwrap();
+    // the mapped font would still be obfuscated
+    assert!(doc.get_mapped_resource("font").is_none());
+    assert_ne!(vec![0x55; 2000], doc.get_resource("font").unwrap());
+    assert_eq!(b"png", doc.get_mapped_resource("image").unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn doc_adobe_font_deobfuscation() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0" unique-identifier="isbn">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title>
+          <dc:identifier id="isbn">9780000000000</dc:identifier>
+          <dc:identifier>urn:uuid:0123abcd-0000-1111-2222-3333444455ff</dc:identifier>
+        </metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="font" href="Fonts/serif.otf" media-type="application/vnd.ms-opentype"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let encryption = r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+        xmlns:enc="http://www.w3.org/2001/04/xmlenc#">
+        <enc:EncryptedData>
+          <enc:EncryptionMethod Algorithm="http://ns.adobe.com/pdf/enc#RC"/>
+          <enc:CipherData><enc:CipherReference URI="Fonts/serif.otf"/></enc:CipherData>
+        </enc:EncryptedData>
+        </encryption>"#;
+    let font: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+    let key = [
+        0x01, 0x23, 0xab, 0xcd, 0x00, 0x00, 0x11, 0x11, 0x22, 0x22, 0x33, 0x33, 0x44, 0x44, 0x55,
+        0xff,
+    ];
+    let mut obfuscated = font.clone();
+    for (i, byte) in obfuscated.iter_mut().take(1024).enumerate() {
+        *byte ^= key[i % key.len()];
+    }
+    let mut doc = EpubDoc::from_bytes(epub_with_font(opf, encryption, &obfuscated)).unwrap();
+    assert_eq!(font, doc.get_resource("font").unwrap());
+
+    // the obfuscation of the extracted fonts round-trips
+    let mut content = font.clone();
+    let uuid = "urn:uuid:0123abcd-0000-1111-2222-3333444455ff";
+    assert!(deobfuscate(ADOBE_OBFUSCATION, uuid, &mut content));
+    assert_eq!(obfuscated, content);
+    assert!(deobfuscate(IDPF_OBFUSCATION, uuid, &mut content));
+    assert!(deobfuscate(IDPF_OBFUSCATION, uuid, &mut content));
+    assert_eq!(obfuscated, content);
+    assert!(!deobfuscate(
+        ADOBE_OBFUSCATION,
+        "9780000000000",
+        &mut content
+    ));
+}
+
+#[test]
+fn doc_drm() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let encrypted = |algorithm: &str, key_info: &str| {
+        format!(
+            r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container"
+            xmlns:enc="http://www.w3.org/2001/04/xmlenc#" xmlns:ds="http://www.w3.org/2000/09/xmldsig#">
+            <enc:EncryptedData>
+              <enc:EncryptionMethod Algorithm="{}"/>{}
+              <enc:CipherData><enc:CipherReference URI="c1.xhtml"/></enc:CipherData>
+            </enc:EncryptedData>
+            </encryption>"#,
+            algorithm, key_info
+        )
+    };
+    let drm = |files: &[(&str, &str)]| {
+        let mut all = vec![("c1.xhtml", "<html/>")];
+        all.extend_from_slice(files);
+        EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &all))
+            .unwrap()
+            .drm()
+    };
+    let aes128 = "http://www.w3.org/2001/04/xmlenc#aes128-cbc";
+    let aes256 = "http://www.w3.org/2001/04/xmlenc#aes256-cbc";
+
+    assert_eq!(Drm::None, drm(&[]));
+    let obfuscated = encrypted("http://www.idpf.org/2008/embedding", "");
+    assert_eq!(
+        Drm::None,
+        drm(&[("META-INF/encryption.xml", obfuscated.as_str())])
+    );
+
+    let adept = encrypted(
+        aes128,
+        "<ds:KeyInfo><resource>urn:uuid:1</resource></ds:KeyInfo>",
+    );
+    let files = [
+        ("META-INF/encryption.xml", adept.as_str()),
+        ("META-INF/rights.xml", "<rights/>"),
+    ];
+    assert_eq!(Drm::AdobeAdept, drm(&files));
+
+    let fairplay = encrypted(aes128, "");
+    let files = [
+        ("META-INF/encryption.xml", fairplay.as_str()),
+        ("META-INF/sinf.xml", "<fairplay:sinf/>"),
+    ];
+    assert_eq!(Drm::AppleFairPlay, drm(&files));
+
+    let lcp = encrypted(
+        aes256,
+        r#"<ds:KeyInfo><ds:RetrievalMethod URI="license.lcpl#/encryption/content_key"/></ds:KeyInfo>"#,
+    );
+    let files = [
+        ("META-INF/encryption.xml", lcp.as_str()),
+        ("META-INF/license.lcpl", "{}"),
+    ];
+    assert_eq!(Drm::ReadiumLcp, drm(&files));
+    // without the license file, it's still an lcp book
+    assert_eq!(Drm::ReadiumLcp, drm(&files[..1]));
+
+    let kobo = encrypted(aes128, "");
+    assert_eq!(
+        Drm::KoboKdrm,
+        drm(&[("META-INF/encryption.xml", kobo.as_str())])
+    );
+    let unknown = encrypted(
+        aes256,
+        "<ds:KeyInfo><ds:KeyName>k</ds:KeyName></ds:KeyInfo>",
+    );
+    assert_eq!(
+        Drm::Unknown,
+        drm(&[("META-INF/encryption.xml", unknown.as_str())])
+    );
+}
+
+#[test]
+fn doc_lcp_license() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let license = r#"{
+        "id": "ef15e740-697f-11e3-949a-0800200c9a66",
+        "issued": "2013-11-04T01:08:15+01:00",
+        "updated": "2014-02-21T09:44:17+01:00",
+        "provider": "https://www.imaginaryebookretailer.com",
+        "encryption": {
+          "profile": "http://readium.org/lcp/basic-profile",
+          "content_key": {"algorithm": "http://www.w3.org/2001/04/xmlenc#aes256-cbc", "encrypted_value": "/k8R"},
+          "user_key": {"algorithm": "http://www.w3.org/2001/04/xmlenc#sha256", "text_hint": "Enter your email address", "key_check": "jJEj"}
+        },
+        "links": [
+          {"rel": "hint", "href": "https://www.imaginaryebookretailer.com/lcp/hint", "type": "text/html"},
+          {"rel": "publication", "href": "https://www.example.com/file.epub", "type": "application/epub+zip", "title": "The book"},
+          {"rel": "status", "href": "https://www.example.com/status/{id}", "templated": true}
+        ],
+        "user": {"id": "d9f298a7-7f34-49e7-8aae-4378ecb1d597"},
+        "rights": {"print": 0, "copy": 2048, "start": "2013-11-04T01:08:15+01:00", "end": "2013-11-25T01:08:15+01:00"}
+    }"#;
+    let files = [("c1.xhtml", "<html/>"), ("META-INF/license.lcpl", license)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    let license = doc.lcp_license().unwrap().unwrap();
+    assert_eq!("ef15e740-697f-11e3-949a-0800200c9a66", license.id);
+    assert_eq!("https://www.imaginaryebookretailer.com", license.provider);
+    assert_eq!(Some(11), license.issued.and_then(|d| d.month));
+    assert_eq!(Some(21), license.updated.and_then(|d| d.day));
+    assert_eq!(
+        Some("http://readium.org/lcp/basic-profile"),
+        license.profile.as_deref()
+    );
+    assert_eq!(
+        Some("Enter your email address"),
+        license.text_hint.as_deref()
+    );
+    assert_eq!(Some(0), license.rights.print);
+    assert_eq!(Some(2048), license.rights.copy);
+    assert_eq!(Some(4), license.rights.start.and_then(|d| d.day));
+    assert_eq!(Some(25), license.rights.end.and_then(|d| d.day));
+
+    assert_eq!(3, license.links.len());
+    let publication = license.link("publication").unwrap();
+    assert_eq!("https://www.example.com/file.epub", publication.href);
+    assert_eq!(
+        Some("application/epub+zip"),
+        publication.media_type.as_deref()
+    );
+    assert_eq!(Some("The book"), publication.title.as_deref());
+    assert!(!publication.templated);
+    assert!(license.link("status").unwrap().templated);
+    assert_eq!(None, license.link("self"));
+
+    let files = [("c1.xhtml", "<html/>"), ("META-INF/license.lcpl", "[1]")];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    assert!(doc.lcp_license().is_err());
+
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    assert_eq!(None, doc.lcp_license().unwrap());
+}
+
+/// An epub with two chapters and this signatures.xml
+fn signed_epub(signatures: &str) -> Vec<u8> {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="Text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="Text/c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let files = [
+        ("Text/c1.xhtml", "<html>one</html>"),
+        ("Text/c2.xhtml", "<html>two</html>"),
+        ("META-INF/signatures.xml", signatures),
+    ];
+    epub_with("application/epub+zip", opf, &files)
+}
+
+const SIGNATURES: &str = r##"<signatures xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+    <Signature Id="sig1" xmlns="http://www.w3.org/2000/09/xmldsig#">
+      <SignedInfo>
+        <CanonicalizationMethod Algorithm="http://www.w3.org/TR/2001/REC-xml-c14n-20010315"/>
+        <SignatureMethod Algorithm="http://www.w3.org/2000/09/xmldsig#rsa-sha1"/>
+        <Reference URI="#Manifest1">
+          <Transforms><Transform Algorithm="http://www.w3.org/TR/2001/REC-xml-c14n-20010315"/></Transforms>
+          <DigestMethod Algorithm="http://www.w3.org/2000/09/xmldsig#sha1"/>
+          <DigestValue>AAAA</DigestValue>
+        </Reference>
+      </SignedInfo>
+      <SignatureValue>
+        c2lnbmF0dXJl
+      </SignatureValue>
+      <Object>
+        <Manifest Id="Manifest1">
+          <Reference URI="Text/c1.xhtml">
+            <DigestMethod Algorithm="http://www.w3.org/2000/09/xmldsig#sha1"/>
+            <DigestValue>2CxTDmO/HewPzAldlik09j3f6Jk=</DigestValue>
+          </Reference>
+          <Reference URI="Text/c2.xhtml">
+            <DigestMethod Algorithm="http://www.w3.org/2001/04/xmlenc#sha256"/>
+            <DigestValue>VM4fzHz5P95RkGBxF92Q0QsdL+HKsaLSb3wjrACT2DY=</DigestValue>
+          </Reference>
+        </Manifest>
+      </Object>
+    </Signature>
+    <Signature Id="sig2" xmlns="http://www.w3.org/2000/09/xmldsig#">
+      <SignedInfo>
+        <SignatureMethod Algorithm="http://www.w3.org/2000/09/xmldsig#rsa-sha1"/>
+        <Reference URI="Text/c2.xhtml">
+          <DigestMethod Algorithm="http://www.w3.org/2000/09/xmldsig#sha1"/>
+          <DigestValue>2CxTDmO/HewPzAldlik09j3f6Jk=</DigestValue>
+        </Reference>
+        <Reference URI="Text/c3.xhtml">
+          <DigestMethod Algorithm="http://www.w3.org/2000/09/xmldsig#sha1"/>
+          <DigestValue>2CxTDmO/HewPzAldlik09j3f6Jk=</DigestValue>
+        </Reference>
+        <Reference URI="Text/c1.xhtml">
+          <DigestMethod Algorithm="http://www.w3.org/2001/04/xmldsig-more#md5"/>
+          <DigestValue>AAAA</DigestValue>
+        </Reference>
+      </SignedInfo>
+    </Signature>
+    </signatures>"##;
+
+#[test]
+fn doc_signatures() {
+    let mut doc = EpubDoc::from_bytes(signed_epub(SIGNATURES)).unwrap();
+    let signatures = doc.signatures().unwrap();
+    assert_eq!(2, signatures.len());
+
+    let sig = &signatures[0];
+    assert_eq!(Some("sig1"), sig.id.as_deref());
+    assert_eq!(
+        "http://www.w3.org/TR/2001/REC-xml-c14n-20010315",
+        sig.canonicalization_method
+    );
+    assert_eq!(
+        "http://www.w3.org/2000/09/xmldsig#rsa-sha1",
+        sig.signature_method
+    );
+    assert_eq!("c2lnbmF0dXJl", sig.signature_value);
+    assert_eq!(3, sig.references.len());
+    assert_eq!("#Manifest1", sig.references[0].uri);
+    assert_eq!(None, sig.references[0].path);
+    assert_eq!(
+        vec!["http://www.w3.org/TR/2001/REC-xml-c14n-20010315"],
+        sig.references[0].transforms
+    );
+    assert_eq!(
+        vec![Path::new("Text/c1.xhtml"), Path::new("Text/c2.xhtml")],
+        sig.referenced_paths()
+    );
+    assert!(sig.is_referenced("Text/./c1.xhtml"));
+    assert!(!sig.is_referenced("content.opf"));
+    assert_eq!(
+        "2CxTDmO/HewPzAldlik09j3f6Jk=",
+        sig.references[1].digest_value
+    );
+    assert!(signatures[1].is_referenced("Text/c3.xhtml"));
+
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    assert!(doc.signatures().unwrap().is_empty());
+    let mut doc = EpubDoc::from_bytes(signed_epub("not xml")).unwrap();
+    assert!(doc.signatures().is_err());
+}
+
+#[cfg(feature = "integrity")]
+#[test]
+fn doc_check_integrity() {
+    use epub::signatures::DigestStatus;
+
+    let mut doc = EpubDoc::from_bytes(signed_epub(SIGNATURES)).unwrap();
+    let checks = doc.check_integrity().unwrap();
+    let statuses: Vec<(&str, &str, DigestStatus)> = checks
+        .iter()
+        .map(|check| {
+            (
+                check.signature.as_deref().unwrap(),
+                check.reference.uri.as_str(),
+                check.status,
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ("sig1", "#Manifest1", DigestStatus::Unsupported),
+            ("sig1", "Text/c1.xhtml", DigestStatus::Valid),
+            ("sig1", "Text/c2.xhtml", DigestStatus::Valid),
+            ("sig2", "Text/c2.xhtml", DigestStatus::Mismatch),
+            ("sig2", "Text/c3.xhtml", DigestStatus::Missing),
+            ("sig2", "Text/c1.xhtml", DigestStatus::Unsupported),
+        ],
+        statuses
+    );
+}
+
+#[test]
+fn doc_meta_inf_files() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest><item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/></manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let rights = r#"<?xml version="1.0"?>
+        <adept:rights xmlns:adept="http://ns.adobe.com/adept">
+          <adept:licenseToken>
+            <adept:user>urn:uuid:user-1</adept:user>
+            <adept:resource>urn:uuid:book-1</adept:resource>
+            <adept:licenseURL>https://nasigningservice.adobe.com/licensesign</adept:licenseURL>
+            <adept:operatorURL>https://acs.example.com/fulfillment</adept:operatorURL>
+            <adept:permissions><adept:display/><adept:excerpt/><adept:print/></adept:permissions>
+          </adept:licenseToken>
+        </adept:rights>"#;
+    let metadata = r##"<metadata xmlns="http://www.idpf.org/2013/metadata"
+        xmlns:dc="http://purl.org/dc/elements/1.1/" xml:lang="en">
+          <dc:identifier id="uid">urn:uuid:book-1</dc:identifier>
+          <dc:title>Container title</dc:title>
+          <meta property="dcterms:modified">2021-03-04T05:06:07Z</meta>
+          <meta property="title-type" refines="#t">main</meta>
+        </metadata>"##;
+    let files = [
+        ("c1.xhtml", "<html/>"),
+        ("META-INF/rights.xml", rights),
+        ("META-INF/metadata.xml", metadata),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let parsed = doc.rights().unwrap().unwrap();
+    assert_eq!(rights, parsed.raw);
+    assert_eq!(
+        Some("http://ns.adobe.com/adept"),
+        parsed.namespace.as_deref()
+    );
+    assert_eq!(Some("urn:uuid:user-1"), parsed.user.as_deref());
+    assert_eq!(Some("urn:uuid:book-1"), parsed.resource.as_deref());
+    assert_eq!(
+        Some("https://nasigningservice.adobe.com/licensesign"),
+        parsed.license_url.as_deref()
+    );
+    assert_eq!(
+        Some("https://acs.example.com/fulfillment"),
+        parsed.operator_url.as_deref()
+    );
+    assert_eq!(vec!["display", "excerpt", "print"], parsed.permissions);
+
+    let container = doc.container_metadata().unwrap().unwrap();
+    assert_eq!(metadata, container.raw);
+    assert_eq!(Some("urn:uuid:book-1"), container.get("identifier"));
+    assert_eq!(Some("Container title"), container.get("title"));
+    assert_eq!(
+        Some("2021-03-04T05:06:07Z"),
+        container.get("dcterms:modified")
+    );
+    assert_eq!(Some("en"), container.items[1].lang.as_deref());
+    assert_eq!(None, container.get("creator"));
+
+    // the vendor files that aren't xml are kept raw
+    let files = [
+        ("c1.xhtml", "<html/>"),
+        ("META-INF/rights.xml", "vendor data"),
+        ("META-INF/metadata.xml", "vendor data"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+    let parsed = doc.rights().unwrap().unwrap();
+    assert_eq!("vendor data", parsed.raw);
+    assert_eq!(None, parsed.resource);
+    let container = doc.container_metadata().unwrap().unwrap();
+    assert_eq!("vendor data", container.raw);
+    assert!(container.items.is_empty());
+
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    assert_eq!(None, doc.rights().unwrap());
+    assert_eq!(None, doc.container_metadata().unwrap());
+}
+
+#[test]
+fn doc_password() {
+    let path = "tests/docs/protected.epub";
+    assert!(matches!(
+        EpubDoc::new(path),
+        Err(EpubError::InvalidPassword(_))
+    ));
+    let mut options = OpenOptions::new();
+    options.password("wrong");
+    assert!(matches!(
+        EpubDoc::new_with_options(path, &options),
+        Err(EpubError::InvalidPassword(_))
+    ));
+    options.password("secret");
+    let mut doc = EpubDoc::new_with_options(path, &options).unwrap();
+    assert_eq!(Some(String::from("Protected")), doc.mdata("title"));
+    assert!(doc
+        .get_current_str()
+        .unwrap()
+        .contains("The secret chapter."));
+}
+
+#[test]
+fn doc_plain_text() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let c1 = r#"<?xml version="1.0" encoding="utf-8"?>
+        <!DOCTYPE html>
+        <html xmlns="http://www.w3.org/1999/xhtml">
+        <head><title>Title</title><style>p { color: red; }</style></head>
+        <body>
+          <!-- a <p>comment</p> -->
+          <h1 class="title">Chapter
+             One</h1>
+          <p>Fish &amp; chips, &lt;tags&gt;, &#8220;quotes&#x201D; and &unknown;.</p>
+          <div><p>Nested <b>bold</b><i>italic</i></p>text after</div>
+          <p>Line one<br/>Line two<br />  </p>
+          <script>var x = "<p>not text</p>";</script>
+          <pre>  fn main() {
+      code();
+  }</pre>
+          <table><tr><th>A</th><th>B</th></tr><tr><td>1</td><td>2</td></tr></table>
+          <ul><li>first</li><li>second</li></ul>
+          <p><![CDATA[raw <text>]]></p>
+        </body>
+        </html>"#;
+    let files = [
+        ("c1.xhtml", c1),
+        ("c2.xhtml", "<html><body><p>Second</p></body></html>"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let expected = [
+        "Chapter One",
+        "Fish & chips, <tags>, \u{201c}quotes\u{201d} and &unknown;.",
+        "Nested bolditalic",
+        "text after",
+        "Line one\nLine two",
+        "fn main() {\ncode();\n}",
+        "A B",
+        "1 2",
+        "first",
+        "second",
+        "raw <text>",
+    ];
+    assert_eq!(expected.join("\n\n"), doc.get_text(0).unwrap());
+    assert_eq!(doc.get_text(0).unwrap(), doc.get_current_text().unwrap());
+    assert_eq!("Second", doc.get_text(1).unwrap());
+    doc.go_next().unwrap();
+    assert_eq!("Second", doc.get_current_text().unwrap());
+    assert!(doc.get_text(2).is_err());
+}
+
+#[test]
+fn doc_extract_text() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <item id="cover" href="cover.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c1b" href="c1b.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="notes.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx">
+          <itemref idref="cover"/><itemref idref="c1"/><itemref idref="c1b"/>
+          <itemref idref="notes" linear="no"/><itemref idref="c2"/>
+        </spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+          <navPoint id="n1" playOrder="1"><navLabel><text> First </text></navLabel><content src="c1.xhtml"/></navPoint>
+          <navPoint id="n2" playOrder="2"><navLabel><text>Second</text></navLabel><content src="c2.xhtml#start"/></navPoint>
+        </navMap></ncx>"#;
+    let files = [
+        ("toc.ncx", ncx),
+        (
+            "cover.xhtml",
+            r#"<html><body><img src="cover.jpg"/></body></html>"#,
+        ),
+        ("c1.xhtml", "<html><body><p>One.</p></body></html>"),
+        (
+            "c1b.xhtml",
+            "<html><body><p>One, continued.</p></body></html>",
+        ),
+        ("notes.xhtml", "<html><body><p>A note.</p></body></html>"),
+        (
+            "c2.xhtml",
+            "<html><body><p id=\"start\">Two.</p></body></html>",
+        ),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    assert_eq!(
+        "One.\n\nOne, continued.\n\nTwo.",
+        doc.extract_text().unwrap()
+    );
+    let mut options = TextOptions::new();
+    options.separator("\n\n* * *\n\n").chapter_titles(true);
+    assert_eq!(
+        "First\n\nOne.\n\n* * *\n\nOne, continued.\n\n* * *\n\nSecond\n\nTwo.",
+        doc.extract_text_with(&options).unwrap()
+    );
+    // the current page doesn't change
+    assert_eq!(0, doc.get_current_page());
+}
+
+#[test]
+fn doc_text_blocks() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let chapter = "<html><head><title>Ignored</title></head><body>\
+        <section><h3>A <em>title</em></h3>\
+        <p>First   line<br/>second &amp; last.</p>\
+        loose text\
+        <ul><li>one</li><li><p>two</p><p>more</p></li></ul>\
+        <blockquote><p>Quoted.</p></blockquote>\
+        <figure><img src=\"a.png\" alt=\"A cat\"/><figcaption>The <b>cat</b></figcaption></figure>\
+        <figure><img src=\"b.png\"/></figure>\
+        <pre>  indented\n    code</pre><p> </p></section></body></html>";
+    let files = [("c1.xhtml", chapter)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let blocks = doc.get_text_blocks(0).unwrap();
+    let summary: Vec<(BlockKind, &str)> = blocks
+        .iter()
+        .map(|block| (block.kind.clone(), block.text.as_str()))
+        .collect();
+    assert_eq!(
+        vec![
+            (BlockKind::Heading { level: 3 }, "A title"),
+            (BlockKind::Paragraph, "First line\nsecond & last."),
+            (BlockKind::Paragraph, "loose text"),
+            (BlockKind::ListItem, "one"),
+            (BlockKind::ListItem, "two"),
+            (BlockKind::ListItem, "more"),
+            (BlockKind::BlockQuote, "Quoted."),
+            (
+                BlockKind::Figure {
+                    alt: Some(String::from("A cat"))
+                },
+                "The cat"
+            ),
+            (BlockKind::Figure { alt: None }, ""),
+            (BlockKind::CodeBlock, "  indented\n    code"),
+        ],
+        summary
+    );
+
+    // the ranges are offsets in the source
+    let source = doc.get_resource_str("c1").unwrap();
+    let text = |block: &TextBlock| source[block.range.clone()].to_string();
+    assert_eq!("A <em>title</em>", text(&blocks[0]));
+    assert_eq!("First   line<br/>second &amp; last.", text(&blocks[1]));
+    assert_eq!(
+        "<img src=\"a.png\" alt=\"A cat\"/><figcaption>The <b>cat</b>",
+        text(&blocks[7])
+    );
+    assert_eq!("<img src=\"b.png\"/>", text(&blocks[8]));
+
+    assert!(doc.get_text_blocks(1).is_err());
+}
+
+#[test]
+fn doc_block_languages() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+          <dc:title>T</dc:title><dc:language>en</dc:language>
+        </metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let c1 = "<html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"fr\" lang=\"fr\"><body>\
+        <p>Bonjour <span lang=\"en\">friend</span>.</p>\
+        <blockquote xml:lang=\"de\"><p>Guten Tag.</p><p lang=\"it\">Ciao.</p></blockquote>\
+        <p>Au revoir.</p><div lang=\"\"><p>???</p></div>\
+        </body></html>";
+    let files = [
+        ("c1.xhtml", c1),
+        ("c2.xhtml", "<html><body><p>Hello.</p></body></html>"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let langs = |blocks: Vec<TextBlock>| -> Vec<(String, Option<String>)> {
+        blocks
+            .into_iter()
+            .map(|block| (block.text, block.lang))
+            .collect()
+    };
+    let lang = |text: &str, lang: Option<&str>| (text.to_string(), lang.map(String::from));
+    assert_eq!(
+        vec![
+            lang("Bonjour friend.", Some("fr")),
+            lang("Guten Tag.", Some("de")),
+            lang("Ciao.", Some("it")),
+            lang("Au revoir.", Some("fr")),
+            // the empty language is unknown, then the one of the publication
+            lang("???", Some("en")),
+        ],
+        langs(doc.get_text_blocks(0).unwrap())
+    );
+    assert_eq!(
+        vec![lang("Hello.", Some("en"))],
+        langs(doc.get_text_blocks(1).unwrap())
+    );
+    assert_eq!(
+        vec![lang("Hello.", None)],
+        langs(epub::text::text_blocks(
+            &doc.get_resource_str("c2").unwrap()
+        ))
+    );
+}
+
+#[test]
+fn doc_text_blocks_large_document() {
+    // the attributes and the entities of every tag of a 64 KB document, the
+    // regexes compiled for each of them took seconds
+    let mut html = String::from("<html xml:lang=\"en\"><body><table>");
+    while html.len() < 1 << 16 {
+        html.push_str("<tr><td colspan=\"2\" class=\"c\"><p id=\"p\" lang=\"fr\">Caf&#233; ");
+        html.push_str("&amp; cr&egrave;me</p></td></tr>");
+    }
+    html.push_str("</table></body></html>");
+
+    let start = std::time::Instant::now();
+    let blocks = epub::text::text_blocks(&html);
+    let tables = epub::tables::parse_tables(&html, "c1");
+    assert!(start.elapsed() < std::time::Duration::from_secs(3));
+    assert_eq!("Café & cr&egrave;me", blocks[0].text);
+    assert_eq!(Some("fr"), blocks[0].lang.as_deref());
+    assert_eq!(2, tables[0].rows[0].cells[0].colspan);
+}
+
+#[test]
+fn doc_ruby() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let chapter = "<html><body>\
+        <p><ruby>東<rp>（</rp><rt>とう</rt><rp>）</rp>京<rp>（</rp><rt>きょう</rt><rp>）</rp></ruby>へ行く。</p>\
+        <p><ruby><rb>明日</rb><rtc><rt>あした</rt></rtc></ruby>、<em>晴れ</em>。</p>\
+        </body></html>";
+    let files = [("c1.xhtml", chapter)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    assert_eq!("東京へ行く。\n\n明日、晴れ。", doc.get_text(0).unwrap());
+
+    let mut options = TextOptions::new();
+    options.ruby(RubyMode::Parentheses);
+    assert_eq!(
+        "東(とう)京(きょう)へ行く。\n\n明日(あした)、晴れ。",
+        doc.get_text_with(0, &options).unwrap()
+    );
+    assert_eq!(
+        "東(とう)京(きょう)へ行く。\n\n明日(あした)、晴れ。",
+        doc.extract_text_with(&options).unwrap()
+    );
+
+    options.ruby(RubyMode::Annotations);
+    let blocks = doc.get_text_blocks_with(0, &options).unwrap();
+    let ruby = |base: &str, reading: &str| Ruby {
+        base: base.to_string(),
+        reading: reading.to_string(),
+    };
+    assert_eq!("東京へ行く。", blocks[0].text);
+    assert_eq!(
+        vec![ruby("東", "とう"), ruby("京", "きょう")],
+        blocks[0].ruby
+    );
+    assert_eq!("明日、晴れ。", blocks[1].text);
+    assert_eq!(vec![ruby("明日", "あした")], blocks[1].ruby);
+    assert!(doc.get_text_blocks(0).unwrap()[0].ruby.is_empty());
+}
+
+#[test]
+fn doc_image_references() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+          <item id="cat" href="images/cat.png" media-type="image/png"/>
+          <item id="map" href="images/map.jpg" media-type="image/jpeg"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/><itemref idref="c1"/></spine>
+        </package>"#;
+    let c1 = "<html><body>\
+        <figure><img src=\"../images/cat.png\" alt=\"A cat\"/>\
+        <figcaption>The <em>cat</em>\n  sleeping</figcaption></figure>\
+        <p><img src=\"../images/line.png\" alt=\"\"/><img src=\"../images/cat.png\"/></p>\
+        </body></html>";
+    let c2 = "<html><body>\
+        <svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\">\
+        <title>A map</title><image xlink:href=\"../images/map.jpg\"/></svg>\
+        </body></html>";
+    let files = [("text/c1.xhtml", c1), ("text/c2.xhtml", c2)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let images = doc.image_references().unwrap();
+    let summary: Vec<_> = images
+        .iter()
+        .map(|image| {
+            (
+                image.document.as_str(),
+                image.href.as_str(),
+                image.resource.as_deref(),
+                image.alt.as_deref(),
+                image.caption.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                "c1",
+                "../images/cat.png",
+                Some("cat"),
+                Some("A cat"),
+                Some("The cat sleeping")
+            ),
+            ("c1", "../images/line.png", None, Some(""), None),
+            ("c1", "../images/cat.png", Some("cat"), None, None),
+            ("c2", "../images/map.jpg", Some("map"), Some("A map"), None),
+        ],
+        summary
+    );
+    assert_eq!(PathBuf::from("images/line.png"), images[1].path);
+    // the ranges are the tags in the documents
+    let source = doc.get_resource_str("c2").unwrap();
+    assert_eq!(
+        "<image xlink:href=\"../images/map.jpg\"/>",
+        &source[images[3].range.clone()]
+    );
+
+    assert_eq!(images[..3], doc.images_of("c1").unwrap()[..]);
+    assert!(doc.images_of("missing").is_err());
+}
+
+#[test]
+fn doc_tables() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/><itemref idref="c2"/></spine>
+        </package>"#;
+    let c1 = "<html><body><p>Before.</p>\
+        <table><caption>Results <b>2024</b></caption>\
+        <thead><tr><td>Name</td><td colspan=\"2\">Score</td></tr></thead>\
+        <tbody>\
+        <tr><th rowspan=\"2\">Ada</th><td>1</td><td>2,5</td></tr>\
+        <tr><td>3</td><td><p>multi</p><p>part</p></td></tr>\
+        <tr><td>Bob</td><td>line<br/>break</td>\
+        <td><table><tr><th>x</th></tr></table>outer</td></tr>\
+        </tbody></table></body></html>";
+    let files = [
+        ("c1.xhtml", c1),
+        ("c2.xhtml", "<html><body><p>No tables.</p></body></html>"),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let tables = doc.tables().unwrap();
+    assert_eq!(2, tables.len());
+    let table = &tables[0];
+    assert_eq!("c1", table.document);
+    assert_eq!(Some("Results 2024"), table.caption.as_deref());
+    let source = doc.get_resource_str("c1").unwrap();
+    assert!(source[table.range.clone()].starts_with("<table><caption>"));
+    assert!(source[table.range.clone()].ends_with("</tbody></table>"));
+
+    let headers: Vec<bool> = table.rows.iter().map(|row| row.header).collect();
+    assert_eq!(vec![true, false, false, false], headers);
+    let cell = &table.rows[1].cells[0];
+    assert_eq!(
+        ("Ada", true, 1, 2),
+        (cell.text.as_str(), cell.header, cell.colspan, cell.rowspan)
+    );
+    assert_eq!(2, table.rows[0].cells[1].colspan);
+    assert_eq!(
+        vec![
+            vec!["Name", "Score", ""],
+            vec!["Ada", "1", "2,5"],
+            vec!["", "3", "multi part"],
+            vec!["Bob", "line\nbreak", "outer"],
+        ],
+        table.grid()
+    );
+    assert_eq!(
+        "Name,Score,\nAda,1,\"2,5\"\n,3,multi part\nBob,\"line\nbreak\",outer\n",
+        table.to_csv()
+    );
+
+    // the nested table is a table of its own, with a header row
+    let nested = &tables[1];
+    assert_eq!(vec![vec!["x"]], nested.grid());
+    assert!(nested.rows[0].header);
+    assert!(table.range.start < nested.range.start && nested.range.end < table.range.end);
+
+    assert!(doc.tables_of("c2").unwrap().is_empty());
+    assert!(doc.tables_of("missing").is_err());
+}
+
+#[test]
+fn doc_search() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="text/notes.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="text/c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine>
+          <itemref idref="c1"/><itemref idref="notes" linear="no"/><itemref idref="c2"/>
+        </spine>
+        </package>"#;
+    let c1 = "<html><body>\
+        <p>The whale is big.</p>\
+        <section id=\"s1\"><h2 id=\"h\">Moby</h2><p>Ésta whale, a White Whale.</p></section>\
+        </body></html>";
+    let files = [
+        ("text/c1.xhtml", c1),
+        (
+            "text/notes.xhtml",
+            "<html><body><p>A whale note.</p></body></html>",
+        ),
+        (
+            "text/c2.xhtml",
+            "<html><body><p id=\"p1\">No match.</p><p>whales</p></body></html>",
+        ),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let hits = doc.search("WHALE").unwrap();
+    let summary: Vec<_> = hits
+        .iter()
+        .map(|hit| {
+            (
+                hit.spine_index,
+                hit.href.display().to_string(),
+                hit.char_offset,
+                hit.text.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (0, String::from("text/c1.xhtml"), 4, "whale"),
+            (0, String::from("text/c1.xhtml#h"), 30, "whale"),
+            (0, String::from("text/c1.xhtml#h"), 45, "Whale"),
+            (2, String::from("text/c2.xhtml#p1"), 11, "whale"),
+        ],
+        summary
+    );
+    // the offsets are in the text of the spine items
+    let text = doc.get_text(0).unwrap();
+    let offset: String = text.chars().skip(hits[1].char_offset).take(5).collect();
+    assert_eq!("whale", offset);
+    assert_eq!(
+        "The whale is big.  Moby  Ésta whale, a White Whale.",
+        hits[1].snippet
+    );
+
+    let mut options = SearchOptions::new();
+    options.case_sensitive(true).context(4);
+    let hits = doc.search_with("Whale", &options).unwrap();
+    assert_eq!(1, hits.len());
+    assert_eq!("ite Whale.", hits[0].snippet);
+
+    assert!(doc.search("").unwrap().is_empty());
+    assert!(doc.search("note").unwrap().is_empty());
+}
+
+#[test]
+fn doc_search_regex() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let c1 = "<html><body><p id=\"a\">Call 555-1234 or\n555-9876.</p>\
+        <p class=\"note\" id=\"b\">Price: <b>12</b> &amp; more.</p></body></html>";
+    let files = [("c1.xhtml", c1)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let re = regex::Regex::new(r"(\d{3})-(\d{4})|(x)").unwrap();
+    let hits = doc.search_regex(&re).unwrap();
+    assert_eq!(2, hits.len());
+    assert_eq!(
+        vec![Some(String::from("555")), Some(String::from("1234")), None],
+        hits[0].captures
+    );
+    assert_eq!(
+        (5, "555-1234"),
+        (hits[0].char_offset, hits[0].text.as_str())
+    );
+    assert_eq!(
+        "Call <mark>555-1234</mark> or 555-9876.  Price: 12 & more.",
+        hits[0].highlighted
+    );
+    assert_eq!(PathBuf::from("c1.xhtml#a"), hits[1].href);
+
+    // the text doesn't have the tags, the source does
+    let re = regex::Regex::new(r"<b>(\d+)</b> &amp;").unwrap();
+    assert!(doc.search_regex(&re).unwrap().is_empty());
+    let mut options = SearchOptions::new();
+    options.raw(true).context(6).highlight("[", "]");
+    let hits = doc.search_regex_with(&re, &options).unwrap();
+    assert_eq!(1, hits.len());
+    assert_eq!(Some("12"), hits[0].captures[0].as_deref());
+    assert_eq!(PathBuf::from("c1.xhtml#b"), hits[0].href);
+    let source = doc.get_resource_str("c1").unwrap();
+    assert_eq!(source.find("<b>12").unwrap(), hits[0].char_offset);
+    assert_eq!("rice: <b>12</b> &amp; more.", hits[0].snippet);
+    assert_eq!("rice: [<b>12</b> &amp;] more.", hits[0].highlighted);
+
+    // the empty matches aren't hits
+    let re = regex::Regex::new(r"z*").unwrap();
+    assert!(doc.search_regex(&re).unwrap().is_empty());
+}
+
+#[test]
+fn doc_stats() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="notes.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine toc="ncx">
+          <itemref idref="c1"/><itemref idref="notes" linear="no"/><itemref idref="c2"/>
+        </spine>
+        </package>"#;
+    let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1"><navMap>
+          <navPoint id="n1" playOrder="1"><navLabel><text>One</text></navLabel><content src="c1.xhtml"/></navPoint>
+        </navMap></ncx>"#;
+    let files = [
+        ("toc.ncx", ncx),
+        (
+            "c1.xhtml",
+            "<html><head><title>Not counted</title></head>\
+             <body><p>It's a well-known fact.</p></body></html>",
+        ),
+        ("notes.xhtml", "<html><body><p>A note.</p></body></html>"),
+        (
+            "c2.xhtml",
+            "<html><body><p>日本語 and more</p></body></html>",
+        ),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let stats = doc.stats().unwrap();
+    assert_eq!(2, stats.chapters.len());
+    let c1 = &stats.chapters[0];
+    assert_eq!(
+        (0, "c1", Some("One")),
+        (c1.spine_index, c1.id.as_str(), c1.title.as_deref())
+    );
+    assert_eq!((4, 20), (c1.words, c1.characters));
+    let c2 = &stats.chapters[1];
+    assert_eq!((2, None), (c2.spine_index, c2.title.as_deref()));
+    assert_eq!((5, 10), (c2.words, c2.characters));
+    assert_eq!((9, 30), (stats.words, stats.characters));
+
+    assert_eq!(Duration::from_secs(4), c2.reading_time(75));
+    assert_eq!(Duration::from_millis(2160), stats.reading_time(250));
+    assert_eq!(Duration::from_secs(0), stats.reading_time(0));
+}
+
+#[test]
+fn doc_chunks() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="c1.xhtml" media-type="application/xhtml+xml"/>
+          <item id="notes" href="notes.xhtml" media-type="application/xhtml+xml"/>
+          <item id="c2" href="c2.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine>
+          <itemref idref="c1"/><itemref idref="notes" linear="no"/><itemref idref="c2"/>
+        </spine>
+        </package>"#;
+    let files = [
+        (
+            "c1.xhtml",
+            "<html><body><p>Short one.</p><p>Also short.</p>\
+             <p>\"A quoted sentence.\" Then another one! And 3.14 is pi.</p></body></html>",
+        ),
+        ("notes.xhtml", "<html><body><p>A note.</p></body></html>"),
+        (
+            "c2.xhtml",
+            "<html><body><p>A sentence with far too many words for a chunk</p></body></html>",
+        ),
+    ];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let mut options = ChunkOptions::new();
+    options.max_chars(30);
+    let chunks = doc.chunks(&options).unwrap();
+    let summary: Vec<(usize, &str)> = chunks
+        .iter()
+        .map(|chunk| (chunk.spine_index, chunk.text.as_str()))
+        .collect();
+    assert_eq!(
+        vec![
+            (0, "Short one.\n\nAlso short."),
+            (0, "\"A quoted sentence.\""),
+            (0, "Then another one!"),
+            (0, "And 3.14 is pi."),
+            (2, "A sentence with far too many"),
+            (2, "words for a chunk"),
+        ],
+        summary
+    );
+    // the offsets map the chunks back to the text of the spine items
+    for chunk in chunks.iter() {
+        let text = doc.get_text(chunk.spine_index).unwrap();
+        assert_eq!(
+            chunk.text,
+            &text[chunk.offset..chunk.offset + chunk.text.len()]
+        );
+    }
+
+    // the tokens bound the chunks too, and a long word is split
+    let mut options = ChunkOptions::new();
+    options
+        .max_tokens(2)
+        .tokenizer(|text| text.split_whitespace().count());
+    let text = "one two three. four";
+    let chunks: Vec<&str> = chunk_text(text, &options)
+        .into_iter()
+        .map(|range| &text[range])
+        .collect();
+    assert_eq!(vec!["one two", "three.", "four"], chunks);
+    let mut options = ChunkOptions::new();
+    options.max_chars(4);
+    let text = "abcdéfghij";
+    let chunks: Vec<&str> = chunk_text(text, &options)
+        .into_iter()
+        .map(|range| &text[range])
+        .collect();
+    assert_eq!(vec!["abcd", "éfgh", "ij"], chunks);
+}
+
+#[test]
+#[cfg(feature = "markdown")]
+fn doc_markdown() {
+    let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>T</dc:title></metadata>
+        <manifest>
+          <item id="c1" href="text/c1.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="c1"/></spine>
+        </package>"#;
+    let chapter = r##"<html><head><title>Ignored</title></head><body>
+        <h1>The <em>first</em> chapter</h1>
+        <p>Some <strong>bold</strong>, <i>italic</i> and <code>code</code>
+           text, with a <a href="c2.xhtml#note">link</a> and 2 * 3 = 6.</p>
+        <p>A line<br/>and another &amp; more.</p>
+        <ul>
+          <li>one</li>
+          <li>two
+            <ol start="3"><li>three</li><li><p>four</p></li></ol>
+          </li>
+        </ul>
+        <blockquote><p>Quoted.</p><p>Again.</p></blockquote>
+        <p><img src="../images/a b.png" alt="A [picture]"/></p>
+        <pre><code>fn main() {
+    println!("hi");
+}</code></pre>
+        <h3>End</h3>
+        </body></html>"##;
+    let files = [("text/c1.xhtml", chapter)];
+    let mut doc = EpubDoc::from_bytes(epub_with("application/epub+zip", opf, &files)).unwrap();
+
+    let expected = "# The *first* chapter\n\n\
+        Some **bold**, *italic* and `code` text, with a [link](c2.xhtml#note) and 2 \\* 3 = 6.\n\n\
+        A line  \nand another & more.\n\n\
+        - one\n\
+        - two\n   \
+        3. three\n   \
+        4. four\n\n\
+        > Quoted.\n\
+        >\n\
+        > Again.\n\n\
+        ![A \\[picture\\]](<../images/a b.png>)\n\n\
+        ```\nfn main() {\n    println!(\"hi\");\n}\n```\n\n\
+        ### End";
+    assert_eq!(expected, doc.get_markdown(0).unwrap());
+    assert_eq!(expected, doc.get_current_markdown().unwrap());
+    assert!(doc.get_markdown(1).is_err());
+    // the markdown is converted back to the same content
+    let mut builder = EpubBuilder::minimal("Title", "Me", "en");
+    builder.add_chapter_md("The first chapter", expected);
+    let mut built = builder.build().unwrap().into_doc().unwrap();
+    assert_eq!(expected, built.get_markdown(1).unwrap());
+}
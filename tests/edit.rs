@@ -0,0 +1,49 @@
+use epub::doc::EpubDoc;
+use std::fs;
+
+#[test]
+fn edit_commit() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    let path = std::env::temp_dir().join("epub-rs-edit-commit.epub");
+
+    let mut session = doc.edit();
+    session
+        .add_resource(
+            "new.xhtml",
+            "Text/new.xhtml",
+            "application/xhtml+xml",
+            "<html/>",
+        )
+        .unwrap();
+    session.add_spine_item("new.xhtml").unwrap();
+    session.remove_resource("cc.png").unwrap();
+    session.commit(&path).unwrap();
+
+    assert_eq!(18, doc.spine.len());
+    let saved = EpubDoc::new(&path).unwrap();
+    assert_eq!(18, saved.spine.len());
+    assert!(saved.resources.contains_key("new.xhtml"));
+    assert!(!saved.resources.contains_key("cc.png"));
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn edit_rollback_on_drop() {
+    let mut doc = EpubDoc::new("test.epub").unwrap();
+    doc.modify_entry("OEBPS/Text/000.xhtml", "<html>kept</html>")
+        .unwrap();
+    {
+        let mut session = doc.edit();
+        session.remove_resource("000.xhtml").unwrap();
+        session.rename_resource("Images/cc.png", "cc.png").unwrap();
+        assert_eq!(16, session.spine.len());
+    }
+
+    assert_eq!(17, doc.spine.len());
+    assert_eq!(23, doc.resources.len());
+    assert!(doc.get_resource_by_path("OEBPS/Images/cc.png").is_ok());
+    assert_eq!(
+        "<html>kept</html>",
+        doc.get_resource_str("000.xhtml").unwrap()
+    );
+}
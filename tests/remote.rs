@@ -0,0 +1,171 @@
+use epub::doc::EpubDoc;
+#[cfg(feature = "http")]
+use epub::remote::HttpRange;
+use epub::remote::{RangeReader, RangeSource};
+use std::cell::Cell;
+use std::io::{self, Read, Seek, SeekFrom};
+#[cfg(feature = "http")]
+use std::io::{BufRead, BufReader, Write};
+#[cfg(feature = "http")]
+use std::net::TcpListener;
+use std::rc::Rc;
+#[cfg(feature = "http")]
+use std::thread;
+
+/// Serves `content` with range requests support, returns the url
+#[cfg(feature = "http")]
+fn serve(content: Vec<u8>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/book.epub", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut range = None;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some(value) = line.trim().strip_prefix("Range: bytes=") {
+                    let mut parts = value.split('-').map(|n| n.parse::<usize>().unwrap());
+                    range = Some((parts.next().unwrap(), parts.next().unwrap()));
+                }
+            }
+            let (start, end) = range.unwrap();
+            let body = &content[start..=end.min(content.len() - 1)];
+            write!(
+                stream,
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n\
+                 Content-Length: {}\r\n\r\n",
+                start,
+                start + body.len() - 1,
+                content.len(),
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(body).unwrap();
+        }
+    });
+    url
+}
+
+/// Redirects every request to `url`, returns the url of the redirect
+#[cfg(feature = "http")]
+fn redirect(url: String) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let from = format!("http://{}/old.epub", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                stream,
+                "HTTP/1.1 302 Found\r\nLocation: {}\r\nContent-Length: 0\r\n\r\n",
+                url
+            )
+            .unwrap();
+        }
+    });
+    from
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn remote_from_url() {
+    let content = std::fs::read("test.epub").unwrap();
+    let url = serve(content.clone());
+    let mut doc = EpubDoc::from_url(&url).unwrap();
+
+    assert_eq!("Todo es mío", doc.mdata("title").unwrap());
+    assert_eq!(17, doc.spine.len());
+    let chapter = doc.get_current_str().unwrap();
+    assert!(chapter.contains("portada.png"));
+
+    assert!(EpubDoc::from_url("https://example.com/book.epub").is_err());
+
+    let doc = EpubDoc::from_url(&redirect(url)).unwrap();
+    assert_eq!(17, doc.spine.len());
+}
+
+struct Counted {
+    content: Vec<u8>,
+    /// bytes read from the source
+    read: Rc<Cell<usize>>,
+}
+
+impl RangeSource for Counted {
+    fn size(&mut self) -> io::Result<u64> {
+        Ok(self.content.len() as u64)
+    }
+
+    fn read_range(&mut self, offset: u64, len: u64) -> io::Result<Vec<u8>> {
+        self.read.set(self.read.get() + len as usize);
+        let start = offset as usize;
+        Ok(self.content[start..start + len as usize].to_vec())
+    }
+}
+
+#[test]
+fn remote_range_reader() {
+    let content = std::fs::read("test.epub").unwrap();
+    let read = Rc::new(Cell::new(0));
+    let source = Counted {
+        content: content.clone(),
+        read: read.clone(),
+    };
+    let mut reader = RangeReader::new(source).unwrap();
+    assert_eq!(content.len() as u64, reader.size());
+    assert_eq!(0, reader.fetched());
+
+    reader.seek(SeekFrom::End(-10)).unwrap();
+    let mut end = vec![];
+    reader.read_to_end(&mut end).unwrap();
+    assert_eq!(&content[content.len() - 10..], &end[..]);
+    assert!(reader
+        .seek(SeekFrom::Current(-(content.len() as i64) - 1))
+        .is_err());
+
+    let mut doc = EpubDoc::from_reader(reader).unwrap();
+    assert_eq!("Todo es mío", doc.mdata("title").unwrap());
+    doc.get_resource("stylesheet.css").unwrap();
+    // the cover image isn't fetched
+    assert!(read.get() < content.len() / 2);
+}
+
+#[test]
+fn remote_range_reader_cache() {
+    let content = vec![7; 3 * 64 * 1024];
+    let read = Rc::new(Cell::new(0));
+    let source = Counted {
+        content,
+        read: read.clone(),
+    };
+    let mut reader = RangeReader::new(source).unwrap().cache_blocks(2);
+    let mut byte = [0];
+    for pos in [0, 64 * 1024, 0, 2 * 64 * 1024, 0, 64 * 1024].iter() {
+        reader.seek(SeekFrom::Start(*pos)).unwrap();
+        reader.read_exact(&mut byte).unwrap();
+    }
+    // the second block is dropped when the third one is fetched
+    assert_eq!(4 * 64 * 1024, read.get());
+    assert_eq!(4 * 64 * 1024, reader.fetched());
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn remote_http_range_url() {
+    assert!(HttpRange::new("http://").is_err());
+    assert!(HttpRange::new("ftp://example.com/book.epub").is_err());
+    assert!(HttpRange::new("http://example.com:80/book.epub").is_ok());
+    assert!(HttpRange::new("http://example.com:port/book.epub").is_err());
+    assert!(HttpRange::new("http://[::1]:8080/book.epub").is_ok());
+    assert!(HttpRange::new("http://[::1]/book.epub").is_ok());
+    assert!(HttpRange::new("http://[::1/book.epub").is_err());
+    assert!(HttpRange::new("http://[::1]8080/book.epub").is_err());
+}
@@ -0,0 +1,66 @@
+use epub::archive::EpubArchive;
+use epub::doc::EpubDoc;
+use epub::store::{DirectoryStore, MemoryStore, ResourceStore};
+use std::fs;
+use std::io::Cursor;
+
+#[test]
+fn store_directory() {
+    let dir = std::env::temp_dir().join("epub-rs-store-directory");
+    let _ = fs::remove_dir_all(&dir);
+    let mut zip = zip::ZipArchive::new(fs::File::open("test.epub").unwrap()).unwrap();
+    zip.extract(&dir).unwrap();
+
+    let store = DirectoryStore::new(&dir).unwrap();
+    assert_eq!(27, store.names().len());
+    let mut doc = EpubDoc::from_store(store).unwrap();
+    let mut zipped = EpubDoc::new("test.epub").unwrap();
+    assert_eq!("Todo es mío", doc.mdata("title").unwrap());
+    assert_eq!(zipped.spine, doc.spine);
+    assert_eq!(zipped.toc.len(), doc.toc.len());
+    assert_eq!(zipped.get_cover().unwrap(), doc.get_cover().unwrap());
+    assert!(doc.get_resource_by_path("OEBPS/missing.xhtml").is_err());
+
+    // modified and packed in a zip
+    doc.set_title("Packed").unwrap();
+    let path = std::env::temp_dir().join("epub-rs-store-directory.epub");
+    doc.save_as(&path).unwrap();
+    let mut packed = EpubDoc::new(&path).unwrap();
+    assert_eq!("Packed", packed.mdata("title").unwrap());
+    assert_eq!(
+        zipped.get_current_str().unwrap(),
+        packed.get_current_str().unwrap()
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn store_memory() {
+    let mut store = MemoryStore::new();
+    store
+        .insert("mimetype", "application/epub+zip")
+        .insert("a % encoded item.xml", "encoded");
+    assert_eq!(vec!["a % encoded item.xml", "mimetype"], store.names());
+
+    let mut archive = EpubArchive::from_store(store);
+    assert_eq!(2, archive.files.len());
+    assert_eq!(
+        "encoded",
+        archive
+            .get_entry_as_str("a%20%25%20encoded%20item.xml")
+            .unwrap()
+    );
+    assert!(archive.get_entry("missing").is_err());
+    // there's no container
+    assert!(EpubDoc::from_store(MemoryStore::new()).is_err());
+
+    archive.modify_entry("new.txt", "new");
+    let epub = archive.write_to(Cursor::new(vec![])).unwrap().into_inner();
+    let mut zipped = EpubArchive::from_bytes(epub).unwrap();
+    assert_eq!(3, zipped.files.len());
+    assert_eq!(
+        "encoded",
+        zipped.get_entry_as_str("a % encoded item.xml").unwrap()
+    );
+}